@@ -0,0 +1,136 @@
+// Copyright 2021 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! A benchmark comparing [`RecvStream::poll_read_chunk`]'s zero-copy reads
+//! against the buffer-based [`AsyncReadExt::read`] loop a caller would
+//! otherwise use: the latter copies every byte out of `quinn_proto`'s
+//! receive buffer into the caller's `Vec`, while the former just hands back
+//! the already-assembled `Bytes` chunk.
+
+use async_std::task;
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use futures::channel::oneshot;
+use futures::future::poll_fn;
+use futures::prelude::*;
+use libp2p_core::identity::Keypair;
+use libp2p_core::transport::ListenerEvent;
+use libp2p_core::{Multiaddr, StreamMuxer, Transport};
+use libp2p_quic::{Config, QuicMuxer, QuicTransport};
+use std::sync::Arc;
+use std::task::Poll;
+
+/// Large enough that the fixed per-chunk overhead (lock + `quinn_proto` call)
+/// is a small fraction of the total, so the comparison is dominated by
+/// whether each chunk is copied.
+const PAYLOAD_LEN: usize = 8 * 1024 * 1024;
+
+fn chunked_vs_buffered_read(c: &mut Criterion) {
+    let _ = env_logger::try_init();
+
+    let payload: Vec<u8> = vec![1; PAYLOAD_LEN];
+
+    let mut group = c.benchmark_group("chunked_read");
+    group.sample_size(10);
+    group.throughput(Throughput::Bytes(payload.len() as u64));
+
+    group.bench_function("buffered_copy", |b| {
+        b.iter(|| run(black_box(&payload), black_box(false)))
+    });
+    group.bench_function("zero_copy_chunks", |b| {
+        b.iter(|| run(black_box(&payload), black_box(true)))
+    });
+
+    group.finish();
+}
+
+/// Keeps polling `conn.poll_event` in the background, as a real
+/// [`Swarm`](https://docs.rs/libp2p-swarm) would for the lifetime of a
+/// connection.
+fn drive_in_background(conn: Arc<QuicMuxer>) {
+    task::spawn(poll_fn(move |cx| loop {
+        match conn.poll_event(cx) {
+            Poll::Ready(Ok(_)) => continue,
+            Poll::Ready(Err(_)) => return Poll::Ready(()),
+            Poll::Pending => return Poll::Pending,
+        }
+    }));
+}
+
+/// Transfers `payload` over a fresh [`QuicTransport`] pair, reading it back
+/// either through [`RecvStream::poll_read_chunk`] (`zero_copy`) or through
+/// the buffer-based [`AsyncReadExt::read`] (otherwise).
+fn run(payload: &[u8], zero_copy: bool) {
+    let addr: Multiaddr = "/ip4/127.0.0.1/udp/0/quic".parse().unwrap();
+    let (addr_sender, addr_receiver) = oneshot::channel();
+    let mut addr_sender = Some(addr_sender);
+    let payload_len = payload.len();
+    let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+
+    let receiver = task::spawn({
+        let transport = transport.clone();
+        async move {
+            let mut listener = transport.listen_on(addr).unwrap();
+            loop {
+                match listener.next().await.unwrap().unwrap() {
+                    ListenerEvent::NewAddress(a) => {
+                        addr_sender.take().unwrap().send(a).unwrap();
+                    }
+                    ListenerEvent::Upgrade { upgrade, .. } => {
+                        let (_peer, conn) = upgrade.await.unwrap();
+                        let conn = Arc::new(conn);
+                        drive_in_background(conn.clone());
+                        let mut recv = poll_fn(|cx| conn.poll_accept_uni(cx)).await.unwrap();
+
+                        if zero_copy {
+                            let mut received = 0;
+                            while let Some(chunk) =
+                                poll_fn(|cx| recv.poll_read_chunk(cx)).await.unwrap()
+                            {
+                                received += chunk.len();
+                            }
+                            assert_eq!(received, payload_len);
+                        } else {
+                            let mut buf = vec![0u8; payload_len];
+                            recv.read_exact(&mut buf).await.unwrap();
+                        }
+                        return;
+                    }
+                    _ => panic!("Unexpected listener event"),
+                }
+            }
+        }
+    });
+
+    task::block_on(async move {
+        let addr = addr_receiver.await.unwrap();
+        let (_peer, conn) = transport.dial(addr).unwrap().await.unwrap();
+        let conn = Arc::new(conn);
+        drive_in_background(conn.clone());
+
+        let mut send = conn.open_uni().unwrap();
+        send.write_all(payload).await.unwrap();
+        send.close().await.unwrap();
+    });
+
+    task::block_on(receiver);
+}
+
+criterion_group!(chunked_read, chunked_vs_buffered_read);
+criterion_main!(chunked_read);
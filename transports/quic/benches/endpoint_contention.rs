@@ -0,0 +1,162 @@
+// Copyright 2021 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Measures aggregate throughput transferring a payload over an increasing
+//! number of connections accepted by a single listener at once, to see
+//! whether that listener's one endpoint-wide lock (guarding its one
+//! `quinn_proto::Endpoint` and the one UDP socket it demultiplexes) becomes
+//! a bottleneck as the connection count grows.
+//!
+//! It's a flat benchmark rather than a before/after comparison: the lock it
+//! exercises is already only ever held for the brief, O(1) bookkeeping of
+//! routing one just-received datagram to the connection it belongs to (see
+//! `dispatch_datagram` in `endpoint.rs`) or similarly small operations; the
+//! actual per-connection `quinn_proto` work - handshake crypto, stream
+//! framing, congestion control - already runs in `Endpoint::drive` without
+//! holding it at all, against a `quinn_proto::Connection` that lives behind
+//! its own `QuicMuxer`'s private lock. Sharding further would mean giving
+//! each shard its own UDP socket and `quinn_proto::Endpoint` - splitting one
+//! listener into several with separate connection-ID spaces - which is a
+//! materially different, larger design than this crate's current
+//! one-socket-per-listener design, so this benchmark exists to show whether
+//! that's actually warranted rather than to compare it against an
+//! implementation of it.
+
+use async_std::task;
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use futures::channel::oneshot;
+use futures::future::poll_fn;
+use futures::prelude::*;
+use libp2p_core::identity::Keypair;
+use libp2p_core::transport::ListenerEvent;
+use libp2p_core::{Multiaddr, StreamMuxer, Transport};
+use libp2p_quic::{Config, QuicMuxer, QuicTransport};
+use std::sync::Arc;
+use std::task::Poll;
+
+/// Small enough that, even multiplied by the largest connection count below,
+/// a run stays quick; large enough to span more than a couple of packets per
+/// connection so the benchmark isn't dominated by handshake cost alone.
+const PAYLOAD_LEN: usize = 64 * 1024;
+
+fn concurrent_connections(c: &mut Criterion) {
+    let _ = env_logger::try_init();
+
+    let payload: Vec<u8> = vec![1; PAYLOAD_LEN];
+
+    let mut group = c.benchmark_group("endpoint_contention");
+    group.sample_size(10);
+
+    for connections in [1u32, 8, 32] {
+        group.throughput(Throughput::Bytes(payload.len() as u64 * connections as u64));
+        group.bench_function(format!("{connections}_connections"), |b| {
+            b.iter(|| run(black_box(&payload), black_box(connections)))
+        });
+    }
+
+    group.finish();
+}
+
+/// Keeps polling `conn.poll_event` in the background, as a real
+/// [`Swarm`](https://docs.rs/libp2p-swarm) would for the lifetime of a
+/// connection.
+fn drive_in_background(conn: Arc<QuicMuxer>) {
+    task::spawn(poll_fn(move |cx| loop {
+        match conn.poll_event(cx) {
+            Poll::Ready(Ok(_)) => continue,
+            Poll::Ready(Err(_)) => return Poll::Ready(()),
+            Poll::Pending => return Poll::Pending,
+        }
+    }));
+}
+
+/// Accepts `connections` dials on a single shared listener and, on each one,
+/// receives `payload`, concurrently.
+async fn listen_and_receive(
+    addr: Multiaddr,
+    connections: u32,
+    addr_tx: oneshot::Sender<Multiaddr>,
+) {
+    let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+    let mut listener = transport.listen_on(addr).unwrap();
+    let mut addr_tx = Some(addr_tx);
+    let mut accepted = Vec::new();
+
+    while accepted.len() < connections as usize {
+        match listener.next().await.unwrap().unwrap() {
+            ListenerEvent::NewAddress(a) => {
+                addr_tx.take().unwrap().send(a).unwrap();
+            }
+            ListenerEvent::Upgrade { upgrade, .. } => {
+                accepted.push(task::spawn(async move {
+                    let (_peer, conn) = upgrade.await.unwrap();
+                    let conn = Arc::new(conn);
+                    drive_in_background(conn.clone());
+                    let mut recv = poll_fn(|cx| conn.poll_accept_uni(cx)).await.unwrap();
+                    let mut buf = vec![0u8; PAYLOAD_LEN];
+                    recv.read_exact(&mut buf).await.unwrap();
+                }));
+            }
+            _ => panic!("unexpected listener event"),
+        }
+    }
+
+    future::join_all(accepted).await;
+}
+
+/// Dials `addr` `connections` times concurrently, each dial its own
+/// `QuicTransport` (and so its own socket and endpoint), and sends
+/// `payload` once the handshake completes.
+async fn dial_and_send(addr: Multiaddr, connections: u32, payload: &[u8]) {
+    let dials = (0..connections).map(|_| {
+        let addr = addr.clone();
+        async move {
+            let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+            let (_peer, conn) = transport.dial(addr).unwrap().await.unwrap();
+            let conn = Arc::new(conn);
+            drive_in_background(conn.clone());
+
+            let mut send = conn.open_uni().unwrap();
+            send.write_all(payload).await.unwrap();
+            send.close().await.unwrap();
+        }
+    });
+
+    future::join_all(dials).await;
+}
+
+/// Transfers `payload` from `connections` independently-socketed dialers to
+/// one shared listener, all at once.
+fn run(payload: &[u8], connections: u32) {
+    let addr: Multiaddr = "/ip4/127.0.0.1/udp/0/quic".parse().unwrap();
+    let (addr_tx, addr_rx) = oneshot::channel();
+
+    let receiver = task::spawn(listen_and_receive(addr, connections, addr_tx));
+
+    task::block_on(async move {
+        let addr = addr_rx.await.unwrap();
+        dial_and_send(addr, connections, payload).await;
+    });
+
+    task::block_on(receiver);
+}
+
+criterion_group!(endpoint_contention, concurrent_connections);
+criterion_main!(endpoint_contention);
@@ -0,0 +1,197 @@
+// Copyright 2021 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! A benchmark comparing transfer completion time for a large object under
+//! `quinn_proto`'s default initial congestion window versus
+//! [`Config::initial_congestion_window`] enlarged well past it.
+
+use async_std::task;
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use futures::channel::oneshot;
+use futures::future::poll_fn;
+use futures::prelude::*;
+use libp2p_core::identity::Keypair;
+use libp2p_core::muxing::StreamMuxerEvent;
+use libp2p_core::transport::ListenerEvent;
+use libp2p_core::{Multiaddr, StreamMuxer, Transport};
+use libp2p_quic::{Config, QuicMuxer, QuicTransport};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+/// Large enough that slow-start's ramp-up time is a meaningful fraction of
+/// the total transfer on a loopback link.
+const PAYLOAD_LEN: usize = 8 * 1024 * 1024;
+
+/// Comfortably past the default (~14.4 KiB), so the two runs start from
+/// visibly different windows.
+const ENLARGED_INITIAL_WINDOW: u64 = 1024 * 1024;
+
+fn transfer_large_object(c: &mut Criterion) {
+    let _ = env_logger::try_init();
+
+    let payload: Vec<u8> = vec![1; PAYLOAD_LEN];
+
+    let mut group = c.benchmark_group("initial_congestion_window");
+    group.sample_size(10);
+    group.throughput(Throughput::Bytes(payload.len() as u64));
+
+    group.bench_function("default", |b| {
+        b.iter(|| run(black_box(&payload), black_box(None)))
+    });
+    group.bench_function("enlarged", |b| {
+        b.iter(|| {
+            run(
+                black_box(&payload),
+                black_box(Some(ENLARGED_INITIAL_WINDOW)),
+            )
+        })
+    });
+
+    group.finish();
+}
+
+/// Keeps polling `conn.poll_event` in the background, as a real
+/// [`Swarm`](https://docs.rs/libp2p-swarm) would for the lifetime of a
+/// connection: `QuicMuxer`'s `StreamMuxer::read_substream`/`write_substream`
+/// only move bytes in and out of `quinn_proto`'s own buffers, so nothing else
+/// here ever drives the endpoint's socket.
+fn drive_in_background(conn: Arc<QuicMuxer>) {
+    task::spawn(poll_fn(move |cx| loop {
+        match conn.poll_event(cx) {
+            Poll::Ready(Ok(_)) => continue,
+            Poll::Ready(Err(_)) => return Poll::Ready(()),
+            Poll::Pending => return Poll::Pending,
+        }
+    }));
+}
+
+/// Polls `read_substream`/`write_substream` to completion.
+///
+/// Unlike the richer, QUIC-specific `SendStream`/`RecvStream` API, these
+/// generic `StreamMuxer` methods don't register a waker when they have
+/// nothing to report yet, so waiting for a wake-up that will never come would
+/// hang; wake ourselves instead and rely on `drive_in_background` to make
+/// progress between polls.
+async fn retry<T>(mut poll: impl FnMut(&mut Context<'_>) -> Poll<T>) -> T {
+    poll_fn(|cx| {
+        let result = poll(cx);
+        if result.is_pending() {
+            cx.waker().wake_by_ref();
+        }
+        result
+    })
+    .await
+}
+
+/// Transfers `payload` over a fresh [`QuicTransport`] pair, optionally
+/// enlarging the initial congestion window on both ends.
+fn run(payload: &[u8], initial_congestion_window: Option<u64>) {
+    let addr: Multiaddr = "/ip4/127.0.0.1/udp/0/quic".parse().unwrap();
+    let (addr_sender, addr_receiver) = oneshot::channel();
+    let mut addr_sender = Some(addr_sender);
+    let payload_len = payload.len();
+    let transport = quic_transport(initial_congestion_window);
+
+    // Spawn the receiver.
+    let receiver = task::spawn({
+        let transport = transport.clone();
+        async move {
+            let mut listener = transport.listen_on(addr).unwrap();
+            loop {
+                match listener.next().await.unwrap().unwrap() {
+                    ListenerEvent::NewAddress(a) => {
+                        addr_sender.take().unwrap().send(a).unwrap();
+                    }
+                    ListenerEvent::Upgrade { upgrade, .. } => {
+                        let (_peer, conn) = upgrade.await.unwrap();
+                        let conn = Arc::new(conn);
+                        match poll_fn(|cx| conn.poll_event(cx)).await {
+                            Ok(StreamMuxerEvent::InboundSubstream(mut s)) => {
+                                drive_in_background(conn.clone());
+
+                                let mut buf = vec![0u8; payload_len];
+                                let mut off = 0;
+                                loop {
+                                    // Read in typical chunk sizes of up to 8KiB.
+                                    let end = off + std::cmp::min(buf.len() - off, 8 * 1024);
+                                    let n = retry(|cx| {
+                                        conn.read_substream(cx, &mut s, &mut buf[off..end])
+                                    })
+                                    .await
+                                    .unwrap();
+                                    off += n;
+                                    if off == buf.len() {
+                                        return;
+                                    }
+                                }
+                            }
+                            Ok(_) => panic!("Unexpected muxer event"),
+                            Err(e) => panic!("Unexpected error: {:?}", e),
+                        }
+                    }
+                    _ => panic!("Unexpected listener event"),
+                }
+            }
+        }
+    });
+
+    // Spawn and block on the sender, i.e. until all data is sent.
+    task::block_on(async move {
+        let addr = addr_receiver.await.unwrap();
+        let (_peer, conn) = transport.dial(addr).unwrap().await.unwrap();
+        let conn = Arc::new(conn);
+        drive_in_background(conn.clone());
+
+        let mut handle = conn.open_outbound();
+        let mut stream = poll_fn(|cx| conn.poll_outbound(cx, &mut handle))
+            .await
+            .unwrap();
+        let mut off = 0;
+        loop {
+            let n = retry(|cx| conn.write_substream(cx, &mut stream, &payload[off..]))
+                .await
+                .unwrap();
+            off += n;
+            if off == payload.len() {
+                retry(|cx| conn.flush_substream(cx, &mut stream))
+                    .await
+                    .unwrap();
+                retry(|cx| conn.shutdown_substream(cx, &mut stream))
+                    .await
+                    .unwrap();
+                return;
+            }
+        }
+    });
+
+    // Wait for all data to be received.
+    task::block_on(receiver);
+}
+
+fn quic_transport(initial_congestion_window: Option<u64>) -> QuicTransport {
+    let mut config = Config::new(&Keypair::generate_ed25519());
+    if let Some(window) = initial_congestion_window {
+        config = config.initial_congestion_window(window);
+    }
+    QuicTransport::new(config)
+}
+
+criterion_group!(initial_congestion_window, transfer_large_object);
+criterion_main!(initial_congestion_window);
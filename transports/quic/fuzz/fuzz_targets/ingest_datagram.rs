@@ -0,0 +1,8 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let from = "127.0.0.1:12345".parse().unwrap();
+    libp2p_quic::fuzz_ingest_datagram(from, data);
+});
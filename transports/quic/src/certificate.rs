@@ -0,0 +1,135 @@
+// Copyright 2021 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Generation of the self-signed TLS certificate that libp2p QUIC connections
+//! are authenticated with, following the `libp2p` TLS handshake specification:
+//! the node's libp2p [`Keypair`] signs the certificate's public key, and the
+//! signature is embedded as a custom X.509 extension so the remote can
+//! recover our [`PeerId`] without a separate handshake.
+
+use libp2p_core::identity::Keypair;
+use libp2p_core::PeerId;
+use std::convert::TryInto;
+
+/// The libp2p-specific X.509 extension OID carrying the signed certificate
+/// public key, as specified by the libp2p TLS handshake specification.
+pub(crate) const P2P_EXT_OID: &[u64] = &[1, 3, 6, 1, 4, 1, 53594, 1, 1];
+
+/// A self-signed X.509 certificate presented during the QUIC/TLS handshake.
+pub(crate) struct Certificate {
+    /// The DER-encoded certificate.
+    pub(crate) certificate: rustls::Certificate,
+    /// The DER-encoded private key matching [`Certificate::certificate`].
+    pub(crate) private_key: rustls::PrivateKey,
+}
+
+/// Generates a new self-signed certificate for `keypair`, embedding a
+/// signature over the certificate's public key so the remote can
+/// authenticate the issuer's [`PeerId`].
+pub(crate) fn generate(keypair: &Keypair) -> Result<Certificate, crate::Error> {
+    let mut params = rcgen::CertificateParams::new(vec![]);
+    params.alg = &rcgen::PKCS_ED25519;
+    let keypair_der = rcgen::KeyPair::generate(&rcgen::PKCS_ED25519)
+        .map_err(|e| crate::Error::Handshake(e.to_string()))?;
+
+    let signature = keypair
+        .sign(keypair_der.public_key_raw())
+        .map_err(|e| crate::Error::Handshake(e.to_string()))?;
+
+    params
+        .custom_extensions
+        .push(rcgen::CustomExtension::from_oid_content(
+            P2P_EXT_OID,
+            encode_signed_key(keypair.public(), &signature),
+        ));
+    params.key_pair = Some(keypair_der);
+
+    let certificate = rcgen::Certificate::from_params(params)
+        .map_err(|e| crate::Error::Handshake(e.to_string()))?;
+
+    Ok(Certificate {
+        certificate: rustls::Certificate(
+            certificate
+                .serialize_der()
+                .map_err(|e| crate::Error::Handshake(e.to_string()))?,
+        ),
+        private_key: rustls::PrivateKey(certificate.serialize_private_key_der()),
+    })
+}
+
+/// Encodes the libp2p public key together with its signature over the
+/// certificate's public key, as a protobuf-free, length-prefixed blob.
+fn encode_signed_key(public_key: libp2p_core::PublicKey, signature: &[u8]) -> Vec<u8> {
+    let encoded_key = public_key.to_protobuf_encoding();
+    let mut out = Vec::with_capacity(encoded_key.len() + signature.len() + 8);
+    out.extend_from_slice(&(encoded_key.len() as u32).to_be_bytes());
+    out.extend_from_slice(&encoded_key);
+    out.extend_from_slice(&(signature.len() as u32).to_be_bytes());
+    out.extend_from_slice(signature);
+    out
+}
+
+/// Extracts the remote [`PeerId`] from a certificate previously produced by
+/// [`generate`], verifying the embedded signature in the process.
+pub(crate) fn extract_peer_id(certificate: &rustls::Certificate) -> Result<PeerId, crate::Error> {
+    let (_, parsed) = x509_parser::parse_x509_certificate(&certificate.0)
+        .map_err(|e| crate::Error::Handshake(format!("invalid certificate: {}", e)))?;
+
+    let oid = x509_parser::oid_registry::Oid::from(P2P_EXT_OID)
+        .map_err(|_| crate::Error::Handshake("invalid libp2p extension oid".into()))?;
+    let extension = parsed
+        .get_extension_unique(&oid)
+        .map_err(|e| crate::Error::Handshake(e.to_string()))?
+        .ok_or_else(|| {
+            crate::Error::Handshake("certificate does not carry a libp2p extension".into())
+        })?;
+
+    let (public_key, signature) = decode_signed_key(extension.value)?;
+    let cert_public_key = parsed.public_key().subject_public_key.data.as_ref();
+    if !public_key.verify(cert_public_key, signature) {
+        return Err(crate::Error::Handshake(
+            "signature over the certificate's public key is invalid".into(),
+        ));
+    }
+
+    Ok(public_key.to_peer_id())
+}
+
+/// Splits the blob produced by [`encode_signed_key`] back into the libp2p
+/// public key and the signature over the certificate's public key.
+fn decode_signed_key(buf: &[u8]) -> Result<(libp2p_core::PublicKey, &[u8]), crate::Error> {
+    let too_short = || crate::Error::Handshake("libp2p extension is truncated".into());
+
+    let key_len_bytes: [u8; 4] = buf.get(0..4).ok_or_else(too_short)?.try_into().unwrap();
+    let key_len = u32::from_be_bytes(key_len_bytes) as usize;
+    let buf = buf.get(4..).ok_or_else(too_short)?;
+
+    let encoded_key = buf.get(..key_len).ok_or_else(too_short)?;
+    let buf = buf.get(key_len..).ok_or_else(too_short)?;
+
+    let sig_len_bytes: [u8; 4] = buf.get(0..4).ok_or_else(too_short)?.try_into().unwrap();
+    let sig_len = u32::from_be_bytes(sig_len_bytes) as usize;
+    let signature = buf.get(4..4 + sig_len).ok_or_else(too_short)?;
+
+    let public_key = libp2p_core::PublicKey::from_protobuf_encoding(encoded_key)
+        .map_err(|e| crate::Error::Handshake(e.to_string()))?;
+
+    Ok((public_key, signature))
+}
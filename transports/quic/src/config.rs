@@ -0,0 +1,1124 @@
+// Copyright 2021 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use crate::Error;
+use libp2p_core::identity::Keypair;
+use libp2p_core::PeerId;
+use std::fmt;
+use std::net::SocketAddr;
+#[cfg(feature = "qlog")]
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Consulted by [`Config::peer_verifier`] after the standard libp2p
+/// certificate verification has already succeeded, with the [`PeerId`] it
+/// recovered and the certificate it was recovered from.
+pub(crate) type PeerVerifier =
+    Arc<dyn Fn(&PeerId, &rustls::Certificate) -> Result<(), Error> + Send + Sync>;
+
+/// Consulted by [`Config::transmit_interceptor`] for every outgoing
+/// `quinn_proto` transmit.
+pub(crate) type TransmitInterceptor =
+    Arc<dyn Fn(&quinn_proto::Transmit) -> TransmitAction + Send + Sync>;
+
+/// Consulted by [`Config::listener_dispatch_policy`] to pick which listener
+/// sharing an endpoint receives a given inbound connection, given its source
+/// address and the number of listeners currently registered on that
+/// endpoint. The result is taken modulo that count, so any value is safe to
+/// return.
+pub(crate) type ListenerDispatchPolicy = Arc<dyn Fn(SocketAddr, usize) -> usize + Send + Sync>;
+
+/// Controls what happens to an inbound connection attempt that arrives while
+/// [`Config::max_pending_connections`] is already full.
+///
+/// See [`Config::backlog_overflow_policy`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BacklogOverflowPolicy {
+    /// Drop the newly arriving connection attempt, leaving the existing
+    /// backlog untouched. The default.
+    Reject,
+    /// Drop the oldest connection still waiting in the backlog to make room
+    /// for the new one.
+    DropOldest,
+}
+
+/// What to do with a single outgoing UDP datagram, as decided by
+/// [`Config::transmit_interceptor`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransmitAction {
+    /// Send it normally.
+    Pass,
+    /// Silently drop it, as though it had been lost in transit; `quinn_proto`'s
+    /// own retransmission logic is what's expected to recover from this, not
+    /// anything on this crate's side.
+    Drop,
+}
+
+/// How a connection orders streams that have data ready to send, configured
+/// via [`Config::stream_scheduler`].
+///
+/// `quinn_proto` 0.7.3 always schedules strictly by priority across
+/// different levels (set per-stream via
+/// [`QuicMuxer::open_bi_with_priority`](crate::QuicMuxer::open_bi_with_priority))
+/// and, within a single level, round-robins: every stream that wrote since
+/// it was last serviced goes to the back of that level's queue rather than
+/// being revisited immediately. There is no public hook in this version of
+/// `quinn_proto` to swap that algorithm out, so [`StreamScheduler::StrictPriority`]
+/// is accepted here and stored on the [`Config`] but currently has no
+/// observable effect beyond what [`StreamScheduler::RoundRobin`] already
+/// does; see [`Config::stream_scheduler`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamScheduler {
+    /// Intended to keep servicing the highest-priority stream with data
+    /// queued until it has none left, rather than rotating among same-level
+    /// streams; not currently distinguishable from [`StreamScheduler::RoundRobin`]
+    /// for the reason given on [`StreamScheduler`] itself.
+    StrictPriority,
+    /// Rotate among streams at the same priority level so none of them
+    /// starves the others, while still servicing strictly higher-priority
+    /// streams first. The default, and currently the only behavior
+    /// `quinn_proto` 0.7.3 actually implements.
+    RoundRobin,
+}
+
+/// Initial per-stream flow-control windows, in bytes, one for each way a
+/// QUIC stream can come into being; see [`Config::stream_windows`].
+///
+/// Mirrors the three `initial_max_stream_data_*` QUIC transport parameters -
+/// there's no fourth, since a unidirectional stream only ever needs a
+/// receive window on the side that didn't open it. `quinn_proto` 0.7.3's
+/// [`TransportConfig::stream_receive_window`] only accepts a single value
+/// applied to all three, though, so setting these fields to different
+/// values doesn't yet grant three independently enforced windows: the
+/// largest of the three given is used for all of them, so no stream type
+/// ends up with less than it asked for. See the test on
+/// [`Config::stream_windows`] for the behavior this produces today.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StreamWindows {
+    /// Window for a bidirectional stream this side opened.
+    pub bidi_local: u64,
+    /// Window for a bidirectional stream the remote opened.
+    pub bidi_remote: u64,
+    /// Window for a unidirectional stream the remote opened (the only kind
+    /// with a receive side at all: the side that opens a unidirectional
+    /// stream never reads from it).
+    pub uni: u64,
+}
+
+/// Which UDP segmentation offloads a connection is allowed to use, for
+/// operators who need an escape hatch on kernels or virtualised NICs known
+/// to mishandle GSO/GRO and corrupt packets as a result.
+///
+/// See [`Config::offloads`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Offloads {
+    /// Generic Segmentation Offload: batching multiple outgoing datagrams
+    /// into a single `sendmsg` call for the kernel to split.
+    pub gso: bool,
+    /// Generic Receive Offload: the kernel coalescing multiple incoming
+    /// datagrams into a single `recvmsg` call.
+    pub gro: bool,
+}
+
+/// A TLS 1.3 cipher suite a connection may negotiate; see
+/// [`Config::set_cipher_suites`].
+///
+/// QUIC's TLS layer is pinned to TLS 1.3 (see
+/// [`tls::make_server_config`](crate::tls::make_server_config)), so these
+/// three - the complete set TLS 1.3 itself defines - are also the complete
+/// set this crate could ever actually negotiate, regardless of however many
+/// more `rustls::ALL_CIPHERSUITES` lists for older TLS versions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CipherSuite {
+    /// `TLS13_AES_128_GCM_SHA256`.
+    Aes128GcmSha256,
+    /// `TLS13_AES_256_GCM_SHA384`.
+    Aes256GcmSha384,
+    /// `TLS13_CHACHA20_POLY1305_SHA256`.
+    Chacha20Poly1305Sha256,
+}
+
+impl CipherSuite {
+    /// Every [`CipherSuite`], in `rustls`' own preference order; the
+    /// default for [`Config::set_cipher_suites`].
+    pub(crate) const ALL: [CipherSuite; 3] = [
+        CipherSuite::Chacha20Poly1305Sha256,
+        CipherSuite::Aes256GcmSha384,
+        CipherSuite::Aes128GcmSha256,
+    ];
+
+    /// The `rustls` suite this variant names.
+    pub(crate) fn to_rustls(self) -> &'static rustls::SupportedCipherSuite {
+        match self {
+            CipherSuite::Aes128GcmSha256 => &rustls::ciphersuite::TLS13_AES_128_GCM_SHA256,
+            CipherSuite::Aes256GcmSha384 => &rustls::ciphersuite::TLS13_AES_256_GCM_SHA384,
+            CipherSuite::Chacha20Poly1305Sha256 => {
+                &rustls::ciphersuite::TLS13_CHACHA20_POLY1305_SHA256
+            }
+        }
+    }
+
+    /// The [`CipherSuite`] naming a `rustls` suite, or `None` if `suite`
+    /// isn't one of the three this crate itself ever offers - which,
+    /// [`CipherSuite`]'s own doc comment notwithstanding, a raw
+    /// `&'static rustls::SupportedCipherSuite` reference has no type-level
+    /// guarantee against, hence the `Option` rather than an infallible
+    /// conversion.
+    pub(crate) fn from_rustls(suite: &rustls::SupportedCipherSuite) -> Option<Self> {
+        CipherSuite::ALL
+            .iter()
+            .copied()
+            .find(|candidate| candidate.to_rustls().suite == suite.suite)
+    }
+}
+
+/// A named elliptic-curve group a connection's ECDHE key exchange may use;
+/// see [`Config::set_kx_groups`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyExchangeGroup {
+    /// X25519.
+    X25519,
+    /// NIST P-256, a.k.a. secp256r1.
+    Secp256r1,
+    /// NIST P-384, a.k.a. secp384r1.
+    Secp384r1,
+}
+
+impl KeyExchangeGroup {
+    /// Every [`KeyExchangeGroup`] `rustls` 0.19 itself supports, in its own
+    /// preference order (see `KeyExchange::supported_groups` in its
+    /// vendored source); the default for [`Config::set_kx_groups`].
+    pub(crate) const ALL: [KeyExchangeGroup; 3] = [
+        KeyExchangeGroup::X25519,
+        KeyExchangeGroup::Secp384r1,
+        KeyExchangeGroup::Secp256r1,
+    ];
+}
+
+/// The configuration for the QUIC transport.
+///
+/// A [`Config`] is consumed when constructing a [`QuicTransport`](crate::QuicTransport)
+/// and determines both the `quinn_proto` transport parameters and the
+/// self-signed TLS certificate presented during the handshake.
+#[derive(Clone)]
+pub struct Config {
+    /// The keypair used to sign the local TLS certificate.
+    pub(crate) keypair: Keypair,
+    /// Maximum duration of inactivity before a connection is timed out.
+    pub(crate) max_idle_timeout: Duration,
+    /// Maximum time a connection is given to finish draining (retransmitting
+    /// its `CONNECTION_CLOSE` in response to any further traffic) after
+    /// [`StreamMuxer::close`](libp2p_core::muxing::StreamMuxer::close) is
+    /// called on it, before its handle is reclaimed regardless; `None`
+    /// waits as long as `quinn_proto`'s own closing timer does instead
+    /// (three times the connection's round-trip estimate at the moment it
+    /// closed), which during a mass disconnect against peers that have
+    /// already vanished can add up to real memory held across however many
+    /// connections are closing at once.
+    pub(crate) close_timeout: Option<Duration>,
+    /// Maximum number of concurrent bidirectional streams the remote may open.
+    pub(crate) max_concurrent_stream_limit: u32,
+    /// Period at which keep-alive packets are sent to the remote.
+    pub(crate) keep_alive_interval: Duration,
+    /// Maximum number of simultaneously established connections.
+    pub(crate) max_connections: u32,
+    /// Maximum number of inbound connections queued up waiting to be
+    /// accepted by the listener.
+    pub(crate) max_pending_connections: u32,
+    /// Maximum number of simultaneously established connections from a
+    /// single remote peer; see [`Config::max_connections_per_peer`].
+    pub(crate) max_connections_per_peer: Option<usize>,
+    /// What to do with an inbound connection attempt that arrives once
+    /// `max_pending_connections` is already full.
+    pub(crate) backlog_overflow_policy: BacklogOverflowPolicy,
+    /// Whether the server demands a client certificate during the handshake.
+    pub(crate) require_client_auth: bool,
+    /// Whether the server allows a client to migrate to a new address
+    /// mid-connection. See [`Config::allow_migration`].
+    pub(crate) allow_migration: bool,
+    /// Called whenever a datagram couldn't be handed to the socket and was
+    /// dropped, in addition to the endpoint's own running counter.
+    pub(crate) on_datagram_dropped: Option<Arc<dyn Fn(SocketAddr, usize) + Send + Sync>>,
+    /// Consulted for every outgoing `quinn_proto` transmit before it reaches
+    /// the socket, letting it be passed through or dropped; see
+    /// [`Config::transmit_interceptor`].
+    pub(crate) transmit_interceptor: Option<TransmitInterceptor>,
+    /// Picks which listener sharing an endpoint receives a given inbound
+    /// connection; `None` sends every connection to the first listener
+    /// registered on that endpoint, the only one that exists in the common
+    /// case of a single [`QuicListenStream`](crate::transport::QuicListenStream)
+    /// per endpoint. See [`Config::listener_dispatch_policy`].
+    pub(crate) listener_dispatch_policy: Option<ListenerDispatchPolicy>,
+    /// How a connection orders streams with data ready to send; see
+    /// [`Config::stream_scheduler`].
+    pub(crate) stream_scheduler: StreamScheduler,
+    /// Initial per-stream flow-control windows; `None` uses `quinn_proto`'s
+    /// own default for all three. See [`Config::stream_windows`].
+    pub(crate) stream_windows: Option<StreamWindows>,
+    /// Maximum number of outgoing datagrams a [`DatagramSink`](crate::DatagramSink)
+    /// will hold queued up waiting to be handed to the connection.
+    pub(crate) datagram_send_buffer_size: usize,
+    /// Bytes a substream's writes are coalesced into before being handed to
+    /// the connection; `0` hands every write straight through. See
+    /// [`Config::stream_write_buffer`].
+    pub(crate) stream_write_buffer: usize,
+    /// Initial congestion window, in bytes; `None` uses `quinn_proto`'s own
+    /// default.
+    pub(crate) initial_congestion_window: Option<u64>,
+    /// Reordering tolerance, in packets, before an unacknowledged packet is
+    /// declared lost; `None` uses `quinn_proto`'s own default.
+    pub(crate) packet_threshold: Option<u32>,
+    /// Reordering tolerance, as a multiple of the smoothed RTT, before an
+    /// unacknowledged packet is declared lost; `None` uses `quinn_proto`'s
+    /// own default.
+    pub(crate) time_threshold: Option<f32>,
+    /// How long a received ack-eliciting packet may sit before it must be
+    /// acknowledged; `None` uses `quinn_proto`'s own default. See
+    /// [`Config::max_ack_delay`].
+    pub(crate) max_ack_delay: Option<Duration>,
+    /// Consulted, after the standard libp2p certificate verification
+    /// succeeds, to accept or reject the remote.
+    pub(crate) peer_verifier: Option<PeerVerifier>,
+    /// Whether a connection should automatically migrate onto a new local
+    /// address once the OS changes its default route out from under it.
+    pub(crate) auto_migrate: bool,
+    /// Whether a connection proactively probes alternate paths; see
+    /// [`Config::migration_probing`].
+    pub(crate) migration_probing: bool,
+    /// Directory a qlog file is written to per connection, if set.
+    #[cfg(feature = "qlog")]
+    pub(crate) qlog_dir: Option<PathBuf>,
+    /// Whether to set `IP_FREEBIND`/`IP_BINDANY` on the listening socket
+    /// before binding it.
+    pub(crate) freebind: bool,
+    /// DSCP codepoint to mark every outgoing packet with, via `IP_TOS`
+    /// (IPv4) or `IPV6_TCLASS` (IPv6); `None` leaves the field at the OS
+    /// default of zero.
+    pub(crate) dscp: Option<u8>,
+    /// Which UDP segmentation offloads this endpoint's socket I/O is
+    /// allowed to use.
+    pub(crate) offloads: Offloads,
+    /// Private key `quinn_proto` uses to generate stateless reset tokens;
+    /// `None` has it pick a random one per [`Endpoint`](crate::endpoint::Endpoint).
+    pub(crate) stateless_reset_key: Option<Vec<u8>>,
+    /// Application protocols offered and accepted during the TLS handshake,
+    /// in preference order. Defaults to the single libp2p ALPN.
+    pub(crate) alpn_protocols: Vec<Vec<u8>>,
+    /// TLS 1.3 cipher suites offered and accepted during the handshake, in
+    /// preference order. See [`Config::set_cipher_suites`].
+    pub(crate) cipher_suites: Vec<CipherSuite>,
+    /// Named groups a connection's ECDHE key exchange may use. See
+    /// [`Config::set_kx_groups`].
+    pub(crate) kx_groups: Vec<KeyExchangeGroup>,
+    /// Length, in bytes, of the locally generated connection IDs this
+    /// endpoint hands out; `None` uses `quinn_proto`'s own default of 8. See
+    /// [`Config::local_cid_len`].
+    pub(crate) local_cid_len: Option<u8>,
+    /// Cumulative bytes a server will send on a given connection before its
+    /// handshake is confirmed; `None` leaves this bounded only by
+    /// `quinn_proto`'s own built-in anti-amplification factor. See
+    /// [`Config::max_unvalidated_handshake_bytes`].
+    pub(crate) max_unvalidated_handshake_bytes: Option<usize>,
+    /// Hard ceiling, in bytes, on the UDP payload size of any packet this
+    /// endpoint sends; `None` uses `quinn_proto`'s own default of 1480. See
+    /// [`Config::max_udp_payload_size`].
+    pub(crate) max_udp_payload_size: Option<u16>,
+    /// Length, in bytes, of the locally generated connection IDs
+    /// `quinn_proto` hands out; `None` uses its own default of 8.
+    ///
+    /// Not exposed publicly: `quinn_proto` only tracks endpoint-wide
+    /// connection ID exhaustion for lengths of 4 bytes or less (see
+    /// [`Error::EndpointAtCapacity`](crate::Error::EndpointAtCapacity)), so
+    /// this exists purely to let tests shrink the ID space enough to drive
+    /// an [`Endpoint`](crate::endpoint::Endpoint) to that limit in a
+    /// reasonable number of connections; no real deployment should ever
+    /// want fewer than the default 8 bytes of collision resistance.
+    #[cfg(test)]
+    pub(crate) connection_id_length: Option<usize>,
+    /// Overrides the single QUIC wire version an [`Endpoint`](crate::endpoint::Endpoint)
+    /// advertises as its `initial_version`; `None` uses `quinn_proto`'s own
+    /// default (the first of its [`DEFAULT_SUPPORTED_VERSIONS`](quinn_proto::DEFAULT_SUPPORTED_VERSIONS)).
+    ///
+    /// Not exposed publicly: every [`Endpoint`](crate::endpoint::Endpoint)
+    /// already accepts *all* of `quinn_proto`'s supported draft versions
+    /// from whichever peer dials in (see the comment on `endpoint_config`
+    /// in [`Endpoint::from_socket`](crate::endpoint::Endpoint::from_socket)),
+    /// so real deployments never need to pick one; this exists purely to
+    /// let a test dial out with a non-default version and confirm the
+    /// listener still negotiates it correctly.
+    #[cfg(test)]
+    pub(crate) quic_version: Option<u32>,
+    /// Whether [`QuicTransport`](crate::QuicTransport) rejects a dial or
+    /// listen address with protocol components it doesn't recognize past the
+    /// `/ip4|ip6/.../udp/PORT/quic` core, instead of ignoring them. See
+    /// [`Config::strict_multiaddr`].
+    pub(crate) strict_multiaddr: bool,
+}
+
+impl fmt::Debug for Config {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut debug_struct = f.debug_struct("Config");
+        debug_struct
+            .field("max_idle_timeout", &self.max_idle_timeout)
+            .field("close_timeout", &self.close_timeout)
+            .field(
+                "max_concurrent_stream_limit",
+                &self.max_concurrent_stream_limit,
+            )
+            .field("keep_alive_interval", &self.keep_alive_interval)
+            .field("max_connections", &self.max_connections)
+            .field("max_pending_connections", &self.max_pending_connections)
+            .field("max_connections_per_peer", &self.max_connections_per_peer)
+            .field("backlog_overflow_policy", &self.backlog_overflow_policy)
+            .field("require_client_auth", &self.require_client_auth)
+            .field("allow_migration", &self.allow_migration)
+            .field("datagram_send_buffer_size", &self.datagram_send_buffer_size)
+            .field("stream_write_buffer", &self.stream_write_buffer)
+            .field("initial_congestion_window", &self.initial_congestion_window)
+            .field("packet_threshold", &self.packet_threshold)
+            .field("time_threshold", &self.time_threshold)
+            .field("max_ack_delay", &self.max_ack_delay)
+            .field("auto_migrate", &self.auto_migrate)
+            .field("migration_probing", &self.migration_probing)
+            .field("freebind", &self.freebind)
+            .field("dscp", &self.dscp)
+            .field("offloads", &self.offloads)
+            .field("stream_scheduler", &self.stream_scheduler)
+            .field("stream_windows", &self.stream_windows)
+            .field("local_cid_len", &self.local_cid_len)
+            .field(
+                "max_unvalidated_handshake_bytes",
+                &self.max_unvalidated_handshake_bytes,
+            )
+            .field("max_udp_payload_size", &self.max_udp_payload_size)
+            .field(
+                "stateless_reset_key",
+                &self.stateless_reset_key.as_ref().map(|_| "[ elided ]"),
+            )
+            .field(
+                "alpn_protocols",
+                &self
+                    .alpn_protocols
+                    .iter()
+                    .map(|p| String::from_utf8_lossy(p).into_owned())
+                    .collect::<Vec<_>>(),
+            )
+            .field("cipher_suites", &self.cipher_suites)
+            .field("kx_groups", &self.kx_groups)
+            .field("strict_multiaddr", &self.strict_multiaddr);
+        #[cfg(feature = "qlog")]
+        debug_struct.field("qlog_dir", &self.qlog_dir);
+        debug_struct.finish()
+    }
+}
+
+impl Config {
+    /// Creates a new [`Config`] for the given identity keypair.
+    pub fn new(keypair: &Keypair) -> Self {
+        Self {
+            keypair: keypair.clone(),
+            max_idle_timeout: Duration::from_secs(10),
+            close_timeout: None,
+            max_concurrent_stream_limit: 128,
+            keep_alive_interval: Duration::from_secs(5),
+            max_connections: 100_000,
+            max_pending_connections: 128,
+            max_connections_per_peer: None,
+            backlog_overflow_policy: BacklogOverflowPolicy::Reject,
+            require_client_auth: true,
+            allow_migration: true,
+            on_datagram_dropped: None,
+            transmit_interceptor: None,
+            listener_dispatch_policy: None,
+            stream_scheduler: StreamScheduler::RoundRobin,
+            stream_windows: None,
+            datagram_send_buffer_size: 16,
+            stream_write_buffer: 0,
+            initial_congestion_window: None,
+            packet_threshold: None,
+            time_threshold: None,
+            max_ack_delay: None,
+            peer_verifier: None,
+            auto_migrate: false,
+            migration_probing: true,
+            #[cfg(feature = "qlog")]
+            qlog_dir: None,
+            freebind: false,
+            dscp: None,
+            offloads: Offloads {
+                gso: false,
+                gro: false,
+            },
+            stateless_reset_key: None,
+            alpn_protocols: vec![b"libp2p".to_vec()],
+            cipher_suites: CipherSuite::ALL.to_vec(),
+            kx_groups: KeyExchangeGroup::ALL.to_vec(),
+            local_cid_len: None,
+            max_unvalidated_handshake_bytes: None,
+            max_udp_payload_size: None,
+            #[cfg(test)]
+            connection_id_length: None,
+            #[cfg(test)]
+            quic_version: None,
+            strict_multiaddr: true,
+        }
+    }
+
+    /// Sets the maximum duration of inactivity before a connection is timed out.
+    pub fn max_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.max_idle_timeout = timeout;
+        self
+    }
+
+    /// Sets how long a closing connection is given to drain before its
+    /// handle is reclaimed regardless of whether the peer ever
+    /// acknowledged the close, bounding how much state a mass disconnect
+    /// against now-unreachable peers can leave behind; see
+    /// [`Config::close_timeout`].
+    pub fn close_timeout(mut self, timeout: Duration) -> Self {
+        self.close_timeout = Some(timeout);
+        self
+    }
+
+    /// Sets the maximum number of concurrent bidirectional streams the remote may open.
+    pub fn max_concurrent_stream_limit(mut self, limit: u32) -> Self {
+        self.max_concurrent_stream_limit = limit;
+        self
+    }
+
+    /// Sets the period at which keep-alive packets are sent to the remote.
+    pub fn keep_alive_interval(mut self, interval: Duration) -> Self {
+        self.keep_alive_interval = interval;
+        self
+    }
+
+    /// Sets the maximum number of simultaneously established connections.
+    ///
+    /// Once reached, further inbound handshakes are refused with a QUIC
+    /// `CONNECTION_REFUSED` until an existing connection closes.
+    pub fn max_connections(mut self, limit: u32) -> Self {
+        self.max_connections = limit;
+        self
+    }
+
+    /// Sets the maximum number of inbound connections that have completed
+    /// their handshake's first round-trip but have not yet been picked up by
+    /// the listener.
+    ///
+    /// Once reached, further incoming handshakes are dropped instead of
+    /// growing this backlog without bound, so a listener that falls behind on
+    /// accepting (or simply isn't polled for a while) can't be made to hold
+    /// an unbounded number of half-accepted connections in memory. A dropped
+    /// handshake attempt fails the same way it would against a host that
+    /// never replied at all.
+    pub fn max_pending_connections(mut self, limit: u32) -> Self {
+        self.max_pending_connections = limit;
+        self
+    }
+
+    /// Sets the maximum number of simultaneously established inbound
+    /// connections a single remote peer (identified by its [`PeerId`],
+    /// recovered from its certificate once the handshake completes) may
+    /// hold at once; `None` (the default) leaves peers unbounded aside from
+    /// [`Config::max_connections`]'s aggregate cap.
+    ///
+    /// Once a peer is at its limit, a further connection attempt from it is
+    /// refused - the same undifferentiated drop a rejected
+    /// [`Config::peer_verifier`] gets - once its certificate has been
+    /// checked, since the peer's identity isn't known any earlier than
+    /// that. This bounds how much of the aggregate connection limit a
+    /// single misbehaving or misconfigured peer can claim for itself, not
+    /// how many connection attempts it can make.
+    pub fn max_connections_per_peer(mut self, limit: usize) -> Self {
+        self.max_connections_per_peer = Some(limit);
+        self
+    }
+
+    /// Sets what happens to an inbound connection attempt that arrives once
+    /// [`Config::max_pending_connections`] is already full.
+    ///
+    /// Defaults to [`BacklogOverflowPolicy::Reject`], dropping the new
+    /// attempt. [`BacklogOverflowPolicy::DropOldest`] instead evicts the
+    /// longest-waiting connection in the backlog, trading its handshake
+    /// attempt for the new one; useful for deployments that would rather
+    /// give a listener that's fallen behind a chance to catch up on recent
+    /// arrivals than enforce strict first-come-first-served ordering.
+    pub fn backlog_overflow_policy(mut self, policy: BacklogOverflowPolicy) -> Self {
+        self.backlog_overflow_policy = policy;
+        self
+    }
+
+    /// Sets whether the server demands a client certificate during the
+    /// handshake.
+    ///
+    /// Defaults to `true`, matching libp2p's usual always-mutual-auth model.
+    /// Deployments that front the QUIC endpoint with anonymous clients plus
+    /// application-level authentication can set this to `false` to accept
+    /// handshakes from peers that don't present a certificate; peers that do
+    /// present one are still authenticated as usual.
+    pub fn require_client_auth(mut self, require: bool) -> Self {
+        self.require_client_auth = require;
+        self
+    }
+
+    /// Sets whether the server allows a client to migrate to a new address
+    /// mid-connection - e.g. after switching networks, or behind a NAT that
+    /// rebound its mapping.
+    ///
+    /// Defaults to `true`, matching `quinn_proto`'s own
+    /// [`ServerConfig::migration`](quinn_proto::ServerConfig::migration)
+    /// default. Some deployments behind a stateful firewall that only keeps
+    /// one 4-tuple per connection alive would rather a migrating client's
+    /// connection fail outright than have it silently pinned to a path the
+    /// firewall is about to start dropping anyway; setting this to `false`
+    /// has the server reject every packet from any address but the one the
+    /// handshake completed on, so such a connection times out instead of
+    /// migrating. Only meaningful on the listening side - `quinn_proto`
+    /// has a dialing client reject a path change from its side too,
+    /// unconditionally, regardless of this setting.
+    pub fn allow_migration(mut self, allow: bool) -> Self {
+        self.allow_migration = allow;
+        self
+    }
+
+    /// Registers a callback invoked whenever the socket layer reports a
+    /// datagram as undeliverable (e.g. one too large for the OS send buffer)
+    /// and it had to be dropped, with the destination and length of the
+    /// dropped datagram.
+    ///
+    /// This is a companion to the transport's own running drop counter, for
+    /// deployments that want to act on drops (e.g. metrics, logging) as they
+    /// happen rather than sampling the counter.
+    pub fn on_datagram_dropped(
+        mut self,
+        callback: impl Fn(SocketAddr, usize) + Send + Sync + 'static,
+    ) -> Self {
+        self.on_datagram_dropped = Some(Arc::new(callback));
+        self
+    }
+
+    /// Registers a callback consulted for every outgoing `quinn_proto`
+    /// transmit - both a connection's own packets and the endpoint's
+    /// connection-less replies (e.g. a stateless `CONNECTION_REFUSED`) -
+    /// just before it would otherwise reach the socket, letting it choose to
+    /// pass the transmit through or drop it.
+    ///
+    /// For chaos testing a connection's tolerance for loss, or feeding a
+    /// transmit count into external pacing/shaping logic. There is no way to
+    /// delay a transmit rather than drop it outright: the pipeline a
+    /// transmit travels through here (see [`Endpoint::poll_outgoing`](crate::endpoint::Endpoint::poll_outgoing))
+    /// is driven synchronously off socket readiness, with nothing of its own
+    /// to wake a caller back up once an artificial delay elapsed, so
+    /// faithfully reordering or holding back an individual datagram isn't
+    /// something this hook can offer yet.
+    pub fn transmit_interceptor(
+        mut self,
+        interceptor: impl Fn(&quinn_proto::Transmit) -> TransmitAction + Send + Sync + 'static,
+    ) -> Self {
+        self.transmit_interceptor = Some(Arc::new(interceptor));
+        self
+    }
+
+    /// Registers a policy that picks which of several
+    /// [`QuicListenStream`](crate::transport::QuicListenStream)s sharing one
+    /// endpoint (see [`QuicTransport::listen_on_shared_endpoint`](crate::QuicTransport::listen_on_shared_endpoint))
+    /// receives a given inbound connection, given its source address and the
+    /// number of listeners currently registered on that endpoint. The
+    /// returned index is taken modulo that count, so a simple hash need not
+    /// worry about the exact range.
+    ///
+    /// Defaults to always picking the first listener registered on an
+    /// endpoint, the same as before this existed: an endpoint with only the
+    /// one listener it's overwhelmingly likely to have is unaffected either
+    /// way, since there's nothing else for a policy to pick between.
+    pub fn listener_dispatch_policy(
+        mut self,
+        policy: impl Fn(SocketAddr, usize) -> usize + Send + Sync + 'static,
+    ) -> Self {
+        self.listener_dispatch_policy = Some(Arc::new(policy));
+        self
+    }
+
+    /// Sets how a connection orders streams with data ready to send, between
+    /// [`StreamScheduler::StrictPriority`] and [`StreamScheduler::RoundRobin`].
+    ///
+    /// Defaults to [`StreamScheduler::RoundRobin`]. See [`StreamScheduler`]
+    /// for why the two currently behave identically: `quinn_proto` 0.7.3
+    /// round-robins within a priority level unconditionally and exposes no
+    /// way for an embedder to turn that off, so this setting is accepted and
+    /// stored for forward compatibility but has no effect on either variant
+    /// yet.
+    pub fn stream_scheduler(mut self, scheduler: StreamScheduler) -> Self {
+        self.stream_scheduler = scheduler;
+        self
+    }
+
+    /// Sets the initial per-stream flow-control windows for bidirectional
+    /// streams this side opens, bidirectional streams the remote opens, and
+    /// unidirectional streams the remote opens, independently.
+    ///
+    /// `quinn_proto`'s single built-in default (~1.25 MB) applies the same
+    /// window to all three; this is for protocols that know their own
+    /// per-stream traffic patterns well enough to want, say, a small window
+    /// on a control bidi stream and a large one on a bulk-transfer uni
+    /// stream. See [`StreamWindows`] for why, on `quinn_proto` 0.7.3, the
+    /// three values given here don't yet produce three independently
+    /// enforced windows.
+    pub fn stream_windows(mut self, windows: StreamWindows) -> Self {
+        self.stream_windows = Some(windows);
+        self
+    }
+
+    /// Sets the maximum number of outgoing datagrams a
+    /// [`DatagramSink`](crate::DatagramSink) will hold queued up waiting to
+    /// be handed to the connection.
+    ///
+    /// `quinn_proto` itself bounds its outgoing datagram queue by total
+    /// bytes, not count, and silently drops the oldest queued datagram to
+    /// stay under that limit rather than rejecting a new one; that's the
+    /// wrong trade-off for a libp2p application expecting to hear about
+    /// backpressure. This bound is enforced independently, in front of
+    /// `quinn_proto`'s own queue: once it's reached, the sink applies
+    /// backpressure through the `Sink` API, and a caller that sends without
+    /// waiting for it gets back [`Error::DatagramQueueFull`](crate::Error::DatagramQueueFull)
+    /// instead of silently losing an older, already-queued datagram.
+    pub fn datagram_send_buffer_size(mut self, size: usize) -> Self {
+        self.datagram_send_buffer_size = size;
+        self
+    }
+
+    /// Sets how many bytes a substream's writes are coalesced into before
+    /// being handed to the connection; `0` (the default) hands every write
+    /// straight through, matching the behavior before this existed.
+    ///
+    /// Each write normally reaches `quinn_proto` immediately, which for a
+    /// protocol that issues many small writes on the same stream can mean
+    /// one packet per write instead of one per several. Buffering here first
+    /// trades that off against latency: bytes sit in this crate until
+    /// `bytes` accumulate or [`AsyncWriteExt::flush`](futures::AsyncWriteExt::flush)
+    /// is called explicitly, so a protocol that cares about a particular
+    /// write reaching the wire promptly should flush after it rather than
+    /// rely on the buffer filling up on its own.
+    pub fn stream_write_buffer(mut self, bytes: usize) -> Self {
+        self.stream_write_buffer = bytes;
+        self
+    }
+
+    /// Sets the initial congestion window, in bytes, used before the first
+    /// round-trip has given the congestion controller a chance to measure
+    /// the path.
+    ///
+    /// `quinn_proto`'s default (~14.4 KiB) is conservative for high-BDP
+    /// links, where it can take many round trips of slow-start growth before
+    /// a transfer reaches the available bandwidth. Raising it lets large
+    /// transfers ramp up faster, at the cost of fairness: a connection that
+    /// starts with a larger window claims a disproportionate share of a
+    /// bottleneck link's capacity from connections (ours or anyone else's)
+    /// that are still starting from the default, and an initial window big
+    /// enough to exceed the path's actual bandwidth-delay product risks
+    /// bursting straight into loss before congestion control gets a chance
+    /// to react. Prefer enlarging this only for links you know to be
+    /// high-bandwidth and high-latency, not as a default for general
+    /// internet-facing peers.
+    pub fn initial_congestion_window(mut self, window: u64) -> Self {
+        self.initial_congestion_window = Some(window);
+        self
+    }
+
+    /// Sets how many packets may arrive out of order ahead of an
+    /// unacknowledged one before that packet is declared lost, per
+    /// [RFC 9002 §6.1.1](https://www.rfc-editor.org/rfc/rfc9002#section-6.1.1).
+    ///
+    /// `quinn_proto`'s default of 3 assumes modest reordering; on paths that
+    /// reorder more heavily (e.g. multipath or load-balanced links), raising
+    /// it avoids declaring merely-delayed packets lost and retransmitting
+    /// them spuriously, at the cost of detecting genuine loss slightly
+    /// later.
+    pub fn packet_threshold(mut self, threshold: u32) -> Self {
+        self.packet_threshold = Some(threshold);
+        self
+    }
+
+    /// Sets how long, as a multiple of the smoothed round-trip time, an
+    /// unacknowledged packet is given to arrive out of order before it's
+    /// declared lost, per [RFC 9002 §6.1.2](https://www.rfc-editor.org/rfc/rfc9002#section-6.1.2).
+    ///
+    /// `quinn_proto`'s default of 9/8 (1.125×) assumes modest reordering;
+    /// raising it, like [`Config::packet_threshold`], trades slower loss
+    /// detection for fewer spurious retransmits on heavily reordering paths.
+    pub fn time_threshold(mut self, threshold: f32) -> Self {
+        self.time_threshold = Some(threshold);
+        self
+    }
+
+    /// Sets how long a received ack-eliciting packet may sit before it must
+    /// be acknowledged, trading ACK overhead against how quickly the peer
+    /// learns a packet arrived.
+    ///
+    /// A bulk transfer that doesn't care about latency can raise this to
+    /// coalesce more data into fewer ACK-only packets; an interactive flow
+    /// that wants the peer's congestion controller and loss detection to
+    /// react promptly should leave it at `quinn_proto`'s default (or lower
+    /// it).
+    ///
+    /// Currently a no-op: `quinn_proto` 0.7.3's `TransportConfig` has no
+    /// public setter for `max_ack_delay`, nor does it implement the QUIC ACK
+    /// frequency extension, so there is no hook this crate can wire the
+    /// value into yet. `delay` is taken now so the signature won't need to
+    /// change once a future `quinn_proto` exposes one.
+    pub fn max_ack_delay(mut self, delay: Duration) -> Self {
+        self.max_ack_delay = Some(delay);
+        self
+    }
+
+    /// Registers a callback consulted, after the standard libp2p certificate
+    /// verification has already recovered and authenticated the remote's
+    /// [`PeerId`], to accept or reject the connection on top of that.
+    ///
+    /// Returning an error fails the upgrade on the side that installed the
+    /// verifier, closing the underlying connection. Because QUIC's
+    /// cryptographic handshake completes independently on each side, a peer
+    /// dialling in does *not* see its own upgrade fail: it observes the
+    /// handshake succeed and only then sees the connection close, the same
+    /// way it would for any other post-handshake rejection. Useful for
+    /// pinning a fixed set of expected peers rather than trusting any libp2p
+    /// identity that presents a well-formed certificate.
+    pub fn peer_verifier(
+        mut self,
+        verifier: impl Fn(&PeerId, &rustls::Certificate) -> Result<(), Error> + Send + Sync + 'static,
+    ) -> Self {
+        self.peer_verifier = Some(Arc::new(verifier));
+        self
+    }
+
+    /// Sets whether a connection should automatically migrate onto a new
+    /// local address once the OS changes its default route out from under
+    /// it (e.g. a mobile device switching from Wi-Fi to cellular),
+    /// rather than requiring the embedder to detect the change and call a
+    /// manual rebind itself.
+    ///
+    /// Currently a no-op: this transport has no `rebind`-style API to move a
+    /// connection onto a new local address in the first place (see
+    /// [`QuicMuxer::path_validated`](crate::QuicMuxer::path_validated)), so
+    /// there is nothing here yet for a background watcher to trigger even if
+    /// it did detect a route change. The setting is stored so callers can
+    /// opt in now and get automatic migration once this transport grows a
+    /// rebind primitive to drive, without another `Config` change.
+    pub fn auto_migrate(mut self, enabled: bool) -> Self {
+        self.auto_migrate = enabled;
+        self
+    }
+
+    /// Sets whether a connection proactively probes alternate network paths,
+    /// the overhead a client on a metered connection may want to avoid,
+    /// rather than only validating a new path reactively, once the remote
+    /// address a packet actually arrives from has already changed.
+    ///
+    /// Currently a no-op: `quinn_proto` 0.7.3 never probes a path it hasn't
+    /// already observed traffic from in the first place - a `PATH_CHALLENGE`
+    /// is only ever sent to revalidate the *previous* path after a migration
+    /// has already happened, see `Connection::migrate` in its vendored
+    /// source - so there is no spontaneous probing on a stable connection to
+    /// disable here yet. The setting is stored so callers can opt out now
+    /// and have it actually suppress proactive probing once this transport
+    /// is built against a `quinn_proto` that grows one, without another
+    /// `Config` change.
+    pub fn migration_probing(mut self, enabled: bool) -> Self {
+        self.migration_probing = enabled;
+        self
+    }
+
+    /// Sets the directory a [qlog](https://quiclog.github.io/internet-drafts/draft-ietf-quic-qlog-main-schema.html)
+    /// file is written to per connection, for loading into `qvis` and
+    /// similar tools when diagnosing interop or performance problems.
+    ///
+    /// Currently a no-op: `quinn_proto` 0.7.3 (the version this transport is
+    /// built against) has no event-tracing or qlog hook of its own, unlike
+    /// the newer `quinn` releases that grew one, so there is nothing in this
+    /// transport that can observe the per-connection event stream a qlog
+    /// writer needs. The directory is stored so callers can start opting
+    /// in now and get qlog output automatically once this transport is
+    /// built against a `quinn_proto` that exposes one, without another
+    /// `Config` change. Gated behind the `qlog` feature in the meantime so
+    /// enabling it costs nothing while it remains inert.
+    #[cfg(feature = "qlog")]
+    pub fn qlog_dir(mut self, dir: PathBuf) -> Self {
+        self.qlog_dir = Some(dir);
+        self
+    }
+
+    /// Sets whether the listening socket is bound with `IP_FREEBIND`
+    /// (Linux) or `IP_BINDANY` (the BSDs), letting `Transport::listen_on`
+    /// succeed on an address not yet assigned to any local interface.
+    ///
+    /// Intended for failover setups where this process needs to start
+    /// listening on a floating virtual IP before that IP has migrated onto
+    /// the host; without this, binding such an address fails with
+    /// `EADDRNOTAVAIL` until it does. Binding still fails, with a clear
+    /// error, on platforms that support neither socket option.
+    pub fn freebind(mut self, enable: bool) -> Self {
+        self.freebind = enable;
+        self
+    }
+
+    /// Marks every packet sent from this endpoint's socket with `dscp` (a
+    /// 6-bit DSCP codepoint, so only its low 6 bits are meaningful) in the
+    /// IPv4 `IP_TOS` / IPv6 traffic class octet's upper 6 bits, leaving the
+    /// ECN bits untouched, so routers on a managed network can prioritize
+    /// this traffic (e.g. real-time media over QUIC) ahead of best-effort
+    /// flows.
+    ///
+    /// Applies to the whole socket rather than per-datagram: this
+    /// transport's socket I/O (see [`Endpoint`](crate::endpoint::Endpoint))
+    /// sends every packet through the same `send_to` call regardless of
+    /// which connection it belongs to, so there is currently nowhere to
+    /// plumb a per-transmit override even if a future libp2p use case wanted
+    /// to mark some connections differently from others on the same
+    /// endpoint.
+    pub fn dscp(mut self, dscp: u8) -> Self {
+        self.dscp = Some(dscp);
+        self
+    }
+
+    /// Sets which UDP segmentation offloads this endpoint's socket I/O may
+    /// use, for operators on kernels or virtualised NICs known to mishandle
+    /// GSO/GRO and corrupt packets as a result.
+    ///
+    /// Currently a no-op: this transport's socket I/O (see
+    /// [`Endpoint`](crate::endpoint::Endpoint)) always sends and receives
+    /// one datagram per syscall, via plain `send_to`/`recv_from`, and never
+    /// attempts `UDP_SEGMENT`/`UDP_GRO` or `sendmmsg`/`recvmmsg` batching in
+    /// the first place — so it already behaves as if both offloads were
+    /// disabled, on every kernel, regardless of what's passed here. The
+    /// setting is stored so callers already have the escape hatch wired up
+    /// and can flip it back on once this transport grows a batched I/O path
+    /// to offload in the first place, without another `Config` change.
+    pub fn offloads(mut self, offloads: Offloads) -> Self {
+        self.offloads = offloads;
+        self
+    }
+
+    /// Sets the private key `quinn_proto` uses to generate the stateless
+    /// reset tokens handed out during the handshake, and to recognize
+    /// datagrams addressed to a connection ID it no longer knows about as
+    /// belonging to a previous instance of the same endpoint rather than
+    /// noise.
+    ///
+    /// Left unset, each [`QuicTransport::listen_on`](crate::QuicTransport::listen_on)
+    /// picks a random key, so after a restart the new process can't produce
+    /// the reset token a still-connected remote remembers from the old one:
+    /// its packets for that connection are silently ignored until it times
+    /// out on its own. Persisting a fixed key across restarts (e.g. loading
+    /// it from disk, generating it once on first start) lets the new
+    /// process immediately send an authenticated reset for any connection
+    /// ID left over from before, so remotes notice the restart and
+    /// reconnect right away instead of waiting out the old connection's
+    /// idle timeout.
+    pub fn stateless_reset_key(mut self, key: Vec<u8>) -> Self {
+        self.stateless_reset_key = Some(key);
+        self
+    }
+
+    /// Overrides the application protocols offered and accepted during the
+    /// TLS handshake, replacing the default single-entry list containing
+    /// only the libp2p ALPN.
+    ///
+    /// An endpoint that listens with more than one protocol in this list
+    /// accepts connections from dialers that only offer any one of them,
+    /// each negotiating whichever is common to both sides; pair this with
+    /// [`QuicMuxer::negotiated_alpn`](crate::QuicMuxer::negotiated_alpn) on
+    /// the accepted connection to tell them apart and route each to the
+    /// handler for its protocol. This transport otherwise has no built-in
+    /// notion of "protocol", since libp2p normally negotiates that itself
+    /// over the substreams of a single connection via multistream-select;
+    /// this exists for interop with non-libp2p QUIC peers that rely on ALPN
+    /// for that instead.
+    ///
+    /// Dialing with a list that omits the libp2p ALPN means the connection
+    /// will fail to reach [`QuicTransport`](crate::QuicTransport)'s own
+    /// libp2p-specific peer identification if it somehow still completes;
+    /// this is intended for listeners to offer a superset of protocols, and
+    /// for dialers that want to pin down which one of those they negotiate.
+    pub fn alpn_protocols(mut self, protocols: Vec<Vec<u8>>) -> Self {
+        self.alpn_protocols = protocols;
+        self
+    }
+
+    /// Restricts the TLS 1.3 cipher suites this endpoint offers and accepts
+    /// during the handshake to `suites`, in the order given; the default is
+    /// every [`CipherSuite`], in `rustls`' own preference order.
+    ///
+    /// Unlike its sibling [`Config::set_kx_groups`], this one actually takes
+    /// effect: `rustls` 0.19.1 does expose a hook for restricting cipher
+    /// suites, just not key-exchange groups. A caller restricting TLS for
+    /// compliance needs both to get what it's actually asking for.
+    ///
+    /// Returns [`Error::InvalidTlsConfig`](crate::Error::InvalidTlsConfig) if
+    /// `suites` is empty: an endpoint with nothing left to offer could never
+    /// complete a handshake with anyone.
+    pub fn set_cipher_suites(mut self, suites: Vec<CipherSuite>) -> Result<Self, Error> {
+        if suites.is_empty() {
+            return Err(Error::InvalidTlsConfig(
+                "set_cipher_suites: suites must not be empty".into(),
+            ));
+        }
+        self.cipher_suites = suites;
+        Ok(self)
+    }
+
+    /// Restricts the named groups a connection's ECDHE key exchange may use
+    /// to `groups`, in the order given; the default is every
+    /// [`KeyExchangeGroup`], in `rustls`' own preference order.
+    ///
+    /// Currently a no-op: `rustls` 0.19.1's `ClientConfig`/`ServerConfig`
+    /// have a `ciphersuites` field (wired up by
+    /// [`Config::set_cipher_suites`]) but no matching hook for key-exchange
+    /// groups - `KeyExchange::supported_groups` in its vendored source
+    /// hardcodes X25519, then secp384r1, then secp256r1, with no way for a
+    /// caller to narrow that list. The setting is still validated and
+    /// stored so callers can restrict it now and have the restriction
+    /// actually take effect once this transport is built against a
+    /// `rustls` that exposes one, without another `Config` change.
+    ///
+    /// Returns [`Error::InvalidTlsConfig`](crate::Error::InvalidTlsConfig) if
+    /// `groups` is empty.
+    pub fn set_kx_groups(mut self, groups: Vec<KeyExchangeGroup>) -> Result<Self, Error> {
+        if groups.is_empty() {
+            return Err(Error::InvalidTlsConfig(
+                "set_kx_groups: groups must not be empty".into(),
+            ));
+        }
+        self.kx_groups = groups;
+        Ok(self)
+    }
+
+    /// Sets the length, in bytes, of the connection IDs this endpoint
+    /// generates for its own connections, in place of `quinn_proto`'s
+    /// default of 8.
+    ///
+    /// Must be no more than 20, the maximum QUIC allows; a larger value is
+    /// rejected once the endpoint is actually constructed, as
+    /// [`Error::InvalidConfig`](crate::Error::InvalidConfig). Useful for CID-based
+    /// routing schemes (e.g. a load balancer or anti-DoS box that steers
+    /// traffic by connection ID) that require every locally issued ID to be
+    /// a specific length; most deployments have no reason to move off the
+    /// default. Note that `quinn_proto` only tracks endpoint-wide connection
+    /// ID exhaustion (see [`Error::EndpointAtCapacity`](crate::Error::EndpointAtCapacity))
+    /// for lengths of 4 bytes or less, so pinning a short length here also
+    /// means accepting a much smaller originating-connection ceiling.
+    pub fn local_cid_len(mut self, len: u8) -> Self {
+        self.local_cid_len = Some(len);
+        self
+    }
+
+    /// Caps the total bytes a server will send on a connection before its
+    /// handshake is confirmed, as a hard backstop beyond `quinn_proto`'s own
+    /// anti-amplification factor (which already limits an unvalidated
+    /// server to sending no more than three times what it has received).
+    ///
+    /// That factor alone can still let a server emit a sizeable response to
+    /// a single small client packet - a large certificate chain, for
+    /// instance - before the client has proven it actually owns the address
+    /// it claims. Setting this gives a public-facing listener an absolute
+    /// ceiling on that response, independent of how much the client has
+    /// sent: once a connection's pre-handshake-confirmation bytes exceed the
+    /// limit, every further handshake transmit on it is dropped rather than
+    /// sent. `None` leaves the amplification factor as the only limit, which
+    /// is `quinn_proto`'s default.
+    ///
+    /// Only applies to the server side of a handshake: a dialling
+    /// connection already knows it reached the address it dialled, so it
+    /// has no comparable unvalidated-address window to bound.
+    pub fn max_unvalidated_handshake_bytes(mut self, limit: usize) -> Self {
+        self.max_unvalidated_handshake_bytes = Some(limit);
+        self
+    }
+
+    /// Clamps the UDP payload size of every packet this endpoint sends
+    /// below `quinn_proto`'s own default of 1480 bytes - useful on paths
+    /// that black-hole packets larger than some lower effective MTU without
+    /// returning an ICMP "too big" (e.g. a WireGuard or GRE tunnel), which
+    /// would otherwise surface as a connection that hangs rather than a
+    /// clean error.
+    ///
+    /// `quinn_proto` 0.7.3 (the version this crate is pinned to) predates
+    /// path MTU discovery: it never probes for a larger size than the one
+    /// fixed here, so there's no discovered packet size for this to clamp
+    /// down from - this value is simply the only packet size ever used.
+    ///
+    /// Since every dial and every listener already binds its own
+    /// [`Endpoint`](crate::endpoint::Endpoint) (see [`QuicTransport`](crate::QuicTransport)'s
+    /// own doc comment), a clamp that should only apply to one particular
+    /// dial - e.g. one known to cross a tunnel with the others - needs no
+    /// separate "per-dial" API: build that one dial's [`QuicTransport`] from
+    /// a [`Config`] with this set, and every other dial made from a
+    /// [`Config`] without it is unaffected.
+    ///
+    /// Rejected by `quinn_proto` as [`Error::InvalidConfig`](crate::Error::InvalidConfig)
+    /// if below its required minimum of 1200 bytes - the smallest a QUIC
+    /// packet must be able to be to stay interoperable - once the endpoint
+    /// is actually constructed.
+    pub fn max_udp_payload_size(mut self, size: u16) -> Self {
+        self.max_udp_payload_size = Some(size);
+        self
+    }
+
+    /// Shrinks the connection ID length so a test can drive an
+    /// [`Endpoint`](crate::endpoint::Endpoint) to
+    /// [`Error::EndpointAtCapacity`](crate::Error::EndpointAtCapacity) in a
+    /// reasonable number of connections.
+    #[cfg(test)]
+    pub(crate) fn connection_id_length(mut self, len: usize) -> Self {
+        self.connection_id_length = Some(len);
+        self
+    }
+
+    /// Picks which of `quinn_proto`'s supported draft QUIC versions this
+    /// [`Endpoint`](crate::endpoint::Endpoint) dials out with, so a test can
+    /// exercise a listener negotiating more than one version on the same
+    /// socket without actually needing separate `/quic` and `/quic-v1`
+    /// multiaddrs (which this transport can't represent; see
+    /// [`Endpoint::from_socket`](crate::endpoint::Endpoint::from_socket)).
+    #[cfg(test)]
+    pub(crate) fn quic_version(mut self, version: u32) -> Self {
+        self.quic_version = Some(version);
+        self
+    }
+
+    /// Sets whether a dial or listen [`Multiaddr`](libp2p_core::multiaddr::Multiaddr)
+    /// with protocol components this transport doesn't recognize past the
+    /// `/ip4|ip6/.../udp/PORT/quic` core is rejected outright (the default)
+    /// or accepted, with the unrecognized trailing components simply
+    /// ignored.
+    ///
+    /// Useful when such an address is handed to this transport by code that
+    /// also hands it to other transports or protocol handlers expecting
+    /// their own trailing components there (e.g. a future `/webrtc` or
+    /// similar protocol this crate's vendored `multiaddr` 0.13 doesn't even
+    /// have a [`Protocol`](libp2p_core::multiaddr::Protocol) variant for
+    /// yet) - strict mode would otherwise make every such address unusable
+    /// with this transport even though the `/ip4|ip6/.../udp/PORT/quic` core
+    /// it actually needs is right there.
+    pub fn strict_multiaddr(mut self, strict: bool) -> Self {
+        self.strict_multiaddr = strict;
+        self
+    }
+}
@@ -0,0 +1,1741 @@
+// Copyright 2021 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! The QUIC [`Endpoint`] multiplexes many connections over a single UDP
+//! socket. It owns the `quinn_proto::Endpoint` state machine, which is
+//! driven cooperatively: every [`QuicMuxer`](crate::muxer::QuicMuxer) that
+//! shares this endpoint polls it on each `poll_event`, mirroring the way
+//! `libp2p-yamux` drives its underlying I/O object from within the muxer.
+//! Incoming datagrams that belong to a connection other than the one
+//! currently polling are queued up for their owner and its task is woken.
+
+use crate::config::{BacklogOverflowPolicy, Config};
+use crate::muxer::Inner as MuxerInner;
+use crate::Error;
+use async_io::Async;
+use bytes::{Bytes, BytesMut};
+use futures::channel::mpsc;
+use futures::ready;
+use futures_timer::Delay;
+use parking_lot::Mutex;
+use quinn_proto::{ConnectionEvent, ConnectionHandle};
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::io;
+use std::net::{IpAddr, SocketAddr, UdpSocket as StdUdpSocket};
+use std::ops::RangeInclusive;
+use std::pin::Pin;
+use std::sync::{Arc, Weak};
+use std::task::{Context, Poll, Waker};
+use std::time::{Duration, Instant};
+
+/// `quinn_proto::DatagramEvent` is generic over the crypto backend; we only
+/// ever use the `rustls`-backed instantiation.
+type DatagramEvent = quinn_proto::DatagramEvent<quinn_proto::crypto::rustls::TlsSession>;
+
+/// The longest connection ID QUIC allows, mirroring `quinn_proto`'s own
+/// (private) `MAX_CID_SIZE`; used to validate
+/// [`Config::local_cid_len`](crate::Config::local_cid_len) ourselves since
+/// `quinn_proto` 0.7.3's [`RandomConnectionIdGenerator::new`](quinn_proto::RandomConnectionIdGenerator::new)
+/// only `debug_assert!`s on it rather than returning a [`ConfigError`](quinn_proto::ConfigError).
+const MAX_CID_SIZE: usize = 20;
+
+/// A fixed-window limit on how many new inbound connections
+/// [`Endpoint::dispatch_datagram`] admits per second, set at runtime through
+/// [`Endpoint::set_accept_rate_limit`].
+///
+/// A fixed window (reset every full second since the limit was last changed,
+/// rather than a continuously draining token bucket) is simpler and, for the
+/// purpose this exists for - blunting a handshake flood rather than smoothly
+/// shaping traffic - close enough: the worst case is up to twice the
+/// configured rate across a window boundary, not unbounded admission.
+struct AcceptRateLimiter {
+    max_per_window: u32,
+    window_start: Instant,
+    admitted_in_window: u32,
+}
+
+impl AcceptRateLimiter {
+    fn new(max_per_window: u32) -> Self {
+        AcceptRateLimiter {
+            max_per_window,
+            window_start: Instant::now(),
+            admitted_in_window: 0,
+        }
+    }
+
+    /// Whether one more connection can be admitted right now; if so, counts
+    /// it against the current window.
+    fn try_admit(&mut self) -> bool {
+        let now = Instant::now();
+        if now.duration_since(self.window_start) >= Duration::from_secs(1) {
+            self.window_start = now;
+            self.admitted_in_window = 0;
+        }
+        if self.admitted_in_window < self.max_per_window {
+            self.admitted_in_window += 1;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Arbitrates which connection's transmit gets the next shot at the socket
+/// once more than one is stalled on its write readiness at the same time,
+/// weighted by priority set through [`Endpoint::set_connection_priority`].
+///
+/// This has no effect while the socket keeps up with demand - the common
+/// case, since a send almost always succeeds immediately - and only
+/// matters once [`Endpoint::poll_outgoing`] starts seeing `WouldBlock`
+/// from more than one connection at once: the highest-priority one among
+/// those actually stalled gets to retry first, rather than whichever task
+/// the executor happens to poll. Connections that never set a priority
+/// default to `0`, the same as every other connection.
+#[derive(Default)]
+struct PriorityScheduler {
+    priorities: HashMap<ConnectionHandle, i32>,
+    /// Connections currently parked in [`Endpoint::poll_outgoing`]'s
+    /// `WouldBlock` branch, each holding the waker to promote once it's
+    /// this connection's turn.
+    stalled: HashMap<ConnectionHandle, Waker>,
+}
+
+impl PriorityScheduler {
+    fn set_priority(&mut self, handle: ConnectionHandle, priority: i32) {
+        self.priorities.insert(handle, priority);
+    }
+
+    fn priority(&self, handle: ConnectionHandle) -> i32 {
+        self.priorities.get(&handle).copied().unwrap_or(0)
+    }
+
+    /// Whether `handle` is clear to retry the socket this round: true
+    /// unless some other *currently stalled* connection outranks it, so a
+    /// connection that has never stalled always proceeds immediately.
+    fn is_leader(&self, handle: ConnectionHandle) -> bool {
+        let mine = self.priority(handle);
+        !self
+            .stalled
+            .keys()
+            .any(|&other| other != handle && self.priority(other) > mine)
+    }
+
+    /// Parks `handle` as stalled, to be woken once it becomes the leader.
+    fn park(&mut self, handle: ConnectionHandle, waker: Waker) {
+        self.stalled.insert(handle, waker);
+    }
+
+    /// Clears `handle`'s stall, e.g. once its send actually succeeds, and
+    /// wakes whichever remaining stalled connection is now the leader so it
+    /// gets an immediate turn instead of waiting on its own independent
+    /// write-readiness notification.
+    fn unstall(&mut self, handle: ConnectionHandle) {
+        self.stalled.remove(&handle);
+        if let Some(leader) = self
+            .stalled
+            .keys()
+            .max_by_key(|&&h| self.priority(h))
+            .copied()
+        {
+            if let Some(waker) = self.stalled.remove(&leader) {
+                waker.wake();
+            }
+        }
+    }
+
+    /// Drops every trace of `handle`, called once its connection is gone
+    /// so a later connection dealt the same (reused) handle doesn't
+    /// inherit its priority or stall state.
+    fn forget(&mut self, handle: ConnectionHandle) {
+        self.priorities.remove(&handle);
+        self.stalled.remove(&handle);
+    }
+}
+
+/// Per-connection state that [`Endpoint::drive`] threads across polls: a
+/// transmit the socket wasn't ready to send, kept so it isn't lost, and the
+/// timer for the connection's next internal deadline (handshake and ACK
+/// retransmission, idle timeout, ...), which `quinn_proto` otherwise expects
+/// its embedder to schedule.
+#[derive(Default)]
+pub(crate) struct Driver {
+    pending_transmit: Option<quinn_proto::Transmit>,
+    timer: Option<Delay>,
+    timer_deadline: Option<Instant>,
+    /// Running total of bytes sent on this connection while it was a server
+    /// handshake not yet confirmed; see [`Config::max_unvalidated_handshake_bytes`].
+    unvalidated_handshake_bytes_sent: usize,
+}
+
+impl Driver {
+    /// Whether a transmit produced by `quinn_proto` is still waiting for the
+    /// socket to become writable, i.e. whether the last call to
+    /// [`Endpoint::drive`] had more outbound work than it could flush.
+    pub(crate) fn has_pending_transmit(&self) -> bool {
+        self.pending_transmit.is_some()
+    }
+
+    /// Whether a timer is currently scheduled for this connection's next
+    /// internal deadline, i.e. whether [`Endpoint::drive`] has run at least
+    /// once for it and `quinn_proto` asked to be woken again.
+    pub(crate) fn has_scheduled_timer(&self) -> bool {
+        self.timer_deadline.is_some()
+    }
+}
+
+/// Snapshot of a single live connection's metadata, passed to the predicate
+/// given to [`Endpoint::close_connections`].
+#[derive(Debug, Clone)]
+pub struct ConnectionInfo {
+    /// The address of the remote peer.
+    pub remote_address: SocketAddr,
+    /// When this connection was registered with its endpoint, i.e. as soon
+    /// as its [`QuicMuxer`](crate::muxer::QuicMuxer) was constructed.
+    pub established_at: Instant,
+}
+
+/// Snapshot of a single not-yet-accepted inbound connection's metadata,
+/// returned by [`Endpoint::pending_connections`].
+#[derive(Debug, Clone)]
+pub struct PendingInfo {
+    /// The address of the remote peer.
+    pub remote_address: SocketAddr,
+    /// When this connection's first packet arrived, i.e. as soon as it was
+    /// queued up waiting for the listener to accept it.
+    pub received_at: Instant,
+}
+
+/// Endpoint-wide byte and datagram counters, returned by
+/// [`Endpoint::aggregate_stats`].
+///
+/// Cumulative since the endpoint was created, same as the per-connection
+/// [`quinn_proto::ConnectionStats`] these are summed from; callers wanting a
+/// rate (e.g. bytes per second) can sample this twice and divide by the
+/// elapsed time themselves.
+#[derive(Debug, Default, Clone, Copy)]
+#[non_exhaustive]
+pub struct EndpointStats {
+    /// Total bytes sent across every live connection registered with this
+    /// endpoint.
+    pub bytes_sent: u64,
+    /// Total bytes received across every live connection registered with
+    /// this endpoint.
+    pub bytes_received: u64,
+    /// Total UDP datagrams sent across every live connection registered
+    /// with this endpoint.
+    pub datagrams_sent: u64,
+    /// Total UDP datagrams received across every live connection
+    /// registered with this endpoint.
+    pub datagrams_received: u64,
+    /// See [`Endpoint::dropped_datagrams`]; included here so this one
+    /// struct is the top-line number an operator needs, without also
+    /// having to call that separately.
+    pub dropped_datagrams: u64,
+}
+
+/// Per-connection detail inside an [`EndpointStateDump`].
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct ConnectionStateDump {
+    /// The address of the remote peer.
+    pub remote_address: SocketAddr,
+    /// When this connection was registered with its endpoint, i.e. as soon
+    /// as its [`QuicMuxer`](crate::muxer::QuicMuxer) was constructed.
+    pub established_at: Instant,
+    /// Whether the handshake has not yet completed.
+    pub is_handshaking: bool,
+    /// Whether the connection has already been closed, by either side or by
+    /// `quinn_proto` itself after an error.
+    pub is_closed: bool,
+    /// Whether a transmit produced by this connection is still waiting for
+    /// the socket to become writable.
+    pub has_pending_transmit: bool,
+    /// Whether this connection's driver has a timer scheduled for its next
+    /// internal deadline - a rough proxy for "is anything still driving this
+    /// connection", since `quinn_proto` keeps one scheduled for as long as
+    /// the connection is alive.
+    pub driver_running: bool,
+}
+
+/// A one-shot, read-only snapshot of an endpoint's internals, returned by
+/// [`Endpoint::dump_state`], for diagnosing a suspected hang without
+/// disturbing whatever is actually driving the endpoint.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct EndpointStateDump {
+    /// One entry per live connection still registered with this endpoint,
+    /// i.e. whose [`QuicMuxer`](crate::muxer::QuicMuxer) hasn't been dropped
+    /// yet.
+    pub connections: Vec<ConnectionStateDump>,
+    /// See [`Endpoint::pending_connections`]; just the count here, since a
+    /// stuck handshake backlog is usually diagnosed by its size alone.
+    pub pending_inbound: usize,
+    /// Whether the underlying socket had a datagram waiting to be read at
+    /// the moment of this snapshot, checked with a non-consuming peek so it
+    /// doesn't compete with whichever task is actually driving the endpoint
+    /// for the next readability notification (see [`Async::poll_readable`]'s
+    /// caveat against two tasks polling the same handle concurrently).
+    pub socket_readable: bool,
+}
+
+/// An event describing a connection's lifecycle on an [`Endpoint`], as
+/// returned by [`Endpoint::events`].
+///
+/// Unlike polling a single [`QuicMuxer`](crate::muxer::QuicMuxer) via
+/// [`StreamMuxer::poll_event`](libp2p_core::muxing::StreamMuxer::poll_event),
+/// this multiplexes every connection sharing the endpoint into one stream,
+/// for a central consumer (e.g. a `NetworkBehaviour` tracking the connection
+/// table) that would rather not poll each one individually.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EndpointEvent {
+    /// A connection's handshake completed and it was handed out as a live
+    /// [`QuicMuxer`](crate::muxer::QuicMuxer).
+    ConnectionEstablished {
+        /// The address of the remote peer.
+        remote_address: SocketAddr,
+    },
+    /// A connection that had already been established was lost.
+    ConnectionClosed {
+        /// The address of the remote peer.
+        remote_address: SocketAddr,
+        /// Why `quinn_proto` considers the connection lost.
+        reason: quinn_proto::ConnectionError,
+    },
+    /// A connection's handshake failed before it was ever established.
+    HandshakeFailed {
+        /// The address of the remote peer.
+        remote_address: SocketAddr,
+        /// Why `quinn_proto` considers the connection lost.
+        reason: quinn_proto::ConnectionError,
+    },
+}
+
+/// A registered connection's metadata plus a weak handle to its
+/// [`QuicMuxer`](crate::muxer::QuicMuxer)'s guarded state, so
+/// [`Endpoint::close_connections`] can reach in and close it without keeping
+/// it alive on its own.
+struct ConnectionEntry {
+    info: ConnectionInfo,
+    muxer: Weak<Mutex<MuxerInner>>,
+}
+
+/// Cumulative time every caller across this endpoint has spent waiting to
+/// acquire [`Inner`]'s mutex, and how many times it's been acquired; see
+/// [`Endpoint::lock_stats`]. Lives outside the mutex it measures, since
+/// timing the wait to acquire a lock from inside that same lock is a
+/// contradiction.
+#[cfg(feature = "lock-contention-metrics")]
+#[derive(Default)]
+struct LockStats {
+    acquisitions: std::sync::atomic::AtomicU64,
+    wait_nanos: std::sync::atomic::AtomicU64,
+}
+
+#[cfg(feature = "lock-contention-metrics")]
+impl LockStats {
+    fn record(&self, wait: Duration) {
+        self.acquisitions
+            .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        self.wait_nanos.fetch_add(
+            wait.as_nanos().min(u128::from(u64::MAX)) as u64,
+            std::sync::atomic::Ordering::Relaxed,
+        );
+    }
+}
+
+/// A snapshot of time spent waiting to acquire the shared endpoint mutex,
+/// returned by [`Endpoint::lock_stats`]; only meaningful with the
+/// `lock-contention-metrics` feature enabled, since without it nothing
+/// records into it.
+#[cfg(feature = "lock-contention-metrics")]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct LockWaitStats {
+    /// Number of times the endpoint mutex has been acquired.
+    pub acquisitions: u64,
+    /// Total time spent waiting to acquire it, summed across every
+    /// acquisition.
+    pub total_wait: Duration,
+}
+
+/// Shared, reference-counted handle to a QUIC [`Endpoint`].
+#[derive(Clone)]
+pub(crate) struct Endpoint(
+    Arc<Mutex<Inner>>,
+    Arc<Async<StdUdpSocket>>,
+    #[cfg(feature = "lock-contention-metrics")] Arc<LockStats>,
+);
+
+struct Inner {
+    endpoint: quinn_proto::Endpoint,
+    /// `ConnectionEvent`s that arrived while a connection other than their
+    /// owner was the one reading the socket, waiting to be picked up by
+    /// their owning [`QuicMuxer`].
+    pending_events: HashMap<ConnectionHandle, VecDeque<ConnectionEvent>>,
+    /// New inbound connections, waiting to be picked up by the listener that
+    /// [`Inner::listener_dispatch_policy`] assigned each one to, alongside
+    /// when it arrived; see [`Endpoint::pending_connections`].
+    pending_connections: VecDeque<(ConnectionHandle, quinn_proto::Connection, Instant, usize)>,
+    /// See [`Config::max_pending_connections`].
+    max_pending_connections: u32,
+    /// See [`Config::backlog_overflow_policy`].
+    backlog_overflow_policy: BacklogOverflowPolicy,
+    /// Number of inbound connections lost to [`Inner::pending_connections`]
+    /// already being at [`Inner::max_pending_connections`]: the new arrival
+    /// under [`BacklogOverflowPolicy::Reject`], or the evicted oldest entry
+    /// under [`BacklogOverflowPolicy::DropOldest`]; see
+    /// [`Endpoint::refused_pending_connections`].
+    refused_pending_connections: u64,
+    /// Wakers of connections that found nothing addressed to them the last
+    /// time they drove the socket, to be woken once something arrives.
+    wakers: HashMap<ConnectionHandle, Waker>,
+    /// One slot per listener registered through [`Endpoint::register_listener`],
+    /// indexed the same way as the `usize` tag on [`Inner::pending_connections`]
+    /// entries; woken individually so one listener's task isn't spuriously
+    /// woken for a connection dispatched to another.
+    listener_wakers: Vec<Option<Waker>>,
+    /// Whether the listener at each index is still registered, indexed the
+    /// same way as [`Inner::listener_wakers`]. A `None` waker slot alone
+    /// can't distinguish "alive but not currently polling" from "gone for
+    /// good" - this can, so [`Endpoint::dispatch_target`] and
+    /// [`Endpoint::mark_listener_dropped`] know never to leave a connection
+    /// queued for an index nothing will ever poll again.
+    listener_alive: Vec<bool>,
+    /// See [`Config::listener_dispatch_policy`]; consulted by
+    /// [`Endpoint::dispatch_datagram`] to tag each newly admitted connection
+    /// with the index, into [`Inner::listener_wakers`], of the listener that
+    /// should pick it up.
+    listener_dispatch_policy: Option<crate::config::ListenerDispatchPolicy>,
+    /// A transmit the endpoint itself queued up (e.g. a stateless
+    /// `CONNECTION_REFUSED`, which is sent before any [`quinn_proto::Connection`]
+    /// exists to own it) that the socket wasn't ready to send, kept so it
+    /// isn't lost.
+    pending_transmit: Option<quinn_proto::Transmit>,
+    /// Number of datagrams the socket layer reported as undeliverable and
+    /// that had to be dropped; see [`Endpoint::dropped_datagrams`].
+    dropped_datagrams: u64,
+    /// See [`Config::on_datagram_dropped`].
+    on_datagram_dropped: Option<Arc<dyn Fn(SocketAddr, usize) + Send + Sync>>,
+    /// See [`Config::transmit_interceptor`]; consulted by [`Endpoint::poll_outgoing`].
+    transmit_interceptor: Option<crate::config::TransmitInterceptor>,
+    /// See [`Config::max_unvalidated_handshake_bytes`]; consulted by [`Endpoint::drive`].
+    max_unvalidated_handshake_bytes: Option<usize>,
+    /// See [`Config::datagram_send_buffer_size`]; read by every
+    /// [`DatagramSink`](crate::muxer::DatagramSink) backed by a connection
+    /// registered on this endpoint.
+    datagram_send_buffer_size: usize,
+    /// Every live connection registered through [`Endpoint::register_connection`],
+    /// for [`Endpoint::close_connections`] to enumerate. Entries whose
+    /// [`QuicMuxer`](crate::muxer::QuicMuxer) has since been dropped are
+    /// pruned lazily, as they're encountered.
+    connections: HashMap<ConnectionHandle, ConnectionEntry>,
+    /// See [`Config::max_connections_per_peer`].
+    max_connections_per_peer: Option<usize>,
+    /// Number of established connections currently reserved against
+    /// [`Inner::max_connections_per_peer`] for each peer with at least one,
+    /// via [`Endpoint::try_reserve_peer_connection`]; entries are removed
+    /// once their count reaches zero rather than left lingering at `0`, so
+    /// this only ever holds peers actually connected right now.
+    peer_connection_counts: HashMap<libp2p_core::PeerId, usize>,
+    /// The peer a reservation in [`Inner::peer_connection_counts`] was made
+    /// for, keyed by the connection handle it was made for, so
+    /// [`Endpoint::forget_connection`] knows which count to release once
+    /// that connection is gone.
+    connection_peers: HashMap<ConnectionHandle, libp2p_core::PeerId>,
+    /// Runtime cap on [`Inner::connections`]' size at the moment a new
+    /// inbound handshake arrives, set through
+    /// [`Endpoint::set_max_connections`]; `None` leaves admission entirely
+    /// to `quinn_proto`'s own [`Config::max_connections`](crate::Config::max_connections),
+    /// which was fixed for this endpoint's lifetime back when it was built.
+    /// This can only ever tighten that original ceiling, never raise it.
+    runtime_max_connections: Option<u32>,
+    /// Runtime cap on how many new inbound connections are admitted per
+    /// second, set through [`Endpoint::set_accept_rate_limit`]; `None`
+    /// leaves admission unrestricted by rate.
+    accept_rate_limiter: Option<AcceptRateLimiter>,
+    /// Number of inbound handshakes refused because
+    /// [`Inner::runtime_max_connections`] or [`Inner::accept_rate_limiter`]
+    /// was already exhausted when they arrived; see
+    /// [`Endpoint::refused_over_runtime_limit`].
+    refused_over_runtime_limit: u64,
+    /// Senders for every outstanding [`Endpoint::events`] stream; pruned
+    /// lazily, as a send to a dropped receiver is encountered.
+    event_subscribers: Vec<mpsc::UnboundedSender<EndpointEvent>>,
+    /// See [`Endpoint::set_connection_priority`]; consulted by
+    /// [`Endpoint::poll_outgoing`].
+    transmit_scheduler: PriorityScheduler,
+    /// Number of [`QuicListenStream`](crate::transport::QuicListenStream)s
+    /// registered on this endpoint (via [`Endpoint::register_listener`]) that
+    /// haven't been dropped yet. Reaching zero doesn't tighten the admission
+    /// caps ([`Inner::runtime_max_connections`], [`Inner::accept_rate_limiter`]),
+    /// which are still consulted exactly as normal; it only affects a
+    /// connection that would otherwise have been admitted, closing it
+    /// immediately instead of sitting in [`Inner::pending_connections`]
+    /// forever with nothing left to poll it out.
+    active_listeners: usize,
+}
+
+impl Endpoint {
+    /// Creates a new endpoint from an already-bound UDP socket, taking
+    /// ownership of it so that it can be shared by every connection dialled
+    /// or accepted through it. The socket may come from a fresh `bind` call
+    /// or, e.g., from systemd socket activation.
+    pub(crate) fn from_socket(config: &Config, socket: StdUdpSocket) -> Result<Self, Error> {
+        let server_config = crate::tls::make_server_config(config)?;
+        // `EndpointConfig::default()` already lists every version in
+        // `quinn_proto::DEFAULT_SUPPORTED_VERSIONS` in `supported_versions`,
+        // so this endpoint already accepts a dial from any peer using any
+        // of those draft versions and replies with a version negotiation
+        // packet otherwise — multiple *draft* QUIC versions are already
+        // negotiated transparently on one listener without anything further
+        // needed here.
+        //
+        // What's not possible is also serving real QUIC v1 (RFC 9000)
+        // alongside the drafts: `quinn_proto` 0.7.3 predates RFC 9000 and
+        // `DEFAULT_SUPPORTED_VERSIONS` contains no `0x0000_0001` entry at
+        // all, so there's no v1 wire behaviour to advertise even if this
+        // crate wanted to. And the `/quic-v1` multiaddr to describe such a
+        // listener couldn't be parsed or emitted either way:
+        // `multiaddr::Protocol` here has only `Protocol::Quic` (the `/quic`
+        // draft form), not a `/quic-v1` variant. Both the wire-level and
+        // the multiaddr-level support this would need are absent from the
+        // vendored dependencies, not just unwired in this crate.
+        let mut endpoint_config = quinn_proto::EndpointConfig::default();
+        #[cfg(test)]
+        if let Some(version) = config.quic_version {
+            endpoint_config
+                .supported_versions(quinn_proto::DEFAULT_SUPPORTED_VERSIONS.to_vec(), version)?;
+        }
+        if let Some(key) = &config.stateless_reset_key {
+            endpoint_config.reset_key(key)?;
+        }
+        if let Some(size) = config.max_udp_payload_size {
+            endpoint_config.max_udp_payload_size(size as u64)?;
+        }
+        let cid_len = config.local_cid_len.map(|len| len as usize);
+        #[cfg(test)]
+        let cid_len = config.connection_id_length.or(cid_len);
+        if let Some(len) = cid_len {
+            if len > MAX_CID_SIZE {
+                return Err(quinn_proto::ConfigError::OutOfBounds.into());
+            }
+            endpoint_config.cid_generator(move || {
+                Box::new(quinn_proto::RandomConnectionIdGenerator::new(len))
+            });
+        }
+        let endpoint =
+            quinn_proto::Endpoint::new(Arc::new(endpoint_config), Some(Arc::new(server_config)));
+
+        Ok(Endpoint(
+            Arc::new(Mutex::new(Inner {
+                endpoint,
+                pending_events: HashMap::new(),
+                pending_connections: VecDeque::new(),
+                max_pending_connections: config.max_pending_connections,
+                backlog_overflow_policy: config.backlog_overflow_policy,
+                refused_pending_connections: 0,
+                wakers: HashMap::new(),
+                listener_wakers: Vec::new(),
+                listener_alive: Vec::new(),
+                listener_dispatch_policy: config.listener_dispatch_policy.clone(),
+                pending_transmit: None,
+                dropped_datagrams: 0,
+                on_datagram_dropped: config.on_datagram_dropped.clone(),
+                transmit_interceptor: config.transmit_interceptor.clone(),
+                max_unvalidated_handshake_bytes: config.max_unvalidated_handshake_bytes,
+                datagram_send_buffer_size: config.datagram_send_buffer_size,
+                connections: HashMap::new(),
+                max_connections_per_peer: config.max_connections_per_peer,
+                peer_connection_counts: HashMap::new(),
+                connection_peers: HashMap::new(),
+                runtime_max_connections: None,
+                accept_rate_limiter: None,
+                refused_over_runtime_limit: 0,
+                event_subscribers: Vec::new(),
+                transmit_scheduler: PriorityScheduler::default(),
+                active_listeners: 0,
+            })),
+            Arc::new(Async::new(socket)?),
+            #[cfg(feature = "lock-contention-metrics")]
+            Arc::new(LockStats::default()),
+        ))
+    }
+
+    /// Locks [`Inner`], recording how long the wait took when the
+    /// `lock-contention-metrics` feature is enabled. Every acquisition of
+    /// that mutex across this file goes through here so
+    /// [`Endpoint::lock_stats`] reflects contention seen by the driver, by
+    /// `dial`/`listen_on`, and by inbound datagram dispatch alike, rather
+    /// than just whichever of those a caller remembered to instrument.
+    fn lock_inner(&self) -> parking_lot::MutexGuard<'_, Inner> {
+        #[cfg(feature = "lock-contention-metrics")]
+        {
+            let start = Instant::now();
+            let guard = self.0.lock();
+            self.2.record(start.elapsed());
+            guard
+        }
+        #[cfg(not(feature = "lock-contention-metrics"))]
+        {
+            self.0.lock()
+        }
+    }
+
+    /// Cumulative count of, and time spent waiting to acquire, the shared
+    /// endpoint mutex since this endpoint was created; requires the
+    /// `lock-contention-metrics` feature. This is the number the docs on
+    /// [`Endpoint::dispatch_datagram`] point at when they say lock
+    /// contention "guides the sharding work" - a nonzero, growing wait time
+    /// under load is the signal that work would be worth doing.
+    #[cfg(feature = "lock-contention-metrics")]
+    pub(crate) fn lock_stats(&self) -> LockWaitStats {
+        LockWaitStats {
+            acquisitions: self
+                .2
+                .acquisitions
+                .load(std::sync::atomic::Ordering::Relaxed),
+            total_wait: Duration::from_nanos(
+                self.2.wait_nanos.load(std::sync::atomic::Ordering::Relaxed),
+            ),
+        }
+    }
+
+    /// Like [`Endpoint::from_socket`], but binds `addr` itself, synchronously.
+    ///
+    /// Each [`Transport::dial`](libp2p_core::Transport::dial) call already
+    /// creates its own [`Endpoint`] with its own socket, so binding it to a
+    /// specific `addr` rather than an unspecified, ephemeral one is exactly
+    /// how a multihomed host picks the source address a given dial goes out
+    /// from; see [`QuicTransport::dial_from`](crate::transport::QuicTransport::dial_from).
+    /// What isn't possible is steering *inbound* traffic or an
+    /// already-open connection's traffic this way: an [`Endpoint`] created
+    /// by [`QuicTransport::listen_on`](crate::transport::QuicTransport::listen_on)
+    /// is bound once and shares that single socket for the rest of its
+    /// life, same as any other connection sharing an already-established
+    /// [`Endpoint`].
+    pub(crate) fn new(config: &Config, addr: SocketAddr) -> Result<Self, Error> {
+        let socket = crate::transport::bind_socket(addr, config.freebind, config.dscp)?;
+        Self::from_socket(config, socket)
+    }
+
+    /// Like [`Endpoint::from_socket`], but binds `addr` itself and does so
+    /// as an `async fn`, for callers already inside an async context that
+    /// would rather `.await` a single uniform constructor than mix in a
+    /// blocking call of their own.
+    ///
+    /// Binding a UDP socket is a handful of cheap syscalls, not a network
+    /// round-trip like a DNS lookup, and this crate doesn't depend on any
+    /// executor's blocking-pool primitive to dispatch work to a background
+    /// thread — so unlike `from_socket`'s caller in [`QuicTransport::listen_on`](crate::transport::QuicTransport),
+    /// which binds on whatever thread calls it, this still runs the bind on
+    /// the calling task rather than off of it. It's async purely so callers
+    /// that only have an async context to construct an endpoint from don't
+    /// need to reach for `block_on` themselves.
+    ///
+    /// [`QuicTransport::listen_on`](crate::transport::QuicTransport) and
+    /// `dial` are themselves synchronous `Transport` trait methods, so
+    /// nothing in this crate calls this yet outside of tests; it's kept
+    /// `pub(crate)` rather than wired into those paths so it's ready the
+    /// day either grows an async counterpart.
+    #[allow(dead_code)]
+    pub(crate) async fn new_async(config: &Config, addr: SocketAddr) -> Result<Self, Error> {
+        Self::new(config, addr)
+    }
+
+    /// Like [`Endpoint::from_socket`], but binds `ip` itself and, rather
+    /// than a single fixed port, tries every port in `ports` in order and
+    /// returns the endpoint on the first one that binds successfully -
+    /// handy for running several instances on one host without each
+    /// needing its own pre-arranged port.
+    ///
+    /// If every port in `ports` is taken, returns the last bind error; if
+    /// `ports` is empty, returns [`Error::Io`] with [`io::ErrorKind::InvalidInput`].
+    #[allow(dead_code)]
+    pub(crate) fn new_in_range(
+        config: &Config,
+        ip: IpAddr,
+        ports: RangeInclusive<u16>,
+    ) -> Result<Self, Error> {
+        let mut last_err = None;
+        for port in ports {
+            match crate::transport::bind_socket(
+                SocketAddr::new(ip, port),
+                config.freebind,
+                config.dscp,
+            ) {
+                Ok(socket) => return Self::from_socket(config, socket),
+                Err(e) => last_err = Some(e),
+            }
+        }
+        Err(last_err
+            .unwrap_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "port range is empty"))
+            .into())
+    }
+
+    /// The local address this endpoint's socket is bound to.
+    pub(crate) fn local_addr(&self) -> Result<SocketAddr, Error> {
+        Ok(self.1.get_ref().local_addr()?)
+    }
+
+    /// Starts dialling `remote`, returning the new connection's handle and
+    /// `quinn_proto` state machine. The handshake itself progresses only
+    /// once the connection's [`QuicMuxer`](crate::muxer::QuicMuxer) is
+    /// polled and datagrams start flowing.
+    ///
+    /// Never attempts 0-RTT: the dial isn't given a
+    /// [`SessionTicketStore`](crate::tls::SessionTicketStore) to consult, so
+    /// there's nowhere for a previous session's ticket to have been cached.
+    /// See [`Endpoint::dial_with_session_tickets`] for that.
+    #[allow(dead_code)]
+    pub(crate) fn dial(
+        &self,
+        config: &Config,
+        remote: SocketAddr,
+    ) -> Result<(ConnectionHandle, quinn_proto::Connection), Error> {
+        self.dial_with_session_tickets(
+            config,
+            remote,
+            &Arc::new(crate::tls::SessionTicketStore::default()),
+        )
+    }
+
+    /// Like [`Endpoint::dial`], but consults (and updates) `session_tickets`
+    /// so that a ticket cached from an earlier connection - in this process
+    /// or, via [`QuicTransport::import_session_tickets`](crate::QuicTransport::import_session_tickets),
+    /// a previous one - lets this dial attempt 0-RTT instead of a full
+    /// handshake.
+    pub(crate) fn dial_with_session_tickets(
+        &self,
+        config: &Config,
+        remote: SocketAddr,
+        session_tickets: &Arc<crate::tls::SessionTicketStore>,
+    ) -> Result<(ConnectionHandle, quinn_proto::Connection), Error> {
+        let client_config = crate::tls::make_client_config(config, session_tickets.clone())?;
+        self.0
+            .lock()
+            .endpoint
+            .connect(client_config, remote, "l")
+            .map_err(|e| match e {
+                quinn_proto::ConnectError::TooManyConnections => Error::EndpointAtCapacity,
+                e => Error::Connect(e),
+            })
+    }
+
+    /// Registers a freshly constructed [`QuicMuxer`](crate::muxer::QuicMuxer)'s
+    /// connection so it can later be found and closed by
+    /// [`Endpoint::close_connections`].
+    pub(crate) fn register_connection(
+        &self,
+        handle: ConnectionHandle,
+        remote_address: SocketAddr,
+        inner: &Arc<Mutex<MuxerInner>>,
+    ) {
+        self.lock_inner().connections.insert(
+            handle,
+            ConnectionEntry {
+                info: ConnectionInfo {
+                    remote_address,
+                    established_at: Instant::now(),
+                },
+                muxer: Arc::downgrade(inner),
+            },
+        );
+        self.broadcast_event(EndpointEvent::ConnectionEstablished { remote_address });
+    }
+
+    /// Attempts to reserve one of `peer_id`'s slots under
+    /// [`Config::max_connections_per_peer`] for the inbound connection
+    /// identified by `handle`, returning whether it succeeded. On success,
+    /// the reservation is released automatically once `handle`'s connection
+    /// is forgotten (see [`Endpoint::forget_connection`]), so callers don't
+    /// need a matching release call of their own.
+    ///
+    /// Called from [`Upgrade`](crate::transport::Upgrade) right after the
+    /// remote's [`PeerId`](libp2p_core::PeerId) is recovered from its
+    /// certificate - the earliest point in the handshake it's known - so
+    /// unlike [`Config::max_pending_connections`], this can't turn away a
+    /// connection attempt before it's paid for its own handshake; it only
+    /// bounds how many of those handshakes a single peer gets to keep open
+    /// at once.
+    pub(crate) fn try_reserve_peer_connection(
+        &self,
+        handle: ConnectionHandle,
+        peer_id: libp2p_core::PeerId,
+    ) -> bool {
+        let mut inner = self.lock_inner();
+        if let Some(limit) = inner.max_connections_per_peer {
+            let count = inner
+                .peer_connection_counts
+                .get(&peer_id)
+                .copied()
+                .unwrap_or(0);
+            if count >= limit {
+                return false;
+            }
+        }
+        *inner.peer_connection_counts.entry(peer_id).or_insert(0) += 1;
+        inner.connection_peers.insert(handle, peer_id);
+        true
+    }
+
+    /// A stream of every [`EndpointEvent`] across every connection sharing
+    /// this endpoint - established, closed, or a handshake that failed -
+    /// for a central consumer that would rather multiplex the whole
+    /// endpoint than poll each [`QuicMuxer`](crate::muxer::QuicMuxer)
+    /// individually.
+    ///
+    /// Each call returns an independent stream starting from the moment
+    /// it's created; events broadcast before this was called are not
+    /// replayed.
+    pub(crate) fn events(&self) -> mpsc::UnboundedReceiver<EndpointEvent> {
+        let (sender, receiver) = mpsc::unbounded();
+        self.lock_inner().event_subscribers.push(sender);
+        receiver
+    }
+
+    /// Pushes `event` to every outstanding [`Endpoint::events`] stream,
+    /// dropping (not erroring) any whose receiver has since gone away.
+    pub(crate) fn broadcast_event(&self, event: EndpointEvent) {
+        let mut inner = self.lock_inner();
+        if inner.event_subscribers.is_empty() {
+            return;
+        }
+        inner
+            .event_subscribers
+            .retain(|sender| sender.unbounded_send(event.clone()).is_ok());
+    }
+
+    /// Closes every live connection for which `predicate` returns `true`,
+    /// with the given QUIC close code and reason, leaving the rest
+    /// untouched.
+    ///
+    /// For operations tooling that needs to act on many connections at once,
+    /// e.g. dropping every connection from a subnet being decommissioned, or
+    /// every connection older than some age.
+    pub(crate) fn close_connections(
+        &self,
+        predicate: impl Fn(&ConnectionInfo) -> bool,
+        code: quinn_proto::VarInt,
+        reason: Bytes,
+    ) {
+        let mut inner = self.lock_inner();
+
+        let mut dead = Vec::new();
+        let mut to_wake = Vec::new();
+        for (handle, entry) in inner.connections.iter() {
+            match entry.muxer.upgrade() {
+                Some(muxer) if predicate(&entry.info) => {
+                    muxer.lock().close(code, reason.clone());
+                    to_wake.push(*handle);
+                }
+                Some(_) => {}
+                None => dead.push(*handle),
+            }
+        }
+
+        for handle in dead {
+            inner.connections.remove(&handle);
+        }
+        for handle in to_wake {
+            if let Some(waker) = inner.wakers.remove(&handle) {
+                waker.wake();
+            }
+        }
+    }
+
+    /// Number of live connections currently registered with this endpoint.
+    ///
+    /// Cheaper than enumerating full [`ConnectionInfo`]s through
+    /// [`Endpoint::close_connections`] for callers (e.g. an admin dashboard)
+    /// that only need a count to poll frequently. Entries whose
+    /// [`QuicMuxer`](crate::muxer::QuicMuxer) has since been dropped are
+    /// pruned as they're encountered, so the result only ever reflects
+    /// connections still backed by a live muxer.
+    pub(crate) fn num_connections(&self) -> usize {
+        let mut inner = self.lock_inner();
+        let dead: Vec<ConnectionHandle> = inner
+            .connections
+            .iter()
+            .filter(|(_, entry)| entry.muxer.upgrade().is_none())
+            .map(|(handle, _)| *handle)
+            .collect();
+        for handle in dead {
+            inner.connections.remove(&handle);
+        }
+        inner.connections.len()
+    }
+
+    /// Endpoint-wide throughput, summed across every live connection's own
+    /// `quinn_proto` counters plus this endpoint's own socket-level
+    /// [`Endpoint::dropped_datagrams`] counter.
+    ///
+    /// For capacity-planning dashboards that want the top-line number
+    /// rather than per-connection detail; see [`EndpointStats`]. Connections
+    /// whose [`QuicMuxer`](crate::muxer::QuicMuxer) has since been dropped
+    /// contribute nothing, same as [`Endpoint::num_connections`].
+    pub(crate) fn aggregate_stats(&self) -> EndpointStats {
+        // Collect the live muxers and let go of the endpoint lock before
+        // locking any of them: `QuicMuxer::drive` locks in the opposite
+        // order (its own lock first, then the endpoint's, while forwarding
+        // events), so holding both at once here would risk deadlocking
+        // against a connection being driven concurrently.
+        let muxers: Vec<_> = {
+            let inner = self.lock_inner();
+            inner
+                .connections
+                .values()
+                .filter_map(|entry| entry.muxer.upgrade())
+                .collect()
+        };
+
+        let mut stats = EndpointStats {
+            dropped_datagrams: self.dropped_datagrams(),
+            ..EndpointStats::default()
+        };
+        for muxer in muxers {
+            let connection_stats = muxer.lock().stats();
+            stats.bytes_sent += connection_stats.udp_tx.bytes;
+            stats.bytes_received += connection_stats.udp_rx.bytes;
+            stats.datagrams_sent += connection_stats.udp_tx.datagrams;
+            stats.datagrams_received += connection_stats.udp_rx.datagrams;
+        }
+        stats
+    }
+
+    /// A one-shot snapshot of this endpoint's internals - every live
+    /// connection's remote address, handshake/close state and driver status,
+    /// plus the inbound handshake backlog size and the socket's own
+    /// readability - for diagnosing a suspected hang.
+    ///
+    /// Deliberately reads only state that already exists rather than, say,
+    /// polling the socket for writability or draining any queue: a
+    /// diagnostic that disturbed the very thing it was inspecting would be
+    /// worse than no diagnostic at all. Connections whose
+    /// [`QuicMuxer`](crate::muxer::QuicMuxer) has since been dropped are
+    /// omitted, same as [`Endpoint::aggregate_stats`].
+    pub(crate) fn dump_state(&self) -> EndpointStateDump {
+        // Same lock-ordering rationale as `aggregate_stats`: collect the
+        // live muxers and let go of the endpoint lock before locking any of
+        // them.
+        let (entries, pending_inbound) = {
+            let inner = self.lock_inner();
+            let entries: Vec<_> = inner
+                .connections
+                .values()
+                .filter_map(|entry| Some((entry.info.clone(), entry.muxer.upgrade()?)))
+                .collect();
+            (entries, inner.pending_connections.len())
+        };
+
+        let connections = entries
+            .into_iter()
+            .map(|(info, muxer)| {
+                let muxer = muxer.lock();
+                ConnectionStateDump {
+                    remote_address: info.remote_address,
+                    established_at: info.established_at,
+                    is_handshaking: muxer.is_handshaking(),
+                    is_closed: muxer.is_closed(),
+                    has_pending_transmit: muxer.has_pending_transmit(),
+                    driver_running: muxer.driver_is_running(),
+                }
+            })
+            .collect();
+
+        EndpointStateDump {
+            connections,
+            pending_inbound,
+            socket_readable: self.socket_readable_hint(),
+        }
+    }
+
+    /// Whether the socket had a datagram waiting to be read at this instant,
+    /// without consuming it or registering a waker: a plain non-blocking
+    /// peek, rather than [`Async::poll_readable`], which [`Endpoint::dump_state`]
+    /// must avoid since two tasks polling the same handle just keep waking
+    /// each other up.
+    fn socket_readable_hint(&self) -> bool {
+        match self.1.get_ref().peek(&mut []) {
+            Ok(_) => true,
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => false,
+            Err(_) => false,
+        }
+    }
+
+    /// Applies an `EndpointEvent` a connection emitted about its own
+    /// bookkeeping (e.g. a connection ID to retire, or that it has fully
+    /// drained and its slot can be freed), returning the `ConnectionEvent`
+    /// it may produce in turn for the same connection.
+    fn handle_endpoint_event(
+        &self,
+        handle: ConnectionHandle,
+        event: quinn_proto::EndpointEvent,
+    ) -> Option<ConnectionEvent> {
+        self.lock_inner().endpoint.handle_event(handle, event)
+    }
+
+    /// Clears every bit of state this endpoint keeps indexed by `handle`
+    /// once its connection has fully drained, just before `quinn_proto`
+    /// becomes free to hand that same handle out to a brand new connection;
+    /// see the call site in [`Endpoint::drive`].
+    fn forget_connection(&self, handle: ConnectionHandle) {
+        let mut inner = self.lock_inner();
+        inner.pending_events.remove(&handle);
+        inner.wakers.remove(&handle);
+        inner.transmit_scheduler.forget(handle);
+        if let Some(peer_id) = inner.connection_peers.remove(&handle) {
+            if let Some(count) = inner.peer_connection_counts.get_mut(&peer_id) {
+                *count -= 1;
+                if *count == 0 {
+                    inner.peer_connection_counts.remove(&peer_id);
+                }
+            }
+        }
+    }
+
+    /// Stops accepting new connections, refusing every future handshake
+    /// attempt with a QUIC `CONNECTION_REFUSED` while connections already
+    /// established (or in the process of being established) continue
+    /// unaffected. Unlike a full `shutdown`, this lets in-flight traffic
+    /// finish; there is no way back from this short of creating a new
+    /// [`Endpoint`].
+    pub(crate) fn stop_accepting(&self) {
+        self.lock_inner().endpoint.reject_new_connections();
+    }
+
+    /// Registers a new [`QuicListenStream`](crate::transport::QuicListenStream)
+    /// on this endpoint, returning the index it should pass to
+    /// [`Endpoint::poll_incoming`] and [`Endpoint::mark_listener_dropped`] to
+    /// address itself specifically - see [`Config::listener_dispatch_policy`]
+    /// for how an inbound connection picks which registered listener
+    /// receives it. Called once per listener, including the first, so a
+    /// freshly created endpoint always starts with none registered.
+    pub(crate) fn register_listener(&self) -> usize {
+        let mut inner = self.lock_inner();
+        inner.active_listeners += 1;
+        inner.listener_wakers.push(None);
+        inner.listener_alive.push(true);
+        inner.listener_wakers.len() - 1
+    }
+
+    /// Marks that the [`QuicListenStream`](crate::transport::QuicListenStream)
+    /// registered as `listener_index` is gone, so nothing will ever poll a
+    /// fresh [`Inner::pending_connections`] entry tagged for it out as a
+    /// `ListenerEvent::Upgrade`. Unlike [`Endpoint::stop_accepting`], this
+    /// doesn't refuse connections outright -
+    /// [`Config::max_connections`](crate::Config::max_connections) and
+    /// [`Endpoint::set_accept_rate_limit`] still decide admission exactly as
+    /// before - it only stops the *last* listener going away from leaving a
+    /// connection that would otherwise have been admitted queued forever
+    /// with nothing left to poll it out; see [`Endpoint::dispatch_datagram`].
+    ///
+    /// If other listeners are still sharing this endpoint,
+    /// [`Inner::pending_connections`] entries already tagged for
+    /// `listener_index` are reassigned to one of them - see
+    /// [`Endpoint::dispatch_target`] - rather than left behind for an index
+    /// nothing will ever poll again.
+    pub(crate) fn mark_listener_dropped(&self, listener_index: usize) {
+        let mut inner = self.lock_inner();
+        inner.active_listeners = inner.active_listeners.saturating_sub(1);
+        inner.listener_wakers[listener_index] = None;
+        inner.listener_alive[listener_index] = false;
+
+        if inner.active_listeners == 0 {
+            return;
+        }
+
+        let fallback = Self::next_alive_listener(&inner, listener_index);
+        let mut woken = false;
+        for entry in inner.pending_connections.iter_mut() {
+            if entry.3 == listener_index {
+                entry.3 = fallback;
+                woken = true;
+            }
+        }
+        if woken {
+            if let Some(waker) = inner.listener_wakers[fallback].take() {
+                waker.wake();
+            }
+        }
+    }
+
+    /// The first still-registered listener index at or after `from`,
+    /// wrapping around - used both to redistribute a dead listener's
+    /// already-queued connections in [`Endpoint::mark_listener_dropped`] and
+    /// as [`Endpoint::dispatch_target`]'s fallback when the dispatch policy
+    /// names a dead one. Panics if every listener is dead; callers are
+    /// expected to have already checked [`Inner::active_listeners`] is
+    /// nonzero.
+    fn next_alive_listener(inner: &Inner, from: usize) -> usize {
+        let listener_count = inner.listener_alive.len();
+        (0..listener_count)
+            .map(|offset| (from + offset) % listener_count)
+            .find(|&i| inner.listener_alive[i])
+            .expect("at least one listener is still alive")
+    }
+
+    /// Drives the endpoint's socket for the connection identified by
+    /// `handle`: applies any events already queued up for it, or, if none
+    /// are pending, reads and dispatches a single incoming datagram. Events
+    /// addressed to other connections are queued for them and their task is
+    /// woken; new inbound connections are queued for the listener.
+    pub(crate) fn poll_connection_event(
+        &self,
+        cx: &mut Context<'_>,
+        handle: ConnectionHandle,
+    ) -> Poll<Result<ConnectionEvent, Error>> {
+        loop {
+            let mut inner = self.lock_inner();
+            if let Some(event) = inner
+                .pending_events
+                .get_mut(&handle)
+                .and_then(VecDeque::pop_front)
+            {
+                return Poll::Ready(Ok(event));
+            }
+            inner.wakers.insert(handle, cx.waker().clone());
+            drop(inner);
+
+            match self.read_one_datagram(cx)? {
+                Poll::Ready(()) => continue,
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+
+    /// Drives the endpoint's socket on behalf of the listener registered as
+    /// `listener_index` (see [`Endpoint::register_listener`]), returning the
+    /// next inbound connection [`Config::listener_dispatch_policy`] assigned
+    /// to it once one has completed its handshake's first round-trip.
+    pub(crate) fn poll_incoming(
+        &self,
+        cx: &mut Context<'_>,
+        listener_index: usize,
+    ) -> Poll<Result<(ConnectionHandle, quinn_proto::Connection), Error>> {
+        loop {
+            let mut inner = self.lock_inner();
+            if let Some(pos) = inner
+                .pending_connections
+                .iter()
+                .position(|(_, _, _, target)| *target == listener_index)
+            {
+                let (handle, connection, _received_at, _target) =
+                    inner.pending_connections.remove(pos).expect("just found");
+                return Poll::Ready(Ok((handle, connection)));
+            }
+            inner.listener_wakers[listener_index] = Some(cx.waker().clone());
+            drop(inner);
+
+            match self.read_one_datagram(cx)? {
+                Poll::Ready(()) => continue,
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+
+    /// Reads a single datagram from the socket, if one is available, and
+    /// dispatches the resulting `quinn_proto` event to whichever connection
+    /// (or the listener) it belongs to.
+    fn read_one_datagram(&self, cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+        // Attempt the read before checking readiness, as recommended by
+        // `Async::read_with`: the socket is almost always readable when
+        // another connection's task has just drained the only pending
+        // datagram, and `poll_readable` only wakes its caller once per
+        // readability notification, so checking it first would needlessly
+        // wait for a fresh notification that may never come.
+        let mut buf = [0; 65535];
+        let (len, from) = loop {
+            match self.1.get_ref().recv_from(&mut buf) {
+                Ok(v) => break v,
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                    ready!(self.1.poll_readable(cx))?;
+                }
+                Err(e) => return Poll::Ready(Err(e.into())),
+            }
+        };
+        let local_ip = self.1.get_ref().local_addr().ok().map(|a| a.ip());
+        self.dispatch_datagram(from, local_ip, BytesMut::from(&buf[..len]));
+
+        // A refused handshake attempt (e.g. after `stop_accepting`) has no
+        // `Connection` to own its `CONNECTION_REFUSED` reply, so the
+        // endpoint queues it itself; flush it the same way a connection
+        // flushes its own transmits.
+        self.drain_endpoint_transmits(cx)
+    }
+
+    /// Hands `data` to `quinn_proto::Endpoint::handle` and routes the
+    /// resulting event the same way [`read_one_datagram`](Self::read_one_datagram)
+    /// does: to an existing connection's queue, or to the listener if it
+    /// starts a new one.
+    ///
+    /// An inbound attempt that arrives while `quinn_proto` itself is out of
+    /// connection ID space (the accept-side counterpart to
+    /// [`Error::EndpointAtCapacity`]) never reaches here at all: `handle`
+    /// already refuses it internally, sending `CONNECTION_REFUSED` the same
+    /// way it does past [`Config::max_connections`], without emitting any
+    /// [`DatagramEvent`] for this method to act on. The backpressure this
+    /// crate does apply on the accept side -
+    /// [`Config::max_pending_connections`]/[`Config::backlog_overflow_policy`],
+    /// below - bounds a different queue: connections `quinn_proto` has
+    /// already admitted but this crate's listener hasn't polled yet.
+    ///
+    /// This, along with the other brief lock acquisitions throughout this
+    /// file, is the only place [`Inner`]'s lock is held while anything
+    /// resembling real work happens, and even here it's just one `HashMap`
+    /// lookup/insert per datagram - the actual per-connection `quinn_proto`
+    /// work (handshake crypto, stream framing, congestion control) runs in
+    /// [`Endpoint::drive`] without this lock held at all, against a
+    /// `quinn_proto::Connection` that already lives behind its own
+    /// [`QuicMuxer`](crate::muxer::QuicMuxer)'s private lock. What's left
+    /// here is serialized because there's exactly one UDP socket and one
+    /// `quinn_proto::Endpoint` connection-ID table to demultiplex incoming
+    /// datagrams against; splitting that further would mean giving each
+    /// shard its own socket and connection-ID space, i.e. turning one
+    /// listener into several, which is a materially bigger change than
+    /// this crate's current one-socket-per-[`Endpoint`] design and out of
+    /// scope here. See `benches/endpoint_contention.rs` for a benchmark of
+    /// this lock's cost under many concurrent connections.
+    /// Picks which registered listener (see [`Endpoint::register_listener`])
+    /// a newly admitted connection from `from` is queued for, per
+    /// [`Config::listener_dispatch_policy`]. With zero or one listener
+    /// registered there's nothing to pick between, so this always returns
+    /// `0` without consulting the policy - the same index
+    /// [`Endpoint::poll_incoming`] uses when only the usual single listener
+    /// exists.
+    ///
+    /// If the policy (or the single-listener default) names an index whose
+    /// listener has since been dropped - see
+    /// [`Endpoint::mark_listener_dropped`] - falls back to the next
+    /// still-registered one instead, so a connection is never queued for an
+    /// index nothing will ever poll again. Callers must only call this once
+    /// at least one listener is confirmed alive, e.g. by checking
+    /// [`Inner::active_listeners`] first.
+    fn dispatch_target(inner: &Inner, from: SocketAddr) -> usize {
+        let listener_count = inner.listener_wakers.len();
+        let preferred = if listener_count <= 1 {
+            0
+        } else {
+            match &inner.listener_dispatch_policy {
+                Some(policy) => policy(from, listener_count) % listener_count,
+                None => 0,
+            }
+        };
+        if inner.listener_alive[preferred] {
+            preferred
+        } else {
+            Self::next_alive_listener(inner, preferred)
+        }
+    }
+
+    fn dispatch_datagram(
+        &self,
+        from: SocketAddr,
+        local_ip: Option<std::net::IpAddr>,
+        data: BytesMut,
+    ) {
+        let mut inner = self.lock_inner();
+        let event = inner
+            .endpoint
+            .handle(std::time::Instant::now(), from, local_ip, None, data);
+
+        match event {
+            Some((handle, DatagramEvent::NewConnection(connection))) => {
+                let over_runtime_max_connections = match inner.runtime_max_connections {
+                    Some(limit) => inner.connections.len() >= limit as usize,
+                    None => false,
+                };
+                let over_accept_rate_limit = match &mut inner.accept_rate_limiter {
+                    Some(limiter) => !limiter.try_admit(),
+                    None => false,
+                };
+                if over_runtime_max_connections || over_accept_rate_limit {
+                    // Same silent drop as the backlog-overflow `Reject` case
+                    // below: `connection` is never driven, so the handshake
+                    // attempt just fails the way it would against a host
+                    // that never replied, rather than this endpoint paying
+                    // to refuse it explicitly.
+                    inner.refused_over_runtime_limit += 1;
+                } else if inner.active_listeners == 0 {
+                    // Passed the admission caps above, but there's no
+                    // listener left to ever pick this up out of
+                    // `pending_connections` in the first place - same silent
+                    // drop as the two cases below, rather than growing a
+                    // backlog nothing will ever drain.
+                    inner.refused_pending_connections += 1;
+                } else if inner.pending_connections.len() < inner.max_pending_connections as usize {
+                    let target = Self::dispatch_target(&inner, from);
+                    inner.pending_connections.push_back((
+                        handle,
+                        connection,
+                        std::time::Instant::now(),
+                        target,
+                    ));
+                    if let Some(waker) = inner.listener_wakers[target].take() {
+                        waker.wake();
+                    }
+                } else if inner.backlog_overflow_policy == BacklogOverflowPolicy::DropOldest {
+                    // Evict the longest-waiting connection to make room; like
+                    // the `Reject` case below, it's dropped without ever
+                    // being driven, so its handshake attempt fails the same
+                    // way it would against a host that never replied at all.
+                    inner.pending_connections.pop_front();
+                    let target = Self::dispatch_target(&inner, from);
+                    inner.pending_connections.push_back((
+                        handle,
+                        connection,
+                        std::time::Instant::now(),
+                        target,
+                    ));
+                    inner.refused_pending_connections += 1;
+                    if let Some(waker) = inner.listener_wakers[target].take() {
+                        waker.wake();
+                    }
+                } else {
+                    // Dropping `connection` without ever driving it sends
+                    // nothing back to the remote; its handshake attempt is
+                    // left to fail the same way it would against a host that
+                    // never replied at all, rather than paying to keep
+                    // growing a backlog nothing is consuming.
+                    inner.refused_pending_connections += 1;
+                }
+            }
+            Some((handle, DatagramEvent::ConnectionEvent(event))) => {
+                inner
+                    .pending_events
+                    .entry(handle)
+                    .or_default()
+                    .push_back(event);
+                if let Some(waker) = inner.wakers.remove(&handle) {
+                    waker.wake();
+                }
+            }
+            None => {}
+        }
+    }
+
+    /// Feeds a single datagram into this endpoint's `quinn_proto::Endpoint`
+    /// as if it had just arrived from `from`, without going through a real
+    /// socket.
+    ///
+    /// Exists so fuzz targets (and the regression test below) can exercise
+    /// packet parsing directly; any resulting connection or queued event is
+    /// still picked up the normal way, by polling the listener or an
+    /// affected [`QuicMuxer`](crate::muxer::QuicMuxer), but nothing here
+    /// sends a reply, so a test using this should drive the endpoint no
+    /// further than confirming `quinn_proto` didn't panic.
+    #[cfg(any(test, fuzzing))]
+    pub(crate) fn ingest_datagram(&self, from: SocketAddr, data: &[u8]) {
+        self.dispatch_datagram(from, None, BytesMut::from(data));
+    }
+
+    /// Sends every transmit the endpoint itself has queued up (as opposed to
+    /// one queued by a specific connection), retrying the one left over from
+    /// a previous call first.
+    fn drain_endpoint_transmits(&self, cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+        loop {
+            let mut inner = self.lock_inner();
+            let transmit = match inner.pending_transmit.take() {
+                Some(transmit) => transmit,
+                None => match inner.endpoint.poll_transmit() {
+                    Some(transmit) => transmit,
+                    None => return Poll::Ready(Ok(())),
+                },
+            };
+            drop(inner);
+
+            match self.poll_outgoing(cx, None, &transmit) {
+                Poll::Ready(Ok(())) => continue,
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                Poll::Pending => {
+                    self.lock_inner().pending_transmit = Some(transmit);
+                    return Poll::Pending;
+                }
+            }
+        }
+    }
+
+    /// Sets the weight [`Endpoint::poll_outgoing`]'s scheduler gives
+    /// `handle` once more than one connection sharing this endpoint is
+    /// stalled on the socket's write readiness at the same time; see
+    /// [`QuicMuxer::set_connection_priority`](crate::muxer::QuicMuxer::set_connection_priority).
+    pub(crate) fn set_connection_priority(&self, handle: ConnectionHandle, priority: i32) {
+        self.0
+            .lock()
+            .transmit_scheduler
+            .set_priority(handle, priority);
+    }
+
+    /// Parks `handle` as stalled in the transmit scheduler, as if
+    /// [`Endpoint::poll_outgoing`] had just seen `WouldBlock` for it; lets a
+    /// test exercise [`PriorityScheduler`]'s arbitration directly, since a
+    /// real loopback UDP send essentially never produces a `WouldBlock` to
+    /// saturate the socket with.
+    #[cfg(test)]
+    pub(crate) fn stall_for_test(&self, handle: ConnectionHandle, waker: Waker) {
+        self.lock_inner().transmit_scheduler.park(handle, waker);
+    }
+
+    /// Whether `handle` is currently [`PriorityScheduler::is_leader`] among
+    /// whichever connections [`Endpoint::stall_for_test`] has parked.
+    #[cfg(test)]
+    pub(crate) fn is_transmit_leader(&self, handle: ConnectionHandle) -> bool {
+        self.lock_inner().transmit_scheduler.is_leader(handle)
+    }
+
+    /// Sends a single pending transmit queued up by a connection, if any.
+    ///
+    /// Every transmit this endpoint produces, whether a connection's own
+    /// packet or a connection-less reply of the endpoint's own, passes
+    /// through here exactly once; see [`Config::transmit_interceptor`],
+    /// consulted first. `handle` is the connection the transmit belongs to,
+    /// for [`PriorityScheduler`] to arbitrate by, or `None` for a
+    /// connection-less transmit of the endpoint's own, which always
+    /// proceeds unscheduled.
+    ///
+    /// A send buffer full enough to return `WouldBlock` registers this
+    /// task's waker for write readiness the same way [`read_one_datagram`](Self::read_one_datagram)
+    /// does for read readiness, so a stalled transmit resumes as soon as
+    /// the socket drains rather than sitting in [`Driver::pending_transmit`]
+    /// until the next unrelated receive happens to re-poll it.
+    pub(crate) fn poll_outgoing(
+        &self,
+        cx: &mut Context<'_>,
+        handle: Option<ConnectionHandle>,
+        transmit: &quinn_proto::Transmit,
+    ) -> Poll<Result<(), Error>> {
+        let interceptor = self.lock_inner().transmit_interceptor.clone();
+        if let Some(interceptor) = interceptor {
+            if interceptor(transmit) == crate::config::TransmitAction::Drop {
+                return Poll::Ready(Ok(()));
+            }
+        }
+
+        // As in `read_one_datagram`, the send is attempted before checking
+        // writability, since the socket is almost always immediately
+        // writable and `poll_writable` only wakes its caller once per
+        // writability notification.
+        loop {
+            if let Some(handle) = handle {
+                let mut inner = self.lock_inner();
+                if !inner.transmit_scheduler.is_leader(handle) {
+                    inner.transmit_scheduler.park(handle, cx.waker().clone());
+                    return Poll::Pending;
+                }
+            }
+
+            match self
+                .1
+                .get_ref()
+                .send_to(&transmit.contents, transmit.destination)
+            {
+                Ok(_) => {
+                    if let Some(handle) = handle {
+                        self.lock_inner().transmit_scheduler.unstall(handle);
+                    }
+                    return Poll::Ready(Ok(()));
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                    if let Some(handle) = handle {
+                        self.0
+                            .lock()
+                            .transmit_scheduler
+                            .park(handle, cx.waker().clone());
+                    }
+                    ready!(self.1.poll_writable(cx))?;
+                }
+                Err(e) => {
+                    self.record_dropped_datagram(transmit.destination, transmit.contents.len());
+                    return Poll::Ready(Err(e.into()));
+                }
+            }
+        }
+    }
+
+    /// Number of datagrams the socket layer has reported as undeliverable
+    /// (e.g. one too large for the OS send buffer) and that were dropped,
+    /// since this endpoint was created.
+    pub(crate) fn dropped_datagrams(&self) -> u64 {
+        self.lock_inner().dropped_datagrams
+    }
+
+    /// Number of inbound connections lost to [`Config::max_pending_connections`]
+    /// already being reached when they arrived, since this endpoint was
+    /// created; see [`Config::backlog_overflow_policy`] for which connection
+    /// that cost under each policy.
+    pub(crate) fn refused_pending_connections(&self) -> u64 {
+        self.lock_inner().refused_pending_connections
+    }
+
+    /// Tightens or loosens, with immediate effect, how many live connections
+    /// this endpoint admits beyond what it was originally configured with;
+    /// `None` removes the runtime cap entirely, leaving admission to
+    /// `quinn_proto`'s own fixed [`Config::max_connections`](crate::Config::max_connections)
+    /// ceiling.
+    ///
+    /// Applies only to handshakes that arrive from now on - connections
+    /// already live, or already sitting in the accept backlog, are
+    /// unaffected either way, even if lowering the cap puts their count over
+    /// it. Since [`Config::max_connections`](crate::Config::max_connections)
+    /// was baked into this endpoint's `quinn_proto::ServerConfig` back when
+    /// it was built and `quinn_proto` 0.7.3 exposes no way to change that
+    /// after the fact, `limit` can only ever tighten that original ceiling;
+    /// a `limit` above it has no effect beyond that ceiling.
+    pub(crate) fn set_max_connections(&self, limit: Option<u32>) {
+        self.lock_inner().runtime_max_connections = limit;
+    }
+
+    /// Tightens or loosens, with immediate effect, how many new inbound
+    /// connections this endpoint admits per second; `None` removes the rate
+    /// limit entirely. See [`AcceptRateLimiter`] for why this is a
+    /// fixed-window count rather than a continuously draining token bucket.
+    ///
+    /// Applies only to admission decisions made from now on; a connection
+    /// already admitted keeps running regardless of a lowered limit
+    /// afterwards.
+    pub(crate) fn set_accept_rate_limit(&self, per_second: Option<u32>) {
+        self.lock_inner().accept_rate_limiter = per_second.map(AcceptRateLimiter::new);
+    }
+
+    /// Number of inbound handshakes refused because the runtime limit set
+    /// through [`Endpoint::set_max_connections`] or
+    /// [`Endpoint::set_accept_rate_limit`] was already exhausted when they
+    /// arrived, since this endpoint was created. Counts both knobs together,
+    /// the same way [`Endpoint::refused_pending_connections`] counts both of
+    /// its policies together.
+    pub(crate) fn refused_over_runtime_limit(&self) -> u64 {
+        self.lock_inner().refused_over_runtime_limit
+    }
+
+    /// Metadata of every inbound connection queued up waiting to be picked up
+    /// by the listener registered as `listener_index`, right now, oldest
+    /// first.
+    ///
+    /// For debugging connections that stall before ever reaching
+    /// [`ListenerEvent::Upgrade`](libp2p_core::transport::ListenerEvent::Upgrade):
+    /// a growing, long-lived backlog here points at the listener not being
+    /// polled often enough, rather than at the handshakes themselves.
+    pub(crate) fn pending_connections(&self, listener_index: usize) -> Vec<PendingInfo> {
+        self.0
+            .lock()
+            .pending_connections
+            .iter()
+            .filter(|(_, _, _, target)| *target == listener_index)
+            .map(|(_, connection, received_at, _)| PendingInfo {
+                remote_address: connection.remote_address(),
+                received_at: *received_at,
+            })
+            .collect()
+    }
+
+    /// Closes and drains every inbound connection still queued up waiting to
+    /// be picked up by the listener registered as `listener_index`, so
+    /// dropping a [`QuicListenStream`](crate::transport::QuicListenStream)
+    /// doesn't silently orphan handshakes nobody is ever going to deliver;
+    /// see its `Drop` impl, the only caller. Connections tagged for a
+    /// different listener sharing this endpoint are left untouched.
+    pub(crate) fn close_pending_connections(&self, listener_index: usize) {
+        let pending: Vec<_> = {
+            let mut inner = self.lock_inner();
+            let mut mine = Vec::new();
+            let mut rest = VecDeque::new();
+            for entry in inner.pending_connections.drain(..) {
+                if entry.3 == listener_index {
+                    mine.push(entry);
+                } else {
+                    rest.push_back(entry);
+                }
+            }
+            inner.pending_connections = rest;
+            mine
+        };
+
+        let waker = futures::task::noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        for (handle, mut connection, _received_at, _target) in pending {
+            connection.close(
+                std::time::Instant::now(),
+                quinn_proto::VarInt::from_u32(0),
+                Default::default(),
+            );
+            let _ = self.drive(&mut cx, handle, &mut connection, &mut Driver::default());
+        }
+    }
+
+    /// See [`Config::datagram_send_buffer_size`].
+    pub(crate) fn datagram_send_buffer_size(&self) -> usize {
+        self.lock_inner().datagram_send_buffer_size
+    }
+
+    /// Number of inbound connections queued up waiting to be picked up by
+    /// the listener, right now.
+    #[cfg(test)]
+    pub(crate) fn pending_connections_len(&self) -> usize {
+        self.lock_inner().pending_connections.len()
+    }
+
+    /// Handles of the inbound connections queued up waiting to be picked up
+    /// by the listener, right now, oldest first; lets a test tell which
+    /// connections [`BacklogOverflowPolicy::DropOldest`] kept versus evicted.
+    #[cfg(test)]
+    pub(crate) fn pending_connection_handles(&self) -> Vec<ConnectionHandle> {
+        self.0
+            .lock()
+            .pending_connections
+            .iter()
+            .map(|(handle, _, _, _)| *handle)
+            .collect()
+    }
+
+    /// Bumps the dropped-datagram counter and, if one is registered, notifies
+    /// [`Config::on_datagram_dropped`]'s callback that `len` bytes addressed
+    /// to `destination` could not be handed to the socket.
+    fn record_dropped_datagram(&self, destination: SocketAddr, len: usize) {
+        let mut inner = self.lock_inner();
+        inner.dropped_datagrams += 1;
+        let callback = inner.on_datagram_dropped.clone();
+        drop(inner);
+        if let Some(callback) = callback {
+            callback(destination, len);
+        }
+    }
+
+    /// Applies every event queued up for `connection`, flushes every
+    /// transmit it has queued up in turn, and fires its next internal
+    /// deadline (e.g. a loss-detection or idle timeout) once it elapses,
+    /// looping until none of that makes further progress without blocking.
+    ///
+    /// Shared by [`QuicMuxer`](crate::muxer::QuicMuxer) and
+    /// [`Upgrade`](crate::transport::Upgrade), which each drive their
+    /// connection's handshake and traffic the same way.
+    pub(crate) fn drive(
+        &self,
+        cx: &mut Context<'_>,
+        handle: ConnectionHandle,
+        connection: &mut quinn_proto::Connection,
+        driver: &mut Driver,
+    ) -> Poll<Result<(), Error>> {
+        let max_unvalidated_handshake_bytes = self.lock_inner().max_unvalidated_handshake_bytes;
+
+        loop {
+            let mut progress = false;
+
+            loop {
+                match self.poll_connection_event(cx, handle) {
+                    Poll::Ready(Ok(event)) => {
+                        connection.handle_event(event);
+                        progress = true;
+                    }
+                    Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                    Poll::Pending => break,
+                }
+            }
+
+            // Forward events the connection emits about its own bookkeeping
+            // (new connection IDs to issue, retired ones, and, once closed,
+            // `Drained` so the endpoint frees its slot) back to the shared
+            // endpoint; a `Drained` connection is otherwise never removed
+            // from the endpoint's live-connection count.
+            while let Some(event) = connection.poll_endpoint_events() {
+                let drained = event.is_drained();
+                if let Some(event) = self.handle_endpoint_event(handle, event) {
+                    connection.handle_event(event);
+                }
+                if drained {
+                    // `quinn_proto` frees `handle`'s slot right as it emits
+                    // this, and may hand it straight back out to the very
+                    // next `dial`/inbound handshake. Without this, a stale
+                    // `pending_events`/`wakers` entry left over from this
+                    // connection would still be sitting under the same key,
+                    // ready to be misdelivered into (or interleaved with)
+                    // whichever connection reuses it next; `is_drained` is
+                    // `quinn_proto`'s own deterministic signal for exactly
+                    // this moment, so there's no need to track a generation
+                    // counter of our own alongside it.
+                    self.forget_connection(handle);
+                }
+                progress = true;
+            }
+
+            loop {
+                let transmit = match driver.pending_transmit.take() {
+                    Some(transmit) => transmit,
+                    None => match connection.poll_transmit(Instant::now()) {
+                        Some(transmit) => transmit,
+                        None => break,
+                    },
+                };
+
+                if connection.side().is_server() && connection.is_handshaking() {
+                    if let Some(max) = max_unvalidated_handshake_bytes {
+                        driver.unvalidated_handshake_bytes_sent += transmit.contents.len();
+                        if driver.unvalidated_handshake_bytes_sent > max {
+                            // Drop it on the floor rather than sending: the
+                            // cap exists precisely so a buggy or malicious
+                            // client can't coax more out of us while its
+                            // address is still unvalidated.
+                            progress = true;
+                            continue;
+                        }
+                    }
+                }
+
+                match self.poll_outgoing(cx, Some(handle), &transmit) {
+                    Poll::Ready(Ok(())) => progress = true,
+                    Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                    Poll::Pending => {
+                        driver.pending_transmit = Some(transmit);
+                        break;
+                    }
+                }
+            }
+
+            match connection.poll_timeout() {
+                Some(deadline) => {
+                    // Only re-arm the timer when `quinn_proto` actually moved
+                    // the deadline: resetting it on every iteration (even
+                    // with an unchanged deadline) repeatedly re-registers it
+                    // with the background timer thread and can trigger
+                    // spurious wakeups, busy-looping this task.
+                    if driver.timer_deadline != Some(deadline) {
+                        let dur = deadline.saturating_duration_since(Instant::now());
+                        match &mut driver.timer {
+                            Some(timer) => timer.reset(dur),
+                            None => driver.timer = Some(Delay::new(dur)),
+                        }
+                        driver.timer_deadline = Some(deadline);
+                    }
+                    if Pin::new(driver.timer.as_mut().unwrap()).poll(cx).is_ready() {
+                        driver.timer_deadline = None;
+                        connection.handle_timeout(Instant::now());
+                        progress = true;
+                    }
+                }
+                None => {
+                    driver.timer = None;
+                    driver.timer_deadline = None;
+                }
+            }
+
+            if !progress {
+                return Poll::Ready(Ok(()));
+            }
+        }
+    }
+}
@@ -0,0 +1,193 @@
+// Copyright 2021 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use std::io;
+use thiserror::Error;
+
+/// Errors that can occur when using the QUIC transport.
+#[derive(Debug, Error)]
+pub enum Error {
+    /// I/O error on the underlying UDP socket.
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+
+    /// The remote's TLS certificate could not be verified.
+    #[error("handshake error: {0}")]
+    Handshake(String),
+
+    /// Building the `rustls` client or server config failed, before any
+    /// handshake bytes changed hands.
+    ///
+    /// Kept distinct from [`Error::Handshake`], which flattens its cause
+    /// into a string, so callers that walk the `source()` chain (e.g. via
+    /// `anyhow`/`eyre`) still reach the underlying [`rustls::TLSError`].
+    #[error("TLS configuration error: {0}")]
+    Tls(#[source] rustls::TLSError),
+
+    /// The QUIC connection was closed or reset by the peer or the local endpoint.
+    #[error("connection error: {0}")]
+    Connection(#[from] quinn_proto::ConnectionError),
+
+    /// [`Endpoint::dial`](crate::endpoint::Endpoint::dial) was rejected by
+    /// `quinn_proto` itself - e.g. an invalid remote address or DNS name -
+    /// rather than by anything specific to this crate.
+    ///
+    /// Kept distinct from [`Error::Handshake`] for the same reason as
+    /// [`Error::Tls`]: so `source()` reaches the underlying
+    /// [`quinn_proto::ConnectError`] instead of a formatted string.
+    #[error("failed to start a QUIC connection: {0}")]
+    Connect(#[source] quinn_proto::ConnectError),
+
+    /// A stream-level error occurred.
+    #[error("stream error: {0}")]
+    Stream(String),
+
+    /// The peer reset this stream's send side with `RESET_STREAM`, carrying
+    /// the application error code it reset with.
+    ///
+    /// Distinct from [`Error::Stream`] so request-response protocols (and
+    /// anything else that assigns meaning to particular reset codes) can
+    /// match on the code directly instead of parsing it back out of a
+    /// formatted string.
+    #[error("stream reset by peer: code {0}")]
+    StreamReset(u64),
+
+    /// The peer stopped this stream's read side with `STOP_SENDING`, carrying
+    /// the application error code it stopped with, surfaced on this side's
+    /// next write.
+    ///
+    /// The write-side counterpart to [`Error::StreamReset`]: that's the
+    /// error code the peer handed this side's *read*, this is the error
+    /// code the peer handed this side's *write*.
+    #[error("peer stopped this stream's write side: code {0}")]
+    SendStopped(u64),
+
+    /// Sending an unreliable datagram failed.
+    #[error("datagram error: {0}")]
+    Datagram(String),
+
+    /// The endpoint has been shut down and can no longer be used.
+    #[error("the endpoint has been shut down")]
+    EndpointDriverAborted,
+
+    /// Every candidate address passed to [`QuicTransport::dial_any`](crate::QuicTransport::dial_any) failed.
+    #[error("every candidate address failed: {0}")]
+    DialAnyFailed(String),
+
+    /// A dialled connection was lost before its handshake completed, with no
+    /// indication the remote ever received anything, rather than for a
+    /// reason that implies the remote was reachable (e.g. a certificate it
+    /// rejected).
+    ///
+    /// Distinct from the other variants above, which are either local
+    /// configuration problems (a malformed multiaddr, a socket that
+    /// wouldn't bind) or signs of life from the remote (a handshake that
+    /// got far enough to fail on content). This one specifically means "this
+    /// address could not be reached directly", which callers can use as the
+    /// trigger to fall back to a relayed connection instead of retrying the
+    /// same address.
+    ///
+    /// [`Error::NoResponse`] is the stricter sibling of this variant: a
+    /// handshake that timed out without so much as one response packet
+    /// arriving, as opposed to one that timed out after the remote did
+    /// respond at least once.
+    #[error("direct connection failed: {0}")]
+    DirectConnectionFailed(String),
+
+    /// A dial's handshake timed out without ever receiving a single UDP
+    /// datagram back from the remote - the signature of `/quic` traffic
+    /// being silently dropped somewhere on the path (a firewall blocking
+    /// UDP outright, a NAT that never opens a return mapping) rather than a
+    /// remote that was reachable but timed out for a reason of its own.
+    ///
+    /// Callers that want to fall back from QUIC to a TCP-based transport
+    /// specifically when UDP looks blocked, rather than on every
+    /// [`Error::DirectConnectionFailed`], can key off this variant instead.
+    #[error("no response received from the remote before the handshake timed out")]
+    NoResponse,
+
+    /// [`QuicMuxer::datagram_sink`](crate::QuicMuxer::datagram_sink) was sent
+    /// a datagram while already holding [`Config::datagram_send_buffer_size`]
+    /// of them, queued up but not yet flushed to the socket.
+    #[error("outgoing datagram queue is full")]
+    DatagramQueueFull,
+
+    /// A deadline passed to [`SendStream::write_with_deadline`](crate::SendStream::write_with_deadline)
+    /// elapsed before the write could be accepted.
+    #[error("write timed out")]
+    WriteTimeout,
+
+    /// [`Config::stateless_reset_key`](crate::Config::stateless_reset_key)
+    /// was rejected by `quinn_proto`.
+    #[error("invalid configuration: {0}")]
+    InvalidConfig(#[from] quinn_proto::ConfigError),
+
+    /// [`Config::set_cipher_suites`](crate::Config::set_cipher_suites) or
+    /// [`Config::set_kx_groups`](crate::Config::set_kx_groups) was given an
+    /// empty list, or a cipher suite that TLS 1.3 (which QUIC mandates)
+    /// never negotiates.
+    #[error("invalid TLS configuration: {0}")]
+    InvalidTlsConfig(String),
+
+    /// The endpoint has exhausted its local connection ID space and cannot
+    /// originate any further connections until some of its existing ones
+    /// close.
+    ///
+    /// Surfaced only for dialling: an inbound connection attempt that
+    /// arrives once the endpoint is in this state is refused by
+    /// `quinn_proto` itself, at the protocol level, before this crate ever
+    /// sees it, the same way it refuses one past
+    /// [`Config::max_connections`](crate::Config::max_connections).
+    #[error("the endpoint cannot originate any further connections")]
+    EndpointAtCapacity,
+
+    /// An in-flight dial was cancelled through
+    /// [`AbortHandle::abort`](crate::AbortHandle::abort).
+    #[error("dial was aborted")]
+    Aborted,
+
+    /// [`QuicTransport::open_stream_to`](crate::QuicTransport::open_stream_to)'s
+    /// `timeout` elapsed before a new connection's handshake was confirmed.
+    #[error("dial timed out")]
+    DialTimedOut,
+
+    /// [`QuicTransport::dial_exclusive`](crate::QuicTransport::dial_exclusive)
+    /// was called for an address that another, not-yet-resolved
+    /// `dial_exclusive` call from the same
+    /// [`QuicTransport`](crate::QuicTransport) (or one of its [`Clone`]s) is
+    /// already dialling.
+    #[error("a dial to this address is already in progress")]
+    DialInProgress,
+
+    /// An inbound connection was refused because the peer presenting it
+    /// already has [`Config::max_connections_per_peer`](crate::Config::max_connections_per_peer)
+    /// established connections open.
+    #[error("peer already has the maximum number of connections open")]
+    TooManyConnectionsFromPeer,
+}
+
+impl From<Error> for io::Error {
+    fn from(e: Error) -> Self {
+        match e {
+            Error::Io(e) => e,
+            e => io::Error::other(e),
+        }
+    }
+}
@@ -0,0 +1,98 @@
+// Copyright 2021 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Implementation of the libp2p `Transport` trait for QUIC.
+//!
+//! Unlike the other transports in this repository, QUIC provides stream
+//! multiplexing and a secure channel as part of the protocol itself, so
+//! [`QuicTransport`] directly yields a [`QuicMuxer`] instead of a raw I/O
+//! object that still needs to be upgraded with `libp2p-noise`/`libp2p-mplex`
+//! and friends.
+//!
+//! # Usage
+//!
+//! [`QuicTransport`] is constructed from a [`Config`], which in turn is
+//! built from the local identity [`Keypair`](libp2p_core::identity::Keypair).
+//! The keypair is used to sign a self-signed TLS certificate that is
+//! presented during the QUIC handshake, allowing the remote peer to
+//! authenticate our [`PeerId`](libp2p_core::PeerId) without a separate
+//! `libp2p-noise` exchange.
+//!
+//! # Known limitations
+//!
+//! This crate is pinned to `quinn_proto` 0.7.3, which predates several
+//! pieces of QUIC behaviour the rest of this crate's API accepts
+//! configuration for. Each of the items below validates and stores the
+//! setting it's given - so a [`Config`] built against a newer `quinn_proto`
+//! would pick it up with no further change here - but it has no effect
+//! against 0.7.3 today. Each one documents this on its own `Config`/
+//! [`QuicMuxer`] item too; this list exists so the gap is visible without
+//! having to find every one of them first:
+//!
+//! - [`QuicMuxer::set_receive_window`]
+//! - [`Config::auto_migrate`]
+//! - [`QuicMuxer::path_validated`]
+//! - [`Config::qlog_dir`]
+//! - [`Config::max_ack_delay`]
+//! - [`QuicMuxer::half_rtt_write_available`]
+//! - [`Config::migration_probing`]
+//! - [`Config::set_kx_groups`]
+//! - [`QuicMuxer::local_connection_id`] / [`QuicMuxer::remote_connection_id`]
+
+mod certificate;
+mod config;
+mod endpoint;
+mod error;
+mod muxer;
+mod substream;
+mod tls;
+mod transport;
+
+pub use config::{
+    BacklogOverflowPolicy, CipherSuite, Config, KeyExchangeGroup, Offloads, StreamScheduler,
+    StreamWindows, TransmitAction,
+};
+pub use endpoint::{ConnectionInfo, EndpointEvent, EndpointStats, PendingInfo};
+pub use error::Error;
+pub use muxer::{
+    BiStream, DatagramSink, NegotiatedCrypto, QuicMuxer, ReadOutcome, RecvStream, SendStream,
+};
+pub use substream::Substream;
+pub use transport::{
+    socketaddr_to_quic_multiaddr, AbortHandle, DialAny, DialProgress, QuicListenStream,
+    QuicTransport,
+};
+
+/// Feeds `data` to the QUIC packet parser as though it had just arrived from
+/// `from`, without a real connection or even a remote peer on the other end.
+///
+/// Not part of the public API: exists only for `fuzz/fuzz_targets/ingest_datagram.rs`
+/// to reach [`endpoint::Endpoint::ingest_datagram`], which `cargo fuzz` can't
+/// call directly since the fuzz target is its own crate.
+#[doc(hidden)]
+#[cfg(fuzzing)]
+pub fn fuzz_ingest_datagram(from: std::net::SocketAddr, data: &[u8]) {
+    let socket = std::net::UdpSocket::bind((std::net::Ipv4Addr::UNSPECIFIED, 0))
+        .expect("binding an ephemeral UDP socket should never fail");
+    let config = Config::new(&libp2p_core::identity::Keypair::generate_ed25519());
+    let endpoint =
+        endpoint::Endpoint::from_socket(&config, socket).expect("endpoint setup should not fail");
+    endpoint.ingest_datagram(from, data);
+}
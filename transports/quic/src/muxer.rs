@@ -0,0 +1,1604 @@
+// Copyright 2021 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use crate::endpoint::{Driver, Endpoint};
+use crate::substream::Substream;
+use crate::{CipherSuite, Error};
+use bytes::Bytes;
+use futures::future::poll_fn;
+use futures::{AsyncRead, AsyncWrite, Sink, Stream};
+use futures_timer::Delay;
+use libp2p_core::muxing::{StreamMuxer, StreamMuxerEvent};
+use parking_lot::Mutex;
+use quinn_proto::crypto::Session;
+use quinn_proto::{ConnectionHandle, Dir, StreamId};
+use std::any::Any;
+use std::future::Future;
+use std::io;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::{Arc, OnceLock};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+/// A single QUIC connection, implementing [`StreamMuxer`].
+///
+/// Opening and accepting substreams, as well as sending and receiving on
+/// them, are all forwarded to the `quinn_proto::Connection` guarded by the
+/// inner mutex; `quinn_proto` itself is synchronous and poll-driven, which
+/// maps directly onto the [`StreamMuxer`] API.
+pub struct QuicMuxer {
+    endpoint: Endpoint,
+    inner: Arc<Mutex<Inner>>,
+    /// Application-defined user data, set via [`QuicMuxer::set_context`] and
+    /// read back via [`QuicMuxer::context`]. See those for why this is a
+    /// [`OnceLock`] rather than a plain `Mutex<Option<_>>`.
+    context: OnceLock<Box<dyn Any + Send + Sync>>,
+}
+
+/// The negotiated cryptographic parameters for a [`QuicMuxer`]'s TLS
+/// session; see [`QuicMuxer::negotiated_crypto`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NegotiatedCrypto {
+    /// The negotiated TLS 1.3 cipher suite.
+    pub cipher_suite: CipherSuite,
+}
+
+/// `pub(crate)` so [`Endpoint`](crate::endpoint::Endpoint) can hold a
+/// [`Weak`](std::sync::Weak) reference to it for
+/// [`Endpoint::close_connections`](crate::endpoint::Endpoint::close_connections),
+/// without needing to know anything else about [`QuicMuxer`].
+pub(crate) struct Inner {
+    connection: quinn_proto::Connection,
+    handle: ConnectionHandle,
+    driver: Driver,
+    /// See [`Config::max_idle_timeout`](crate::Config::max_idle_timeout);
+    /// read by [`QuicMuxer::effective_idle_timeout`].
+    max_idle_timeout: Duration,
+    /// See [`Config::close_timeout`](crate::Config::close_timeout); read by
+    /// [`StreamMuxer::close`].
+    close_timeout: Option<Duration>,
+    /// Set the first time [`StreamMuxer::close`] actually closes the
+    /// connection, to the point in time after which it gives up waiting for
+    /// draining to finish regardless of `quinn_proto`'s own closing timer;
+    /// `None` both before that and if [`Config::close_timeout`](crate::Config::close_timeout)
+    /// was never set.
+    close_deadline: Option<Instant>,
+    /// Set the first time [`StreamMuxer::poll_event`] observes
+    /// `quinn_proto`'s `Event::ConnectionLost`, to the reason it reported;
+    /// read back by [`QuicMuxer::close_reason`].
+    close_reason: Option<quinn_proto::ConnectionError>,
+    /// See [`Config::stream_write_buffer`](crate::Config::stream_write_buffer);
+    /// read by [`StreamMuxer::write_substream`] and [`StreamMuxer::flush_substream`].
+    stream_write_buffer: usize,
+    /// Set by [`QuicMuxer::on_bandwidth_change`]; checked every time this
+    /// connection is driven.
+    bandwidth_watch: Option<BandwidthWatch>,
+    /// When application data was last sent or received on this connection;
+    /// read by [`QuicMuxer::idle_duration`]. Set to the connection's
+    /// creation time initially, and bumped on every successful stream read
+    /// or write - deliberately not on every drive, so a connection kept
+    /// alive only by `quinn_proto`'s own keep-alives/ACKs still reports
+    /// growing idle time.
+    last_app_activity: Instant,
+}
+
+/// State backing [`QuicMuxer::on_bandwidth_change`].
+struct BandwidthWatch {
+    /// Fraction of the last-reported congestion window that the current one
+    /// must differ by, in either direction, before `callback` fires again.
+    threshold: f64,
+    last_reported_cwnd: u64,
+    callback: Arc<dyn Fn(u64) + Send + Sync>,
+}
+
+/// Converts a `quinn_proto::ReadError` encountered while reading a stream
+/// into our own [`Error`], singling out [`quinn_proto::ReadError::Reset`] as
+/// [`Error::StreamReset`] so the peer's application error code survives
+/// structured rather than being flattened into [`Error::Stream`]'s message.
+fn read_error(e: quinn_proto::ReadError) -> Error {
+    match e {
+        quinn_proto::ReadError::Reset(code) => Error::StreamReset(code.into()),
+        e => Error::Stream(e.to_string()),
+    }
+}
+
+/// Converts a `quinn_proto::WriteError` encountered while writing a stream
+/// into our own [`Error`], singling out [`quinn_proto::WriteError::Stopped`]
+/// as [`Error::SendStopped`] so the peer's `STOP_SENDING` application error
+/// code survives structured rather than being flattened into
+/// [`Error::Stream`]'s message, the same way [`read_error`] does for
+/// [`quinn_proto::ReadError::Reset`].
+fn write_error(e: quinn_proto::WriteError) -> Error {
+    match e {
+        quinn_proto::WriteError::Stopped(code) => Error::SendStopped(code.into()),
+        e => Error::Stream(e.to_string()),
+    }
+}
+
+/// Hands as much of `substream.write_buffer` to `inner.connection` as flow
+/// control allows right now, trimming off whatever was accepted; see
+/// [`Config::stream_write_buffer`](crate::Config::stream_write_buffer). Flow
+/// control stopping short of the whole buffer is not an error: the
+/// remainder is simply left for the next call to pick up.
+fn drain_write_buffer(inner: &mut Inner, substream: &mut Substream) -> Result<(), Error> {
+    while !substream.write_buffer.is_empty() {
+        match inner
+            .connection
+            .send_stream(substream.id)
+            .write(&substream.write_buffer)
+        {
+            Ok(written) => {
+                substream.write_buffer.drain(..written);
+            }
+            Err(quinn_proto::WriteError::Blocked) => break,
+            Err(e) => return Err(write_error(e)),
+        }
+    }
+    Ok(())
+}
+
+impl Inner {
+    /// Closes the connection in place; the counterpart to
+    /// [`StreamMuxer::close`] reachable from outside `QuicMuxer` itself, for
+    /// [`Endpoint::close_connections`](crate::endpoint::Endpoint::close_connections)
+    /// to use via its registry.
+    pub(crate) fn close(&mut self, code: quinn_proto::VarInt, reason: Bytes) {
+        self.connection
+            .close(std::time::Instant::now(), code, reason);
+    }
+
+    /// `quinn_proto`'s own transmit/frame counters for this connection, the
+    /// counterpart to [`Inner::close`] reachable from outside `QuicMuxer`
+    /// itself, for [`Endpoint::aggregate_stats`](crate::endpoint::Endpoint::aggregate_stats)
+    /// to use via its registry.
+    pub(crate) fn stats(&self) -> quinn_proto::ConnectionStats {
+        self.connection.stats()
+    }
+
+    /// Whether the handshake has not yet completed; one of the per-connection
+    /// fields [`Endpoint::dump_state`](crate::endpoint::Endpoint::dump_state)
+    /// reads via its registry.
+    pub(crate) fn is_handshaking(&self) -> bool {
+        self.connection.is_handshaking()
+    }
+
+    /// Whether this connection has already been closed, by either side or by
+    /// `quinn_proto` itself after an error; counterpart to
+    /// [`Inner::is_handshaking`] for
+    /// [`Endpoint::dump_state`](crate::endpoint::Endpoint::dump_state).
+    pub(crate) fn is_closed(&self) -> bool {
+        self.connection.is_closed()
+    }
+
+    /// Whether this connection's [`Driver`] has a transmit still waiting for
+    /// the socket to become writable; read by
+    /// [`Endpoint::dump_state`](crate::endpoint::Endpoint::dump_state).
+    pub(crate) fn has_pending_transmit(&self) -> bool {
+        self.driver.has_pending_transmit()
+    }
+
+    /// Whether this connection's [`Driver`] has a timer scheduled for its
+    /// next internal deadline, read by
+    /// [`Endpoint::dump_state`](crate::endpoint::Endpoint::dump_state) as a
+    /// proxy for "is anything still driving this connection" - `quinn_proto`
+    /// always keeps one scheduled (idle timeout, ACKs, ...) for as long as
+    /// the connection is alive.
+    pub(crate) fn driver_is_running(&self) -> bool {
+        self.driver.has_scheduled_timer()
+    }
+}
+
+/// A token for an outbound substream that is being opened.
+///
+/// Unlike e.g. `libp2p-yamux`, opening a QUIC stream never actually blocks:
+/// `quinn_proto` hands out a [`StreamId`] synchronously, subject only to the
+/// peer's advertised concurrent stream limit.
+#[derive(Debug)]
+pub struct OutboundOpening(Option<StreamId>);
+
+impl QuicMuxer {
+    pub(crate) fn new(
+        endpoint: Endpoint,
+        connection: quinn_proto::Connection,
+        handle: ConnectionHandle,
+        max_idle_timeout: Duration,
+        close_timeout: Option<Duration>,
+        stream_write_buffer: usize,
+    ) -> Self {
+        let remote_address = connection.remote_address();
+        let inner = Arc::new(Mutex::new(Inner {
+            connection,
+            handle,
+            driver: Driver::default(),
+            max_idle_timeout,
+            close_timeout,
+            close_deadline: None,
+            close_reason: None,
+            stream_write_buffer,
+            bandwidth_watch: None,
+            last_app_activity: Instant::now(),
+        }));
+        endpoint.register_connection(handle, remote_address, &inner);
+        QuicMuxer {
+            endpoint,
+            inner,
+            context: OnceLock::new(),
+        }
+    }
+
+    /// Attaches `value` to this connection as application-defined context
+    /// (e.g. a session id, or a higher-level protocol's negotiated
+    /// features), retrievable later with [`QuicMuxer::context`] without
+    /// reaching for an external `HashMap<ConnectionId, _>` keyed off some
+    /// side channel.
+    ///
+    /// Only the first call takes effect: like the rest of this API,
+    /// [`QuicMuxer`] is typically shared as `&QuicMuxer`/`Arc<QuicMuxer>`
+    /// across tasks, so `context` can only safely hand back `&T` if nothing
+    /// can replace the stored value out from under an outstanding
+    /// reference; a later call silently doing nothing, rather than racily
+    /// overwriting a value someone might be holding a reference to, is the
+    /// honest trade-off for that. Set it once, as early as you have the
+    /// value to store (e.g. right after a handshake upgrade completes).
+    pub fn set_context<T: Send + Sync + 'static>(&self, value: T) {
+        let _ = self.context.set(Box::new(value));
+    }
+
+    /// The value last stored with [`QuicMuxer::set_context`], or `None` if
+    /// nothing has been stored yet or it was stored as a different type
+    /// than `T`.
+    pub fn context<T: Send + Sync + 'static>(&self) -> Option<&T> {
+        self.context.get()?.downcast_ref::<T>()
+    }
+
+    /// Registers `callback` to be invoked with this connection's current
+    /// congestion window, in bytes, whenever it changes by at least
+    /// `threshold` - a fraction of the last-reported value, e.g. `0.25` for
+    /// a 25% change - in either direction since the last call.
+    ///
+    /// The congestion window is `quinn_proto`'s own estimate of how much
+    /// data can be in flight on this connection at once, which it shrinks
+    /// on packet loss and grows as transfers succeed; it's the closest
+    /// thing this transport surfaces to an available-bandwidth estimate,
+    /// and a sharp drop in it is the signal an adaptive bitrate encoder
+    /// wants in order to back off. It is checked every time this connection
+    /// is driven forward - by [`StreamMuxer::poll_event`] as well as by
+    /// reading or writing any of its streams - rather than on its own
+    /// timer, consistent with this crate having no background tasks of its
+    /// own; a connection nothing is polling won't fire the callback even if
+    /// its window has changed.
+    ///
+    /// Only the first call takes effect, like [`QuicMuxer::set_context`].
+    pub fn on_bandwidth_change(
+        &self,
+        threshold: f64,
+        callback: impl Fn(u64) + Send + Sync + 'static,
+    ) {
+        let mut inner = self.inner.lock();
+        if inner.bandwidth_watch.is_some() {
+            return;
+        }
+        let last_reported_cwnd = inner.connection.stats().path.cwnd;
+        inner.bandwidth_watch = Some(BandwidthWatch {
+            threshold,
+            last_reported_cwnd,
+            callback: Arc::new(callback),
+        });
+    }
+
+    /// How long it has been since application data was last sent or
+    /// received on this connection, as opposed to `quinn_proto`'s own
+    /// keep-alives, ACKs or other protocol-level traffic - useful for a
+    /// caller implementing its own reaping policy on top of
+    /// [`Config::max_idle_timeout`](crate::Config::max_idle_timeout), which
+    /// only bounds total silence including that protocol-level traffic.
+    /// Resets to zero on the next stream read or write that actually moves
+    /// data.
+    pub fn idle_duration(&self) -> Duration {
+        self.inner.lock().last_app_activity.elapsed()
+    }
+
+    /// Sets this connection's weight for its
+    /// [`Endpoint`](crate::endpoint::Endpoint)'s transmit scheduler,
+    /// consulted only once more than one connection sharing the same
+    /// endpoint is stalled on the socket's write readiness at the same
+    /// time - the highest-priority one among those actually stalled gets
+    /// the next shot at it. Has no effect while the socket keeps up with
+    /// demand, which is the common case, since a send almost always
+    /// succeeds immediately; it only biases who goes first once the socket
+    /// itself is the bottleneck. Defaults to `0`, same as every connection
+    /// that never calls this.
+    pub fn set_connection_priority(&self, priority: i32) {
+        let handle = self.inner.lock().handle;
+        self.endpoint.set_connection_priority(handle, priority);
+    }
+
+    /// Runs `f` with direct, mutable access to the underlying
+    /// `quinn_proto::Connection`, for reaching whatever `quinn_proto` feature
+    /// this crate doesn't wrap yet.
+    ///
+    /// Gated behind the `unstable-internals` feature because nothing stops
+    /// `f` from violating invariants the rest of this crate relies on - e.g.
+    /// opening or closing streams [`QuicMuxer`] never learns about. There is
+    /// no stability guarantee on what's reachable through here: both
+    /// `quinn_proto` and [`Inner`]'s shape can change between patch releases
+    /// of this crate without notice.
+    #[cfg(feature = "unstable-internals")]
+    pub fn with_raw_connection<R>(&self, f: impl FnOnce(&mut quinn_proto::Connection) -> R) -> R {
+        f(&mut self.inner.lock().connection)
+    }
+
+    /// The largest unreliable datagram the remote currently accepts, or
+    /// `None` if the remote does not support the QUIC datagram extension.
+    ///
+    /// This can shrink over the lifetime of the connection as the path MTU
+    /// changes, so it should be re-read before every send rather than cached.
+    pub fn max_datagram_size(&self) -> Option<usize> {
+        self.inner.lock().connection.datagrams().max_size()
+    }
+
+    /// Whether we attempted 0-RTT early data and the remote rejected it,
+    /// meaning the connection fell back to a normal 1-RTT handshake and any
+    /// early data we sent was discarded.
+    ///
+    /// Dials made through [`QuicTransport`](crate::QuicTransport) (as
+    /// opposed to a bare [`Endpoint`](crate::endpoint::Endpoint) call that
+    /// skips [`Endpoint::dial_with_session_tickets`](crate::endpoint::Endpoint::dial_with_session_tickets))
+    /// attempt 0-RTT automatically once a session ticket for the remote has
+    /// been cached by an earlier connection - see
+    /// [`QuicTransport::export_session_tickets`](crate::QuicTransport::export_session_tickets).
+    /// Before that, or for a remote dialled for the first time, there is no
+    /// ticket to offer and this always reads `false`.
+    pub fn early_data_rejected(&self) -> bool {
+        let inner = self.inner.lock();
+        inner.connection.has_0rtt() && !inner.connection.accepted_0rtt()
+    }
+
+    /// Whether we attempted 0-RTT early data and the remote accepted it,
+    /// the complement of [`QuicMuxer::early_data_rejected`]: together with
+    /// it, the pair distinguishes "no ticket was offered" (both `false`)
+    /// from either outcome of actually offering one.
+    pub fn early_data_accepted(&self) -> bool {
+        let inner = self.inner.lock();
+        inner.connection.has_0rtt() && inner.connection.accepted_0rtt()
+    }
+
+    /// Whether this connection's `quinn_proto` state has 1-RTT send keys
+    /// installed early enough to write application data to a stream before
+    /// the handshake is confirmed (0.5-RTT data, sent by a server after its
+    /// own `Finished` but before it has received the client's).
+    ///
+    /// `quinn_proto` 0.7.3 only surfaces the full handshake completing, via
+    /// [`Event::Connected`](quinn_proto::Event::Connected); it keeps whether
+    /// a space has 1-RTT keys installed entirely internal, so there is no
+    /// way to observe this moment from outside. Since [`QuicMuxer`] itself
+    /// is only ever handed to a caller once `Upgrade` has already observed
+    /// that same event, by the time any caller can reach `open_bi`/`open_uni`
+    /// on the server side the full handshake - including the client's
+    /// `Finished` - has already been processed, so this always reads
+    /// `false`.
+    ///
+    /// Sending application data at 0.5-RTT, once available, will need its
+    /// own security review before it's wired up: it means writing to a
+    /// client the server hasn't yet confirmed holds the private key it
+    /// claims to, so a server that requires client certificates must not
+    /// treat 0.5-RTT data as sent to an authenticated peer.
+    pub fn half_rtt_write_available(&self) -> bool {
+        false
+    }
+
+    /// The connection ID we are currently reachable under, or `None` if the
+    /// underlying `quinn_proto` version in use doesn't expose it.
+    ///
+    /// Useful for matching this connection up with the entries a UDP load
+    /// balancer routes by connection ID, and for noticing when it changes
+    /// after a CID rotation.
+    ///
+    /// `quinn_proto` 0.7.3 (the version this transport is built against)
+    /// keeps the active local and remote connection IDs entirely internal to
+    /// its `Endpoint`/`Connection` state machines and exposes no accessor
+    /// for either; this always reads `None` until that's available upstream.
+    pub fn local_connection_id(&self) -> Option<Vec<u8>> {
+        None
+    }
+
+    /// The connection ID we are currently addressing the remote with, or
+    /// `None` for the same reason as [`QuicMuxer::local_connection_id`].
+    pub fn remote_connection_id(&self) -> Option<Vec<u8>> {
+        None
+    }
+
+    /// The peer's address on the path this connection is currently using.
+    ///
+    /// Unlike [`Transport::Output`](libp2p_core::Transport::Output)'s address,
+    /// which is fixed at dial/accept time, this tracks `quinn_proto`'s own
+    /// path state: if the peer's address changes mid-connection (e.g. a NAT
+    /// rebinding, or the peer migrating networks) and `quinn_proto` accepts
+    /// the new path, this reflects the new address from then on.
+    pub fn remote_address(&self) -> SocketAddr {
+        self.inner.lock().connection.remote_address()
+    }
+
+    /// Resolves once this connection's 1-RTT keys are confirmed, as opposed
+    /// to merely installed: security-sensitive callers that must not send
+    /// application data until the handshake is confirmed, not just
+    /// complete, should await this before writing rather than assuming a
+    /// freshly received [`QuicMuxer`] is already past that point.
+    ///
+    /// In practice it always resolves immediately: a [`QuicMuxer`] is only
+    /// ever handed out once [`Upgrade`](crate::transport::Upgrade) observes
+    /// `quinn_proto`'s [`Event::Connected`](quinn_proto::Event::Connected),
+    /// which `quinn_proto` 0.7.3 only emits once the handshake is confirmed
+    /// on this side, not merely complete (see the comment on `Upgrade`
+    /// itself). This exists as an explicit, self-documenting call site for
+    /// that invariant rather than requiring every such caller to rely on it
+    /// implicitly, and as the one place that would need real waiting logic
+    /// if this transport ever grew 0-RTT support, where a muxer can exist
+    /// before its 1-RTT keys are confirmed.
+    pub async fn keys_ready(&self) {}
+
+    /// Whether the path this connection is currently using has completed
+    /// QUIC's PATH_CHALLENGE/PATH_RESPONSE validation.
+    ///
+    /// This transport does not yet implement connection migration (there is
+    /// no `rebind`-style API to move a connection onto a new local address),
+    /// and `quinn_proto` 0.7.3 keeps its per-path validation state
+    /// (`paths::PathData::validated`) entirely internal, exposing neither an
+    /// accessor nor a migration/validation [`Event`](quinn_proto::Event)
+    /// variant. Until one of those lands upstream, a connection only ever
+    /// has the single path it completed its handshake on, which is
+    /// therefore always already validated, so this always reads `true`.
+    pub fn path_validated(&self) -> bool {
+        true
+    }
+
+    /// Why `quinn_proto` reported this connection as lost, or `None` if
+    /// [`StreamMuxer::poll_event`] hasn't observed an `Event::ConnectionLost`
+    /// for it yet.
+    ///
+    /// This only ever gets populated by polling: nothing drives the
+    /// connection on its own, so a muxer nobody has called `poll_event` (or
+    /// [`StreamMuxer::close`], which drives it too) on since the peer closed
+    /// it will still report `None` here even though `quinn_proto` itself
+    /// already knows better.
+    pub fn close_reason(&self) -> Option<quinn_proto::ConnectionError> {
+        self.inner.lock().close_reason.clone()
+    }
+
+    /// Resolves once this connection closes for any reason - local close,
+    /// peer close, idle timeout, or an error - with the reason, so a caller
+    /// that only wants to react to termination can await this instead of
+    /// polling [`QuicMuxer::close_reason`] itself.
+    ///
+    /// Unlike [`QuicMuxer::close_reason`], which only reflects whatever the
+    /// last call to [`StreamMuxer::poll_event`] (or this future) happened to
+    /// observe, this drives the connection on its own, so it resolves even
+    /// if nothing else is polling the muxer.
+    pub fn closed(&self) -> impl Future<Output = quinn_proto::ConnectionError> + '_ {
+        poll_fn(move |cx| {
+            let mut inner = self.inner.lock();
+            if let Some(reason) = &inner.close_reason {
+                return Poll::Ready(reason.clone());
+            }
+            // A driving error (e.g. the endpoint driver itself was dropped)
+            // isn't representable as a `quinn_proto::ConnectionError`; the
+            // connection is as good as closed either way, so fall through
+            // and keep draining for the real reason on the next poll rather
+            // than losing it here.
+            let _ = self.drive(cx, &mut inner);
+            if let Some(reason) = self.drain_connection_lost(&mut inner) {
+                return Poll::Ready(reason);
+            }
+            Poll::Pending
+        })
+    }
+
+    /// The idle timeout actually in force for this connection: the true
+    /// value QUIC negotiates is the minimum of both peers'
+    /// [`Config::max_idle_timeout`](crate::Config::max_idle_timeout), so a
+    /// connection can die sooner than the locally configured value if the
+    /// remote asked for something shorter.
+    ///
+    /// `quinn_proto` 0.7.3 computes and stores that negotiated minimum
+    /// (`Connection::idle_timeout`), but keeps the field private and exposes
+    /// no accessor for it, nor for the peer's advertised
+    /// `max_idle_timeout` transport parameter that would let us compute it
+    /// ourselves (`TransportParameters`' fields are `pub(crate)` to
+    /// `quinn_proto`, even though `crypto::Session::transport_parameters`
+    /// that would return them is public). Until one of those is exposed
+    /// upstream, this can only report the value we configured on our own
+    /// side, which is the true negotiated timeout whenever it happens to be
+    /// the smaller of the two.
+    pub fn effective_idle_timeout(&self) -> Duration {
+        self.inner.lock().max_idle_timeout
+    }
+
+    /// `quinn_proto`'s own transmit/frame counters for this connection; lets
+    /// a test observe effects (e.g. retransmits caused by reordering) that
+    /// aren't otherwise surfaced through [`QuicMuxer`]'s public API.
+    #[cfg(test)]
+    pub(crate) fn stats(&self) -> quinn_proto::ConnectionStats {
+        self.inner.lock().stats()
+    }
+
+    /// Number of datagrams the socket layer has reported as undeliverable
+    /// (e.g. too large for its current send buffer) and that were dropped,
+    /// since this connection's endpoint was created.
+    ///
+    /// Helps distinguish packet loss caused by local buffer exhaustion from
+    /// loss on the network itself; see also [`Config::on_datagram_dropped`](crate::Config::on_datagram_dropped).
+    pub fn dropped_datagrams(&self) -> u64 {
+        self.endpoint.dropped_datagrams()
+    }
+
+    /// Cumulative count of, and time spent waiting to acquire, this
+    /// connection's shared endpoint mutex since the endpoint was created -
+    /// requires the `lock-contention-metrics` feature; see
+    /// [`Endpoint::lock_stats`](crate::endpoint::Endpoint::lock_stats).
+    ///
+    /// Shared across every [`QuicMuxer`] and [`QuicListenStream`](crate::transport::QuicListenStream)
+    /// on the same endpoint, so it reflects contention from the driver and
+    /// from every connection dialled or accepted through it, not just this
+    /// one.
+    #[cfg(feature = "lock-contention-metrics")]
+    pub fn lock_contention(&self) -> crate::endpoint::LockWaitStats {
+        self.endpoint.lock_stats()
+    }
+
+    /// The DER-encoded certificate chain the peer presented during the TLS
+    /// handshake, in the order it was received, or `None` if the handshake
+    /// hasn't completed yet.
+    ///
+    /// [`Config::peer_verifier`](crate::Config::peer_verifier) only ever
+    /// sees the leaf certificate, already reduced to the [`PeerId`](libp2p_core::PeerId)
+    /// it embeds; this exposes the full chain as presented, for callers that
+    /// need it verbatim (e.g. audit logging, or verification beyond what
+    /// `peer_verifier` covers).
+    pub fn peer_certificates(&self) -> Option<Vec<rustls::Certificate>> {
+        self.inner
+            .lock()
+            .connection
+            .crypto_session()
+            .peer_identity()
+            .map(|chain| chain.into_iter().collect())
+    }
+
+    /// The application protocol negotiated via ALPN during the TLS
+    /// handshake, or `None` if the handshake somehow completed without
+    /// negotiating one.
+    ///
+    /// Always `Some` in practice: [`Config::alpn_protocols`](crate::Config::alpn_protocols)
+    /// is never empty, so `rustls` always has at least the libp2p ALPN to
+    /// negotiate. Useful for an endpoint configured with more than one
+    /// protocol in that list to tell, once a connection is established,
+    /// which one this particular dialer asked for.
+    pub fn negotiated_alpn(&self) -> Option<Vec<u8>> {
+        self.inner
+            .lock()
+            .connection
+            .crypto_session()
+            .handshake_data()
+            .and_then(|data| data.protocol)
+    }
+
+    /// The cipher suite negotiated for this connection's TLS session, or
+    /// `None` if the handshake hasn't completed yet.
+    ///
+    /// This crate pins TLS to 1.3 (see [`tls::make_server_config`](crate::tls::make_server_config)),
+    /// so the suite alone already determines the AEAD and hash in use; it's
+    /// the only part of the negotiation `rustls` 0.19's public `Session`
+    /// trait actually surfaces after the fact
+    /// (`get_negotiated_ciphersuite`). The signature scheme used to sign the
+    /// handshake transcript and the ECDHE group from [`Config::set_kx_groups`]
+    /// that was actually chosen are both decided and then discarded
+    /// internally during the handshake - `rustls` 0.19 keeps no record of
+    /// either reachable from outside the crate - so there's nothing for this
+    /// to report for those even though they're negotiated too.
+    pub fn negotiated_crypto(&self) -> Option<NegotiatedCrypto> {
+        let suite = self
+            .inner
+            .lock()
+            .connection
+            .crypto_session()
+            .get_negotiated_ciphersuite()?;
+        Some(NegotiatedCrypto {
+            cipher_suite: CipherSuite::from_rustls(suite)?,
+        })
+    }
+
+    /// Opens a bidirectional stream, returning typed, independently readable
+    /// and writable ends rather than the [`StreamId`]-based [`Substream`]
+    /// used by the [`StreamMuxer`] impl.
+    ///
+    /// Like [`StreamMuxer::open_outbound`], `quinn_proto` hands out the
+    /// stream ID synchronously, subject only to the peer's advertised
+    /// concurrent stream limit.
+    pub fn open_bi(&self) -> Result<BiStream<'_>, Error> {
+        let mut inner = self.inner.lock();
+        let id = inner.connection.streams().open(Dir::Bi).ok_or_else(|| {
+            Error::Stream("the peer's concurrent stream limit was reached".into())
+        })?;
+        Ok(BiStream {
+            send: SendStream::new(self, id),
+            recv: RecvStream::new(self, id),
+        })
+    }
+
+    /// Like [`QuicMuxer::open_bi`], but assigns the new stream `priority`
+    /// (higher values are serviced first) instead of leaving it at
+    /// `quinn_proto`'s default of `0`.
+    ///
+    /// Priority only orders the connection's own send scheduling among its
+    /// streams; it has no bearing on anything the peer does on its end, and
+    /// can be changed later for an already-open stream through
+    /// `quinn_proto`'s own `Streams::set_priority`, which this crate doesn't
+    /// currently expose a wrapper for since nothing has needed to re-prioritize
+    /// a stream mid-flight yet.
+    pub fn open_bi_with_priority(&self, priority: i32) -> Result<BiStream<'_>, Error> {
+        let mut inner = self.inner.lock();
+        let id = inner.connection.streams().open(Dir::Bi).ok_or_else(|| {
+            Error::Stream("the peer's concurrent stream limit was reached".into())
+        })?;
+        let _ = inner.connection.send_stream(id).set_priority(priority);
+        Ok(BiStream {
+            send: SendStream::new(self, id),
+            recv: RecvStream::new(self, id),
+        })
+    }
+
+    /// Opens a unidirectional stream, returning only its send half: QUIC
+    /// gives the opener of a unidirectional stream no receiving side at all,
+    /// so unlike [`QuicMuxer::open_bi`] there is no [`RecvStream`] to pair it
+    /// with. The peer accepts its read-only half through
+    /// [`QuicMuxer::poll_accept_uni`].
+    pub fn open_uni(&self) -> Result<SendStream<'_>, Error> {
+        let mut inner = self.inner.lock();
+        let id = inner.connection.streams().open(Dir::Uni).ok_or_else(|| {
+            Error::Stream("the peer's concurrent stream limit was reached".into())
+        })?;
+        Ok(SendStream::new(self, id))
+    }
+
+    /// Accepts the next unidirectional stream opened by the peer, if any.
+    ///
+    /// Counterpart to [`QuicMuxer::open_uni`] on the receiving end. Peer-opened
+    /// bidirectional streams are still delivered through
+    /// [`StreamMuxer::poll_event`]'s [`StreamMuxerEvent::InboundSubstream`],
+    /// since those already have both a read and a write half; this exists
+    /// because unidirectional ones don't fit that shape.
+    pub fn poll_accept_uni(&self, cx: &mut Context<'_>) -> Poll<Result<RecvStream<'_>, Error>> {
+        let mut inner = self.inner.lock();
+        if let Some(id) = inner.connection.streams().accept(Dir::Uni) {
+            return Poll::Ready(Ok(RecvStream::new(self, id)));
+        }
+
+        if let Poll::Ready(Err(e)) = self.drive(cx, &mut inner) {
+            return Poll::Ready(Err(e));
+        }
+
+        match inner.connection.streams().accept(Dir::Uni) {
+            Some(id) => Poll::Ready(Ok(RecvStream::new(self, id))),
+            None => Poll::Pending,
+        }
+    }
+
+    /// Returns a [`Sink`] for sending unreliable datagrams over this
+    /// connection.
+    ///
+    /// Payloads larger than [`QuicMuxer::max_datagram_size`] are rejected
+    /// outright. Otherwise, as with any QUIC datagram, delivery and ordering
+    /// are not guaranteed, though on a loopback connection they are not
+    /// expected to be lost either. The sink applies back-pressure once
+    /// [`Config::datagram_send_buffer_size`](crate::Config::datagram_send_buffer_size)
+    /// datagrams have been queued without the endpoint having flushed them
+    /// all to the socket; a caller that sends without waiting for that
+    /// back-pressure gets [`Error::DatagramQueueFull`] instead.
+    pub fn datagram_sink(&self) -> DatagramSink<'_> {
+        DatagramSink {
+            muxer: self,
+            pending: 0,
+        }
+    }
+
+    /// Returns a [`Stream`] of unreliable datagrams received from the peer,
+    /// built on top of [`QuicMuxer::poll_datagram`]. The stream ends once
+    /// the connection closes, reporting [`None`] rather than the `Error`
+    /// that caused it.
+    pub fn incoming_datagrams(&self) -> IncomingDatagrams<'_> {
+        IncomingDatagrams { muxer: self }
+    }
+
+    /// Polls for the next datagram received from the peer.
+    pub fn poll_datagram(&self, cx: &mut Context<'_>) -> Poll<Result<Vec<u8>, Error>> {
+        let mut inner = self.inner.lock();
+        if let Some(data) = inner.connection.datagrams().recv() {
+            return Poll::Ready(Ok(data.to_vec()));
+        }
+
+        if let Poll::Ready(Err(e)) = self.drive(cx, &mut inner) {
+            return Poll::Ready(Err(e));
+        }
+
+        match inner.connection.datagrams().recv() {
+            Some(data) => Poll::Ready(Ok(data.to_vec())),
+            None => Poll::Pending,
+        }
+    }
+
+    /// Drains every unreliable datagram already buffered for this
+    /// connection, without waiting for a fresh one to arrive or registering
+    /// this task's waker.
+    ///
+    /// Any datagram the remote sent before this side got around to polling
+    /// for one isn't lost: the shared endpoint applies every queued
+    /// `quinn_proto` connection event - including ones that arrived while
+    /// this connection was still sitting in the accept backlog - the first
+    /// time anything drives it, so by the time a [`QuicMuxer`] exists at
+    /// all, `quinn_proto` is already holding whatever arrived in the
+    /// meantime. This is for callers that would rather collect that backlog
+    /// in one synchronous pass right after accept than drain it one
+    /// [`QuicMuxer::poll_datagram`] future at a time.
+    pub fn drain_buffered_datagrams(&self) -> Vec<Vec<u8>> {
+        let mut inner = self.inner.lock();
+        let mut datagrams = Vec::new();
+        while let Some(data) = inner.connection.datagrams().recv() {
+            datagrams.push(data.to_vec());
+        }
+        datagrams
+    }
+
+    /// Sends a QUIC `PING` frame and resolves once the peer's acknowledgement
+    /// is reflected in the connection's round-trip time estimate, returning
+    /// how long that took; useful as an application-level liveness probe
+    /// that doesn't need a stream of its own.
+    ///
+    /// `quinn_proto` 0.7.3 has no event tied to a single probe's
+    /// acknowledgement specifically — [`quinn_proto::Connection::rtt`] is a
+    /// continuously updated estimate fed by every acked packet, not a queue
+    /// of discrete per-probe samples. This waits for the next change in that
+    /// estimate after sending the ping and reports the wall-clock time that
+    /// took, which is an accurate round trip on an otherwise idle connection
+    /// (the common case for a health check) but can be skewed by unrelated
+    /// traffic acked in the same window on a busy one.
+    pub fn ping(&self) -> impl Future<Output = Result<Duration, Error>> + '_ {
+        let mut state: Option<(Instant, Duration)> = None;
+        poll_fn(move |cx| {
+            let mut inner = self.inner.lock();
+            let (sent_at, baseline) = *state.get_or_insert_with(|| {
+                let baseline = inner.connection.rtt();
+                inner.connection.ping();
+                (Instant::now(), baseline)
+            });
+
+            if inner.connection.rtt() != baseline {
+                return Poll::Ready(Ok(sent_at.elapsed()));
+            }
+
+            if let Poll::Ready(Err(e)) = self.drive(cx, &mut inner) {
+                return Poll::Ready(Err(e));
+            }
+
+            if inner.connection.rtt() != baseline {
+                return Poll::Ready(Ok(sent_at.elapsed()));
+            }
+
+            Poll::Pending
+        })
+    }
+
+    /// Closes the connection, reporting `reason` to the peer tagged with a
+    /// QUIC transport error `code` (e.g.
+    /// [`TransportErrorCode::PROTOCOL_VIOLATION`](quinn_proto::TransportErrorCode::PROTOCOL_VIOLATION))
+    /// rather than an opaque application code, for when the caller's own
+    /// protocol - not `quinn_proto` itself - detected a violation and wants
+    /// the peer to be able to tell the two apart. Resolves once the
+    /// connection finishes draining, the same as [`StreamMuxer::close`].
+    ///
+    /// `quinn_proto` 0.7.3's [`Connection::close`](quinn_proto::Connection::close)
+    /// only ever builds an *application*-level close (`Close::Application`);
+    /// the transport-level close path that would actually tag this
+    /// `CONNECTION_CLOSE` at the frame level goes through
+    /// `Connection::close_inner`, which is private to `quinn_proto`'s own
+    /// `connection` module and not reachable from outside it. Until that's
+    /// exposed upstream, `code`'s numeric value is folded into the
+    /// application close's own error code instead, so the peer still
+    /// observes it via [`QuicMuxer::close_reason`] even though, at the wire
+    /// level, this is indistinguishable from a plain [`StreamMuxer::close`].
+    pub fn close_with_transport_error(
+        &self,
+        code: quinn_proto::TransportErrorCode,
+        reason: impl Into<Bytes>,
+    ) -> impl Future<Output = Result<(), Error>> + '_ {
+        let code =
+            quinn_proto::VarInt::from_u64(u64::from(code)).unwrap_or(quinn_proto::VarInt::MAX);
+        let reason = reason.into();
+        let mut closed = false;
+        poll_fn(move |cx| {
+            if !closed {
+                let mut inner = self.inner.lock();
+                if !inner.connection.is_closed() {
+                    inner.connection.close(Instant::now(), code, reason.clone());
+                    inner.close_deadline =
+                        inner.close_timeout.map(|timeout| Instant::now() + timeout);
+                }
+                closed = true;
+            }
+            StreamMuxer::close(self, cx)
+        })
+    }
+
+    /// Adjusts this connection's flow-control receive window at runtime, so
+    /// that an application which classifies a peer only after dialling or
+    /// accepting it (e.g. as a bulk-transfer peer that warrants a generous
+    /// window, versus a chatty control peer that doesn't) can hand out
+    /// buffer budget accordingly instead of needing to know it upfront.
+    ///
+    /// Currently a no-op: `quinn_proto` 0.7.3 (the version this transport is
+    /// built against) only ever grows the connection-level receive window
+    /// from inside its own read path, as `Connection::read`/`recv` consume
+    /// buffered stream data (`StreamsState::add_read_credits`); that method
+    /// is `pub(super)` to `quinn_proto`'s `connection` module and there is no
+    /// public equivalent this transport can call to request extra credit
+    /// and enqueue the resulting `MAX_DATA` frame itself. `bytes` is taken
+    /// now so the signature won't need to change once a future
+    /// `quinn_proto` exposes a runtime hook for this.
+    pub fn set_receive_window(&self, bytes: u64) {
+        let _ = bytes;
+    }
+
+    fn send_datagram(&self, data: Vec<u8>) -> Result<(), Error> {
+        self.inner
+            .lock()
+            .connection
+            .datagrams()
+            .send(Bytes::from(data))
+            .map_err(|e| Error::Datagram(e.to_string()))
+    }
+
+    fn has_pending_datagram_transmit(&self) -> bool {
+        self.inner.lock().driver.has_pending_transmit()
+    }
+
+    /// Applies every event the endpoint has queued up for this connection,
+    /// flushes its outgoing transmits, and fires its next internal deadline,
+    /// which between them advance its handshake, acks, and keep-alive state.
+    fn drive(&self, cx: &mut Context<'_>, inner: &mut Inner) -> Poll<Result<(), Error>> {
+        let result =
+            self.endpoint
+                .drive(cx, inner.handle, &mut inner.connection, &mut inner.driver);
+
+        if let Some(watch) = &mut inner.bandwidth_watch {
+            let cwnd = inner.connection.stats().path.cwnd;
+            let last = watch.last_reported_cwnd;
+            let changed_by = (cwnd as f64 - last as f64).abs();
+            if last == 0 || changed_by / last as f64 >= watch.threshold {
+                watch.last_reported_cwnd = cwnd;
+                (watch.callback)(cwnd);
+            }
+        }
+
+        result
+    }
+
+    /// Drains `quinn_proto`'s own event queue so it doesn't grow unbounded,
+    /// setting [`Inner::close_reason`] and broadcasting
+    /// [`EndpointEvent::ConnectionClosed`](crate::endpoint::EndpointEvent::ConnectionClosed)
+    /// the moment an `Event::ConnectionLost` turns up. Returns that reason,
+    /// or `None` if the queue held nothing of interest; shared by
+    /// [`StreamMuxer::poll_event`] (which only cares that the connection
+    /// died) and [`QuicMuxer::closed`] (which cares why).
+    fn drain_connection_lost(&self, inner: &mut Inner) -> Option<quinn_proto::ConnectionError> {
+        while let Some(event) = inner.connection.poll() {
+            if let quinn_proto::Event::ConnectionLost { reason } = event {
+                inner.close_reason = Some(reason.clone());
+                self.endpoint
+                    .broadcast_event(crate::endpoint::EndpointEvent::ConnectionClosed {
+                        remote_address: inner.connection.remote_address(),
+                        reason: reason.clone(),
+                    });
+                return Some(reason);
+            }
+        }
+        None
+    }
+}
+
+/// A [`Sink`] for sending unreliable datagrams over a [`QuicMuxer`], returned
+/// by [`QuicMuxer::datagram_sink`].
+pub struct DatagramSink<'a> {
+    muxer: &'a QuicMuxer,
+    /// Datagrams handed to `quinn_proto` since the endpoint last confirmed it
+    /// had nothing left to transmit for this connection; used as an
+    /// approximation of how much outbound data is still buffered.
+    pending: usize,
+}
+
+impl<'a> Sink<Vec<u8>> for DatagramSink<'a> {
+    type Error = Error;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let this = self.get_mut();
+        let mut inner = this.muxer.inner.lock();
+        if let Poll::Ready(Err(e)) = this.muxer.drive(cx, &mut inner) {
+            return Poll::Ready(Err(e));
+        }
+        drop(inner);
+
+        if !this.muxer.has_pending_datagram_transmit() {
+            this.pending = 0;
+        }
+
+        if this.pending < this.muxer.endpoint.datagram_send_buffer_size() {
+            Poll::Ready(Ok(()))
+        } else {
+            Poll::Pending
+        }
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: Vec<u8>) -> Result<(), Self::Error> {
+        let this = self.get_mut();
+        if this.pending >= this.muxer.endpoint.datagram_send_buffer_size() {
+            return Err(Error::DatagramQueueFull);
+        }
+        this.muxer.send_datagram(item)?;
+        this.pending += 1;
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let this = self.get_mut();
+        let mut inner = this.muxer.inner.lock();
+        if let Poll::Ready(Err(e)) = this.muxer.drive(cx, &mut inner) {
+            return Poll::Ready(Err(e));
+        }
+        drop(inner);
+
+        if this.muxer.has_pending_datagram_transmit() {
+            Poll::Pending
+        } else {
+            this.pending = 0;
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.poll_flush(cx)
+    }
+}
+
+/// A [`Stream`] of unreliable datagrams received over a [`QuicMuxer`],
+/// returned by [`QuicMuxer::incoming_datagrams`].
+pub struct IncomingDatagrams<'a> {
+    muxer: &'a QuicMuxer,
+}
+
+impl<'a> Stream for IncomingDatagrams<'a> {
+    type Item = Vec<u8>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        match self.muxer.poll_datagram(cx) {
+            Poll::Ready(Ok(data)) => Poll::Ready(Some(data)),
+            Poll::Ready(Err(_)) => Poll::Ready(None),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+/// A bidirectional stream opened with [`QuicMuxer::open_bi`], split into its
+/// independently readable and writable halves up front, the way `quinn`'s own
+/// `open_bi` does.
+pub struct BiStream<'a> {
+    pub send: SendStream<'a>,
+    pub recv: RecvStream<'a>,
+}
+
+/// The writable half of a stream, returned by [`QuicMuxer::open_bi`] and, on
+/// its own, by [`QuicMuxer::open_uni`].
+pub struct SendStream<'a> {
+    muxer: &'a QuicMuxer,
+    id: StreamId,
+}
+
+impl<'a> SendStream<'a> {
+    pub(crate) fn new(muxer: &'a QuicMuxer, id: StreamId) -> Self {
+        SendStream { muxer, id }
+    }
+
+    /// Writes to this stream like [`AsyncWrite::poll_write`], but fails with
+    /// [`Error::WriteTimeout`] instead of blocking forever if `deadline`
+    /// passes before the peer's flow control grants enough send window to
+    /// accept any of `buf`.
+    ///
+    /// Lives here rather than on the bare [`Substream`] used by the
+    /// [`StreamMuxer`] impl: a [`Substream`] is just a [`StreamId`], with no
+    /// reference back to the connection it belongs to, so it has nothing to
+    /// poll or drive on its own. [`SendStream`] already carries that
+    /// reference (it's how its [`AsyncWrite`] impl drives the connection on
+    /// `WriteError::Blocked`), which this builds on directly.
+    ///
+    /// As with `poll_write`, a successful write may cover only part of
+    /// `buf`; callers that need every byte written before the deadline
+    /// should loop this the same way they would `poll_write`.
+    pub async fn write_with_deadline(
+        &mut self,
+        buf: &[u8],
+        deadline: Instant,
+    ) -> Result<usize, Error> {
+        let mut timeout = Delay::new(deadline.saturating_duration_since(Instant::now()));
+        poll_fn(|cx| {
+            if Pin::new(&mut timeout).poll(cx).is_ready() {
+                return Poll::Ready(Err(Error::WriteTimeout));
+            }
+
+            let mut inner = self.muxer.inner.lock();
+            match inner.connection.send_stream(self.id).write(buf) {
+                Ok(written) => Poll::Ready(Ok(written)),
+                Err(quinn_proto::WriteError::Blocked) => {
+                    if let Poll::Ready(Err(e)) = self.muxer.drive(cx, &mut inner) {
+                        return Poll::Ready(Err(e));
+                    }
+                    Poll::Pending
+                }
+                Err(e) => Poll::Ready(Err(write_error(e))),
+            }
+        })
+        .await
+    }
+
+    /// Writes every byte of `buf` to this stream and then sends a QUIC
+    /// `FIN`, as a single logical step rather than a separate `write_all`
+    /// followed by [`AsyncWriteExt::close`](futures::AsyncWriteExt::close):
+    /// request-response protocols that always finish the send side right
+    /// after their request/response body don't need a second round through
+    /// `poll_close`, and `quinn_proto` can fold the `FIN` into the same
+    /// final packet as the tail of `buf` instead of needing one of its own.
+    ///
+    /// Lives here rather than on the bare [`Substream`] used by the
+    /// [`StreamMuxer`] impl, for the same reason
+    /// [`write_with_deadline`](Self::write_with_deadline) does: a
+    /// [`Substream`] has no reference back to the connection it belongs to.
+    pub async fn write_all_and_finish(&mut self, buf: &[u8]) -> Result<(), Error> {
+        let mut written = 0;
+        poll_fn(|cx| loop {
+            if written == buf.len() {
+                let mut inner = self.muxer.inner.lock();
+                return Poll::Ready(match inner.connection.send_stream(self.id).finish() {
+                    Ok(()) | Err(quinn_proto::FinishError::UnknownStream) => Ok(()),
+                    Err(quinn_proto::FinishError::Stopped(_)) => Ok(()),
+                });
+            }
+
+            let mut inner = self.muxer.inner.lock();
+            match inner.connection.send_stream(self.id).write(&buf[written..]) {
+                Ok(n) => {
+                    written += n;
+                    continue;
+                }
+                Err(quinn_proto::WriteError::Blocked) => {
+                    if let Poll::Ready(Err(e)) = self.muxer.drive(cx, &mut inner) {
+                        return Poll::Ready(Err(e));
+                    }
+                    return Poll::Pending;
+                }
+                Err(e) => return Poll::Ready(Err(write_error(e))),
+            }
+        })
+        .await
+    }
+
+    /// Abandons this stream's send side with a QUIC `RESET_STREAM` carrying
+    /// `error_code`, instead of the clean `FIN` [`write_all_and_finish`](Self::write_all_and_finish)
+    /// sends. The peer's read side sees [`Error::StreamReset`] with this code
+    /// rather than a clean EOF, letting protocols that assign meaning to
+    /// particular codes (e.g. "cancelled", "unsupported") signal that instead
+    /// of a generic close.
+    ///
+    /// Consumes `self`: once reset, there is nothing further to send on this
+    /// stream.
+    pub fn reset(self, error_code: u32) -> Result<(), Error> {
+        let mut inner = self.muxer.inner.lock();
+        match inner
+            .connection
+            .send_stream(self.id)
+            .reset(quinn_proto::VarInt::from_u32(error_code))
+        {
+            Ok(()) | Err(quinn_proto::UnknownStream { .. }) => Ok(()),
+        }
+    }
+}
+
+/// The readable half of a stream, returned by [`QuicMuxer::open_bi`] and, on
+/// its own, by [`QuicMuxer::poll_accept_uni`].
+///
+/// Losing interest in this side - either by calling [`stop`](Self::stop)
+/// explicitly or simply dropping it - always tells the peer via
+/// `STOP_SENDING`, so its writes fail fast instead of continuing to spend
+/// bandwidth and flow-control credit on data this side will never read.
+pub struct RecvStream<'a> {
+    muxer: &'a QuicMuxer,
+    id: StreamId,
+    /// Set by [`stop`](Self::stop), so `Drop` doesn't send a second,
+    /// redundant `STOP_SENDING` with a different (default) error code on
+    /// top of the one already sent explicitly.
+    stopped: bool,
+}
+
+impl<'a> RecvStream<'a> {
+    pub(crate) fn new(muxer: &'a QuicMuxer, id: StreamId) -> Self {
+        RecvStream {
+            muxer,
+            id,
+            stopped: false,
+        }
+    }
+
+    /// Reads like [`AsyncRead::poll_read`], but fails open instead of
+    /// blocking forever: if `timeout` passes before `buf` fills, returns
+    /// whatever arrived in time (possibly nothing) tagged
+    /// [`ReadOutcome::TimedOut`], rather than conflating "no data yet" with
+    /// [`ReadOutcome::Eof`] the way a plain read returning zero would.
+    ///
+    /// Lives here rather than on the bare [`Substream`] used by the
+    /// [`StreamMuxer`] impl, for the same reason
+    /// [`SendStream::write_with_deadline`] does: a [`Substream`] is just a
+    /// [`StreamId`], with nothing to poll or drive on its own.
+    pub async fn read_with_timeout(
+        &mut self,
+        buf: &mut [u8],
+        timeout: Duration,
+    ) -> Result<(usize, ReadOutcome), Error> {
+        let mut delay = Delay::new(timeout);
+        let mut filled = 0;
+        poll_fn(|cx| loop {
+            if filled == buf.len() {
+                return Poll::Ready(Ok((filled, ReadOutcome::Filled)));
+            }
+
+            let mut inner = self.muxer.inner.lock();
+            let mut recv_stream = inner.connection.recv_stream(self.id);
+            let mut chunks = match recv_stream.read(true) {
+                Ok(chunks) => chunks,
+                Err(e) => return Poll::Ready(Err(Error::Stream(e.to_string()))),
+            };
+            let next = chunks.next(buf.len() - filled);
+            // See the same call in `poll_read`: `Chunks` panics on drop
+            // unless `finalize` is called first.
+            let _ = chunks.finalize();
+            match next {
+                Ok(Some(chunk)) => {
+                    buf[filled..filled + chunk.bytes.len()].copy_from_slice(&chunk.bytes);
+                    filled += chunk.bytes.len();
+                    continue;
+                }
+                Ok(None) => return Poll::Ready(Ok((filled, ReadOutcome::Eof))),
+                Err(quinn_proto::ReadError::Blocked) => {
+                    if Pin::new(&mut delay).poll(cx).is_ready() {
+                        return Poll::Ready(Ok((filled, ReadOutcome::TimedOut)));
+                    }
+                    if let Poll::Ready(Err(e)) = self.muxer.drive(cx, &mut inner) {
+                        return Poll::Ready(Err(e));
+                    }
+                    return Poll::Pending;
+                }
+                Err(e) => return Poll::Ready(Err(read_error(e))),
+            }
+        })
+        .await
+    }
+
+    /// Like [`AsyncRead::poll_read`], but hands back whatever [`Bytes`]
+    /// `quinn_proto` already assembled for the next ordered chunk instead of
+    /// copying it into a caller-supplied buffer - `quinn_proto::Chunk::bytes`
+    /// is already a zero-copy, reference-counted slice of its receive
+    /// buffer, and `poll_read`'s `copy_from_slice` into `buf` throws that
+    /// away for callers who'd rather hold onto the chunk as-is (e.g. a frame
+    /// parser that just stores or forwards it). Returns `Ok(None)` on EOF.
+    ///
+    /// Chunk boundaries follow whatever `quinn_proto` happened to reassemble
+    /// from the wire, not the sender's original write sizes; callers that
+    /// need the original framing back must reassemble it themselves, e.g. by
+    /// concatenating chunks the way [`read_with_timeout`](Self::read_with_timeout)
+    /// does internally.
+    pub fn poll_read_chunk(&mut self, cx: &mut Context<'_>) -> Poll<Result<Option<Bytes>, Error>> {
+        let mut inner = self.muxer.inner.lock();
+        let mut recv_stream = inner.connection.recv_stream(self.id);
+        let mut chunks = match recv_stream.read(true) {
+            Ok(chunks) => chunks,
+            Err(e) => return Poll::Ready(Err(Error::Stream(e.to_string()))),
+        };
+        let next = chunks.next(usize::MAX);
+        // `Chunks` panics on drop unless `finalize` is called first; see the
+        // same call in `poll_read`.
+        let _ = chunks.finalize();
+        match next {
+            Ok(Some(chunk)) => {
+                inner.last_app_activity = Instant::now();
+                Poll::Ready(Ok(Some(chunk.bytes)))
+            }
+            Ok(None) => Poll::Ready(Ok(None)),
+            Err(quinn_proto::ReadError::Blocked) => {
+                if let Poll::Ready(Err(e)) = self.muxer.drive(cx, &mut inner) {
+                    return Poll::Ready(Err(e));
+                }
+                Poll::Pending
+            }
+            Err(e) => Poll::Ready(Err(read_error(e))),
+        }
+    }
+
+    /// Reports how many bytes of this stream's incoming data `quinn_proto`
+    /// is currently holding that haven't been read yet, so a caller can
+    /// decide whether to read eagerly or let flow control apply
+    /// backpressure to the sender instead. Returns an error if the stream
+    /// is no longer known to the connection (e.g. already read to EOF).
+    ///
+    /// Currently always reports `0`: `quinn_proto` 0.7.3 keeps this
+    /// accounting - the gap between the highest offset it has assembled and
+    /// the offset already read - private to its own `streams` module, and
+    /// the only way to learn it from outside is to actually drain the
+    /// assembled bytes via [`quinn_proto::Chunks::next`], which would
+    /// consume them and defeat the point of a peek. It still validates the
+    /// stream itself, so an unknown stream is reported accurately even
+    /// though its occupancy can't be yet.
+    pub fn buffered(&self) -> Result<u64, Error> {
+        let mut inner = self.muxer.inner.lock();
+        let result = match inner.connection.recv_stream(self.id).read(true) {
+            Ok(chunks) => {
+                // No `next()` call: finalizing immediately re-inserts the
+                // stream's state unchanged, so this never consumes data.
+                let _ = chunks.finalize();
+                Ok(0)
+            }
+            Err(e) => Err(Error::Stream(e.to_string())),
+        };
+        result
+    }
+
+    /// Tells the peer to stop sending on this stream's write side
+    /// (`STOP_SENDING`) carrying `error_code`, instead of waiting for it to
+    /// finish or reset on its own. The peer's next write sees
+    /// [`Error::SendStopped`] with this code, the same way a reset stream's
+    /// reader sees [`Error::StreamReset`] on the other side.
+    ///
+    /// Consumes `self`: once stopped, there is nothing further to read from
+    /// this stream, and dropping it (which [`stop`](Self::stop) already did
+    /// on this side's behalf) would otherwise try to do the same thing again
+    /// with a different, default error code.
+    pub fn stop(mut self, error_code: u32) -> Result<(), Error> {
+        self.stopped = true;
+        let mut inner = self.muxer.inner.lock();
+        match inner
+            .connection
+            .recv_stream(self.id)
+            .stop(quinn_proto::VarInt::from_u32(error_code))
+        {
+            Ok(()) | Err(quinn_proto::UnknownStream { .. }) => Ok(()),
+        }
+    }
+}
+
+impl<'a> Drop for RecvStream<'a> {
+    /// Sends `STOP_SENDING` with error code `0` if [`stop`](Self::stop)
+    /// wasn't already called explicitly - so simply losing interest in this
+    /// side (e.g. a protocol that only reads a fixed-size header and never
+    /// touches the rest of the body) still tells the peer to stop writing,
+    /// rather than leaving it to find out only once its own idle timeout or
+    /// flow-control window forces the issue.
+    fn drop(&mut self) {
+        if !self.stopped {
+            let mut inner = self.muxer.inner.lock();
+            let _ = inner
+                .connection
+                .recv_stream(self.id)
+                .stop(quinn_proto::VarInt::from_u32(0));
+        }
+    }
+}
+
+/// How [`RecvStream::read_with_timeout`] ended, to tell a full read apart
+/// from a deadline or a closed stream without conflating either with
+/// success.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReadOutcome {
+    /// `buf` was completely filled before the deadline.
+    Filled,
+    /// `timeout` passed before `buf` filled; it holds whatever arrived in
+    /// time, which may be nothing at all.
+    TimedOut,
+    /// The peer closed its send side (a QUIC `FIN`) before `buf` filled.
+    Eof,
+}
+
+impl<'a> AsyncWrite for SendStream<'a> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        let mut inner = this.muxer.inner.lock();
+        match inner.connection.send_stream(this.id).write(buf) {
+            Ok(written) => {
+                if written > 0 {
+                    inner.last_app_activity = Instant::now();
+                }
+                Poll::Ready(Ok(written))
+            }
+            Err(quinn_proto::WriteError::Blocked) => {
+                if let Poll::Ready(Err(e)) = this.muxer.drive(cx, &mut inner) {
+                    return Poll::Ready(Err(e.into()));
+                }
+                Poll::Pending
+            }
+            Err(e) => Poll::Ready(Err(write_error(e).into())),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    /// Half-closes this stream: sends a QUIC `FIN` on the send side so the
+    /// peer observes EOF on read, without touching the receive side at all.
+    /// On a bidirectional stream opened with [`QuicMuxer::open_bi`], the
+    /// peer can still write and this side can still read after this
+    /// returns; only a subsequent `reset` (which this never issues) would
+    /// tear the stream down instead of half-closing it.
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let mut inner = this.muxer.inner.lock();
+        match inner.connection.send_stream(this.id).finish() {
+            Ok(()) | Err(quinn_proto::FinishError::UnknownStream) => Poll::Ready(Ok(())),
+            Err(quinn_proto::FinishError::Stopped(_)) => Poll::Ready(Ok(())),
+        }
+    }
+}
+
+impl<'a> AsyncRead for RecvStream<'a> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        let mut inner = this.muxer.inner.lock();
+        let mut recv_stream = inner.connection.recv_stream(this.id);
+        let mut chunks = match recv_stream.read(true) {
+            Ok(chunks) => chunks,
+            Err(e) => return Poll::Ready(Err(Error::Stream(e.to_string()).into())),
+        };
+        let next = chunks.next(buf.len());
+        // `Chunks` panics on drop unless `finalize` is called first; its
+        // `ShouldTransmit` return value just hints that flow-control credit
+        // could be sent sooner, which the next drive of this connection
+        // (background-polled for the lifetime of a real connection) covers.
+        let _ = chunks.finalize();
+        match next {
+            Ok(Some(chunk)) => {
+                buf[..chunk.bytes.len()].copy_from_slice(&chunk.bytes);
+                inner.last_app_activity = Instant::now();
+                Poll::Ready(Ok(chunk.bytes.len()))
+            }
+            Ok(None) => Poll::Ready(Ok(0)),
+            Err(quinn_proto::ReadError::Blocked) => {
+                if let Poll::Ready(Err(e)) = this.muxer.drive(cx, &mut inner) {
+                    return Poll::Ready(Err(e.into()));
+                }
+                Poll::Pending
+            }
+            Err(e) => Poll::Ready(Err(read_error(e).into())),
+        }
+    }
+}
+
+impl StreamMuxer for QuicMuxer {
+    type Substream = Substream;
+    type OutboundSubstream = OutboundOpening;
+    type Error = Error;
+
+    fn poll_event(
+        &self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<StreamMuxerEvent<Self::Substream>, Self::Error>> {
+        let mut inner = self.inner.lock();
+        if let Some(id) = inner.connection.streams().accept(Dir::Bi) {
+            return Poll::Ready(Ok(StreamMuxerEvent::InboundSubstream(Substream::new(id))));
+        }
+
+        if let Poll::Ready(Err(e)) = self.drive(cx, &mut inner) {
+            return Poll::Ready(Err(e));
+        }
+
+        if let Some(reason) = self.drain_connection_lost(&mut inner) {
+            return Poll::Ready(Err(Error::Handshake(reason.to_string())));
+        }
+
+        if let Some(id) = inner.connection.streams().accept(Dir::Bi) {
+            return Poll::Ready(Ok(StreamMuxerEvent::InboundSubstream(Substream::new(id))));
+        }
+
+        Poll::Pending
+    }
+
+    fn open_outbound(&self) -> Self::OutboundSubstream {
+        let mut inner = self.inner.lock();
+        OutboundOpening(inner.connection.streams().open(Dir::Bi))
+    }
+
+    fn poll_outbound(
+        &self,
+        _cx: &mut Context<'_>,
+        substream: &mut Self::OutboundSubstream,
+    ) -> Poll<Result<Self::Substream, Self::Error>> {
+        match substream.0.take() {
+            Some(id) => Poll::Ready(Ok(Substream::new(id))),
+            None => Poll::Ready(Err(Error::Stream(
+                "the peer's concurrent stream limit was reached".into(),
+            ))),
+        }
+    }
+
+    fn destroy_outbound(&self, _substream: Self::OutboundSubstream) {}
+
+    fn read_substream(
+        &self,
+        _cx: &mut Context<'_>,
+        substream: &mut Self::Substream,
+        buf: &mut [u8],
+    ) -> Poll<Result<usize, Self::Error>> {
+        let mut inner = self.inner.lock();
+        let result = match inner.connection.recv_stream(substream.id).read(true) {
+            Ok(mut chunks) => {
+                let result = match chunks.next(buf.len()) {
+                    Ok(Some(chunk)) => {
+                        buf[..chunk.bytes.len()].copy_from_slice(&chunk.bytes);
+                        Poll::Ready(Ok(chunk.bytes.len()))
+                    }
+                    Ok(None) => Poll::Ready(Ok(0)),
+                    Err(quinn_proto::ReadError::Blocked) => Poll::Pending,
+                    Err(e) => Poll::Ready(Err(read_error(e))),
+                };
+                // `Chunks` panics on drop if `finalize` wasn't called first.
+                let _ = chunks.finalize();
+                result
+            }
+            Err(e) => Poll::Ready(Err(Error::Stream(e.to_string()))),
+        };
+        result
+    }
+
+    /// With [`Config::stream_write_buffer`](crate::Config::stream_write_buffer)
+    /// unset (the default), hands `buf` straight to the connection, as
+    /// before. Otherwise, `buf` is first appended to `substream`'s own
+    /// buffer, and only pushed on to the connection once that accumulates at
+    /// least that many bytes - coalescing several small writes into fewer,
+    /// larger ones - with the rest held back for a later write or
+    /// [`flush_substream`](Self::flush_substream) to push through.
+    fn write_substream(
+        &self,
+        _cx: &mut Context<'_>,
+        substream: &mut Self::Substream,
+        buf: &[u8],
+    ) -> Poll<Result<usize, Self::Error>> {
+        let mut inner = self.inner.lock();
+        if inner.stream_write_buffer == 0 {
+            return match inner.connection.send_stream(substream.id).write(buf) {
+                Ok(written) => Poll::Ready(Ok(written)),
+                Err(quinn_proto::WriteError::Blocked) => Poll::Pending,
+                Err(e) => Poll::Ready(Err(write_error(e))),
+            };
+        }
+
+        substream.write_buffer.extend_from_slice(buf);
+        if substream.write_buffer.len() >= inner.stream_write_buffer {
+            if let Err(e) = drain_write_buffer(&mut inner, substream) {
+                return Poll::Ready(Err(e));
+            }
+        }
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    /// Pushes whatever [`Config::stream_write_buffer`](crate::Config::stream_write_buffer)
+    /// is still holding back on `substream` through to the connection, same
+    /// as [`write_substream`](Self::write_substream) does once the buffer
+    /// fills up. Flow control stopping short of all of it isn't reported as
+    /// pending: the remainder stays buffered for the next write or flush to
+    /// pick up, rather than holding up the caller for it.
+    fn flush_substream(
+        &self,
+        _cx: &mut Context<'_>,
+        substream: &mut Self::Substream,
+    ) -> Poll<Result<(), Self::Error>> {
+        let mut inner = self.inner.lock();
+        match drain_write_buffer(&mut inner, substream) {
+            Ok(()) => Poll::Ready(Ok(())),
+            Err(e) => Poll::Ready(Err(e)),
+        }
+    }
+
+    /// Half-closes `substream`'s send side, the same way [`SendStream::poll_close`]
+    /// does: a `FIN`, not a reset, so the peer sees EOF on read while this
+    /// side can still be read from and the peer can still write back.
+    fn shutdown_substream(
+        &self,
+        _cx: &mut Context<'_>,
+        substream: &mut Self::Substream,
+    ) -> Poll<Result<(), Self::Error>> {
+        let mut inner = self.inner.lock();
+        match inner.connection.send_stream(substream.id).finish() {
+            Ok(()) | Err(quinn_proto::FinishError::UnknownStream) => Poll::Ready(Ok(())),
+            Err(quinn_proto::FinishError::Stopped(_)) => Poll::Ready(Ok(())),
+        }
+    }
+
+    fn destroy_substream(&self, _substream: Self::Substream) {}
+
+    /// Closes the connection and waits for it to finish draining (i.e. for
+    /// `quinn_proto` to confirm the peer either acknowledged the
+    /// `CONNECTION_CLOSE` or stopped responding long enough for its own
+    /// closing timer to lapse) before reporting `Ready`, so the caller's
+    /// bookkeeping doesn't outlive the connection's. Draining can't complete
+    /// before the `CONNECTION_CLOSE` frame queued below has actually been
+    /// handed to the socket, so awaiting this to completion is also a
+    /// guarantee against dropping everything right after `close` returns and
+    /// losing that frame to a socket write that never happened. If
+    /// [`Config::close_timeout`](crate::Config::close_timeout) is set and
+    /// the peer never responds at all - the case this exists for, e.g. a
+    /// mass disconnect against now-unreachable peers - this instead gives up
+    /// and reports `Ready` once that much time has passed since the first
+    /// call, reclaiming this side's handle regardless of whether `quinn_proto`
+    /// ever actually reaches `Drained`.
+    fn close(&self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let mut inner = self.inner.lock();
+        if !inner.connection.is_closed() {
+            inner.connection.close(
+                std::time::Instant::now(),
+                quinn_proto::VarInt::from_u32(0),
+                Default::default(),
+            );
+            inner.close_deadline = inner.close_timeout.map(|timeout| Instant::now() + timeout);
+        }
+
+        if inner.connection.is_drained() {
+            return Poll::Ready(Ok(()));
+        }
+
+        if let Some(deadline) = inner.close_deadline {
+            if Instant::now() >= deadline {
+                return Poll::Ready(Ok(()));
+            }
+        }
+
+        if let Poll::Ready(Err(e)) = self.drive(cx, &mut inner) {
+            return Poll::Ready(Err(e));
+        }
+
+        if inner.connection.is_drained() {
+            Poll::Ready(Ok(()))
+        } else {
+            Poll::Pending
+        }
+    }
+
+    fn flush_all(&self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+}
@@ -0,0 +1,47 @@
+// Copyright 2021 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use quinn_proto::StreamId;
+
+/// A QUIC substream, as handed out by [`QuicMuxer`](crate::QuicMuxer).
+///
+/// This is a thin handle: the actual send/receive buffers are owned by the
+/// `quinn_proto::Connection` driven inside the muxer, and are looked up by
+/// [`Substream::id`] on every read/write. The one exception is its own
+/// `write_buffer`, which this handle owns directly so
+/// [`Config::stream_write_buffer`](crate::Config::stream_write_buffer) can
+/// coalesce several of its writes before any of them reach the connection.
+#[derive(Debug)]
+pub struct Substream {
+    pub(crate) id: StreamId,
+    /// Bytes from a prior [`StreamMuxer::write_substream`](libp2p_core::muxing::StreamMuxer::write_substream)
+    /// call not yet handed to the connection; see
+    /// [`Config::stream_write_buffer`](crate::Config::stream_write_buffer).
+    pub(crate) write_buffer: Vec<u8>,
+}
+
+impl Substream {
+    pub(crate) fn new(id: StreamId) -> Self {
+        Substream {
+            id,
+            write_buffer: Vec::new(),
+        }
+    }
+}
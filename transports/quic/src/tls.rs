@@ -0,0 +1,282 @@
+// Copyright 2021 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Wires the self-signed certificate produced by [`crate::certificate`] into
+//! `rustls`/`quinn_proto` server and client configurations.
+
+use crate::certificate;
+use crate::config::Config;
+use crate::Error;
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::convert::{TryFrom, TryInto};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Builds the `quinn_proto` server configuration used to accept inbound
+/// connections, presenting our self-signed certificate to dialers.
+pub(crate) fn make_server_config(config: &Config) -> Result<quinn_proto::ServerConfig, Error> {
+    let cert = certificate::generate(&config.keypair)?;
+
+    let mut crypto = rustls::ServerConfig::new(Arc::new(ClientAuth {
+        mandatory: config.require_client_auth,
+    }));
+    crypto
+        .set_single_cert(vec![cert.certificate], cert.private_key)
+        .map_err(Error::Tls)?;
+    crypto.alpn_protocols = config.alpn_protocols.clone();
+    crypto.versions = vec![rustls::ProtocolVersion::TLSv1_3];
+    crypto.ciphersuites = config
+        .cipher_suites
+        .iter()
+        .map(|suite| suite.to_rustls())
+        .collect();
+    // `rustls::quic::ServerQuicExt::new_quic` asserts this is 0 or exactly
+    // `0xffff_ffff` - QUIC's own flow control, not this field, bounds how
+    // much early data a client can actually send.
+    crypto.max_early_data_size = 0xffff_ffff;
+
+    // `ServerConfig`'s other fields are private, so the struct-update syntax
+    // used in `make_client_config` isn't available here.
+    #[allow(clippy::field_reassign_with_default)]
+    let mut server_config = quinn_proto::ServerConfig::default();
+    server_config.crypto = Arc::new(crypto);
+    server_config.concurrent_connections(config.max_connections);
+    server_config.migration(config.allow_migration);
+    if let Some(transport_config) = build_transport_config(config)? {
+        server_config.transport = Arc::new(transport_config);
+    }
+    Ok(server_config)
+}
+
+/// Builds the `quinn_proto` client configuration used to dial out, presenting
+/// our self-signed certificate and accepting the remote's in turn; actual
+/// peer authentication happens via [`certificate::extract_peer_id`] rather
+/// than a conventional certificate authority chain.
+///
+/// `session_tickets` is consulted (and updated) by `rustls` as 0-RTT session
+/// tickets are issued and redeemed; pass the same store across every dial
+/// that should be able to resume each other's sessions, and see
+/// [`SessionTicketStore::export`] for persisting it across restarts.
+pub(crate) fn make_client_config(
+    config: &Config,
+    session_tickets: Arc<SessionTicketStore>,
+) -> Result<quinn_proto::ClientConfig, Error> {
+    let cert = certificate::generate(&config.keypair)?;
+
+    let mut crypto = rustls::ClientConfig::new();
+    crypto
+        .set_single_client_cert(vec![cert.certificate], cert.private_key)
+        .map_err(Error::Tls)?;
+    crypto.alpn_protocols = config.alpn_protocols.clone();
+    crypto
+        .dangerous()
+        .set_certificate_verifier(Arc::new(AcceptAnyCertificate));
+    crypto.versions = vec![rustls::ProtocolVersion::TLSv1_3];
+    crypto.ciphersuites = config
+        .cipher_suites
+        .iter()
+        .map(|suite| suite.to_rustls())
+        .collect();
+    crypto.enable_early_data = true;
+    crypto.set_persistence(session_tickets);
+
+    let mut client_config = quinn_proto::ClientConfig {
+        crypto: Arc::new(crypto),
+        ..Default::default()
+    };
+    if let Some(transport_config) = build_transport_config(config)? {
+        client_config.transport = Arc::new(transport_config);
+    }
+    Ok(client_config)
+}
+
+/// A [`rustls::StoresClientSessions`] implementation that, unlike `rustls`'
+/// own [`rustls::ClientSessionMemoryCache`], can be exported to and restored
+/// from an opaque byte blob, so that 0-RTT session tickets earned before a
+/// process restart aren't thrown away with it. See
+/// [`QuicTransport::export_session_tickets`](crate::QuicTransport::export_session_tickets).
+#[derive(Default)]
+pub(crate) struct SessionTicketStore(Mutex<HashMap<Vec<u8>, Vec<u8>>>);
+
+impl rustls::StoresClientSessions for SessionTicketStore {
+    fn put(&self, key: Vec<u8>, value: Vec<u8>) -> bool {
+        self.0.lock().insert(key, value);
+        true
+    }
+
+    fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
+        self.0.lock().get(key).cloned()
+    }
+}
+
+impl SessionTicketStore {
+    /// Encodes every ticket currently cached as a sequence of
+    /// length-prefixed `(key, value)` pairs. The encoding is a private
+    /// implementation detail of this crate and may change between releases;
+    /// the only supported use of the returned bytes is feeding them back
+    /// into [`SessionTicketStore::import`].
+    ///
+    /// These bytes let whoever holds them resume a 0-RTT connection as this
+    /// peer, so store and transmit them with the same care as a private
+    /// key - e.g. not in plaintext on shared storage.
+    pub(crate) fn export(&self) -> Vec<u8> {
+        let cache = self.0.lock();
+        let mut out = Vec::new();
+        for (key, value) in cache.iter() {
+            out.extend_from_slice(&(key.len() as u64).to_le_bytes());
+            out.extend_from_slice(key);
+            out.extend_from_slice(&(value.len() as u64).to_le_bytes());
+            out.extend_from_slice(value);
+        }
+        out
+    }
+
+    /// Merges tickets previously produced by [`SessionTicketStore::export`]
+    /// into this store, alongside whatever is already cached. A malformed
+    /// or truncated blob is ignored rather than treated as an error: at
+    /// worst it costs some otherwise-avoidable full handshakes, not a
+    /// reason to fail startup.
+    pub(crate) fn import(&self, data: &[u8]) {
+        let mut cache = self.0.lock();
+        let mut rest = data;
+        while let Some((key, value, tail)) = read_length_prefixed_pair(rest) {
+            cache.insert(key, value);
+            rest = tail;
+        }
+    }
+}
+
+/// Reads one `(key, value)` pair off the front of `data`, each preceded by
+/// its length as a little-endian `u64`, returning the unconsumed remainder -
+/// the inverse of the encoding written by [`SessionTicketStore::export`].
+fn read_length_prefixed_pair(data: &[u8]) -> Option<(Vec<u8>, Vec<u8>, &[u8])> {
+    let (key, rest) = read_length_prefixed(data)?;
+    let (value, rest) = read_length_prefixed(rest)?;
+    Some((key, value, rest))
+}
+
+/// Reads one length-prefixed byte string off the front of `data`, returning
+/// it alongside the unconsumed remainder.
+fn read_length_prefixed(data: &[u8]) -> Option<(Vec<u8>, &[u8])> {
+    if data.len() < std::mem::size_of::<u64>() {
+        return None;
+    }
+    let (len, rest) = data.split_at(std::mem::size_of::<u64>());
+    let len = usize::try_from(u64::from_le_bytes(len.try_into().ok()?)).ok()?;
+    if rest.len() < len {
+        return None;
+    }
+    let (value, rest) = rest.split_at(len);
+    Some((value.to_vec(), rest))
+}
+
+/// `quinn_proto::TransportConfig`'s own default for
+/// [`TransportConfig::max_idle_timeout`](quinn_proto::TransportConfig::max_idle_timeout),
+/// matching [`Config::max_idle_timeout`]'s default so the common case of
+/// leaving it unset doesn't spuriously trip [`build_transport_config`]'s
+/// early return.
+const DEFAULT_MAX_IDLE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Builds a `quinn_proto` transport configuration reflecting whichever of
+/// [`Config::initial_congestion_window`], [`Config::packet_threshold`],
+/// [`Config::time_threshold`], [`Config::stream_windows`] and
+/// [`Config::max_idle_timeout`] differ from `quinn_proto`'s own defaults, or
+/// `None` if none do, so that callers can leave `quinn_proto`'s own default
+/// `Arc<TransportConfig>` in place rather than needlessly replacing it.
+fn build_transport_config(config: &Config) -> Result<Option<quinn_proto::TransportConfig>, Error> {
+    if config.initial_congestion_window.is_none()
+        && config.packet_threshold.is_none()
+        && config.time_threshold.is_none()
+        && config.stream_windows.is_none()
+        && config.max_idle_timeout == DEFAULT_MAX_IDLE_TIMEOUT
+    {
+        return Ok(None);
+    }
+
+    let mut transport_config = quinn_proto::TransportConfig::default();
+    if let Some(window) = config.initial_congestion_window {
+        let mut congestion_controller = quinn_proto::congestion::NewRenoConfig::default();
+        congestion_controller.initial_window(window);
+        transport_config.congestion_controller_factory(Arc::new(congestion_controller));
+    }
+    if let Some(threshold) = config.packet_threshold {
+        transport_config.packet_threshold(threshold);
+    }
+    if let Some(threshold) = config.time_threshold {
+        transport_config.time_threshold(threshold);
+    }
+    if let Some(windows) = config.stream_windows {
+        // `quinn_proto` only has one lumped `stream_receive_window`; take
+        // the largest of the three so no stream type ends up with less than
+        // it asked for. See `StreamWindows`.
+        let window = windows.bidi_local.max(windows.bidi_remote).max(windows.uni);
+        transport_config.stream_receive_window(window)?;
+    }
+    transport_config.max_idle_timeout(Some(config.max_idle_timeout))?;
+    Ok(Some(transport_config))
+}
+
+/// We don't use `rustls`' notion of client authentication for identifying the
+/// peer: every client that does present a certificate authenticates by
+/// proving ownership of its libp2p [`PeerId`](libp2p_core::PeerId) through
+/// the custom certificate extension, not through a CA-issued chain. Whether a
+/// certificate is required at all is controlled by [`Config::require_client_auth`].
+struct ClientAuth {
+    mandatory: bool,
+}
+
+impl rustls::ClientCertVerifier for ClientAuth {
+    fn client_auth_mandatory(&self, _sni: Option<&webpki::DNSName>) -> Option<bool> {
+        Some(self.mandatory)
+    }
+
+    fn client_auth_root_subjects(
+        &self,
+        _sni: Option<&webpki::DNSName>,
+    ) -> Option<rustls::DistinguishedNames> {
+        Some(rustls::DistinguishedNames::new())
+    }
+
+    fn verify_client_cert(
+        &self,
+        _presented_certs: &[rustls::Certificate],
+        _sni: Option<&webpki::DNSName>,
+    ) -> Result<rustls::ClientCertVerified, rustls::TLSError> {
+        Ok(rustls::ClientCertVerified::assertion())
+    }
+}
+
+/// Certificate verification is performed separately by recovering the
+/// embedded libp2p [`PeerId`](libp2p_core::PeerId); `rustls` is only told to
+/// accept whatever chain the remote presents.
+struct AcceptAnyCertificate;
+
+impl rustls::ServerCertVerifier for AcceptAnyCertificate {
+    fn verify_server_cert(
+        &self,
+        _roots: &rustls::RootCertStore,
+        _presented_certs: &[rustls::Certificate],
+        _dns_name: webpki::DNSNameRef,
+        _ocsp_response: &[u8],
+    ) -> Result<rustls::ServerCertVerified, rustls::TLSError> {
+        Ok(rustls::ServerCertVerified::assertion())
+    }
+}
@@ -0,0 +1,8628 @@
+// Copyright 2021 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use crate::config::{Config, PeerVerifier};
+use crate::endpoint::{
+    ConnectionInfo, Driver, Endpoint, EndpointEvent, EndpointStateDump, EndpointStats, PendingInfo,
+};
+use crate::muxer::QuicMuxer;
+use crate::Error;
+use bytes::Bytes;
+use futures::channel::mpsc;
+use futures::future::poll_fn;
+use futures::prelude::*;
+use futures_timer::Delay;
+use libp2p_core::multiaddr::{Multiaddr, Protocol};
+use libp2p_core::transport::{ListenerEvent, Transport, TransportError};
+use libp2p_core::PeerId;
+use parking_lot::Mutex;
+use quinn_proto::crypto::Session;
+use quinn_proto::ConnectionHandle;
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::net::{SocketAddr, UdpSocket as StdUdpSocket};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll, Waker};
+use std::time::Duration;
+
+/// Implementation of the libp2p [`Transport`] trait for QUIC.
+///
+/// Every call to [`QuicTransport::listen_on`] or [`QuicTransport::dial`]
+/// binds its own UDP socket and [`Endpoint`]; unlike `libp2p-tcp` there is
+/// no port-reuse support yet.
+///
+/// `Transport` is implemented on `QuicTransport` itself, by value, rather
+/// than on a borrow of the underlying [`Endpoint`] handle, so it already
+/// drops straight into [`Transport::boxed`]/[`Transport::map`] the way
+/// `libp2p-tcp`'s does; there is no separate `into_transport()`-style
+/// conversion to reach for. `Endpoint` stays `pub(crate)` - it's the
+/// internal, already-`Clone`, reference-counted plumbing that
+/// [`QuicTransport::listen_on`]/[`QuicTransport::dial`] hand out
+/// [`QuicMuxer`]s on top of, not something callers construct directly.
+#[derive(Clone)]
+pub struct QuicTransport {
+    config: Config,
+    /// Connections opened by [`QuicTransport::open_stream_to`], keyed by the
+    /// remote address they were dialled at, shared across every [`Clone`] of
+    /// this transport. Empty, and never consulted, unless that method is
+    /// actually used - [`Transport::dial`]/[`Transport::listen_on`] don't
+    /// read or write it.
+    request_response_cache: Arc<Mutex<HashMap<SocketAddr, Arc<QuicMuxer>>>>,
+    /// Addresses a [`QuicTransport::dial_exclusive`] call made through this
+    /// transport (or any of its [`Clone`]s) is currently dialling but hasn't
+    /// yet resolved or failed, so a second concurrent
+    /// [`QuicTransport::dial_exclusive`] call to the same address can be
+    /// rejected with [`Error::DialInProgress`] instead of opening a second,
+    /// independent `quinn_proto` connection to it. [`Transport::dial`] itself
+    /// neither reads nor writes this - it allows any number of concurrent
+    /// connections to the same remote.
+    in_flight_dials: Arc<Mutex<HashSet<SocketAddr>>>,
+    /// 0-RTT session tickets earned by dials made through this transport (or
+    /// any of its [`Clone`]s, or imported via
+    /// [`QuicTransport::import_session_tickets`]), consulted by every
+    /// subsequent dial so it can attempt 0-RTT instead of a full handshake.
+    /// Each dial gets its own [`Endpoint`], so this - not the endpoint -
+    /// is what survives from one dial to the next.
+    session_tickets: Arc<crate::tls::SessionTicketStore>,
+}
+
+impl QuicTransport {
+    /// Creates a new [`QuicTransport`] from the given [`Config`].
+    pub fn new(config: Config) -> Self {
+        QuicTransport {
+            config,
+            request_response_cache: Arc::new(Mutex::new(HashMap::new())),
+            in_flight_dials: Arc::new(Mutex::new(HashSet::new())),
+            session_tickets: Arc::new(crate::tls::SessionTicketStore::default()),
+        }
+    }
+
+    /// Encodes every 0-RTT session ticket this transport (or any of its
+    /// [`Clone`]s) has cached so far into an opaque blob that
+    /// [`QuicTransport::import_session_tickets`] can restore, e.g. into a
+    /// fresh process after a restart.
+    ///
+    /// These bytes are as sensitive as a private key - anyone who obtains
+    /// them can resume a 0-RTT connection as this peer - so store and
+    /// transmit them with matching care (e.g. not in plaintext on shared
+    /// storage).
+    pub fn export_session_tickets(&self) -> Vec<u8> {
+        self.session_tickets.export()
+    }
+
+    /// Restores session tickets previously produced by
+    /// [`QuicTransport::export_session_tickets`], merging them into
+    /// whatever this transport already has cached so dials made afterwards
+    /// can attempt 0-RTT against a peer resumed from a previous process.
+    ///
+    /// A malformed or truncated blob is ignored rather than rejected with an
+    /// error: at worst it costs some otherwise-avoidable full handshakes.
+    pub fn import_session_tickets(&self, data: &[u8]) {
+        self.session_tickets.import(data);
+    }
+
+    /// Like [`Transport::listen_on`], but listens on an already-bound UDP
+    /// socket instead of binding a new one from a [`Multiaddr`].
+    ///
+    /// This is useful for socket activation (e.g. systemd), where the socket
+    /// may already be bound to a privileged port, or inherited from a file
+    /// descriptor, before this process starts. The listen address is derived
+    /// from the socket's own `local_addr()`.
+    pub fn listen_on_socket(self, socket: StdUdpSocket) -> Result<QuicListenStream, Error> {
+        let endpoint = Endpoint::from_socket(&self.config, socket)?;
+        let listen_addr = endpoint.local_addr()?;
+        let listener_index = endpoint.register_listener();
+
+        Ok(QuicListenStream {
+            endpoint,
+            listener_index,
+            listen_addr,
+            reported_listen_addr: false,
+            peer_verifier: self.config.peer_verifier,
+            max_idle_timeout: self.config.max_idle_timeout,
+            close_timeout: self.config.close_timeout,
+            stream_write_buffer: self.config.stream_write_buffer,
+        })
+    }
+
+    /// Adds a second (or further) [`QuicListenStream`] sharing `existing`'s
+    /// endpoint - the same UDP socket and `quinn_proto` connection-ID table -
+    /// rather than binding one of its own.
+    ///
+    /// Which of the listeners sharing an endpoint receives a given inbound
+    /// connection is decided by [`Config::listener_dispatch_policy`]; without
+    /// one configured, every connection goes to whichever listener was
+    /// registered first (`existing`, if this is the only other one).
+    pub fn listen_on_shared_endpoint(&self, existing: &QuicListenStream) -> QuicListenStream {
+        let endpoint = existing.endpoint.clone();
+        let listener_index = endpoint.register_listener();
+
+        QuicListenStream {
+            endpoint,
+            listener_index,
+            listen_addr: existing.listen_addr,
+            reported_listen_addr: false,
+            peer_verifier: existing.peer_verifier.clone(),
+            max_idle_timeout: existing.max_idle_timeout,
+            close_timeout: existing.close_timeout,
+            stream_write_buffer: existing.stream_write_buffer,
+        }
+    }
+
+    /// Races a [`Transport::dial`] against every address in `addrs`
+    /// concurrently, resolving with the first handshake to complete and
+    /// dropping (and so aborting) every other attempt.
+    ///
+    /// This implements a QUIC analogue of [happy eyeballs](https://en.wikipedia.org/wiki/Happy_Eyeballs)
+    /// for a peer that advertises addresses of more than one IP family:
+    /// rather than trying them one at a time and paying a full handshake
+    /// timeout for each unreachable one before moving to the next, every
+    /// candidate is dialled at once.
+    ///
+    /// Addresses this transport doesn't support are skipped rather than
+    /// failing the whole race outright; the returned future only fails once
+    /// every candidate, supported or not, has failed.
+    pub fn dial_any(self, addrs: Vec<Multiaddr>) -> Result<DialAny, TransportError<Error>> {
+        let mut pending = Vec::new();
+        let mut errors = Vec::new();
+        for addr in addrs {
+            match self.clone().dial(addr) {
+                Ok(upgrade) => pending.push(upgrade),
+                Err(e) => errors.push(e.to_string()),
+            }
+        }
+
+        if pending.is_empty() {
+            return Err(TransportError::Other(Error::DialAnyFailed(
+                errors.join("; "),
+            )));
+        }
+
+        Ok(DialAny { pending, errors })
+    }
+}
+
+/// Only ever matches an already-resolved `/ip4/.../udp/.../quic` or
+/// `/ip6/.../udp/.../quic` address - `/dns4`, `/dns6` and `/dnsaddr` are
+/// deliberately not handled here. Resolving and caching DNS names is
+/// [`libp2p_dns`](https://docs.rs/libp2p-dns)'s job, wrapped transparently
+/// around whichever inner `Transport` (this one included) ends up dialing
+/// the resolved address; teaching this crate its own resolver and TTL cache
+/// would duplicate that wrapper and let the two disagree about what's
+/// cached. A `/dns4` address reaching `dial` or `listen_on` below falls
+/// through to the `Err(())` case and surfaces as
+/// [`TransportError::MultiaddrNotSupported`].
+///
+/// `strict` (see [`Config::strict_multiaddr`]) controls what happens to a
+/// trailing component after the core that isn't `/p2p` (already tolerated
+/// unconditionally) - `/quic/something-else`, in string form, since
+/// `addr.pop()` below consumes it before `Protocol::Quic` is even reached.
+/// With `strict` such a component is rejected like any other unexpected
+/// one; without it, it's ignored and popping continues into the core.
+fn multiaddr_to_socketaddr(mut addr: Multiaddr, strict: bool) -> Result<SocketAddr, ()> {
+    let mut port = None;
+    let mut seen_quic = false;
+    while let Some(proto) = addr.pop() {
+        match proto {
+            Protocol::Quic => seen_quic = true,
+            Protocol::Udp(p) if seen_quic => match port {
+                Some(_) => return Err(()),
+                None => port = Some(p),
+            },
+            Protocol::Ip4(ipv4) if seen_quic => match port {
+                Some(port) => return Ok(SocketAddr::new(ipv4.into(), port)),
+                None => return Err(()),
+            },
+            Protocol::Ip6(ipv6) if seen_quic => match port {
+                Some(port) => return Ok(SocketAddr::new(ipv6.into(), port)),
+                None => return Err(()),
+            },
+            Protocol::P2p(_) => {}
+            _ if !strict && !seen_quic => {}
+            _ => return Err(()),
+        }
+    }
+    Err(())
+}
+
+/// Creates a UDP socket bound to `addr`, optionally with `IP_FREEBIND`
+/// (Linux) / `IP_BINDANY` (the BSDs) set beforehand per [`Config::freebind`],
+/// and/or `IP_TOS`/`IPV6_TCLASS` set per [`Config::dscp`].
+///
+/// `socket2` 0.4 (the version this crate is pinned to) has no wrapper for
+/// `IP_FREEBIND`/`IP_BINDANY` or `IPV6_TCLASS`, so those are set with a raw
+/// `setsockopt` call instead; see [`set_freebind`] and [`set_tclass`].
+pub(crate) fn bind_socket(
+    addr: SocketAddr,
+    freebind: bool,
+    dscp: Option<u8>,
+) -> io::Result<StdUdpSocket> {
+    let domain = if addr.is_ipv4() {
+        socket2::Domain::IPV4
+    } else {
+        socket2::Domain::IPV6
+    };
+    let socket = socket2::Socket::new(domain, socket2::Type::DGRAM, Some(socket2::Protocol::UDP))?;
+    if freebind {
+        set_freebind(&socket)?;
+    }
+    if let Some(dscp) = dscp {
+        set_dscp(&socket, addr.is_ipv4(), dscp)?;
+    }
+    socket.bind(&addr.into())?;
+    Ok(socket.into())
+}
+
+/// Sets `dscp` (see [`Config::dscp`]) in the upper 6 bits of the IPv4
+/// `IP_TOS` / IPv6 traffic class octet, leaving the lower 2 (ECN) bits at
+/// zero.
+fn set_dscp(socket: &socket2::Socket, is_ipv4: bool, dscp: u8) -> io::Result<()> {
+    let tos = ((dscp & 0x3F) as u32) << 2;
+    if is_ipv4 {
+        socket.set_tos(tos)
+    } else {
+        set_tclass(socket, tos)
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn set_freebind(socket: &socket2::Socket) -> io::Result<()> {
+    set_ip_sockopt(socket, libc::IP_FREEBIND)
+}
+
+#[cfg(any(
+    target_os = "freebsd",
+    target_os = "openbsd",
+    target_os = "netbsd",
+    target_os = "dragonfly"
+))]
+fn set_freebind(socket: &socket2::Socket) -> io::Result<()> {
+    set_ip_sockopt(socket, libc::IP_BINDANY)
+}
+
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "freebsd",
+    target_os = "openbsd",
+    target_os = "netbsd",
+    target_os = "dragonfly"
+)))]
+fn set_freebind(_socket: &socket2::Socket) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Other,
+        "Config::freebind is not supported on this platform",
+    ))
+}
+
+#[cfg(any(
+    target_os = "linux",
+    target_os = "freebsd",
+    target_os = "openbsd",
+    target_os = "netbsd",
+    target_os = "dragonfly"
+))]
+fn set_ip_sockopt(socket: &socket2::Socket, option: libc::c_int) -> io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    let enable: libc::c_int = 1;
+    // SAFETY: `socket` outlives the call, and `enable` is a valid `c_int`
+    // whose size matches the `option_len` passed alongside it.
+    let result = unsafe {
+        libc::setsockopt(
+            socket.as_raw_fd(),
+            libc::IPPROTO_IP,
+            option,
+            &enable as *const libc::c_int as *const libc::c_void,
+            std::mem::size_of_val(&enable) as libc::socklen_t,
+        )
+    };
+    if result == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+#[cfg(any(
+    target_os = "linux",
+    target_os = "freebsd",
+    target_os = "openbsd",
+    target_os = "netbsd",
+    target_os = "dragonfly"
+))]
+fn set_tclass(socket: &socket2::Socket, tclass: u32) -> io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    let tclass = tclass as libc::c_int;
+    // SAFETY: `socket` outlives the call, and `tclass` is a valid `c_int`
+    // whose size matches the `option_len` passed alongside it.
+    let result = unsafe {
+        libc::setsockopt(
+            socket.as_raw_fd(),
+            libc::IPPROTO_IPV6,
+            libc::IPV6_TCLASS,
+            &tclass as *const libc::c_int as *const libc::c_void,
+            std::mem::size_of_val(&tclass) as libc::socklen_t,
+        )
+    };
+    if result == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "freebsd",
+    target_os = "openbsd",
+    target_os = "netbsd",
+    target_os = "dragonfly"
+)))]
+fn set_tclass(_socket: &socket2::Socket, _tclass: u32) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Other,
+        "Config::dscp is not supported for IPv6 sockets on this platform",
+    ))
+}
+
+/// The inverse of [`multiaddr_to_socketaddr`]: builds the `/ip4|ip6/.../udp/PORT/quic`
+/// address this crate would itself advertise for `socket_addr`, for a caller
+/// that already has a `SocketAddr` (e.g. from [`Endpoint::local_addr`](crate::endpoint::Endpoint::local_addr))
+/// and would otherwise have to hand-assemble the same `Protocol` sequence.
+///
+/// There's no `version` parameter to pick `/quic` vs. `/quic-v1`: this crate's
+/// vendored `multiaddr` has only `Protocol::Quic`, the draft form, not a
+/// `/quic-v1` variant, so a real QUIC v1 (RFC 9000) address can't be emitted
+/// here any more than [`Endpoint::from_socket`](crate::endpoint::Endpoint::from_socket)
+/// can serve v1 on the wire - see its doc comment for why.
+pub fn socketaddr_to_quic_multiaddr(socket_addr: SocketAddr) -> Multiaddr {
+    let mut addr = Multiaddr::from(socket_addr.ip());
+    addr.push(Protocol::Udp(socket_addr.port()));
+    addr.push(Protocol::Quic);
+    addr
+}
+
+/// Reserves `addr` in `dials` for the duration of one
+/// [`QuicTransport::dial_exclusive`] call, removing it again on drop - i.e.
+/// on every early return between reserving it and handing the reservation
+/// off to the [`Upgrade`] that call produces, via
+/// [`InFlightDialGuard::defuse`].
+struct InFlightDialGuard {
+    dials: Arc<Mutex<HashSet<SocketAddr>>>,
+    addr: SocketAddr,
+    defused: bool,
+}
+
+impl InFlightDialGuard {
+    /// Reserves `addr` in `dials`, or returns `None` if it's already
+    /// reserved by another in-flight dial.
+    fn new(dials: Arc<Mutex<HashSet<SocketAddr>>>, addr: SocketAddr) -> Option<Self> {
+        if dials.lock().insert(addr) {
+            Some(InFlightDialGuard {
+                dials,
+                addr,
+                defused: false,
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Hands the reservation off to whatever will release it later (see
+    /// [`Upgrade::with_dial_dedup`]) instead of releasing it when this guard
+    /// is dropped.
+    fn defuse(mut self) -> (Arc<Mutex<HashSet<SocketAddr>>>, SocketAddr) {
+        self.defused = true;
+        (self.dials.clone(), self.addr)
+    }
+}
+
+impl Drop for InFlightDialGuard {
+    fn drop(&mut self) {
+        if !self.defused {
+            self.dials.lock().remove(&self.addr);
+        }
+    }
+}
+
+impl Transport for QuicTransport {
+    type Output = (PeerId, QuicMuxer);
+    type Error = Error;
+    type Listener = QuicListenStream;
+    type ListenerUpgrade = Upgrade;
+    type Dial = Upgrade;
+
+    fn listen_on(self, addr: Multiaddr) -> Result<Self::Listener, TransportError<Self::Error>> {
+        let socket_addr = multiaddr_to_socketaddr(addr.clone(), self.config.strict_multiaddr)
+            .map_err(|()| TransportError::MultiaddrNotSupported(addr))?;
+        let socket = bind_socket(socket_addr, self.config.freebind, self.config.dscp)
+            .map_err(|e| TransportError::Other(e.into()))?;
+        let endpoint =
+            Endpoint::from_socket(&self.config, socket).map_err(TransportError::Other)?;
+        let listen_addr = endpoint.local_addr().map_err(TransportError::Other)?;
+        let listener_index = endpoint.register_listener();
+
+        Ok(QuicListenStream {
+            endpoint,
+            listener_index,
+            listen_addr,
+            reported_listen_addr: false,
+            peer_verifier: self.config.peer_verifier,
+            max_idle_timeout: self.config.max_idle_timeout,
+            close_timeout: self.config.close_timeout,
+            stream_write_buffer: self.config.stream_write_buffer,
+        })
+    }
+
+    fn dial(self, addr: Multiaddr) -> Result<Self::Dial, TransportError<Self::Error>> {
+        let socket_addr = multiaddr_to_socketaddr(addr.clone(), self.config.strict_multiaddr)
+            .map_err(|()| TransportError::MultiaddrNotSupported(addr))?;
+        let socket = bind_socket(
+            (std::net::Ipv4Addr::UNSPECIFIED, 0).into(),
+            false,
+            self.config.dscp,
+        )
+        .map_err(|e| TransportError::Other(e.into()))?;
+        let endpoint =
+            Endpoint::from_socket(&self.config, socket).map_err(TransportError::Other)?;
+        let (handle, connection) = endpoint
+            .dial_with_session_tickets(&self.config, socket_addr, &self.session_tickets)
+            .map_err(TransportError::Other)?;
+
+        Ok(Upgrade::new(
+            endpoint,
+            handle,
+            connection,
+            self.config.peer_verifier,
+            self.config.max_idle_timeout,
+            self.config.close_timeout,
+            self.config.stream_write_buffer,
+        ))
+    }
+
+    fn address_translation(&self, _listen: &Multiaddr, _observed: &Multiaddr) -> Option<Multiaddr> {
+        None
+    }
+}
+
+impl QuicTransport {
+    /// Like [`Transport::dial`], but also returns a stream of milestones
+    /// reached along the way - the initial packet sent, the peer's response
+    /// received, the handshake confirmed - for UIs that want to show
+    /// "connecting…" progress instead of only finding out once the dial
+    /// resolves (or fails).
+    ///
+    /// Purely additive: the returned [`Upgrade`] resolves exactly as
+    /// [`Transport::dial`]'s would, whether or not anything ever polls the
+    /// progress stream. It ends (`None`) once the handshake is confirmed or
+    /// the dial fails, whichever happens first; a failed dial may end the
+    /// stream without ever emitting [`DialProgress::HandshakeConfirmed`].
+    pub fn dial_with_progress(
+        self,
+        addr: Multiaddr,
+    ) -> Result<(mpsc::UnboundedReceiver<DialProgress>, Upgrade), TransportError<Error>> {
+        let socket_addr = multiaddr_to_socketaddr(addr.clone(), self.config.strict_multiaddr)
+            .map_err(|()| TransportError::MultiaddrNotSupported(addr))?;
+        let socket = bind_socket(
+            (std::net::Ipv4Addr::UNSPECIFIED, 0).into(),
+            false,
+            self.config.dscp,
+        )
+        .map_err(|e| TransportError::Other(e.into()))?;
+        let endpoint =
+            Endpoint::from_socket(&self.config, socket).map_err(TransportError::Other)?;
+        let (handle, connection) = endpoint
+            .dial_with_session_tickets(&self.config, socket_addr, &self.session_tickets)
+            .map_err(TransportError::Other)?;
+
+        let (sender, receiver) = mpsc::unbounded();
+        let upgrade = Upgrade::new(
+            endpoint,
+            handle,
+            connection,
+            self.config.peer_verifier,
+            self.config.max_idle_timeout,
+            self.config.close_timeout,
+            self.config.stream_write_buffer,
+        )
+        .with_progress_sender(sender);
+
+        Ok((receiver, upgrade))
+    }
+
+    /// Like [`Transport::dial`], but binds the dial's own socket to `source`
+    /// instead of an unspecified, ephemeral address - for a multihomed host
+    /// that needs a particular connection to leave from a specific local IP
+    /// (e.g. one bound to a specific interface or carrying a specific
+    /// routing policy), rather than whichever one the OS's default route
+    /// would otherwise pick.
+    ///
+    /// This is possible at all because every dial already gets its own
+    /// [`Endpoint`] with its own socket - see
+    /// [`Endpoint::new`](crate::endpoint::Endpoint::new) - unlike a
+    /// listener, whose single socket is shared by every connection it
+    /// accepts and fixed for its whole lifetime. There is no equivalent
+    /// per-*inbound*-connection source selection, and no way to change an
+    /// already-open connection's source after the fact.
+    pub fn dial_from(
+        self,
+        addr: Multiaddr,
+        source: SocketAddr,
+    ) -> Result<Upgrade, TransportError<Error>> {
+        let socket_addr = multiaddr_to_socketaddr(addr.clone(), self.config.strict_multiaddr)
+            .map_err(|()| TransportError::MultiaddrNotSupported(addr))?;
+        let endpoint = Endpoint::new(&self.config, source).map_err(TransportError::Other)?;
+        let (handle, connection) = endpoint
+            .dial_with_session_tickets(&self.config, socket_addr, &self.session_tickets)
+            .map_err(TransportError::Other)?;
+
+        Ok(Upgrade::new(
+            endpoint,
+            handle,
+            connection,
+            self.config.peer_verifier,
+            self.config.max_idle_timeout,
+            self.config.close_timeout,
+            self.config.stream_write_buffer,
+        ))
+    }
+
+    /// Like [`Transport::dial`], but rejected with [`Error::DialInProgress`]
+    /// if this transport (or any of its [`Clone`]s) already has a
+    /// [`QuicTransport::dial_exclusive`] call to the same address in flight,
+    /// instead of opening a second, independent `quinn_proto` connection to
+    /// it.
+    ///
+    /// [`Transport::dial`] itself intentionally allows any number of
+    /// concurrent connections to the same remote - flooding a single
+    /// listener with many independent connections to exercise its accept
+    /// backlog, for instance, is a legitimate and already-tested use of it.
+    /// This method is for callers who instead want "dialling the same
+    /// address twice before the first attempt resolves was almost certainly
+    /// a mistake" guarded against explicitly, e.g. a caller retrying a dial
+    /// on a timer without first checking whether the previous attempt is
+    /// still outstanding.
+    pub fn dial_exclusive(self, addr: Multiaddr) -> Result<Upgrade, TransportError<Error>> {
+        let socket_addr = multiaddr_to_socketaddr(addr.clone(), self.config.strict_multiaddr)
+            .map_err(|()| TransportError::MultiaddrNotSupported(addr.clone()))?;
+        let guard = InFlightDialGuard::new(self.in_flight_dials.clone(), socket_addr)
+            .ok_or(TransportError::Other(Error::DialInProgress))?;
+
+        let upgrade = self.dial(addr)?;
+        let (in_flight_dials, socket_addr) = guard.defuse();
+        Ok(upgrade.with_dial_dedup(in_flight_dials, socket_addr))
+    }
+
+    /// Reuses an already-open connection to `addr` if this transport has
+    /// dialled it before and the connection is still alive, or dials a new
+    /// one otherwise - the common need of a request-response client that
+    /// wants "give me a connection to this peer" without tracking one
+    /// itself.
+    ///
+    /// Opening the actual stream is left to the caller as the trivial
+    /// `muxer.open_bi()` call right after this resolves, the same as every
+    /// other stream-opening call site in this crate: [`BiStream`] borrows
+    /// the [`QuicMuxer`] it's opened on, so a single call can't hand back
+    /// both an owned muxer and a stream borrowed from it.
+    ///
+    /// `timeout` only bounds dialling a *new* connection - a cache hit
+    /// resolves immediately. A cached connection is checked for liveness
+    /// via [`QuicMuxer::close_reason`] before being handed back out, but
+    /// nothing drives it between calls to this method (this crate doesn't
+    /// spawn its own background tasks), so one that's gone quiet for a
+    /// while may already have been dropped by the peer's idle timeout
+    /// without this side noticing; the next call just dials fresh in that
+    /// case. The cache is keyed on [`SocketAddr`], not [`PeerId`] -
+    /// dialling the same peer at a different address is a cache miss.
+    ///
+    /// Unlike [`Transport::dial`], which (per [`QuicTransport`]'s own doc
+    /// comment) binds a fresh UDP socket and [`Endpoint`] on every call,
+    /// repeated calls to this method across [`Clone`]s of the same
+    /// [`QuicTransport`] share one cache, so a reused connection also
+    /// reuses the socket and [`Endpoint`] it was first dialled on.
+    pub async fn open_stream_to(
+        &self,
+        addr: Multiaddr,
+        timeout: Duration,
+    ) -> Result<Arc<QuicMuxer>, TransportError<Error>> {
+        let socket_addr = multiaddr_to_socketaddr(addr.clone(), self.config.strict_multiaddr)
+            .map_err(|()| TransportError::MultiaddrNotSupported(addr.clone()))?;
+
+        if let Some(muxer) = self.request_response_cache.lock().get(&socket_addr) {
+            if muxer.close_reason().is_none() {
+                return Ok(muxer.clone());
+            }
+        }
+
+        let upgrade = self.clone().dial(addr)?;
+        let delay = Delay::new(timeout);
+        let (_, muxer) = match future::select(upgrade, delay).await {
+            future::Either::Left((result, _)) => result.map_err(TransportError::Other)?,
+            future::Either::Right(((), _)) => {
+                return Err(TransportError::Other(Error::DialTimedOut))
+            }
+        };
+
+        let muxer = Arc::new(muxer);
+        self.request_response_cache
+            .lock()
+            .insert(socket_addr, muxer.clone());
+        Ok(muxer)
+    }
+}
+
+/// A milestone reached while dialling, reported through the stream returned
+/// by [`QuicTransport::dial_with_progress`].
+///
+/// There is no milestone between [`DialProgress::SentInitial`] and
+/// [`DialProgress::ReceivedResponse`] for, say, a retransmitted initial
+/// packet: `quinn_proto` doesn't surface retransmits as a distinct event,
+/// only the handshake's two real turning points on the dialling side.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DialProgress {
+    /// The initial handshake packet has been handed to the socket.
+    ///
+    /// Reported as soon as [`QuicTransport::dial_with_progress`] returns,
+    /// since [`Endpoint::dial`](crate::endpoint::Endpoint::dial) already
+    /// queues it before constructing the [`Upgrade`] at all.
+    SentInitial,
+    /// The peer's response to the initial packet has arrived, and
+    /// `quinn_proto` has handshake data ready to process from it.
+    ReceivedResponse,
+    /// The handshake is confirmed on this side; a [`QuicMuxer`] is about to
+    /// be handed out. The last milestone this stream reports.
+    HandshakeConfirmed,
+}
+
+/// A stream of inbound QUIC connections produced by [`QuicTransport::listen_on`].
+pub struct QuicListenStream {
+    endpoint: Endpoint,
+    /// Index this listener was assigned by [`Endpoint::register_listener`];
+    /// see [`Config::listener_dispatch_policy`] for how it decides which
+    /// inbound connection, on an endpoint possibly shared with other
+    /// listeners via [`QuicTransport::listen_on_shared_endpoint`], is this
+    /// listener's to pick up.
+    listener_index: usize,
+    listen_addr: SocketAddr,
+    reported_listen_addr: bool,
+    peer_verifier: Option<PeerVerifier>,
+    /// See [`Config::max_idle_timeout`]; threaded through to
+    /// [`QuicMuxer::effective_idle_timeout`] for connections this listener
+    /// accepts.
+    max_idle_timeout: Duration,
+    /// See [`Config::close_timeout`]; threaded through to every [`QuicMuxer`]
+    /// this listener hands out.
+    close_timeout: Option<Duration>,
+    /// See [`Config::stream_write_buffer`]; threaded through to every
+    /// [`QuicMuxer`] this listener hands out.
+    stream_write_buffer: usize,
+}
+
+impl QuicListenStream {
+    /// Stops accepting new inbound connections, refusing every future
+    /// handshake attempt with a QUIC `CONNECTION_REFUSED`, while connections
+    /// already established continue transferring data unaffected.
+    ///
+    /// Unlike dropping this [`QuicListenStream`] outright, this does not
+    /// tear down the underlying socket: already-accepted [`QuicMuxer`]s keep
+    /// sharing it to drive their connections. There is no way to resume
+    /// accepting afterwards.
+    pub fn stop_accepting(&self) {
+        self.endpoint.stop_accepting();
+    }
+
+    /// Number of datagrams the socket layer has reported as undeliverable
+    /// (e.g. too large for its current send buffer) and that were dropped,
+    /// since this listener's endpoint was created.
+    ///
+    /// Helps distinguish packet loss caused by local buffer exhaustion from
+    /// loss on the network itself; see also [`Config::on_datagram_dropped`].
+    pub fn dropped_datagrams(&self) -> u64 {
+        self.endpoint.dropped_datagrams()
+    }
+
+    /// Number of inbound connections refused because
+    /// [`Config::max_pending_connections`] was already reached when they
+    /// arrived, since this listener's endpoint was created.
+    pub fn refused_pending_connections(&self) -> u64 {
+        self.endpoint.refused_pending_connections()
+    }
+
+    /// Tightens or loosens, with immediate effect, how many live connections
+    /// this listener admits beyond [`Config::max_connections`]; `None`
+    /// removes the runtime cap entirely.
+    ///
+    /// Only handshakes that arrive after this call are affected - lowering
+    /// the cap below the current [`QuicListenStream::num_connections`] does
+    /// not retroactively close anything, it just refuses new attempts until
+    /// enough of the existing ones close on their own to fall back under it.
+    /// Since `quinn_proto` 0.7.3 bakes [`Config::max_connections`] into this
+    /// listener's `quinn_proto::ServerConfig` for good back when it was
+    /// built, `limit` can only ever tighten that original ceiling, never
+    /// raise it.
+    pub fn set_max_connections(&self, limit: Option<u32>) {
+        self.endpoint.set_max_connections(limit);
+    }
+
+    /// Tightens or loosens, with immediate effect, how many new inbound
+    /// connections this listener admits per second; `None` removes the rate
+    /// limit entirely.
+    ///
+    /// Only admission decisions made after this call are affected; a
+    /// connection already admitted keeps running regardless of a lowered
+    /// limit afterwards.
+    pub fn set_accept_rate_limit(&self, per_second: Option<u32>) {
+        self.endpoint.set_accept_rate_limit(per_second);
+    }
+
+    /// Number of inbound handshakes refused because the runtime limit set
+    /// through [`QuicListenStream::set_max_connections`] or
+    /// [`QuicListenStream::set_accept_rate_limit`] was already exhausted
+    /// when they arrived, since this listener's endpoint was created.
+    pub fn refused_over_runtime_limit(&self) -> u64 {
+        self.endpoint.refused_over_runtime_limit()
+    }
+
+    /// Closes every connection accepted by this listener for which
+    /// `predicate` returns `true`, with the given QUIC close code and
+    /// reason, leaving the rest open.
+    ///
+    /// For operations tooling that needs to act on many connections at
+    /// once, e.g. dropping every connection from a subnet being
+    /// decommissioned, or every connection older than some age. Connections
+    /// dialled out through [`QuicTransport::dial`] are unaffected, since
+    /// each dial gets its own endpoint rather than sharing this listener's.
+    pub fn close_connections(
+        &self,
+        predicate: impl Fn(&ConnectionInfo) -> bool,
+        code: quinn_proto::VarInt,
+        reason: Bytes,
+    ) {
+        self.endpoint.close_connections(predicate, code, reason);
+    }
+
+    /// Metadata of every inbound connection accepted by `quinn_proto` but not
+    /// yet picked up by this listener, right now, oldest first.
+    ///
+    /// For debugging connection-stall issues: a backlog that keeps growing,
+    /// or whose oldest entry's [`PendingInfo::received_at`] keeps getting
+    /// further in the past, points at this listener not being polled often
+    /// enough, as opposed to slow or failing handshakes on the remote end.
+    pub fn pending_connections(&self) -> Vec<PendingInfo> {
+        self.endpoint.pending_connections(self.listener_index)
+    }
+
+    /// Number of connections accepted by this listener that are still live,
+    /// right now.
+    ///
+    /// Cheaper than [`QuicListenStream::close_connections`] with an
+    /// always-`false` predicate for callers (e.g. an admin dashboard) that
+    /// only need a count to poll frequently. Connections dialled out
+    /// through [`QuicTransport::dial`] are not included, since each dial
+    /// gets its own endpoint rather than sharing this listener's.
+    pub fn num_connections(&self) -> usize {
+        self.endpoint.num_connections()
+    }
+
+    /// Endpoint-wide throughput: every live connection's own transmit/receive
+    /// byte and datagram counters, summed together, plus this listener's
+    /// [`QuicListenStream::dropped_datagrams`].
+    ///
+    /// The top-line number for capacity planning, as opposed to
+    /// [`QuicListenStream::close_connections`]'s per-connection detail.
+    /// Connections dialled out through [`QuicTransport::dial`] are not
+    /// included, since each dial gets its own endpoint rather than sharing
+    /// this listener's.
+    pub fn aggregate_stats(&self) -> EndpointStats {
+        self.endpoint.aggregate_stats()
+    }
+
+    /// A one-shot snapshot of this listener's endpoint - every live
+    /// connection's remote address, handshake/close state and driver status,
+    /// plus the inbound handshake backlog size and whether the socket has a
+    /// datagram waiting - for diagnosing a hang in production without
+    /// disturbing whatever is actually driving the endpoint.
+    ///
+    /// Connections dialled out through [`QuicTransport::dial`] are not
+    /// included, since each dial gets its own endpoint rather than sharing
+    /// this listener's.
+    pub fn dump_state(&self) -> EndpointStateDump {
+        self.endpoint.dump_state()
+    }
+
+    /// A stream of every [`EndpointEvent`] across every connection accepted
+    /// by this listener - established, closed, or a handshake that failed -
+    /// for a central consumer (e.g. a `NetworkBehaviour` tracking the
+    /// connection table) that would rather multiplex the whole listener
+    /// than poll each accepted [`QuicMuxer`] individually.
+    ///
+    /// Each call returns an independent stream starting from the moment
+    /// it's created; events emitted before this was called are not
+    /// replayed. Connections dialled out through [`QuicTransport::dial`]
+    /// are not included, since each dial gets its own endpoint rather than
+    /// sharing this listener's.
+    pub fn events(&self) -> impl Stream<Item = EndpointEvent> {
+        self.endpoint.events()
+    }
+
+    /// Combines [`QuicListenStream::stop_accepting`] with waiting for every
+    /// already-accepted connection to finish, for a zero-downtime handoff to
+    /// a new process listening on the same port (e.g. via `SO_REUSEPORT`):
+    /// refuse new connections here so the new process picks them up instead,
+    /// then resolve once this process's share of the traffic has actually
+    /// drained, or once `deadline` elapses, whichever comes first.
+    ///
+    /// Does not close whatever connections are still open when `deadline`
+    /// passes; callers that need a hard cutoff afterwards can follow up with
+    /// [`QuicListenStream::close_connections`].
+    pub async fn begin_drain(&self, deadline: Duration) {
+        self.stop_accepting();
+
+        const POLL_INTERVAL: Duration = Duration::from_millis(20);
+        let mut deadline_timer = Delay::new(deadline);
+        let mut poll_timer = Delay::new(POLL_INTERVAL);
+        poll_fn(|cx| {
+            if self.num_connections() == 0 {
+                return Poll::Ready(());
+            }
+            if Pin::new(&mut deadline_timer).poll(cx).is_ready() {
+                return Poll::Ready(());
+            }
+            // Nothing currently wakes this task when a connection closes, so
+            // fall back to re-checking on a short interval instead of
+            // blocking forever.
+            if Pin::new(&mut poll_timer).poll(cx).is_ready() {
+                poll_timer.reset(POLL_INTERVAL);
+            }
+            Poll::Pending
+        })
+        .await
+    }
+}
+
+impl Drop for QuicListenStream {
+    fn drop(&mut self) {
+        // Nothing is left to ever poll a connection that arrives from here
+        // on out of `pending_connections` - the endpoint itself typically
+        // outlives this listener, kept alive by already-accepted
+        // connections' own handle to it - so mark it gone rather than
+        // letting a connection that passes admission sit there unaccepted
+        // forever. This doesn't change what gets admitted in the first
+        // place: `Config::max_connections` and `set_accept_rate_limit` are
+        // consulted exactly as before, see `Endpoint::dispatch_datagram`.
+        self.endpoint.mark_listener_dropped(self.listener_index);
+
+        // Connections `quinn_proto` had already admitted but this listener
+        // never got around to polling out as a `ListenerEvent::Upgrade` have
+        // no other owner; left alone they'd just be dropped unclosed here,
+        // leaving the remote to learn about it (if at all) only once its own
+        // idle timeout lapses. Closing them now gets the `CONNECTION_CLOSE`
+        // out the door immediately instead.
+        self.endpoint.close_pending_connections(self.listener_index);
+    }
+}
+
+impl Stream for QuicListenStream {
+    type Item = Result<ListenerEvent<Upgrade, Error>, Error>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+
+        if !this.reported_listen_addr {
+            this.reported_listen_addr = true;
+            return Poll::Ready(Some(Ok(ListenerEvent::NewAddress(
+                socketaddr_to_quic_multiaddr(this.listen_addr),
+            ))));
+        }
+
+        match this.endpoint.poll_incoming(cx, this.listener_index) {
+            Poll::Ready(Ok((handle, connection))) => {
+                Poll::Ready(Some(Ok(ListenerEvent::Upgrade {
+                    upgrade: Upgrade::new(
+                        this.endpoint.clone(),
+                        handle,
+                        connection,
+                        this.peer_verifier.clone(),
+                        this.max_idle_timeout,
+                        this.close_timeout,
+                        this.stream_write_buffer,
+                    ),
+                    local_addr: socketaddr_to_quic_multiaddr(this.listen_addr),
+                    remote_addr: socketaddr_to_quic_multiaddr(this.listen_addr),
+                })))
+            }
+            Poll::Ready(Err(e)) => Poll::Ready(Some(Err(e))),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+/// Shared between an [`Upgrade`] and every [`AbortHandle`] obtained from it,
+/// so `abort()` can take effect without needing its own access to the
+/// `quinn_proto` connection: it just flags the intent and wakes whichever
+/// task is currently polling the `Upgrade`, which does the actual teardown
+/// next time it's polled.
+#[derive(Default)]
+struct AbortState {
+    aborted: bool,
+    waker: Option<Waker>,
+}
+
+/// A handle that can cancel an in-flight [`Upgrade`] from outside it,
+/// obtained from [`Upgrade::abort_handle`].
+///
+/// Dropping the `Upgrade` itself cancels the dial too: the underlying
+/// `quinn_proto` connection is closed and its handle freed either way.
+/// `abort()` is for callers that hold on to some other handle (e.g. because
+/// the `Upgrade` was moved into a `FuturesUnordered`) and need to cancel a
+/// specific dial without dropping whatever is driving it.
+#[derive(Clone)]
+pub struct AbortHandle(Arc<Mutex<AbortState>>);
+
+impl AbortHandle {
+    /// Requests that the dial this handle was obtained from be aborted.
+    ///
+    /// If the handshake had already made progress, the connection is closed
+    /// with a `CONNECTION_CLOSE` rather than simply abandoned, so the remote
+    /// learns about it promptly instead of waiting for its own idle timeout.
+    pub fn abort(&self) {
+        let mut state = self.0.lock();
+        state.aborted = true;
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+/// Future that resolves once the QUIC handshake for a dialled or accepted
+/// connection has completed, yielding the remote's [`PeerId`] and a
+/// [`QuicMuxer`] for the established connection.
+pub struct Upgrade {
+    endpoint: Endpoint,
+    handle: ConnectionHandle,
+    connection: Option<quinn_proto::Connection>,
+    driver: Driver,
+    // `is_handshaking()` can already be false (e.g. for the dialling side,
+    // once it has locally finished emitting its own handshake flight) well
+    // before the remote has confirmed the handshake on its end, so it isn't
+    // a safe signal that a [`QuicMuxer`] is ready to hand out. `Event::Connected`
+    // only fires once `quinn_proto` considers this side of the connection
+    // fully established.
+    connected: bool,
+    peer_verifier: Option<PeerVerifier>,
+    /// See [`Config::max_idle_timeout`]; carried through to the
+    /// [`QuicMuxer`] this resolves to.
+    max_idle_timeout: Duration,
+    /// See [`Config::close_timeout`]; carried through to the
+    /// [`QuicMuxer`] this resolves to.
+    close_timeout: Option<Duration>,
+    /// See [`Config::stream_write_buffer`]; carried through to the
+    /// [`QuicMuxer`] this resolves to.
+    stream_write_buffer: usize,
+    abort: Arc<Mutex<AbortState>>,
+    /// Set once `connection.close` has been called in response to
+    /// [`AbortHandle::abort`], or once the handshake itself has failed (a
+    /// rejected peer certificate, a timeout, a remote `CONNECTION_CLOSE`),
+    /// so `poll` stops waiting on the handshake and starts waiting on the
+    /// connection to finish draining instead - in every case, `handle`'s
+    /// slot isn't actually free until `quinn_proto` reports `Drained`, and
+    /// nothing drives that connection again once this `Upgrade` resolves
+    /// and is dropped.
+    closing: bool,
+    /// The error to report once `closing` finishes draining; `None` only
+    /// when `closing` was set by [`AbortHandle::abort`] instead of a
+    /// handshake failure, in which case [`Error::Aborted`] is reported.
+    failure: Option<Error>,
+    /// See [`QuicTransport::dial_with_progress`]; `None` for every
+    /// [`Upgrade`] obtained any other way, including every listener-side
+    /// one, since progress is only ever reported for outbound dials.
+    progress: Option<mpsc::UnboundedSender<DialProgress>>,
+    /// The [`QuicTransport::in_flight_dials`] entry reserved for this dial by
+    /// [`InFlightDialGuard`], released once this `Upgrade` is dropped -
+    /// whether it resolved successfully, failed, or was abandoned mid-flight.
+    /// `None` for every `Upgrade` obtained any other way, including every
+    /// listener-side one and every plain [`QuicTransport::dial`], since only
+    /// [`QuicTransport::dial_exclusive`] reserves an entry.
+    dial_dedup: Option<(Arc<Mutex<HashSet<SocketAddr>>>, SocketAddr)>,
+}
+
+impl Upgrade {
+    pub(crate) fn new(
+        endpoint: Endpoint,
+        handle: ConnectionHandle,
+        connection: quinn_proto::Connection,
+        peer_verifier: Option<PeerVerifier>,
+        max_idle_timeout: Duration,
+        close_timeout: Option<Duration>,
+        stream_write_buffer: usize,
+    ) -> Self {
+        Upgrade {
+            endpoint,
+            handle,
+            connection: Some(connection),
+            driver: Driver::default(),
+            connected: false,
+            peer_verifier,
+            max_idle_timeout,
+            close_timeout,
+            stream_write_buffer,
+            abort: Arc::new(Mutex::new(AbortState::default())),
+            closing: false,
+            failure: None,
+            progress: None,
+            dial_dedup: None,
+        }
+    }
+
+    /// Attaches `sender` as this [`Upgrade`]'s [`DialProgress`] channel,
+    /// reporting [`DialProgress::SentInitial`] immediately since the
+    /// initial packet is already queued by the time an [`Upgrade`] exists
+    /// at all; see [`QuicTransport::dial_with_progress`].
+    pub(crate) fn with_progress_sender(
+        mut self,
+        sender: mpsc::UnboundedSender<DialProgress>,
+    ) -> Self {
+        let _ = sender.unbounded_send(DialProgress::SentInitial);
+        self.progress = Some(sender);
+        self
+    }
+
+    /// Arranges for `addr`'s reservation in `dials` - made by
+    /// [`InFlightDialGuard`] when this `Upgrade` was dialled - to be
+    /// released once this `Upgrade` is dropped.
+    pub(crate) fn with_dial_dedup(
+        mut self,
+        dials: Arc<Mutex<HashSet<SocketAddr>>>,
+        addr: SocketAddr,
+    ) -> Self {
+        self.dial_dedup = Some((dials, addr));
+        self
+    }
+
+    /// Returns a handle that can cancel this dial from outside it; see
+    /// [`AbortHandle::abort`].
+    pub fn abort_handle(&self) -> AbortHandle {
+        AbortHandle(self.abort.clone())
+    }
+}
+
+/// Resolves `this` once `connection` has reached `quinn_proto`'s `Drained`
+/// state, now that `this.closing` is set.
+///
+/// `quinn_proto` doesn't always arm a timer on the way into `Draining` - a
+/// `CLOSE` frame received mid-handshake is one such case - so `poll_timeout`
+/// reporting nothing left to wait for while still short of `Drained` means
+/// no further wakeup will ever come; treat that the same as `Drained`
+/// rather than hanging forever, accepting that `handle`'s slot may leak in
+/// that one corner case exactly as it did before `closing` existed.
+fn closing_error(
+    failure: &mut Option<Error>,
+    connection: &mut quinn_proto::Connection,
+) -> Poll<Error> {
+    if connection.is_drained() || connection.poll_timeout().is_none() {
+        Poll::Ready(failure.take().unwrap_or(Error::Aborted))
+    } else {
+        Poll::Pending
+    }
+}
+
+impl Future for Upgrade {
+    type Output = Result<(PeerId, QuicMuxer), Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let connection = this
+            .connection
+            .as_mut()
+            .expect("Upgrade is not polled again after completion");
+
+        let mut abort = this.abort.lock();
+        if abort.aborted && !this.closing {
+            // Closing here rather than just dropping `connection` outright
+            // is what actually tells the remote, rather than leaving it to
+            // its own idle timeout to notice.
+            connection.close(
+                std::time::Instant::now(),
+                quinn_proto::VarInt::from_u32(0),
+                Default::default(),
+            );
+            this.closing = true;
+        }
+        abort.waker = Some(cx.waker().clone());
+        drop(abort);
+
+        if let Poll::Ready(Err(e)) =
+            this.endpoint
+                .drive(cx, this.handle, connection, &mut this.driver)
+        {
+            return Poll::Ready(Err(e));
+        }
+
+        if this.closing {
+            // `Endpoint::drive` already forwards `connection`'s endpoint
+            // events - including `Drained`, which frees `handle`'s slot -
+            // as a side effect of the call above; once it has, there's
+            // nothing left for this `Upgrade` to do but report why it never
+            // resolved into a live connection. Until then, `drive` has
+            // already re-armed the close timer that'll wake this task once
+            // draining finishes.
+            return match closing_error(&mut this.failure, connection) {
+                Poll::Ready(e) => {
+                    this.connection = None;
+                    Poll::Ready(Err(e))
+                }
+                Poll::Pending => Poll::Pending,
+            };
+        }
+
+        // Captured before draining events below: a `Connected` and a
+        // `ConnectionLost` can both be queued up by the same `drive` call
+        // above and come out of this same batch together, in which case
+        // treating the handshake as having already succeeded would defer
+        // the loss to `QuicMuxer::poll_event` - which, unlike this `Upgrade`,
+        // has no equivalent of `closing_error`'s guard against `quinn_proto`
+        // never arming a wakeup for it. Only a `Connected` from a *previous*
+        // poll should be trusted to make that handoff.
+        let was_connected = this.connected;
+
+        while let Some(event) = connection.poll() {
+            match event {
+                quinn_proto::Event::HandshakeDataReady => {
+                    if let Some(sender) = &this.progress {
+                        let _ = sender.unbounded_send(DialProgress::ReceivedResponse);
+                    }
+                }
+                quinn_proto::Event::Connected => {
+                    this.connected = true;
+                    if let Some(sender) = this.progress.take() {
+                        let _ = sender.unbounded_send(DialProgress::HandshakeConfirmed);
+                    }
+                }
+                // A handshake that times out without the remote ever having
+                // responded is the signature of an address that simply
+                // isn't reachable directly (a firewall, a symmetric NAT, a
+                // typo'd port), as opposed to one that got far enough to
+                // fail for a reason of its own; surface that distinction so
+                // callers can tell "try relaying instead" apart from a
+                // local or remote configuration problem. When not so much as
+                // one UDP datagram ever came back, narrow it further to
+                // `Error::NoResponse`, the signature of UDP itself being
+                // blocked, so callers can key a QUIC-to-TCP transport
+                // fallback off that specifically.
+                // Unlike the failure sites below, `quinn_proto` has already
+                // torn `connection` down by the time it reports
+                // `ConnectionLost` (that's how it learned the connection was
+                // lost) - it may already be drained, so check before
+                // assuming a later wakeup will come; see `closing_error`.
+                quinn_proto::Event::ConnectionLost {
+                    reason: reason @ quinn_proto::ConnectionError::TimedOut,
+                } if !was_connected => {
+                    this.endpoint
+                        .broadcast_event(EndpointEvent::HandshakeFailed {
+                            remote_address: connection.remote_address(),
+                            reason: reason.clone(),
+                        });
+                    this.failure = Some(if connection.stats().udp_rx.datagrams == 0 {
+                        Error::NoResponse
+                    } else {
+                        Error::DirectConnectionFailed(reason.to_string())
+                    });
+                    this.closing = true;
+                    return match closing_error(&mut this.failure, connection) {
+                        Poll::Ready(e) => {
+                            this.connection = None;
+                            Poll::Ready(Err(e))
+                        }
+                        Poll::Pending => Poll::Pending,
+                    };
+                }
+                quinn_proto::Event::ConnectionLost { reason } if !was_connected => {
+                    this.endpoint
+                        .broadcast_event(EndpointEvent::HandshakeFailed {
+                            remote_address: connection.remote_address(),
+                            reason: reason.clone(),
+                        });
+                    this.failure = Some(Error::Handshake(reason.to_string()));
+                    this.closing = true;
+                    return match closing_error(&mut this.failure, connection) {
+                        Poll::Ready(e) => {
+                            this.connection = None;
+                            Poll::Ready(Err(e))
+                        }
+                        Poll::Pending => Poll::Pending,
+                    };
+                }
+                // Once the handshake itself has completed, a lost
+                // connection is the muxer's problem to report through
+                // `QuicMuxer::poll_event`, not this `Upgrade`'s - see
+                // `peer_verifier_rejects_unpinned_peers`, which depends on
+                // the dialer's own handshake succeeding even though the
+                // listener is about to reject it and close the connection.
+                quinn_proto::Event::ConnectionLost { .. } => {}
+                _ => {}
+            }
+        }
+
+        if !this.connected {
+            return Poll::Pending;
+        }
+
+        let remote_certificate = match connection
+            .crypto_session()
+            .peer_identity()
+            .and_then(|chain| chain.iter().next().cloned())
+        {
+            Some(cert) => cert,
+            None => {
+                // Without an explicit close, a rejected `Connection` is
+                // simply dropped un-closed: the remote sees nothing wrong
+                // until it eventually times out, rather than promptly.
+                connection.close(
+                    std::time::Instant::now(),
+                    quinn_proto::VarInt::from_u32(0),
+                    Default::default(),
+                );
+                let _ = this
+                    .endpoint
+                    .drive(cx, this.handle, connection, &mut this.driver);
+                this.failure = Some(Error::Handshake(
+                    "peer did not present a certificate".into(),
+                ));
+                this.closing = true;
+                return Poll::Pending;
+            }
+        };
+        let peer_id = match crate::certificate::extract_peer_id(&remote_certificate) {
+            Ok(peer_id) => peer_id,
+            Err(e) => {
+                connection.close(
+                    std::time::Instant::now(),
+                    quinn_proto::VarInt::from_u32(0),
+                    Default::default(),
+                );
+                let _ = this
+                    .endpoint
+                    .drive(cx, this.handle, connection, &mut this.driver);
+                this.failure = Some(e);
+                this.closing = true;
+                return Poll::Pending;
+            }
+        };
+        if let Some(verifier) = &this.peer_verifier {
+            if let Err(e) = verifier(&peer_id, &remote_certificate) {
+                // Without an explicit close, a rejected `Connection` is
+                // simply dropped un-closed: the remote sees nothing wrong
+                // until it eventually times out, rather than promptly.
+                connection.close(
+                    std::time::Instant::now(),
+                    quinn_proto::VarInt::from_u32(0),
+                    Default::default(),
+                );
+                let _ = this
+                    .endpoint
+                    .drive(cx, this.handle, connection, &mut this.driver);
+                this.failure = Some(e);
+                this.closing = true;
+                return Poll::Pending;
+            }
+        }
+        if connection.side().is_server()
+            && !this
+                .endpoint
+                .try_reserve_peer_connection(this.handle, peer_id)
+        {
+            // Same undifferentiated drop as a `peer_verifier` rejection
+            // above: the remote finds out via its own idle timeout rather
+            // than an explicit signal, since [`Error::TooManyConnectionsFromPeer`]
+            // would otherwise tell it exactly why to keep retrying.
+            connection.close(
+                std::time::Instant::now(),
+                quinn_proto::VarInt::from_u32(0),
+                Default::default(),
+            );
+            let _ = this
+                .endpoint
+                .drive(cx, this.handle, connection, &mut this.driver);
+            this.failure = Some(Error::TooManyConnectionsFromPeer);
+            this.closing = true;
+            return Poll::Pending;
+        }
+
+        let connection = this.connection.take().expect("checked above");
+        Poll::Ready(Ok((
+            peer_id,
+            QuicMuxer::new(
+                this.endpoint.clone(),
+                connection,
+                this.handle,
+                this.max_idle_timeout,
+                this.close_timeout,
+                this.stream_write_buffer,
+            ),
+        )))
+    }
+}
+
+impl Drop for Upgrade {
+    fn drop(&mut self) {
+        if let Some((dials, addr)) = self.dial_dedup.take() {
+            dials.lock().remove(&addr);
+        }
+
+        // `Some` here means the handshake never resolved one way or the
+        // other (`poll` always takes `connection` before returning
+        // `Ready`): the caller lost interest mid-handshake, either by
+        // dropping this `Upgrade` directly or by calling `AbortHandle::abort`
+        // and then dropping it without polling again to let the draining
+        // above run its course. Either way, one last close-and-drive here -
+        // rather than just letting `connection` fall out of scope unclosed -
+        // at least gets the `CONNECTION_CLOSE` itself out the door
+        // immediately, so the remote learns about it now rather than at its
+        // own idle timeout.
+        //
+        // Freeing `handle`'s slot is a different matter: `quinn_proto` only
+        // reports a connection drained once its local draining timer has
+        // actually elapsed, and discovering that requires polling the
+        // connection again afterwards (see `Endpoint::drive`'s loop). Once
+        // this `Upgrade` is dropped, nothing will ever do that for this
+        // connection again - unlike a live `QuicMuxer`, which keeps getting
+        // driven by whatever task holds it. So dropping an `Upgrade`
+        // outright, rather than calling `AbortHandle::abort` and continuing
+        // to poll it to completion, leaks the connection ID slot for good,
+        // not just until the draining timer fires; see
+        // `dropping_an_upgrade_outright_never_frees_its_connection_id`.
+        if let Some(mut connection) = self.connection.take() {
+            connection.close(
+                std::time::Instant::now(),
+                quinn_proto::VarInt::from_u32(0),
+                Default::default(),
+            );
+            let waker = futures::task::noop_waker();
+            let mut cx = Context::from_waker(&waker);
+            let _ = self
+                .endpoint
+                .drive(&mut cx, self.handle, &mut connection, &mut self.driver);
+        }
+    }
+}
+
+/// Future produced by [`QuicTransport::dial_any`]: polls every remaining
+/// candidate [`Upgrade`] each time it's woken, returns the first to resolve
+/// successfully, and drops the rest.
+pub struct DialAny {
+    pending: Vec<Upgrade>,
+    /// `Display` of each candidate's failure so far, in case every one of
+    /// them ends up failing and the caller wants to know why.
+    errors: Vec<String>,
+}
+
+impl Future for DialAny {
+    type Output = Result<(PeerId, QuicMuxer), Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+
+        let mut i = 0;
+        while i < this.pending.len() {
+            match Pin::new(&mut this.pending[i]).poll(cx) {
+                Poll::Ready(Ok(output)) => return Poll::Ready(Ok(output)),
+                Poll::Ready(Err(e)) => {
+                    this.errors.push(e.to_string());
+                    this.pending.remove(i);
+                }
+                Poll::Pending => i += 1,
+            }
+        }
+
+        if this.pending.is_empty() {
+            return Poll::Ready(Err(Error::DialAnyFailed(this.errors.join("; "))));
+        }
+
+        Poll::Pending
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BacklogOverflowPolicy;
+    use crate::CipherSuite;
+    use crate::Config;
+    use crate::Offloads;
+    use crate::TransmitAction;
+    use crate::{BiStream, ReadOutcome, RecvStream, SendStream, StreamScheduler, StreamWindows};
+    use futures::stream;
+    use libp2p_core::identity::Keypair;
+    use libp2p_core::muxing::{StreamMuxer, StreamMuxerEvent};
+    use std::sync::Arc;
+
+    /// Keeps polling `muxer.poll_event` in the background, as a real
+    /// [`Swarm`](https://docs.rs/libp2p-swarm) would for the lifetime of a
+    /// connection: some of the handshake's confirmation data (e.g. the
+    /// dialler's own `Finished` message) is only flushed once the muxer
+    /// it's handed off to keeps being driven, not by `Upgrade` itself.
+    fn drive_in_background(muxer: Arc<QuicMuxer>) {
+        async_std::task::spawn(future::poll_fn(move |cx| loop {
+            match muxer.poll_event(cx) {
+                Poll::Ready(Ok(_)) => continue,
+                Poll::Ready(Err(_)) => return Poll::Ready(()),
+                Poll::Pending => return Poll::Pending,
+            }
+        }));
+    }
+
+    /// Like [`drive_in_background`], but gives up once `deadline` elapses
+    /// rather than running forever: a connection we closed ourselves never
+    /// reports `Event::ConnectionLost` back through `poll_event` (only the
+    /// peer observing our close does), so driving it this way for every
+    /// round of a churn test would otherwise pile up one permanently
+    /// pending background task per round. Draining fully completes well
+    /// within a handful of round-trip times on loopback, so `deadline` is
+    /// only ever a backstop against that pile-up, not load-bearing for
+    /// correctness.
+    fn drive_in_background_briefly(muxer: Arc<QuicMuxer>, deadline: Duration) {
+        async_std::task::spawn(async move {
+            let _ = async_std::future::timeout(
+                deadline,
+                future::poll_fn(move |cx| loop {
+                    match muxer.poll_event(cx) {
+                        Poll::Ready(Ok(_)) => continue,
+                        Poll::Ready(Err(_)) => return Poll::Ready(()),
+                        Poll::Pending => return Poll::Pending,
+                    }
+                }),
+            )
+            .await;
+        });
+    }
+
+    mod dial {
+        //! Outbound `dial`/`dial_from`/`dial_with_progress` behavior: address parsing, handshake completion, and per-dial error paths.
+        use super::*;
+
+        /// [`socketaddr_to_quic_multiaddr`] should produce exactly the address
+        /// [`multiaddr_to_socketaddr`] would parse back into the original
+        /// `SocketAddr`, for both IP families.
+        #[test]
+        fn socketaddr_to_quic_multiaddr_round_trips_through_multiaddr_to_socketaddr() {
+            let v4 = SocketAddr::new(std::net::Ipv4Addr::new(127, 0, 0, 1).into(), 1234);
+            assert_eq!(
+                multiaddr_to_socketaddr(socketaddr_to_quic_multiaddr(v4), true),
+                Ok(v4)
+            );
+
+            let v6 = SocketAddr::new(std::net::Ipv6Addr::LOCALHOST.into(), 4321);
+            assert_eq!(
+                multiaddr_to_socketaddr(socketaddr_to_quic_multiaddr(v6), true),
+                Ok(v6)
+            );
+        }
+
+        /// An unrecognized protocol trailing the `/ip4/.../udp/PORT/quic` core
+        /// is rejected in strict mode (the default).
+        ///
+        /// The vendored `multiaddr` 0.13 has no `/webtransport` protocol - or
+        /// anything else a future QUIC-based transport might tack on - to
+        /// demonstrate this with directly, so `/ws` stands in for "some
+        /// protocol this core doesn't itself understand" here.
+        #[test]
+        fn multiaddr_to_socketaddr_rejects_a_trailing_extension_when_strict() {
+            let addr: Multiaddr = "/ip4/127.0.0.1/udp/1234/quic/ws".parse().unwrap();
+            assert_eq!(multiaddr_to_socketaddr(addr, true), Err(()));
+        }
+
+        /// The same address [`multiaddr_to_socketaddr_rejects_a_trailing_extension_when_strict`]
+        /// rejects is accepted, with the trailing `/ws` ignored, once
+        /// [`Config::strict_multiaddr`] is turned off.
+        #[test]
+        fn multiaddr_to_socketaddr_ignores_a_trailing_extension_when_lenient() {
+            let addr: Multiaddr = "/ip4/127.0.0.1/udp/1234/quic/ws".parse().unwrap();
+            let expected = SocketAddr::new(std::net::Ipv4Addr::new(127, 0, 0, 1).into(), 1234);
+            assert_eq!(multiaddr_to_socketaddr(addr, false), Ok(expected));
+        }
+
+        /// [`QuicTransport`] already implements [`Transport`] by value (not on a
+        /// borrow of some shared `Endpoint` handle, the way the trait's own docs
+        /// anticipate some transports might need to), so [`Transport::boxed`]
+        /// and the rest of the `Transport::map`/`boxed` combinators drop onto it
+        /// exactly as they would onto `libp2p-tcp` or `libp2p-dns`. This is a
+        /// compile-and-connect check that composing `QuicTransport` this way
+        /// keeps working, not a regression test for any specific bug.
+        #[test]
+        fn boxed_transport_completes_a_handshake() {
+            env_logger::try_init().ok();
+
+            async fn listener(
+                addr: Multiaddr,
+                mut ready_tx: futures::channel::mpsc::Sender<Multiaddr>,
+            ) {
+                let transport =
+                    QuicTransport::new(Config::new(&Keypair::generate_ed25519())).boxed();
+                let mut listener = transport.listen_on(addr).unwrap();
+                loop {
+                    match listener.next().await.unwrap().unwrap() {
+                        ListenerEvent::NewAddress(listen_addr) => {
+                            ready_tx.send(listen_addr).await.unwrap();
+                        }
+                        ListenerEvent::Upgrade { upgrade, .. } => {
+                            let (_, muxer) = upgrade.await.unwrap();
+                            drive_in_background(Arc::new(muxer));
+                            return;
+                        }
+                        ListenerEvent::Error(e) => panic!("Unexpected listener error: {}", e),
+                        ListenerEvent::AddressExpired(_) => {}
+                    }
+                }
+            }
+
+            async fn dialer(mut ready_rx: futures::channel::mpsc::Receiver<Multiaddr>) {
+                let addr = ready_rx.next().await.unwrap();
+                let transport =
+                    QuicTransport::new(Config::new(&Keypair::generate_ed25519())).boxed();
+                let (_, muxer) = transport.dial(addr).unwrap().await.unwrap();
+                drive_in_background(Arc::new(muxer));
+            }
+
+            let addr: Multiaddr = "/ip4/127.0.0.1/udp/0/quic".parse().unwrap();
+            let (ready_tx, ready_rx) = futures::channel::mpsc::channel(1);
+            let listener = async_std::task::spawn(listener(addr, ready_tx));
+            async_std::task::block_on(dialer(ready_rx));
+            async_std::task::block_on(listener);
+        }
+
+        #[test]
+        fn completes_handshake_on_pre_bound_socket() {
+            env_logger::try_init().ok();
+
+            async fn listener(
+                socket: StdUdpSocket,
+                mut ready_tx: futures::channel::mpsc::Sender<Multiaddr>,
+            ) {
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+                let mut listener = transport.listen_on_socket(socket).unwrap();
+                loop {
+                    match listener.next().await.unwrap().unwrap() {
+                        ListenerEvent::NewAddress(listen_addr) => {
+                            ready_tx.send(listen_addr).await.unwrap();
+                        }
+                        ListenerEvent::Upgrade { upgrade, .. } => {
+                            upgrade.await.unwrap();
+                            return;
+                        }
+                        ListenerEvent::Error(e) => panic!("Unexpected listener error: {}", e),
+                        ListenerEvent::AddressExpired(_) => {}
+                    }
+                }
+            }
+
+            async fn dialer(mut ready_rx: futures::channel::mpsc::Receiver<Multiaddr>) {
+                let addr = ready_rx.next().await.unwrap();
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+                let (_, muxer) = transport.dial(addr).unwrap().await.unwrap();
+                drive_in_background(Arc::new(muxer));
+            }
+
+            let socket = StdUdpSocket::bind((std::net::Ipv4Addr::LOCALHOST, 0)).unwrap();
+            let (ready_tx, ready_rx) = futures::channel::mpsc::channel(1);
+            let listener = async_std::task::spawn(listener(socket, ready_tx));
+            async_std::task::block_on(dialer(ready_rx));
+            async_std::task::block_on(listener);
+        }
+
+        /// Binds an address not assigned to any local interface, which fails
+        /// with `EADDRNOTAVAIL` without [`Config::freebind`], and confirms
+        /// [`super::bind_socket`] succeeds at the same bind once it's set.
+        #[cfg(target_os = "linux")]
+        #[test]
+        #[ignore] // Requires a kernel that actually honors IP_FREEBIND; some
+                  // sandboxed network stacks (e.g. gVisor) accept the
+                  // `setsockopt` call without enforcing the relaxed bind check,
+                  // so this only demonstrates the intended behavior on a real
+                  // Linux host.
+        fn freebind_allows_binding_an_address_not_yet_assigned_to_any_interface() {
+            let addr: SocketAddr = "10.255.255.1:0".parse().unwrap();
+
+            assert!(
+                StdUdpSocket::bind(addr).is_err(),
+                "expected a plain bind to a non-local address to fail without freebind"
+            );
+
+            bind_socket(addr, true, None)
+                .expect("Config::freebind should allow binding a non-local address");
+        }
+
+        /// Confirms [`super::bind_socket`] actually sets `IP_TOS` to the shifted
+        /// DSCP codepoint [`Config::dscp`] asks for, by reading it back off the
+        /// socket rather than just trusting `setsockopt` didn't error.
+        #[cfg(any(
+            target_os = "linux",
+            target_os = "freebsd",
+            target_os = "openbsd",
+            target_os = "netbsd",
+            target_os = "dragonfly",
+            target_os = "macos"
+        ))]
+        #[test]
+        fn dscp_is_reflected_in_the_socket_s_tos() {
+            let addr: SocketAddr = "127.0.0.1:0".parse().unwrap();
+
+            let socket = bind_socket(addr, false, Some(46))
+                .expect("binding with a DSCP value set should not fail");
+            let socket = socket2::Socket::from(socket);
+
+            assert_eq!(socket.tos().unwrap(), 46u32 << 2);
+        }
+
+        /// [`QuicTransport::dial_with_progress`] reports
+        /// [`DialProgress::SentInitial`], [`DialProgress::ReceivedResponse`],
+        /// then [`DialProgress::HandshakeConfirmed`] in that order, and nothing
+        /// after, over the course of one successful loopback dial.
+        #[test]
+        fn dial_with_progress_reports_milestones_in_order() {
+            env_logger::try_init().ok();
+
+            async fn listener(
+                addr: Multiaddr,
+                mut ready_tx: futures::channel::mpsc::Sender<Multiaddr>,
+            ) {
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+                let mut listener = transport.listen_on(addr).unwrap();
+                loop {
+                    match listener.next().await.unwrap().unwrap() {
+                        ListenerEvent::NewAddress(listen_addr) => {
+                            ready_tx.send(listen_addr).await.unwrap();
+                        }
+                        ListenerEvent::Upgrade { upgrade, .. } => {
+                            let (_, muxer) = upgrade.await.unwrap();
+                            drive_in_background(Arc::new(muxer));
+                            return;
+                        }
+                        ListenerEvent::Error(e) => panic!("Unexpected listener error: {}", e),
+                        ListenerEvent::AddressExpired(_) => {}
+                    }
+                }
+            }
+
+            async fn dialer(
+                mut ready_rx: futures::channel::mpsc::Receiver<Multiaddr>,
+            ) -> Vec<DialProgress> {
+                let addr = ready_rx.next().await.unwrap();
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+                let (mut progress, upgrade) = transport.dial_with_progress(addr).unwrap();
+                let (_, muxer) = upgrade.await.unwrap();
+                drive_in_background(Arc::new(muxer));
+
+                let mut milestones = Vec::new();
+                while let Some(milestone) = progress.next().await {
+                    milestones.push(milestone);
+                }
+                milestones
+            }
+
+            let addr: Multiaddr = "/ip4/127.0.0.1/udp/0/quic".parse().unwrap();
+            let (ready_tx, ready_rx) = futures::channel::mpsc::channel(1);
+            let listener = async_std::task::spawn(listener(addr, ready_tx));
+            let milestones = async_std::task::block_on(dialer(ready_rx));
+            async_std::task::block_on(listener);
+
+            assert_eq!(
+                milestones,
+                vec![
+                    DialProgress::SentInitial,
+                    DialProgress::ReceivedResponse,
+                    DialProgress::HandshakeConfirmed,
+                ]
+            );
+        }
+
+        /// [`QuicTransport::dial_from`] lets a multihomed host pick which local
+        /// address a given dial leaves from: two dials to the same listener,
+        /// each pinned to a different loopback source address, arrive with two
+        /// different source addresses as seen from the listener's side.
+        #[test]
+        fn dial_from_lets_two_dials_use_distinct_source_addresses() {
+            env_logger::try_init().ok();
+
+            async fn listener(
+                addr: Multiaddr,
+                mut ready_tx: futures::channel::mpsc::Sender<Multiaddr>,
+            ) {
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+                let mut listener = transport.listen_on(addr).unwrap();
+                let mut seen = Vec::new();
+                loop {
+                    match listener.next().await.unwrap().unwrap() {
+                        ListenerEvent::NewAddress(listen_addr) => {
+                            ready_tx.send(listen_addr).await.unwrap();
+                        }
+                        ListenerEvent::Upgrade { upgrade, .. } => {
+                            let (_, muxer) = upgrade.await.unwrap();
+                            seen.push(muxer.remote_address().ip());
+                            drive_in_background(Arc::new(muxer));
+                            if seen.len() == 2 {
+                                break;
+                            }
+                        }
+                        ListenerEvent::Error(e) => panic!("Unexpected listener error: {}", e),
+                        ListenerEvent::AddressExpired(_) => {}
+                    }
+                }
+
+                let expected: std::collections::HashSet<std::net::IpAddr> = vec![
+                    std::net::Ipv4Addr::new(127, 0, 0, 2).into(),
+                    std::net::Ipv4Addr::new(127, 0, 0, 3).into(),
+                ]
+                .into_iter()
+                .collect();
+                let actual: std::collections::HashSet<_> = seen.into_iter().collect();
+                assert_eq!(
+                    actual, expected,
+                    "each dial_from call should have reached the listener from \
+                     its own pinned source address"
+                );
+            }
+
+            async fn dialer(addr: Multiaddr, source: SocketAddr) {
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+                let upgrade = transport.dial_from(addr, source).unwrap();
+                let (_, muxer) = upgrade.await.unwrap();
+                drive_in_background(Arc::new(muxer));
+            }
+
+            let addr: Multiaddr = "/ip4/127.0.0.1/udp/0/quic".parse().unwrap();
+            let (ready_tx, mut ready_rx) = futures::channel::mpsc::channel(1);
+            let listener = async_std::task::spawn(listener(addr, ready_tx));
+            let listen_addr = async_std::task::block_on(ready_rx.next()).unwrap();
+
+            let source_a: SocketAddr = "127.0.0.2:0".parse().unwrap();
+            let source_b: SocketAddr = "127.0.0.3:0".parse().unwrap();
+            let dialer_a = async_std::task::spawn(dialer(listen_addr.clone(), source_a));
+            let dialer_b = async_std::task::spawn(dialer(listen_addr, source_b));
+            async_std::task::block_on(dialer_a);
+            async_std::task::block_on(dialer_b);
+            async_std::task::block_on(listener);
+        }
+
+        /// `set_context`/`context` round-trip an arbitrary `T` by type: nothing
+        /// is stored until `set_context` is called, the value comes back
+        /// through `context::<T>` afterwards, a second `set_context` call is a
+        /// no-op, and asking for a type that was never stored reads `None`
+        /// rather than panicking or downcasting incorrectly.
+        #[test]
+        fn context_round_trips_a_custom_user_data_type() {
+            env_logger::try_init().ok();
+
+            #[derive(Debug, PartialEq)]
+            struct SessionId(u32);
+
+            async fn listener(
+                addr: Multiaddr,
+                mut ready_tx: futures::channel::mpsc::Sender<Multiaddr>,
+            ) {
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+                let mut listener = transport.listen_on(addr).unwrap();
+                loop {
+                    match listener.next().await.unwrap().unwrap() {
+                        ListenerEvent::NewAddress(listen_addr) => {
+                            ready_tx.send(listen_addr).await.unwrap();
+                        }
+                        ListenerEvent::Upgrade { upgrade, .. } => {
+                            let (_, muxer) = upgrade.await.unwrap();
+                            assert!(muxer.context::<SessionId>().is_none());
+
+                            muxer.set_context(SessionId(42));
+                            assert_eq!(muxer.context::<SessionId>(), Some(&SessionId(42)));
+
+                            muxer.set_context(SessionId(7));
+                            assert_eq!(muxer.context::<SessionId>(), Some(&SessionId(42)));
+                            assert!(muxer.context::<&'static str>().is_none());
+                            return;
+                        }
+                        ListenerEvent::Error(e) => panic!("Unexpected listener error: {}", e),
+                        ListenerEvent::AddressExpired(_) => {}
+                    }
+                }
+            }
+
+            async fn dialer(mut ready_rx: futures::channel::mpsc::Receiver<Multiaddr>) {
+                let addr = ready_rx.next().await.unwrap();
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+                let (_, muxer) = transport.dial(addr).unwrap().await.unwrap();
+                drive_in_background(Arc::new(muxer));
+            }
+
+            let addr: Multiaddr = "/ip4/127.0.0.1/udp/0/quic".parse().unwrap();
+            let (ready_tx, ready_rx) = futures::channel::mpsc::channel(1);
+            let listener = async_std::task::spawn(listener(addr, ready_tx));
+            async_std::task::block_on(dialer(ready_rx));
+            async_std::task::block_on(listener);
+        }
+
+        /// `dial_any` races one address nobody is listening on against one that
+        /// actually has a listener behind it, and should resolve with the
+        /// latter well before the black hole's handshake attempt would time out.
+        #[test]
+        fn dial_any_picks_the_reachable_address_and_ignores_the_black_hole() {
+            env_logger::try_init().ok();
+
+            async fn listener(
+                addr: Multiaddr,
+                mut ready_tx: futures::channel::mpsc::Sender<Multiaddr>,
+            ) {
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+                let mut listener = transport.listen_on(addr).unwrap();
+                loop {
+                    match listener.next().await.unwrap().unwrap() {
+                        ListenerEvent::NewAddress(listen_addr) => {
+                            ready_tx.send(listen_addr).await.unwrap();
+                        }
+                        ListenerEvent::Upgrade { upgrade, .. } => {
+                            let (_, muxer) = upgrade.await.unwrap();
+                            drive_in_background(Arc::new(muxer));
+                            return;
+                        }
+                        ListenerEvent::Error(e) => panic!("Unexpected listener error: {}", e),
+                        ListenerEvent::AddressExpired(_) => {}
+                    }
+                }
+            }
+
+            async fn dialer(mut ready_rx: futures::channel::mpsc::Receiver<Multiaddr>) {
+                let good_addr = ready_rx.next().await.unwrap();
+                // A UDP port that never has anything bound to it on loopback:
+                // every packet sent to it simply vanishes, exactly like a
+                // firewalled or otherwise unreachable real-world address.
+                let black_hole_addr: Multiaddr = "/ip4/127.0.0.1/udp/1/quic".parse().unwrap();
+
+                let transport = QuicTransport::new(
+                    // Short enough that, if `dial_any` picked the black hole, the
+                    // test would still finish in reasonable time; long enough
+                    // that it can't be mistaken for the winning address's own
+                    // (much faster) loopback handshake.
+                    Config::new(&Keypair::generate_ed25519())
+                        .max_idle_timeout(std::time::Duration::from_millis(500)),
+                );
+
+                let start = std::time::Instant::now();
+                let (peer_id, muxer) = transport
+                    .dial_any(vec![black_hole_addr, good_addr])
+                    .unwrap()
+                    .await
+                    .unwrap();
+                let elapsed = start.elapsed();
+
+                assert!(
+                    elapsed < std::time::Duration::from_millis(500),
+                    "dial_any took {:?}, as long as the black hole's own idle timeout; \
+                     it likely waited for the black hole instead of racing it",
+                    elapsed
+                );
+
+                let _ = peer_id;
+                drive_in_background(Arc::new(muxer));
+            }
+
+            let addr: Multiaddr = "/ip4/127.0.0.1/udp/0/quic".parse().unwrap();
+            let (ready_tx, ready_rx) = futures::channel::mpsc::channel(1);
+            let listener = async_std::task::spawn(listener(addr, ready_tx));
+            async_std::task::block_on(dialer(ready_rx));
+            async_std::task::block_on(listener);
+        }
+
+        /// Dialling an address nothing is listening on times out without ever
+        /// hearing back from the remote at all, which should surface as
+        /// `Error::NoResponse` - the signature of UDP itself being blocked -
+        /// rather than the generic `Handshake` variant or the less specific
+        /// `Error::DirectConnectionFailed`, so a caller can tell "try a TCP
+        /// transport instead, UDP looks blocked" apart from a handshake that
+        /// failed for its own reasons.
+        #[test]
+        fn dialing_an_unreachable_address_yields_no_response() {
+            env_logger::try_init().ok();
+
+            // A UDP port that never has anything bound to it on loopback: every
+            // packet sent to it simply vanishes, exactly like a firewalled or
+            // otherwise unreachable real-world address.
+            let black_hole_addr: Multiaddr = "/ip4/127.0.0.1/udp/1/quic".parse().unwrap();
+
+            let transport = QuicTransport::new(
+                Config::new(&Keypair::generate_ed25519())
+                    .max_idle_timeout(std::time::Duration::from_millis(200)),
+            );
+
+            let result = async_std::task::block_on(transport.dial(black_hole_addr).unwrap())
+                .map(|_| ())
+                .unwrap_err();
+
+            assert!(
+                matches!(result, Error::NoResponse),
+                "expected a no-response error dialling an address that never \
+                 sends anything back, got {:?}",
+                result
+            );
+        }
+
+        /// `Endpoint::dial` rejects a port-0 remote address itself, before any
+        /// I/O happens, as [`Error::Connect`] wrapping `quinn_proto`'s own
+        /// `ConnectError::InvalidRemoteAddress`; unlike [`Error::Handshake`],
+        /// which only keeps a formatted string, `source()` should still reach
+        /// that underlying `ConnectError`.
+        #[test]
+        fn connect_failure_preserves_its_source_in_the_error_chain() {
+            env_logger::try_init().ok();
+
+            let config = Config::new(&Keypair::generate_ed25519());
+            let socket = StdUdpSocket::bind((std::net::Ipv4Addr::LOCALHOST, 0)).unwrap();
+            let endpoint = Endpoint::from_socket(&config, socket).unwrap();
+            let remote: SocketAddr = "127.0.0.1:0".parse().unwrap();
+
+            let error = endpoint.dial(&config, remote).unwrap_err();
+
+            assert!(
+                matches!(error, Error::Connect(_)),
+                "expected a port-0 remote address to be rejected as Error::Connect, \
+                 got {:?}",
+                error
+            );
+            let source = std::error::Error::source(&error)
+                .expect("Error::Connect should keep its ConnectError as its source");
+            assert_eq!(
+                source.downcast_ref::<quinn_proto::ConnectError>(),
+                Some(&quinn_proto::ConnectError::InvalidRemoteAddress(remote)),
+                "expected source() to return the ConnectError that caused the failure"
+            );
+        }
+
+        /// Several dials issued concurrently from the same [`Endpoint`] all
+        /// contend for its one `inner` mutex - the driver polling each
+        /// connection's handshake, and the initial `Endpoint::dial` calls
+        /// themselves, all go through [`Endpoint::lock_inner`]. With
+        /// `lock-contention-metrics` enabled, [`Endpoint::lock_stats`] should
+        /// reflect that: at least one acquisition per dial, and some nonzero
+        /// total wait, since even an uncontended `parking_lot::Mutex::lock`
+        /// takes measurable wall-clock time.
+        #[test]
+        #[cfg(feature = "lock-contention-metrics")]
+        fn concurrent_dials_from_one_endpoint_record_nonzero_lock_wait() {
+            env_logger::try_init().ok();
+
+            async fn listener(
+                addr: Multiaddr,
+                mut ready_tx: futures::channel::mpsc::Sender<Multiaddr>,
+            ) {
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+                let mut listener = transport.listen_on(addr).unwrap();
+                loop {
+                    match listener.next().await.unwrap().unwrap() {
+                        ListenerEvent::NewAddress(listen_addr) => {
+                            ready_tx.send(listen_addr).await.unwrap();
+                        }
+                        ListenerEvent::Upgrade { upgrade, .. } => {
+                            let (_, muxer) = upgrade.await.unwrap();
+                            drive_in_background(Arc::new(muxer));
+                            return;
+                        }
+                        ListenerEvent::Error(e) => panic!("Unexpected listener error: {}", e),
+                        ListenerEvent::AddressExpired(_) => {}
+                    }
+                }
+            }
+
+            const DIALS: usize = 8;
+            let addr: Multiaddr = "/ip4/127.0.0.1/udp/0/quic".parse().unwrap();
+            let mut listeners = Vec::new();
+            let mut ready_rxs = Vec::new();
+            for _ in 0..DIALS {
+                let (ready_tx, ready_rx) = futures::channel::mpsc::channel(1);
+                listeners.push(async_std::task::spawn(listener(addr.clone(), ready_tx)));
+                ready_rxs.push(ready_rx);
+            }
+
+            let config = Config::new(&Keypair::generate_ed25519());
+            let socket = StdUdpSocket::bind((std::net::Ipv4Addr::LOCALHOST, 0)).unwrap();
+            let endpoint = Endpoint::from_socket(&config, socket).unwrap();
+
+            async_std::task::block_on(async {
+                let mut dials = Vec::new();
+                for mut ready_rx in ready_rxs {
+                    let remote_addr = ready_rx.next().await.unwrap();
+                    let remote = multiaddr_to_socketaddr(remote_addr, true).unwrap();
+                    let (handle, connection) = endpoint.dial(&config, remote).unwrap();
+                    let endpoint = endpoint.clone();
+                    let config = config.clone();
+                    dials.push(async_std::task::spawn(async move {
+                        Upgrade::new(
+                            endpoint,
+                            handle,
+                            connection,
+                            config.peer_verifier,
+                            config.max_idle_timeout,
+                            config.close_timeout,
+                            config.stream_write_buffer,
+                        )
+                        .await
+                        .unwrap()
+                        .1
+                    }));
+                }
+                for dial in dials {
+                    drive_in_background(Arc::new(dial.await));
+                }
+            });
+
+            for listener in listeners {
+                async_std::task::block_on(listener);
+            }
+
+            let stats = endpoint.lock_stats();
+            assert!(
+                stats.acquisitions > 0,
+                "dialing and driving several connections should have acquired the endpoint mutex"
+            );
+            assert!(
+                stats.total_wait > Duration::ZERO,
+                "acquiring the mutex, even uncontended, should register some measurable wait"
+            );
+        }
+
+        /// A second [`QuicTransport::dial_exclusive`] for the same address,
+        /// issued through a [`Clone`] of the same [`QuicTransport`] before the
+        /// first resolves, is rejected with [`Error::DialInProgress`] rather
+        /// than opening a second, independent `quinn_proto` connection to the
+        /// same remote. Once the first dial resolves, the address is free to be
+        /// dialled again. Plain [`Transport::dial`] is unaffected by any of
+        /// this - see `accept_backlog_stays_bounded_under_a_flood_of_connections`
+        /// for deliberately-concurrent dials to one address through it.
+        #[test]
+        fn concurrent_dials_to_the_same_address_reject_the_second() {
+            env_logger::try_init().ok();
+
+            async fn listener(
+                addr: Multiaddr,
+                mut ready_tx: futures::channel::mpsc::Sender<Multiaddr>,
+            ) {
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+                let mut listener = transport.listen_on(addr).unwrap();
+                loop {
+                    match listener.next().await.unwrap().unwrap() {
+                        ListenerEvent::NewAddress(listen_addr) => {
+                            ready_tx.send(listen_addr).await.unwrap();
+                        }
+                        ListenerEvent::Upgrade { upgrade, .. } => {
+                            let (_, muxer) = upgrade.await.unwrap();
+                            drive_in_background(Arc::new(muxer));
+                            return;
+                        }
+                        ListenerEvent::Error(e) => panic!("Unexpected listener error: {}", e),
+                        ListenerEvent::AddressExpired(_) => {}
+                    }
+                }
+            }
+
+            async fn dialer(mut ready_rx: futures::channel::mpsc::Receiver<Multiaddr>) {
+                let addr = ready_rx.next().await.unwrap();
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+
+                let first = transport.clone().dial_exclusive(addr.clone()).unwrap();
+
+                // Checked via `.err()` rather than keeping the whole `Result`
+                // around: its `Ok` side is an `Upgrade`, which would otherwise
+                // have to be kept alive (for `Drop`) across the `.await` below,
+                // inflating this function's generated future with a second
+                // `Upgrade`-sized slot it never actually needs.
+                let second_dial_err = transport.clone().dial_exclusive(addr.clone()).err();
+                assert!(
+                    matches!(
+                        second_dial_err,
+                        Some(TransportError::Other(Error::DialInProgress))
+                    ),
+                    "a second dial to an address already being dialled should be rejected"
+                );
+
+                let (_, muxer) = first.await.unwrap();
+                drive_in_background(Arc::new(muxer));
+
+                // The first dial has resolved and released its reservation, so
+                // the address is free to be dialled again - this one just races
+                // the listener's own connection and is dropped once it's made.
+                drop(transport.dial_exclusive(addr).unwrap());
+            }
+
+            let addr: Multiaddr = "/ip4/127.0.0.1/udp/0/quic".parse().unwrap();
+            let (ready_tx, ready_rx) = futures::channel::mpsc::channel(1);
+            let listener = async_std::task::spawn(listener(addr, ready_tx));
+            async_std::task::block_on(dialer(ready_rx));
+            async_std::task::block_on(listener);
+        }
+
+        /// Exhausts a one-byte connection ID space (same trick as
+        /// [`endpoint_at_capacity_is_surfaced_once_connection_ids_run_out`]) so
+        /// that an [`Upgrade`] freeing its handle is the only way a further
+        /// dial on the same [`Endpoint`] can ever succeed, then confirms
+        /// aborting a mid-handshake dial is exactly that: the local handle
+        /// frees up once the abort has fully drained, and the remote, who was
+        /// mid-handshake with it, sees its own [`Upgrade`] fail rather than
+        /// hang waiting on a handshake that will now never complete.
+        #[test]
+        fn aborting_a_dial_tears_down_the_connection_on_both_sides() {
+            env_logger::try_init().ok();
+
+            async fn listener(
+                addr: Multiaddr,
+                mut ready_tx: futures::channel::mpsc::Sender<Multiaddr>,
+            ) {
+                let transport = QuicTransport::new(
+                    Config::new(&Keypair::generate_ed25519())
+                        .max_idle_timeout(Duration::from_secs(10)),
+                );
+                let mut listener = transport.listen_on(addr).unwrap();
+                loop {
+                    match listener.next().await.unwrap().unwrap() {
+                        ListenerEvent::NewAddress(listen_addr) => {
+                            ready_tx.send(listen_addr).await.unwrap();
+                        }
+                        ListenerEvent::Upgrade { upgrade, .. } => match upgrade.await {
+                            Err(_) => {
+                                // The dial the test is about to abort; the
+                                // remote side of the same teardown. Keep
+                                // listening for the one that's allowed to
+                                // complete.
+                                continue;
+                            }
+                            Ok((_, muxer)) => {
+                                drive_in_background(Arc::new(muxer));
+                                return;
+                            }
+                        },
+                        ListenerEvent::Error(e) => panic!("Unexpected listener error: {}", e),
+                        ListenerEvent::AddressExpired(_) => {}
+                    }
+                }
+            }
+
+            async fn dialer(mut ready_rx: futures::channel::mpsc::Receiver<Multiaddr>) {
+                let addr = ready_rx.next().await.unwrap();
+                let remote = multiaddr_to_socketaddr(addr, true).unwrap();
+
+                let config = Config::new(&Keypair::generate_ed25519())
+                    .connection_id_length(1)
+                    .max_idle_timeout(Duration::from_secs(10));
+                let socket = StdUdpSocket::bind((std::net::Ipv4Addr::LOCALHOST, 0)).unwrap();
+                let endpoint = Endpoint::from_socket(&config, socket).unwrap();
+
+                // Run the one-byte connection ID space dry against a black hole;
+                // these never need to be driven any further than this, same as
+                // `endpoint_at_capacity_is_surfaced_once_connection_ids_run_out`.
+                let black_hole: SocketAddr = "127.0.0.1:1".parse().unwrap();
+                let mut spares = Vec::new();
+                loop {
+                    match endpoint.dial(&config, black_hole) {
+                        Ok(pair) => spares.push(pair),
+                        Err(Error::EndpointAtCapacity) => break,
+                        Err(e) => panic!("unexpected error while exhausting connection IDs: {}", e),
+                    }
+                }
+
+                // Free exactly one slot back up by aborting one of the spares
+                // and driving it to completion, so there's room for the
+                // connection this test actually dials and aborts below. A plain
+                // drop wouldn't do: as `Upgrade`'s `Drop` impl notes, freeing the
+                // slot still depends on the endpoint being driven again
+                // afterwards, which nothing does once the `Upgrade` is gone.
+                let (spare_handle, spare_connection) = spares.pop().unwrap();
+                let spare_upgrade = Upgrade::new(
+                    endpoint.clone(),
+                    spare_handle,
+                    spare_connection,
+                    None,
+                    config.max_idle_timeout,
+                    config.close_timeout,
+                    config.stream_write_buffer,
+                );
+                let spare_abort = spare_upgrade.abort_handle();
+                spare_abort.abort();
+                let _ = spare_upgrade.await;
+
+                let (handle, connection) = endpoint.dial(&config, remote).unwrap();
+                let mut upgrade = Upgrade::new(
+                    endpoint.clone(),
+                    handle,
+                    connection,
+                    None,
+                    config.max_idle_timeout,
+                    config.close_timeout,
+                    config.stream_write_buffer,
+                );
+                let abort = upgrade.abort_handle();
+
+                // Send the first handshake flight so the abort below is
+                // aborting a connection the remote has actually heard of, not
+                // just one that allocated a local handle.
+                let waker = futures::task::noop_waker();
+                let mut cx = Context::from_waker(&waker);
+                assert!(matches!(
+                    Pin::new(&mut upgrade).poll(&mut cx),
+                    Poll::Pending
+                ));
+                async_std::task::sleep(Duration::from_millis(50)).await;
+
+                abort.abort();
+                let result = upgrade.await.map(|_| ());
+                assert!(
+                    matches!(result, Err(Error::Aborted)),
+                    "expected the aborted dial to resolve to Error::Aborted, got {:?}",
+                    result
+                );
+
+                // The aborted dial's handle is only truly freed once draining
+                // has run its course, same as any other self-closed connection;
+                // retry the same way `max_connections_refuses_beyond_cap_until_one_closes`
+                // does rather than asserting on the very next attempt.
+                let mut established = None;
+                for _ in 0..500 {
+                    match endpoint.dial(&config, remote) {
+                        Ok(pair) => {
+                            established = Some(pair);
+                            break;
+                        }
+                        Err(Error::EndpointAtCapacity) => {
+                            async_std::task::sleep(Duration::from_millis(20)).await
+                        }
+                        Err(e) => panic!("unexpected error after abort: {}", e),
+                    }
+                }
+                let (handle, connection) = established
+                    .expect("the aborted dial's connection ID should eventually free up");
+
+                let (_, muxer) = Upgrade::new(
+                    endpoint,
+                    handle,
+                    connection,
+                    None,
+                    config.max_idle_timeout,
+                    config.close_timeout,
+                    config.stream_write_buffer,
+                )
+                .await
+                .unwrap();
+                drive_in_background(Arc::new(muxer));
+            }
+
+            let addr: Multiaddr = "/ip4/127.0.0.1/udp/0/quic".parse().unwrap();
+            let (ready_tx, ready_rx) = futures::channel::mpsc::channel(1);
+            let listener_task = async_std::task::spawn(Box::pin(listener(addr, ready_tx)));
+            async_std::task::block_on(Box::pin(dialer(ready_rx)));
+            async_std::task::block_on(listener_task);
+        }
+
+        /// Companion to [`aborting_a_dial_tears_down_the_connection_on_both_sides`]:
+        /// same one-byte connection ID space trick, but this time the mid-handshake
+        /// [`Upgrade`] is dropped outright rather than aborted and polled to
+        /// completion. As [`Upgrade`]'s `Drop` impl explains, nothing is left to
+        /// notice the connection finish draining once the `Upgrade` is gone, so
+        /// the slot never comes back - confirmed here by retrying a further dial
+        /// for much longer than draining would ever take and still finding the
+        /// endpoint at capacity.
+        #[test]
+        fn dropping_an_upgrade_outright_never_frees_its_connection_id() {
+            env_logger::try_init().ok();
+
+            let config = Config::new(&Keypair::generate_ed25519())
+                .connection_id_length(1)
+                .max_idle_timeout(Duration::from_secs(10));
+            let socket = StdUdpSocket::bind((std::net::Ipv4Addr::LOCALHOST, 0)).unwrap();
+            let endpoint = Endpoint::from_socket(&config, socket).unwrap();
+
+            // Run the one-byte connection ID space dry against a black hole, same
+            // as `aborting_a_dial_tears_down_the_connection_on_both_sides`.
+            let black_hole: SocketAddr = "127.0.0.1:1".parse().unwrap();
+            let mut spares = Vec::new();
+            loop {
+                match endpoint.dial(&config, black_hole) {
+                    Ok(pair) => spares.push(pair),
+                    Err(Error::EndpointAtCapacity) => break,
+                    Err(e) => panic!("unexpected error while exhausting connection IDs: {}", e),
+                }
+            }
+
+            // Start a handshake on the last spare, advance it far enough that the
+            // endpoint actually has a live `Connection` for it, then drop the
+            // `Upgrade` directly instead of aborting it.
+            let (handle, connection) = spares.pop().unwrap();
+            let mut upgrade = Upgrade::new(
+                endpoint.clone(),
+                handle,
+                connection,
+                None,
+                config.max_idle_timeout,
+                config.close_timeout,
+                config.stream_write_buffer,
+            );
+            let waker = futures::task::noop_waker();
+            let mut cx = Context::from_waker(&waker);
+            assert!(matches!(
+                Pin::new(&mut upgrade).poll(&mut cx),
+                Poll::Pending
+            ));
+            drop(upgrade);
+
+            // Give draining far longer than it would ever need - the previous
+            // test's own abort-and-await case frees up well within this many
+            // retries - and confirm the slot still never comes back.
+            for _ in 0..500 {
+                match endpoint.dial(&config, black_hole) {
+                    Err(Error::EndpointAtCapacity) => {
+                        async_std::task::block_on(async_std::task::sleep(Duration::from_millis(20)))
+                    }
+                    Ok(_) => panic!(
+                        "dropping the Upgrade outright freed its connection ID; \
+                         Upgrade::drop's documented limitation no longer holds"
+                    ),
+                    Err(e) => panic!("unexpected error while retrying: {}", e),
+                }
+            }
+        }
+
+        /// Rejects an inbound handshake via [`Config::peer_verifier`] and
+        /// confirms the listener's connection ID comes back afterwards: the
+        /// listener's own endpoint is run dry with outbound black-hole dials
+        /// (the same one-byte connection ID space trick as
+        /// `dropping_an_upgrade_outright_never_frees_its_connection_id`), one
+        /// spare is then freed back up through the existing abort path so the
+        /// real dialer below has exactly one slot to take, and the listener's
+        /// [`Upgrade`] - now made to drain to completion on a rejected handshake
+        /// by the fix for the leak that test documents - has to give that slot
+        /// back before a further black-hole dial can succeed again.
+        #[test]
+        fn rejecting_a_peer_during_handshake_frees_its_connection_id() {
+            env_logger::try_init().ok();
+
+            async fn listener(
+                addr: Multiaddr,
+                ready_tx: futures::channel::oneshot::Sender<Multiaddr>,
+            ) -> Endpoint {
+                let transport = QuicTransport::new(
+                    Config::new(&Keypair::generate_ed25519())
+                        .connection_id_length(1)
+                        .max_idle_timeout(Duration::from_millis(500))
+                        .peer_verifier(|_, _| Err(Error::Handshake("nobody is pinned".into()))),
+                );
+                let mut listener = transport.listen_on(addr).unwrap();
+                let endpoint = listener.endpoint.clone();
+
+                let listen_addr = loop {
+                    match listener.next().await.unwrap().unwrap() {
+                        ListenerEvent::NewAddress(listen_addr) => break listen_addr,
+                        ListenerEvent::Upgrade { .. } => {
+                            panic!("handshake arrived before the connection ID space was exhausted")
+                        }
+                        ListenerEvent::Error(e) => panic!("Unexpected listener error: {}", e),
+                        ListenerEvent::AddressExpired(_) => {}
+                    }
+                };
+
+                // Run this endpoint's one-byte connection ID space dry, then free
+                // exactly one spare back up through the existing abort path, so
+                // the real dialer below has exactly one slot to take.
+                let spare_config = Config::new(&Keypair::generate_ed25519());
+                let black_hole: SocketAddr = "127.0.0.1:1".parse().unwrap();
+                let mut spares = Vec::new();
+                loop {
+                    match endpoint.dial(&spare_config, black_hole) {
+                        Ok(pair) => spares.push(pair),
+                        Err(Error::EndpointAtCapacity) => break,
+                        Err(e) => panic!("unexpected error while exhausting connection IDs: {}", e),
+                    }
+                }
+                let (handle, connection) =
+                    spares.pop().expect("at least one connection ID to spare");
+                let freed = Upgrade::new(
+                    endpoint.clone(),
+                    handle,
+                    connection,
+                    None,
+                    spare_config.max_idle_timeout,
+                    spare_config.close_timeout,
+                    spare_config.stream_write_buffer,
+                );
+                let abort = freed.abort_handle();
+                abort.abort();
+                freed.await.map(|_| ()).unwrap_err();
+
+                // Safe to report the listen address only now: nothing else will
+                // compete with the real dialer below for the one slot just freed.
+                ready_tx.send(listen_addr).unwrap();
+
+                loop {
+                    match listener.next().await.unwrap().unwrap() {
+                        ListenerEvent::Upgrade { upgrade, .. } => {
+                            let err = upgrade.await.map(|_| ()).unwrap_err();
+                            assert!(
+                                matches!(err, Error::Handshake(_)),
+                                "rejected handshake should fail with Error::Handshake, got {:?}",
+                                err
+                            );
+                            return endpoint;
+                        }
+                        ListenerEvent::NewAddress(_) => {}
+                        ListenerEvent::Error(e) => panic!("Unexpected listener error: {}", e),
+                        ListenerEvent::AddressExpired(_) => {}
+                    }
+                }
+            }
+
+            async fn dialer(addr: Multiaddr) {
+                let transport = QuicTransport::new(
+                    Config::new(&Keypair::generate_ed25519())
+                        .max_idle_timeout(Duration::from_millis(500)),
+                );
+                // Unlike `peer_verifier_rejects_unpinned_peers`, this endpoint's
+                // connection ID space is down to its last spare slot, so the
+                // listener's rejection can land before this side's own
+                // handshake even reaches `Connected` - either way is evidence of
+                // the same rejection, just observed at a different point.
+                if let Ok((_, muxer)) = transport.dial(addr).unwrap().await {
+                    assert!(
+                        poll_fn(|cx| muxer.poll_event(cx)).await.is_err(),
+                        "connection rejected by the listener's peer_verifier \
+                         should close right away instead of staying open"
+                    );
+                }
+            }
+
+            let addr: Multiaddr = "/ip4/127.0.0.1/udp/0/quic".parse().unwrap();
+            let (ready_tx, ready_rx) = futures::channel::oneshot::channel();
+            let listener_task = async_std::task::spawn(Box::pin(listener(addr, ready_tx)));
+            let listen_addr = async_std::task::block_on(ready_rx).unwrap();
+            async_std::task::block_on(dialer(listen_addr));
+            let endpoint = async_std::task::block_on(listener_task);
+
+            let config = Config::new(&Keypair::generate_ed25519());
+            let black_hole: SocketAddr = "127.0.0.1:1".parse().unwrap();
+            for _ in 0..500 {
+                match endpoint.dial(&config, black_hole) {
+                    Ok(_) => return,
+                    Err(Error::EndpointAtCapacity) => {
+                        async_std::task::block_on(async_std::task::sleep(Duration::from_millis(20)))
+                    }
+                    Err(e) => panic!("unexpected error while retrying: {}", e),
+                }
+            }
+            panic!(
+                "rejecting a peer during the handshake never freed its connection \
+                 ID slot"
+            );
+        }
+
+        /// [`Endpoint::new_async`] should bind a real, usable socket just like
+        /// the sync [`Endpoint::from_socket`] path does; there's nothing
+        /// `async`-specific to observe beyond being awaitable, so this just
+        /// confirms the endpoint it returns actually ended up bound.
+        #[test]
+        fn new_async_binds_a_usable_endpoint() {
+            async_std::task::block_on(async {
+                let config = Config::new(&Keypair::generate_ed25519());
+                let addr: SocketAddr = "127.0.0.1:0".parse().unwrap();
+                let endpoint = Endpoint::new_async(&config, addr).await.unwrap();
+
+                let local_addr = endpoint.local_addr().unwrap();
+                assert_eq!(local_addr.ip(), addr.ip());
+                assert_ne!(local_addr.port(), 0);
+            });
+        }
+
+        /// Occupies the first port in a range with an unrelated socket, then
+        /// confirms [`Endpoint::new_in_range`] skips over it and binds the
+        /// next one instead of failing outright.
+        #[test]
+        fn new_in_range_falls_back_past_an_occupied_port() {
+            let ip = "127.0.0.1".parse().unwrap();
+            let occupied = StdUdpSocket::bind((ip, 0)).unwrap();
+            let first_port = occupied.local_addr().unwrap().port();
+
+            let config = Config::new(&Keypair::generate_ed25519());
+            let endpoint =
+                Endpoint::new_in_range(&config, ip, first_port..=first_port + 1).unwrap();
+
+            let local_addr = endpoint.local_addr().unwrap();
+            assert_eq!(local_addr.ip(), ip);
+            assert_eq!(local_addr.port(), first_port + 1);
+        }
+    }
+
+    mod listen {
+        //! `listen_on` and the `QuicListenStream` it returns: accepting connections, ALPN routing, and shared-endpoint dispatch.
+        use super::*;
+
+        #[test]
+        fn stop_accepting_refuses_new_connections_but_keeps_existing() {
+            env_logger::try_init().ok();
+
+            async fn listener(
+                addr: Multiaddr,
+                mut ready_tx: futures::channel::mpsc::Sender<Multiaddr>,
+                mut stopped_tx: futures::channel::mpsc::Sender<()>,
+            ) -> Vec<u8> {
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+                let mut listener = transport.listen_on(addr).unwrap();
+
+                let muxer = loop {
+                    match listener.next().await.unwrap().unwrap() {
+                        ListenerEvent::NewAddress(listen_addr) => {
+                            ready_tx.send(listen_addr).await.unwrap();
+                        }
+                        ListenerEvent::Upgrade { upgrade, .. } => {
+                            let (_, muxer) = upgrade.await.unwrap();
+                            break Arc::new(muxer);
+                        }
+                        ListenerEvent::Error(e) => panic!("Unexpected listener error: {}", e),
+                        ListenerEvent::AddressExpired(_) => {}
+                    }
+                };
+
+                listener.stop_accepting();
+                stopped_tx.send(()).await.unwrap();
+
+                future::poll_fn(|cx| muxer.poll_datagram(cx)).await.unwrap()
+            }
+
+            async fn dialer(
+                mut ready_rx: futures::channel::mpsc::Receiver<Multiaddr>,
+                mut stopped_rx: futures::channel::mpsc::Receiver<()>,
+            ) {
+                let addr = ready_rx.next().await.unwrap();
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+                let (_, muxer) = transport.clone().dial(addr.clone()).unwrap().await.unwrap();
+                let muxer = Arc::new(muxer);
+
+                stopped_rx.next().await.unwrap();
+
+                assert!(
+                    transport.dial(addr).unwrap().await.is_err(),
+                    "dial after stop_accepting should be refused"
+                );
+
+                muxer.datagram_sink().send(vec![42]).await.unwrap();
+                drive_in_background(muxer);
+            }
+
+            let addr: Multiaddr = "/ip4/127.0.0.1/udp/0/quic".parse().unwrap();
+            let (ready_tx, ready_rx) = futures::channel::mpsc::channel(1);
+            let (stopped_tx, stopped_rx) = futures::channel::mpsc::channel(1);
+            let listener = async_std::task::spawn(listener(addr, ready_tx, stopped_tx));
+            async_std::task::block_on(dialer(ready_rx, stopped_rx));
+            let received = async_std::task::block_on(listener);
+
+            assert_eq!(received, vec![42]);
+        }
+
+        /// A listener configured with [`Config::alpn_protocols`] listing two
+        /// protocols accepts dialers that each only offer one of the two, and
+        /// [`QuicMuxer::negotiated_alpn`] reports back which one a given
+        /// connection negotiated; routing each connection to the right handler
+        /// is then just a matter of matching on that and forwarding the muxer
+        /// down the corresponding channel, as this test does with two.
+        #[test]
+        fn alpn_protocols_lets_a_listener_route_connections_to_different_handlers() {
+            env_logger::try_init().ok();
+
+            const LIBP2P_ALPN: &[u8] = b"libp2p";
+            const CUSTOM_ALPN: &[u8] = b"custom/1.0";
+
+            async fn listener(
+                addr: Multiaddr,
+                mut ready_tx: futures::channel::mpsc::Sender<Multiaddr>,
+                mut libp2p_handler_tx: futures::channel::mpsc::Sender<QuicMuxer>,
+                mut custom_handler_tx: futures::channel::mpsc::Sender<QuicMuxer>,
+            ) {
+                let config = Config::new(&Keypair::generate_ed25519())
+                    .alpn_protocols(vec![LIBP2P_ALPN.to_vec(), CUSTOM_ALPN.to_vec()]);
+                let transport = QuicTransport::new(config);
+                let mut listener = transport.listen_on(addr).unwrap();
+                for _ in 0..2 {
+                    loop {
+                        match listener.next().await.unwrap().unwrap() {
+                            ListenerEvent::NewAddress(listen_addr) => {
+                                ready_tx.send(listen_addr).await.unwrap();
+                            }
+                            ListenerEvent::Upgrade { upgrade, .. } => {
+                                let (_, muxer) = upgrade.await.unwrap();
+                                match muxer.negotiated_alpn().as_deref() {
+                                    Some(CUSTOM_ALPN) => {
+                                        custom_handler_tx.send(muxer).await.unwrap()
+                                    }
+                                    Some(LIBP2P_ALPN) => {
+                                        libp2p_handler_tx.send(muxer).await.unwrap()
+                                    }
+                                    other => panic!("unexpected negotiated ALPN: {:?}", other),
+                                }
+                                break;
+                            }
+                            ListenerEvent::Error(e) => panic!("Unexpected listener error: {}", e),
+                            ListenerEvent::AddressExpired(_) => {}
+                        }
+                    }
+                }
+            }
+
+            async fn dialer(addr: Multiaddr, alpn: &[u8]) {
+                let config =
+                    Config::new(&Keypair::generate_ed25519()).alpn_protocols(vec![alpn.to_vec()]);
+                let transport = QuicTransport::new(config);
+                let (_, muxer) = transport.dial(addr).unwrap().await.unwrap();
+                drive_in_background(Arc::new(muxer));
+            }
+
+            let addr: Multiaddr = "/ip4/127.0.0.1/udp/0/quic".parse().unwrap();
+            let (ready_tx, mut ready_rx) = futures::channel::mpsc::channel(1);
+            let (libp2p_handler_tx, mut libp2p_handler_rx) = futures::channel::mpsc::channel(1);
+            let (custom_handler_tx, mut custom_handler_rx) = futures::channel::mpsc::channel(1);
+            let listener = async_std::task::spawn(listener(
+                addr,
+                ready_tx,
+                libp2p_handler_tx,
+                custom_handler_tx,
+            ));
+
+            async_std::task::block_on(async {
+                let listen_addr = ready_rx.next().await.unwrap();
+                dialer(listen_addr.clone(), LIBP2P_ALPN).await;
+                dialer(listen_addr, CUSTOM_ALPN).await;
+
+                let libp2p_muxer = libp2p_handler_rx.next().await.unwrap();
+                assert_eq!(libp2p_muxer.negotiated_alpn().as_deref(), Some(LIBP2P_ALPN));
+
+                let custom_muxer = custom_handler_rx.next().await.unwrap();
+                assert_eq!(custom_muxer.negotiated_alpn().as_deref(), Some(CUSTOM_ALPN));
+            });
+            async_std::task::block_on(listener);
+        }
+
+        /// A listener's [`Endpoint`] already lists every one of `quinn_proto`'s
+        /// supported draft QUIC versions in `supported_versions`, so it accepts
+        /// a dial using any of them and negotiates the rest away transparently.
+        /// Dials two clients at the same listener, each pinned (via the
+        /// test-only [`Config::quic_version`]) to a *different* entry of
+        /// [`quinn_proto::DEFAULT_SUPPORTED_VERSIONS`], and confirms both
+        /// connect successfully. There is no equivalent test pinning one side
+        /// to real QUIC v1 instead of a second draft: neither `quinn_proto`
+        /// 0.7.3 nor the vendored `multiaddr` crate have anything to pin it to
+        /// (see the comment on `endpoint_config` in
+        /// [`Endpoint::from_socket`](crate::endpoint::Endpoint::from_socket)).
+        #[test]
+        fn listener_negotiates_either_of_two_draft_quic_versions() {
+            // Juggling a listener and two dialers' connections concurrently
+            // nests enough generated `Future` state to overflow a default 2 MiB
+            // test thread stack; run the body on a thread with more headroom,
+            // as `stateless_reset_key_lets_a_restarted_listener_reset_an_old_connection`
+            // does for the same reason.
+            std::thread::Builder::new()
+                .stack_size(8 * 1024 * 1024)
+                .spawn(run)
+                .unwrap()
+                .join()
+                .unwrap();
+
+            fn run() {
+                env_logger::try_init().ok();
+
+                async fn listener(
+                    addr: Multiaddr,
+                    mut ready_tx: futures::channel::mpsc::Sender<Multiaddr>,
+                ) {
+                    let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+                    let mut listener = transport.listen_on(addr).unwrap();
+                    let mut connected = 0;
+                    loop {
+                        match listener.next().await.unwrap().unwrap() {
+                            ListenerEvent::NewAddress(listen_addr) => {
+                                ready_tx.send(listen_addr).await.unwrap();
+                            }
+                            ListenerEvent::Upgrade { upgrade, .. } => {
+                                let (_, muxer) = upgrade.await.unwrap();
+                                drive_in_background(Arc::new(muxer));
+                                connected += 1;
+                                if connected == 2 {
+                                    return;
+                                }
+                            }
+                            ListenerEvent::Error(e) => panic!("Unexpected listener error: {}", e),
+                            ListenerEvent::AddressExpired(_) => {}
+                        }
+                    }
+                }
+
+                async fn dialer(addr: Multiaddr, version: u32) {
+                    let config = Config::new(&Keypair::generate_ed25519()).quic_version(version);
+                    let transport = QuicTransport::new(config);
+                    let (_, muxer) = transport.dial(addr).unwrap().await.unwrap();
+                    drive_in_background(Arc::new(muxer));
+                }
+
+                let addr: Multiaddr = "/ip4/127.0.0.1/udp/0/quic".parse().unwrap();
+                let (ready_tx, mut ready_rx) = futures::channel::mpsc::channel(1);
+                let listener = async_std::task::spawn(listener(addr, ready_tx));
+                let listen_addr = async_std::task::block_on(ready_rx.next()).unwrap();
+
+                let versions = quinn_proto::DEFAULT_SUPPORTED_VERSIONS;
+                async_std::task::block_on(future::join(
+                    dialer(listen_addr.clone(), versions[0]),
+                    dialer(listen_addr, versions[1]),
+                ));
+                async_std::task::block_on(listener);
+            }
+        }
+
+        /// Two [`QuicListenStream`]s sharing one endpoint via
+        /// [`QuicTransport::listen_on_shared_endpoint`], with a
+        /// [`Config::listener_dispatch_policy`] that routes by the parity of the
+        /// dialler's source port, should each only ever see connections from the
+        /// source ports their half of the split claims.
+        #[test]
+        fn listener_dispatch_policy_routes_deterministically_by_source_address_parity() {
+            env_logger::try_init().ok();
+
+            async fn accept_one(listener: &mut QuicListenStream) -> SocketAddr {
+                loop {
+                    match listener.next().await.unwrap().unwrap() {
+                        ListenerEvent::Upgrade { upgrade, .. } => {
+                            let (_, muxer) = upgrade.await.unwrap();
+                            let remote = muxer.remote_address();
+                            drive_in_background(Arc::new(muxer));
+                            return remote;
+                        }
+                        ListenerEvent::Error(e) => panic!("Unexpected listener error: {}", e),
+                        ListenerEvent::NewAddress(_) | ListenerEvent::AddressExpired(_) => {}
+                    }
+                }
+            }
+
+            async fn dialer(addr: Multiaddr, source: SocketAddr) {
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+                let (_, muxer) = transport.dial_from(addr, source).unwrap().await.unwrap();
+                drive_in_background(Arc::new(muxer));
+            }
+
+            let config = Config::new(&Keypair::generate_ed25519()).listener_dispatch_policy(
+                |from, _listener_count| if from.port() % 2 == 0 { 0 } else { 1 },
+            );
+            let transport = QuicTransport::new(config);
+
+            let addr: Multiaddr = "/ip4/127.0.0.1/udp/0/quic".parse().unwrap();
+            let mut listener_a = transport.clone().listen_on(addr).unwrap();
+            let listen_addr = async_std::task::block_on(async {
+                match listener_a.next().await.unwrap().unwrap() {
+                    ListenerEvent::NewAddress(listen_addr) => listen_addr,
+                    ListenerEvent::Upgrade { .. } => panic!("Unexpected upgrade before NewAddress"),
+                    ListenerEvent::Error(e) => panic!("Unexpected listener error: {}", e),
+                    ListenerEvent::AddressExpired(_) => panic!("Unexpected address expiry"),
+                }
+            });
+
+            let mut listener_b = transport.listen_on_shared_endpoint(&listener_a);
+            async_std::task::block_on(async {
+                match listener_b.next().await.unwrap().unwrap() {
+                    ListenerEvent::NewAddress(_) => {}
+                    ListenerEvent::Upgrade { .. } => panic!("Unexpected upgrade before NewAddress"),
+                    ListenerEvent::Error(e) => panic!("Unexpected listener error: {}", e),
+                    ListenerEvent::AddressExpired(_) => panic!("Unexpected address expiry"),
+                }
+            });
+
+            let even_source: SocketAddr = "127.0.0.1:41000".parse().unwrap();
+            let odd_source: SocketAddr = "127.0.0.1:41001".parse().unwrap();
+
+            let dial_even = async_std::task::spawn(dialer(listen_addr.clone(), even_source));
+            let dial_odd = async_std::task::spawn(dialer(listen_addr, odd_source));
+
+            let accept_a = async_std::task::spawn(async move {
+                let remote = accept_one(&mut listener_a).await;
+                (listener_a, remote)
+            });
+            let accept_b = async_std::task::spawn(async move {
+                let remote = accept_one(&mut listener_b).await;
+                (listener_b, remote)
+            });
+
+            async_std::task::block_on(dial_even);
+            async_std::task::block_on(dial_odd);
+            let (_listener_a, remote_a) = async_std::task::block_on(accept_a);
+            let (_listener_b, remote_b) = async_std::task::block_on(accept_b);
+
+            assert_eq!(
+                remote_a.port(),
+                even_source.port(),
+                "listener A (dispatch index 0) should only ever receive the even-port dial"
+            );
+            assert_eq!(
+                remote_b.port(),
+                odd_source.port(),
+                "listener B (dispatch index 1) should only ever receive the odd-port dial"
+            );
+        }
+
+        /// Dropping one of two [`QuicListenStream`]s sharing an endpoint - while
+        /// a [`Config::listener_dispatch_policy`] keeps routing new connections
+        /// to the now-dead index - must not strand those connections in
+        /// [`Inner::pending_connections`] forever: [`Endpoint::dispatch_target`]
+        /// and [`Endpoint::mark_listener_dropped`] redirect them to the
+        /// surviving listener instead. Before that fix, every odd-port dial
+        /// below (routed to listener B's index) would sit undrained, growing
+        /// [`QuicListenStream::pending_connections`] without bound and never
+        /// resolving this test's `accept_one` calls.
+        #[test]
+        fn dropping_one_of_two_shared_listeners_redistributes_its_dispatched_backlog() {
+            // Juggling two shared listeners and five concurrent dialers' connections
+            // nests enough generated `Future` state to overflow a default 2 MiB test
+            // thread stack; run the body on a thread with more headroom, as
+            // `listener_negotiates_either_of_two_draft_quic_versions` does for the
+            // same reason.
+            std::thread::Builder::new()
+                .stack_size(8 * 1024 * 1024)
+                .spawn(run)
+                .unwrap()
+                .join()
+                .unwrap();
+
+            fn run() {
+                env_logger::try_init().ok();
+
+                async fn accept_one(listener: &mut QuicListenStream) {
+                    loop {
+                        match listener.next().await.unwrap().unwrap() {
+                            ListenerEvent::Upgrade { upgrade, .. } => {
+                                let (_, muxer) = upgrade.await.unwrap();
+                                drive_in_background(Arc::new(muxer));
+                                return;
+                            }
+                            ListenerEvent::Error(e) => panic!("Unexpected listener error: {}", e),
+                            ListenerEvent::NewAddress(_) | ListenerEvent::AddressExpired(_) => {}
+                        }
+                    }
+                }
+
+                async fn dialer(addr: Multiaddr, source: SocketAddr) {
+                    let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+                    let (_, muxer) = transport.dial_from(addr, source).unwrap().await.unwrap();
+                    drive_in_background(Arc::new(muxer));
+                }
+
+                const ODD_DIALS: usize = 5;
+
+                let config = Config::new(&Keypair::generate_ed25519()).listener_dispatch_policy(
+                    |from, _listener_count| if from.port() % 2 == 0 { 0 } else { 1 },
+                );
+                let transport = QuicTransport::new(config);
+
+                let addr: Multiaddr = "/ip4/127.0.0.1/udp/0/quic".parse().unwrap();
+                let mut listener_a = transport.clone().listen_on(addr).unwrap();
+                let listen_addr = async_std::task::block_on(async {
+                    match listener_a.next().await.unwrap().unwrap() {
+                        ListenerEvent::NewAddress(listen_addr) => listen_addr,
+                        ListenerEvent::Upgrade { .. } => {
+                            panic!("Unexpected upgrade before NewAddress")
+                        }
+                        ListenerEvent::Error(e) => panic!("Unexpected listener error: {}", e),
+                        ListenerEvent::AddressExpired(_) => panic!("Unexpected address expiry"),
+                    }
+                });
+
+                let mut listener_b = transport.listen_on_shared_endpoint(&listener_a);
+                async_std::task::block_on(async {
+                    match listener_b.next().await.unwrap().unwrap() {
+                        ListenerEvent::NewAddress(_) => {}
+                        ListenerEvent::Upgrade { .. } => {
+                            panic!("Unexpected upgrade before NewAddress")
+                        }
+                        ListenerEvent::Error(e) => panic!("Unexpected listener error: {}", e),
+                        ListenerEvent::AddressExpired(_) => panic!("Unexpected address expiry"),
+                    }
+                });
+
+                // Listener B (dispatch index 1) is gone before any odd-port dial
+                // below ever arrives; every one of them is still routed there by
+                // the policy, same as if it were still alive.
+                drop(listener_b);
+
+                let odd_sources: Vec<SocketAddr> = (0..ODD_DIALS)
+                    .map(|i| format!("127.0.0.1:{}", 42001 + 2 * i).parse().unwrap())
+                    .collect();
+                let dials: Vec<_> = odd_sources
+                    .iter()
+                    .map(|&source| async_std::task::spawn(dialer(listen_addr.clone(), source)))
+                    .collect();
+
+                // All of them must still be accepted, through the one surviving
+                // listener, within this test's normal timeout - before the fix they
+                // would sit in the backlog forever and this would hang.
+                async_std::task::block_on(async {
+                    for _ in 0..ODD_DIALS {
+                        accept_one(&mut listener_a).await;
+                    }
+                });
+                for dial in dials {
+                    async_std::task::block_on(dial);
+                }
+
+                assert_eq!(
+                    listener_a.pending_connections().len(),
+                    0,
+                    "every connection dispatched to the dead listener should have been \
+                 redistributed to, and then drained by, the surviving one"
+                );
+            }
+        }
+
+        /// [`QuicListenStream::events`] should report [`EndpointEvent::ConnectionEstablished`]
+        /// as soon as a dial is accepted, then [`EndpointEvent::ConnectionClosed`]
+        /// once the listener's own [`QuicMuxer::poll_event`] notices the dialer
+        /// closing it - a central consumer subscribed to the stream sees both
+        /// without ever polling the muxer itself.
+        #[test]
+        fn endpoint_events_reports_established_then_closed_for_a_dialled_connection() {
+            env_logger::try_init().ok();
+
+            async fn listener(
+                addr: Multiaddr,
+                mut ready_tx: futures::channel::mpsc::Sender<Multiaddr>,
+            ) {
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+                let mut listener = transport.listen_on(addr).unwrap();
+                let mut events = listener.events();
+                let muxer = loop {
+                    match listener.next().await.unwrap().unwrap() {
+                        ListenerEvent::NewAddress(listen_addr) => {
+                            ready_tx.send(listen_addr).await.unwrap();
+                        }
+                        ListenerEvent::Upgrade { upgrade, .. } => break upgrade.await.unwrap().1,
+                        ListenerEvent::Error(e) => panic!("Unexpected listener error: {}", e),
+                        ListenerEvent::AddressExpired(_) => {}
+                    }
+                };
+
+                match events.next().await.unwrap() {
+                    EndpointEvent::ConnectionEstablished { remote_address } => {
+                        assert_eq!(remote_address, muxer.remote_address());
+                    }
+                    other => panic!("expected ConnectionEstablished, got {:?}", other),
+                }
+
+                assert!(
+                    future::poll_fn(|cx| muxer.poll_event(cx)).await.is_err(),
+                    "poll_event should report the dialer's close as a lost connection"
+                );
+
+                match events.next().await.unwrap() {
+                    EndpointEvent::ConnectionClosed {
+                        remote_address,
+                        reason,
+                    } => {
+                        assert_eq!(remote_address, muxer.remote_address());
+                        assert!(
+                            matches!(reason, quinn_proto::ConnectionError::ApplicationClosed(_)),
+                            "expected the dialer's own close() to surface as an application close, got {:?}",
+                            reason
+                        );
+                    }
+                    other => panic!("expected ConnectionClosed, got {:?}", other),
+                }
+            }
+
+            async fn dialer(mut ready_rx: futures::channel::mpsc::Receiver<Multiaddr>) {
+                let addr = ready_rx.next().await.unwrap();
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+                let (_, muxer) = transport.dial(addr).unwrap().await.unwrap();
+                // See `close_with_transport_error_surfaces_code_and_reason_to_the_peer`:
+                // this side's own handshake resolving doesn't mean the listener
+                // has reached `Connected` on its end yet, so closing too eagerly
+                // would race the listener's `Upgrade` instead of letting it hand
+                // out a muxer to observe the close through.
+                async_std::task::sleep(Duration::from_millis(50)).await;
+                future::poll_fn(|cx| StreamMuxer::close(&muxer, cx))
+                    .await
+                    .unwrap();
+            }
+
+            let addr: Multiaddr = "/ip4/127.0.0.1/udp/0/quic".parse().unwrap();
+            let (ready_tx, ready_rx) = futures::channel::mpsc::channel(1);
+            let listener = async_std::task::spawn(listener(addr, ready_tx));
+            async_std::task::block_on(dialer(ready_rx));
+            async_std::task::block_on(listener);
+        }
+
+        #[test]
+        fn peer_verifier_rejects_unpinned_peers() {
+            env_logger::try_init().ok();
+
+            async fn listener(
+                addr: Multiaddr,
+                mut ready_tx: futures::channel::mpsc::Sender<Multiaddr>,
+                pinned: PeerId,
+            ) {
+                let transport = QuicTransport::new(
+                    Config::new(&Keypair::generate_ed25519())
+                        .max_idle_timeout(std::time::Duration::from_millis(500))
+                        .peer_verifier(move |peer_id, _certificate| {
+                            if *peer_id == pinned {
+                                Ok(())
+                            } else {
+                                Err(Error::Handshake("peer is not the pinned peer".into()))
+                            }
+                        }),
+                );
+                let mut listener = transport.listen_on(addr).unwrap();
+                loop {
+                    match listener.next().await.unwrap().unwrap() {
+                        ListenerEvent::NewAddress(listen_addr) => {
+                            ready_tx.send(listen_addr).await.unwrap();
+                        }
+                        ListenerEvent::Upgrade { upgrade, .. } => match upgrade.await {
+                            Ok((_, muxer)) => {
+                                drive_in_background(Arc::new(muxer));
+                                return;
+                            }
+                            Err(_) => {
+                                // The rejected dialer; keep listening for the
+                                // pinned one.
+                                continue;
+                            }
+                        },
+                        ListenerEvent::Error(e) => panic!("Unexpected listener error: {}", e),
+                        ListenerEvent::AddressExpired(_) => {}
+                    }
+                }
+            }
+
+            async fn dialer(
+                mut ready_rx: futures::channel::mpsc::Receiver<Multiaddr>,
+                pinned_keypair: Keypair,
+            ) {
+                let addr = ready_rx.next().await.unwrap();
+
+                // An unpinned identity dialling first: QUIC's cryptographic
+                // handshake completes independently on each side, so this
+                // dialer's own upgrade still succeeds even though the listener's
+                // verifier is about to reject it; what it does see is the
+                // connection closing right away instead of staying usable.
+                let rejected = QuicTransport::new(
+                    Config::new(&Keypair::generate_ed25519())
+                        .max_idle_timeout(std::time::Duration::from_millis(500)),
+                );
+                let (_, muxer) = rejected.dial(addr.clone()).unwrap().await.unwrap();
+                assert!(
+                    future::poll_fn(|cx| muxer.poll_event(cx)).await.is_err(),
+                    "connection rejected by the listener's peer_verifier should \
+                     close right away instead of staying open"
+                );
+
+                // The pinned identity should be let through.
+                let pinned = QuicTransport::new(Config::new(&pinned_keypair));
+                let (_, muxer) = pinned.dial(addr).unwrap().await.unwrap();
+                drive_in_background(Arc::new(muxer));
+            }
+
+            let pinned_keypair = Keypair::generate_ed25519();
+            let pinned_peer_id = PeerId::from(pinned_keypair.public());
+
+            let addr: Multiaddr = "/ip4/127.0.0.1/udp/0/quic".parse().unwrap();
+            let (ready_tx, ready_rx) = futures::channel::mpsc::channel(1);
+            // Boxed for the same reason as `max_connections_refuses_beyond_cap_until_one_closes`:
+            // two QUIC connections' worth of state across many `.await` points
+            // can overflow a worker thread's stack if moved onto it by value.
+            let listener =
+                async_std::task::spawn(Box::pin(listener(addr, ready_tx, pinned_peer_id)));
+            async_std::task::block_on(Box::pin(dialer(ready_rx, pinned_keypair)));
+            async_std::task::block_on(listener);
+        }
+
+        /// Drops the listener mid-flood, while several handshake attempts are
+        /// still sitting in its accept backlog, and confirms those are closed
+        /// out rather than leaked: the backlog is fully drained once the
+        /// listener goes away, even though this test keeps its own clone of the
+        /// `Endpoint` alive throughout, so that draining is observably this
+        /// crate's `Drop` impl at work, not just the listener happening to be
+        /// `Inner`'s last owner.
+        #[test]
+        fn dropping_the_listener_closes_out_its_accept_backlog() {
+            env_logger::try_init().ok();
+
+            const CAP: u32 = 4;
+            const FLOOD: usize = 50;
+
+            async fn listener(
+                addr: Multiaddr,
+                mut ready_tx: futures::channel::mpsc::Sender<Multiaddr>,
+            ) -> QuicListenStream {
+                let transport = QuicTransport::new(
+                    Config::new(&Keypair::generate_ed25519()).max_pending_connections(CAP),
+                );
+                let mut listener = transport.listen_on(addr).unwrap();
+                // As in `accept_backlog_stays_bounded_under_a_flood_of_connections`:
+                // accept only the first connection, then keep driving its shared
+                // socket in the background without ever polling the listener
+                // again, so the flood below piles up in the accept backlog.
+                loop {
+                    match listener.next().await.unwrap().unwrap() {
+                        ListenerEvent::NewAddress(listen_addr) => {
+                            ready_tx.send(listen_addr).await.unwrap();
+                        }
+                        ListenerEvent::Upgrade { upgrade, .. } => {
+                            let (_, muxer) = upgrade.await.unwrap();
+                            drive_in_background(Arc::new(muxer));
+                            return listener;
+                        }
+                        ListenerEvent::Error(e) => panic!("Unexpected listener error: {}", e),
+                        ListenerEvent::AddressExpired(_) => {}
+                    }
+                }
+            }
+
+            async fn dialer(mut ready_rx: futures::channel::mpsc::Receiver<Multiaddr>) {
+                let addr = ready_rx.next().await.unwrap();
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+
+                let (_, first) = transport.clone().dial(addr.clone()).unwrap().await.unwrap();
+                drive_in_background(Arc::new(first));
+
+                for _ in 0..FLOOD {
+                    async_std::task::spawn(transport.clone().dial(addr.clone()).unwrap());
+                }
+
+                async_std::task::sleep(std::time::Duration::from_millis(500)).await;
+            }
+
+            let addr: Multiaddr = "/ip4/127.0.0.1/udp/0/quic".parse().unwrap();
+            let (ready_tx, ready_rx) = futures::channel::mpsc::channel(1);
+            let listener_task = async_std::task::spawn(Box::pin(listener(addr, ready_tx)));
+            async_std::task::block_on(Box::pin(dialer(ready_rx)));
+            let listener = async_std::task::block_on(listener_task);
+
+            let endpoint = listener.endpoint.clone();
+            assert!(
+                endpoint.pending_connections_len() > 0,
+                "flood should have left a non-empty accept backlog to drop the listener against"
+            );
+
+            drop(listener);
+
+            assert_eq!(
+                endpoint.pending_connections_len(),
+                0,
+                "dropping the listener should close out every connection still queued in its accept backlog"
+            );
+        }
+
+        /// Drops the listener, then dials a brand new connection against the
+        /// same endpoint - kept alive by the first connection's own muxer, same
+        /// as `dropping_the_listener_closes_out_its_accept_backlog` - and
+        /// confirms the new dial is refused rather than left to complete its
+        /// handshake with nobody ever going to poll it out of the accept
+        /// backlog.
+        #[test]
+        fn dialing_after_the_listener_is_dropped_is_refused_not_left_dangling() {
+            env_logger::try_init().ok();
+
+            async fn listener(
+                addr: Multiaddr,
+                mut ready_tx: futures::channel::mpsc::Sender<Multiaddr>,
+            ) -> QuicListenStream {
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+                let mut listener = transport.listen_on(addr).unwrap();
+                loop {
+                    match listener.next().await.unwrap().unwrap() {
+                        ListenerEvent::NewAddress(listen_addr) => {
+                            ready_tx.send(listen_addr).await.unwrap();
+                        }
+                        ListenerEvent::Upgrade { upgrade, .. } => {
+                            let (_, muxer) = upgrade.await.unwrap();
+                            drive_in_background(Arc::new(muxer));
+                            return listener;
+                        }
+                        ListenerEvent::Error(e) => panic!("Unexpected listener error: {}", e),
+                        ListenerEvent::AddressExpired(_) => {}
+                    }
+                }
+            }
+
+            async fn dialer(
+                mut ready_rx: futures::channel::mpsc::Receiver<Multiaddr>,
+            ) -> Multiaddr {
+                let addr = ready_rx.next().await.unwrap();
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+                let (_, first) = transport.dial(addr.clone()).unwrap().await.unwrap();
+                drive_in_background(Arc::new(first));
+                addr
+            }
+
+            let addr: Multiaddr = "/ip4/127.0.0.1/udp/0/quic".parse().unwrap();
+            let (ready_tx, ready_rx) = futures::channel::mpsc::channel(1);
+            let listener_task = async_std::task::spawn(listener(addr, ready_tx));
+            let addr = async_std::task::block_on(dialer(ready_rx));
+            let listener = async_std::task::block_on(listener_task);
+            let endpoint = listener.endpoint.clone();
+
+            drop(listener);
+
+            // Never admitted at all, so - like dialing straight into a black
+            // hole - the only way for the dialer to notice is its own idle
+            // timeout; kept short so the test doesn't hang on the default one.
+            let transport = QuicTransport::new(
+                Config::new(&Keypair::generate_ed25519())
+                    .max_idle_timeout(Duration::from_millis(300)),
+            );
+            let result = async_std::task::block_on(transport.dial(addr).unwrap())
+                .map(|_| ())
+                .unwrap_err();
+            assert!(
+                matches!(result, Error::NoResponse),
+                "expected the dial to time out unanswered, got {:?}",
+                result
+            );
+            assert_eq!(
+                endpoint.pending_connections_len(),
+                0,
+                "a connection admitted after the listener was dropped should never be queued up \
+                 for a listener that no longer exists to pick it up"
+            );
+        }
+
+        /// Simulates a listener restarting at the same address with the same
+        /// [`Config::stateless_reset_key`]: the original listener (and its
+        /// connection) disappears without closing anything, as on an unclean
+        /// process exit, but the dialer keeps sending keep-alives to the same
+        /// address regardless. Once a replacement listener configured with the
+        /// same key takes over that address, `quinn_proto` recognizes the
+        /// dialer's traffic as addressed to a connection ID it doesn't know and
+        /// replies with a stateless reset carrying the token the dialer
+        /// recorded during the original handshake, so the dialer notices the
+        /// restart via [`quinn_proto::ConnectionError::Reset`] instead of only
+        /// finding out once its own idle timeout elapses.
+        #[test]
+        fn stateless_reset_key_lets_a_restarted_listener_reset_an_old_connection() {
+            // Juggling two listeners and a dialer's connection concurrently
+            // nests enough generated `Future` state to overflow a default 2 MiB
+            // test thread stack; run the body on a thread with more headroom.
+            std::thread::Builder::new()
+                .stack_size(8 * 1024 * 1024)
+                .spawn(run)
+                .unwrap()
+                .join()
+                .unwrap();
+
+            fn run() {
+                env_logger::try_init().ok();
+
+                let reset_key = vec![0x7fu8; 64];
+
+                async fn original_listener(
+                    socket: StdUdpSocket,
+                    reset_key: Vec<u8>,
+                    mut ready_tx: futures::channel::mpsc::Sender<Multiaddr>,
+                ) {
+                    let config =
+                        Config::new(&Keypair::generate_ed25519()).stateless_reset_key(reset_key);
+                    let transport = QuicTransport::new(config);
+                    let mut listener = transport.listen_on_socket(socket).unwrap();
+                    loop {
+                        match listener.next().await.unwrap().unwrap() {
+                            ListenerEvent::NewAddress(listen_addr) => {
+                                ready_tx.send(listen_addr).await.unwrap();
+                            }
+                            ListenerEvent::Upgrade { upgrade, .. } => {
+                                upgrade.await.unwrap();
+                                // Dropping `listener` (and so its socket) here,
+                                // without closing the connection, mirrors a process
+                                // that exits uncleanly: the dialer has no idea
+                                // anything changed.
+                                return;
+                            }
+                            ListenerEvent::Error(e) => panic!("Unexpected listener error: {}", e),
+                            ListenerEvent::AddressExpired(_) => {}
+                        }
+                    }
+                }
+
+                async fn dialer(
+                    mut ready_rx: futures::channel::mpsc::Receiver<Multiaddr>,
+                ) -> Error {
+                    let addr = ready_rx.next().await.unwrap();
+                    let config = Config::new(&Keypair::generate_ed25519())
+                        .keep_alive_interval(Duration::from_millis(20));
+                    let transport = QuicTransport::new(config);
+                    let (_, muxer) = transport.dial(addr).unwrap().await.unwrap();
+
+                    future::poll_fn(|cx| loop {
+                        match muxer.poll_event(cx) {
+                            Poll::Ready(Ok(_)) => continue,
+                            Poll::Ready(Err(e)) => return Poll::Ready(e),
+                            Poll::Pending => return Poll::Pending,
+                        }
+                    })
+                    .await
+                }
+
+                let socket = StdUdpSocket::bind((std::net::Ipv4Addr::LOCALHOST, 0)).unwrap();
+                let listen_addr = socket.local_addr().unwrap();
+
+                let (ready_tx, ready_rx) = futures::channel::mpsc::channel(1);
+                let dialer_task = async_std::task::spawn(dialer(ready_rx));
+                async_std::task::block_on(original_listener(socket, reset_key.clone(), ready_tx));
+
+                // The original listener's socket is gone; bind a fresh one at the
+                // same address, configured with the same key, to play the role of
+                // the restarted process. Nothing ever accepts a new connection on
+                // it, so just keep its socket read loop running in the background
+                // for `drain_endpoint_transmits` to reply with the reset from.
+                let socket2 = StdUdpSocket::bind(listen_addr).unwrap();
+                let config2 =
+                    Config::new(&Keypair::generate_ed25519()).stateless_reset_key(reset_key);
+                let mut listener2 = QuicTransport::new(config2)
+                    .listen_on_socket(socket2)
+                    .unwrap();
+                async_std::task::spawn(future::poll_fn(move |cx| loop {
+                    match Pin::new(&mut listener2).poll_next(cx) {
+                        Poll::Ready(Some(_)) => continue,
+                        Poll::Ready(None) => return Poll::Ready(()),
+                        Poll::Pending => return Poll::Pending,
+                    }
+                }));
+
+                let error = async_std::task::block_on(dialer_task);
+                assert!(
+                    matches!(&error, Error::Handshake(reason) if reason.contains("reset")),
+                    "expected the replacement listener's stateless reset to close the \
+                 dialer's stale connection as a peer reset, got {:?}",
+                    error
+                );
+            }
+        }
+
+        /// Exercises [`crate::tls::make_server_config`]'s wiring of
+        /// [`Config::require_client_auth`] directly against `rustls`, in-memory
+        /// and without a socket: with client auth required, a client that
+        /// presents no certificate must fail the handshake; with it optional,
+        /// the same anonymous client must be accepted.
+        #[test]
+        fn require_client_auth_toggle_controls_whether_anonymous_clients_are_accepted() {
+            struct AcceptAnyServerCert;
+
+            impl rustls::ServerCertVerifier for AcceptAnyServerCert {
+                fn verify_server_cert(
+                    &self,
+                    _roots: &rustls::RootCertStore,
+                    _presented_certs: &[rustls::Certificate],
+                    _dns_name: webpki::DNSNameRef,
+                    _ocsp_response: &[u8],
+                ) -> Result<rustls::ServerCertVerified, rustls::TLSError> {
+                    Ok(rustls::ServerCertVerified::assertion())
+                }
+            }
+
+            fn anonymous_client_config() -> rustls::ClientConfig {
+                let mut crypto = rustls::ClientConfig::new();
+                crypto
+                    .dangerous()
+                    .set_certificate_verifier(Arc::new(AcceptAnyServerCert));
+                crypto.versions = vec![rustls::ProtocolVersion::TLSv1_3];
+                crypto
+            }
+
+            // A minimal in-memory TLS handshake loop, bypassing QUIC entirely,
+            // modelled on `rustls`' own `do_handshake` test helper.
+            fn handshake_succeeds(require_client_auth: bool) -> bool {
+                use rustls::Session;
+
+                let config = Config::new(&Keypair::generate_ed25519())
+                    .require_client_auth(require_client_auth);
+                let server_config = crate::tls::make_server_config(&config).unwrap();
+                let mut server = rustls::ServerSession::new(&server_config.crypto);
+
+                let name = webpki::DNSNameRef::try_from_ascii_str("localhost").unwrap();
+                let mut client =
+                    rustls::ClientSession::new(&Arc::new(anonymous_client_config()), name);
+
+                let mut buf = [0u8; 65536];
+                while client.is_handshaking() || server.is_handshaking() {
+                    while client.wants_write() {
+                        let n = client.write_tls(&mut &mut buf[..]).unwrap();
+                        let _ = server.read_tls(&mut &buf[..n]);
+                    }
+                    if server.process_new_packets().is_err() {
+                        return false;
+                    }
+                    while server.wants_write() {
+                        let n = server.write_tls(&mut &mut buf[..]).unwrap();
+                        let _ = client.read_tls(&mut &buf[..n]);
+                    }
+                    if client.process_new_packets().is_err() {
+                        return false;
+                    }
+                }
+                true
+            }
+
+            assert!(
+                !handshake_succeeds(true),
+                "an anonymous client should be rejected when client auth is required"
+            );
+            assert!(
+                handshake_succeeds(false),
+                "an anonymous client should be accepted when client auth is optional"
+            );
+        }
+
+        /// `listen_on` with a port-0 address resolves the real bound port
+        /// before ever constructing the [`QuicListenStream`], and its
+        /// `ListenerEvent::NewAddress` - the listener's very first event,
+        /// emitted once that stream is first polled rather than any earlier -
+        /// reports that resolved port rather than the unresolved `0` the caller
+        /// asked for.
+        #[test]
+        fn listen_on_reports_the_concrete_port_after_a_port_zero_bind() {
+            let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+            let addr: Multiaddr = "/ip4/127.0.0.1/udp/0/quic".parse().unwrap();
+            let mut listener = transport.listen_on(addr).unwrap();
+
+            let first_event = async_std::task::block_on(listener.next()).unwrap().unwrap();
+            let listen_addr = match first_event {
+                ListenerEvent::NewAddress(listen_addr) => listen_addr,
+                ListenerEvent::Upgrade { .. } => panic!("expected NewAddress, got Upgrade"),
+                ListenerEvent::AddressExpired(_) => {
+                    panic!("expected NewAddress, got AddressExpired")
+                }
+                ListenerEvent::Error(e) => panic!("expected NewAddress, got Error({})", e),
+            };
+
+            let socket_addr = multiaddr_to_socketaddr(listen_addr, true).unwrap();
+            assert_eq!(
+                socket_addr.ip(),
+                "127.0.0.1".parse::<std::net::IpAddr>().unwrap()
+            );
+            assert_ne!(socket_addr.port(), 0);
+        }
+    }
+
+    mod backlog {
+        //! Connection admission limits: `max_connections`, the pending-connection backlog, overflow policy, and connection-ID exhaustion.
+        use super::*;
+
+        #[test]
+        fn max_connections_refuses_beyond_cap_until_one_closes() {
+            env_logger::try_init().ok();
+
+            async fn listener(
+                addr: Multiaddr,
+                mut ready_tx: futures::channel::mpsc::Sender<Multiaddr>,
+                mut first_muxer_tx: Option<futures::channel::oneshot::Sender<QuicMuxer>>,
+            ) {
+                let transport = QuicTransport::new(
+                    Config::new(&Keypair::generate_ed25519()).max_connections(1),
+                );
+                let mut listener = transport.listen_on(addr).unwrap();
+                loop {
+                    match listener.next().await.unwrap().unwrap() {
+                        ListenerEvent::NewAddress(listen_addr) => {
+                            ready_tx.send(listen_addr).await.unwrap();
+                        }
+                        ListenerEvent::Upgrade { upgrade, .. } => {
+                            let (_, muxer) = upgrade.await.unwrap();
+                            match first_muxer_tx.take() {
+                                Some(tx) => {
+                                    tx.send(muxer).unwrap_or_else(|_| panic!("dialer dropped"))
+                                }
+                                None => return,
+                            }
+                        }
+                        ListenerEvent::Error(e) => panic!("Unexpected listener error: {}", e),
+                        ListenerEvent::AddressExpired(_) => {}
+                    }
+                }
+            }
+
+            async fn dialer(
+                mut ready_rx: futures::channel::mpsc::Receiver<Multiaddr>,
+                first_muxer_rx: futures::channel::oneshot::Receiver<QuicMuxer>,
+            ) {
+                let addr = ready_rx.next().await.unwrap();
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+
+                let (_, first) = transport.clone().dial(addr.clone()).unwrap().await.unwrap();
+                drive_in_background(Arc::new(first));
+
+                assert!(
+                    transport.clone().dial(addr.clone()).unwrap().await.is_err(),
+                    "dial beyond max_connections should be refused"
+                );
+
+                // Close the listener's side of the first connection and keep
+                // driving it so it can finish draining, freeing its slot.
+                let first_accepted = first_muxer_rx.await.unwrap();
+                future::poll_fn(|cx| StreamMuxer::close(&first_accepted, cx))
+                    .await
+                    .unwrap();
+                drive_in_background(Arc::new(first_accepted));
+
+                // The slot frees up asynchronously as the connection drains;
+                // retry the next dial until it succeeds or we give up.
+                for _ in 0..100 {
+                    match transport.clone().dial(addr.clone()).unwrap().await {
+                        Ok((_, muxer)) => {
+                            // As in `drive_in_background`'s own doc comment: our
+                            // final handshake-confirmation flight is only
+                            // flushed once this muxer is driven again, which the
+                            // listener's own `Upgrade` is waiting on.
+                            drive_in_background(Arc::new(muxer));
+                            return;
+                        }
+                        Err(_) => {
+                            async_std::task::sleep(std::time::Duration::from_millis(20)).await
+                        }
+                    }
+                }
+                panic!("dial kept being refused after the first connection closed");
+            }
+
+            let addr: Multiaddr = "/ip4/127.0.0.1/udp/0/quic".parse().unwrap();
+            let (ready_tx, ready_rx) = futures::channel::mpsc::channel(1);
+            let (first_muxer_tx, first_muxer_rx) = futures::channel::oneshot::channel();
+            // Boxed because these futures hold enough state across their many
+            // `.await` points (two QUIC connections plus the retry loop) to
+            // overflow a worker thread's stack if moved onto it by value.
+            let listener =
+                async_std::task::spawn(Box::pin(listener(addr, ready_tx, Some(first_muxer_tx))));
+            async_std::task::block_on(Box::pin(dialer(ready_rx, first_muxer_rx)));
+            async_std::task::block_on(listener);
+        }
+
+        /// Unlike [`max_connections_refuses_beyond_cap_until_one_closes`], which
+        /// bakes its cap into [`Config::max_connections`] up front, this lowers
+        /// [`QuicListenStream::set_max_connections`] only after the first
+        /// connection is already live, and confirms it still takes effect for
+        /// the very next handshake - refusing it the same way exceeding
+        /// `Config::max_connections` would - without touching the connection
+        /// already established.
+        #[test]
+        fn set_max_connections_refuses_new_connections_without_closing_existing_ones() {
+            env_logger::try_init().ok();
+
+            async fn listener(
+                addr: Multiaddr,
+                mut ready_tx: futures::channel::mpsc::Sender<Multiaddr>,
+                mut first_muxer_tx: Option<futures::channel::oneshot::Sender<QuicMuxer>>,
+            ) {
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+                let mut listener = transport.listen_on(addr).unwrap();
+                loop {
+                    match listener.next().await.unwrap().unwrap() {
+                        ListenerEvent::NewAddress(listen_addr) => {
+                            ready_tx.send(listen_addr).await.unwrap();
+                        }
+                        ListenerEvent::Upgrade { upgrade, .. } => {
+                            let (_, muxer) = upgrade.await.unwrap();
+                            let tx = first_muxer_tx
+                                .take()
+                                .expect("a second connection should never have been admitted");
+                            // Only tighten the cap once the first connection is
+                            // actually live, so it's the dialer's *second*
+                            // attempt that gets caught by it, not the first.
+                            listener.set_max_connections(Some(1));
+                            tx.send(muxer).unwrap_or_else(|_| panic!("dialer dropped"));
+                            return;
+                        }
+                        ListenerEvent::Error(e) => panic!("Unexpected listener error: {}", e),
+                        ListenerEvent::AddressExpired(_) => {}
+                    }
+                }
+            }
+
+            async fn dialer(
+                mut ready_rx: futures::channel::mpsc::Receiver<Multiaddr>,
+                first_muxer_rx: futures::channel::oneshot::Receiver<QuicMuxer>,
+            ) {
+                let addr = ready_rx.next().await.unwrap();
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+
+                let (_, first) = transport.clone().dial(addr.clone()).unwrap().await.unwrap();
+                drive_in_background(Arc::new(first));
+
+                let first_accepted = first_muxer_rx.await.unwrap();
+                drive_in_background(Arc::new(first_accepted));
+
+                // Never admitted at all, so - like dialling straight into a
+                // black hole - the only way to notice is our own idle timeout;
+                // kept short so the test doesn't hang on the default one.
+                let second = QuicTransport::new(
+                    Config::new(&Keypair::generate_ed25519())
+                        .max_idle_timeout(std::time::Duration::from_millis(300)),
+                );
+                let result = second.dial(addr).unwrap().await.map(|_| ()).unwrap_err();
+                assert!(
+                    matches!(result, Error::NoResponse),
+                    "dial beyond the runtime-lowered cap should be refused, got {:?}",
+                    result
+                );
+            }
+
+            let addr: Multiaddr = "/ip4/127.0.0.1/udp/0/quic".parse().unwrap();
+            let (ready_tx, ready_rx) = futures::channel::mpsc::channel(1);
+            let (first_muxer_tx, first_muxer_rx) = futures::channel::oneshot::channel();
+            let listener_task =
+                async_std::task::spawn(Box::pin(listener(addr, ready_tx, Some(first_muxer_tx))));
+            async_std::task::block_on(Box::pin(dialer(ready_rx, first_muxer_rx)));
+            async_std::task::block_on(listener_task);
+        }
+
+        /// Mirrors [`set_max_connections_refuses_new_connections_without_closing_existing_ones`]
+        /// but for [`QuicListenStream::set_accept_rate_limit`]: once the limit is
+        /// exhausted for the current window, a further handshake attempt is
+        /// refused the same way, while the connections already admitted this
+        /// window are unaffected.
+        #[test]
+        fn set_accept_rate_limit_refuses_connections_once_the_window_is_exhausted() {
+            env_logger::try_init().ok();
+
+            async fn listener(
+                addr: Multiaddr,
+                mut ready_tx: futures::channel::mpsc::Sender<Multiaddr>,
+                mut first_muxer_tx: Option<futures::channel::oneshot::Sender<QuicMuxer>>,
+            ) {
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+                let mut listener = transport.listen_on(addr).unwrap();
+                loop {
+                    match listener.next().await.unwrap().unwrap() {
+                        ListenerEvent::NewAddress(listen_addr) => {
+                            ready_tx.send(listen_addr).await.unwrap();
+                        }
+                        ListenerEvent::Upgrade { upgrade, .. } => {
+                            let (_, muxer) = upgrade.await.unwrap();
+                            let tx = first_muxer_tx
+                                .take()
+                                .expect("a second connection should never have been admitted");
+                            // Only tighten the limit once the first connection
+                            // has already used up the window's one admission,
+                            // so it's the dialer's *second* attempt that's
+                            // caught by it, not the first.
+                            listener.set_accept_rate_limit(Some(1));
+                            tx.send(muxer).unwrap_or_else(|_| panic!("dialer dropped"));
+                            return;
+                        }
+                        ListenerEvent::Error(e) => panic!("Unexpected listener error: {}", e),
+                        ListenerEvent::AddressExpired(_) => {}
+                    }
+                }
+            }
+
+            async fn dialer(
+                mut ready_rx: futures::channel::mpsc::Receiver<Multiaddr>,
+                first_muxer_rx: futures::channel::oneshot::Receiver<QuicMuxer>,
+            ) {
+                let addr = ready_rx.next().await.unwrap();
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+
+                let (_, first) = transport.clone().dial(addr.clone()).unwrap().await.unwrap();
+                drive_in_background(Arc::new(first));
+
+                let first_accepted = first_muxer_rx.await.unwrap();
+                drive_in_background(Arc::new(first_accepted));
+
+                let second = QuicTransport::new(
+                    Config::new(&Keypair::generate_ed25519())
+                        .max_idle_timeout(std::time::Duration::from_millis(300)),
+                );
+                let result = second.dial(addr).unwrap().await.map(|_| ()).unwrap_err();
+                assert!(
+                    matches!(result, Error::NoResponse),
+                    "dial once the accept rate limit's window is exhausted should be \
+                     refused, got {:?}",
+                    result
+                );
+            }
+
+            let addr: Multiaddr = "/ip4/127.0.0.1/udp/0/quic".parse().unwrap();
+            let (ready_tx, ready_rx) = futures::channel::mpsc::channel(1);
+            let (first_muxer_tx, first_muxer_rx) = futures::channel::oneshot::channel();
+            let listener_task =
+                async_std::task::spawn(Box::pin(listener(addr, ready_tx, Some(first_muxer_tx))));
+            async_std::task::block_on(Box::pin(dialer(ready_rx, first_muxer_rx)));
+            async_std::task::block_on(listener_task);
+        }
+
+        /// [`Config::max_connections_per_peer`] refuses a peer's second
+        /// connection once its first is already established, while a different
+        /// peer's connection is unaffected.
+        #[test]
+        fn max_connections_per_peer_refuses_a_second_connection_from_the_same_peer() {
+            env_logger::try_init().ok();
+
+            async fn listener(
+                addr: Multiaddr,
+                mut ready_tx: futures::channel::mpsc::Sender<Multiaddr>,
+                first_accepted_tx: futures::channel::oneshot::Sender<()>,
+            ) {
+                let transport = QuicTransport::new(
+                    Config::new(&Keypair::generate_ed25519())
+                        .max_idle_timeout(std::time::Duration::from_millis(500))
+                        .max_connections_per_peer(1),
+                );
+                let mut listener = transport.listen_on(addr).unwrap();
+                let mut first_accepted_tx = Some(first_accepted_tx);
+                let mut accepted = 0;
+                loop {
+                    match listener.next().await.unwrap().unwrap() {
+                        ListenerEvent::NewAddress(listen_addr) => {
+                            ready_tx.send(listen_addr).await.unwrap();
+                        }
+                        ListenerEvent::Upgrade { upgrade, .. } => match upgrade.await {
+                            Ok((_, muxer)) => {
+                                drive_in_background(Arc::new(muxer));
+                                accepted += 1;
+                                if let Some(tx) = first_accepted_tx.take() {
+                                    tx.send(()).unwrap_or_else(|_| panic!("dialer dropped"));
+                                }
+                                if accepted == 2 {
+                                    return;
+                                }
+                            }
+                            Err(_) => {
+                                // The same peer's second connection, refused for
+                                // already being at its cap; keep listening for
+                                // the other peer's.
+                                continue;
+                            }
+                        },
+                        ListenerEvent::Error(e) => panic!("Unexpected listener error: {}", e),
+                        ListenerEvent::AddressExpired(_) => {}
+                    }
+                }
+            }
+
+            async fn dialer(
+                mut ready_rx: futures::channel::mpsc::Receiver<Multiaddr>,
+                first_accepted_rx: futures::channel::oneshot::Receiver<()>,
+            ) {
+                let addr = ready_rx.next().await.unwrap();
+
+                let same_peer = Keypair::generate_ed25519();
+                let first = QuicTransport::new(
+                    Config::new(&same_peer).max_idle_timeout(std::time::Duration::from_millis(500)),
+                );
+                let (_, first_muxer) = first.dial(addr.clone()).unwrap().await.unwrap();
+                drive_in_background(Arc::new(first_muxer));
+
+                // Wait for the listener to have actually registered the first
+                // connection against the peer's cap before dialling the second,
+                // rather than racing the listener's own bookkeeping.
+                first_accepted_rx.await.unwrap();
+
+                let second = QuicTransport::new(
+                    Config::new(&same_peer).max_idle_timeout(std::time::Duration::from_millis(500)),
+                );
+                let (_, second_muxer) = second.dial(addr.clone()).unwrap().await.unwrap();
+                assert!(
+                    future::poll_fn(|cx| second_muxer.poll_event(cx))
+                        .await
+                        .is_err(),
+                    "a second connection from a peer already at its per-peer cap \
+                     should be refused, closing right away instead of staying open"
+                );
+
+                let other_peer = QuicTransport::new(
+                    Config::new(&Keypair::generate_ed25519())
+                        .max_idle_timeout(std::time::Duration::from_millis(500)),
+                );
+                let (_, other_muxer) = other_peer.dial(addr).unwrap().await.unwrap();
+                drive_in_background(Arc::new(other_muxer));
+            }
+
+            let addr: Multiaddr = "/ip4/127.0.0.1/udp/0/quic".parse().unwrap();
+            let (ready_tx, ready_rx) = futures::channel::mpsc::channel(1);
+            let (first_accepted_tx, first_accepted_rx) = futures::channel::oneshot::channel();
+            // Boxed for the same reason as `max_connections_refuses_beyond_cap_until_one_closes`:
+            // several QUIC connections' worth of state across many `.await`
+            // points can overflow a worker thread's stack if moved onto it by
+            // value.
+            let listener_task =
+                async_std::task::spawn(Box::pin(listener(addr, ready_tx, first_accepted_tx)));
+            async_std::task::block_on(Box::pin(dialer(ready_rx, first_accepted_rx)));
+            async_std::task::block_on(listener_task);
+        }
+
+        #[test]
+        fn close_connections_closes_only_matching_source_ip() {
+            env_logger::try_init().ok();
+
+            let targeted_ip: std::net::IpAddr = std::net::Ipv4Addr::new(127, 0, 0, 2).into();
+
+            async fn listener(
+                addr: Multiaddr,
+                mut ready_tx: futures::channel::mpsc::Sender<Multiaddr>,
+                closed_tx: futures::channel::oneshot::Sender<()>,
+                targeted_ip: std::net::IpAddr,
+            ) {
+                let transport = QuicTransport::new(
+                    Config::new(&Keypair::generate_ed25519())
+                        .max_idle_timeout(std::time::Duration::from_millis(500)),
+                );
+                let mut listener = transport.listen_on(addr).unwrap();
+                let mut accepted = 0;
+                loop {
+                    match listener.next().await.unwrap().unwrap() {
+                        ListenerEvent::NewAddress(listen_addr) => {
+                            ready_tx.send(listen_addr).await.unwrap();
+                        }
+                        ListenerEvent::Upgrade { upgrade, .. } => {
+                            let (_, muxer) = upgrade.await.unwrap();
+                            drive_in_background(Arc::new(muxer));
+                            accepted += 1;
+                            if accepted == 2 {
+                                break;
+                            }
+                        }
+                        ListenerEvent::Error(e) => panic!("Unexpected listener error: {}", e),
+                        ListenerEvent::AddressExpired(_) => {}
+                    }
+                }
+
+                listener.close_connections(
+                    |info| info.remote_address.ip() == targeted_ip,
+                    quinn_proto::VarInt::from_u32(0),
+                    Bytes::new(),
+                );
+                closed_tx.send(()).unwrap();
+            }
+
+            // Dials `remote` from a socket explicitly bound to `local_addr`,
+            // bypassing `QuicTransport::dial`'s own unspecified-address bind so
+            // the test can control what source IP the listener sees, the same
+            // way `dropped_datagrams_are_counted_when_the_socket_reports_them_undeliverable`
+            // reaches into `Endpoint` directly for something the public API
+            // doesn't expose a knob for.
+            async fn dial_from(local_addr: SocketAddr, remote: Multiaddr) -> QuicMuxer {
+                let config = Config::new(&Keypair::generate_ed25519())
+                    .max_idle_timeout(std::time::Duration::from_millis(500));
+                let remote = multiaddr_to_socketaddr(remote, true).unwrap();
+                let socket = StdUdpSocket::bind(local_addr).unwrap();
+                let endpoint = Endpoint::from_socket(&config, socket).unwrap();
+                let (handle, connection) = endpoint.dial(&config, remote).unwrap();
+                let (_, muxer) = Upgrade::new(
+                    endpoint,
+                    handle,
+                    connection,
+                    config.peer_verifier,
+                    config.max_idle_timeout,
+                    config.close_timeout,
+                    config.stream_write_buffer,
+                )
+                .await
+                .unwrap();
+                muxer
+            }
+
+            async fn dialer(
+                mut ready_rx: futures::channel::mpsc::Receiver<Multiaddr>,
+                closed_rx: futures::channel::oneshot::Receiver<()>,
+                targeted_ip: std::net::IpAddr,
+            ) {
+                let addr = ready_rx.next().await.unwrap();
+
+                let targeted = dial_from((targeted_ip, 0).into(), addr.clone()).await;
+                let spared = dial_from((std::net::Ipv4Addr::LOCALHOST, 0).into(), addr).await;
+
+                closed_rx.await.unwrap();
+
+                assert!(
+                    future::poll_fn(|cx| targeted.poll_event(cx)).await.is_err(),
+                    "the connection dialled from the targeted source IP should have been closed"
+                );
+
+                let waker = futures::task::noop_waker();
+                let mut cx = Context::from_waker(&waker);
+                assert!(
+                    matches!(spared.poll_event(&mut cx), Poll::Pending),
+                    "the connection dialled from a different source IP should be left open"
+                );
+            }
+
+            let addr: Multiaddr = "/ip4/127.0.0.1/udp/0/quic".parse().unwrap();
+            let (ready_tx, ready_rx) = futures::channel::mpsc::channel(1);
+            let (closed_tx, closed_rx) = futures::channel::oneshot::channel();
+            // Boxed for the same reason as `max_connections_refuses_beyond_cap_until_one_closes`:
+            // two QUIC connections' worth of state across many `.await` points
+            // can overflow a worker thread's stack if moved onto it by value.
+            let listener =
+                async_std::task::spawn(Box::pin(listener(addr, ready_tx, closed_tx, targeted_ip)));
+            async_std::task::block_on(Box::pin(dialer(ready_rx, closed_rx, targeted_ip)));
+            async_std::task::block_on(listener);
+        }
+
+        #[test]
+        fn num_connections_tracks_connections_opening_and_closing() {
+            env_logger::try_init().ok();
+
+            async fn listener(
+                addr: Multiaddr,
+                mut ready_tx: futures::channel::mpsc::Sender<Multiaddr>,
+            ) {
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+                let mut listener = transport.listen_on(addr).unwrap();
+
+                assert_eq!(listener.num_connections(), 0);
+
+                let first = loop {
+                    match listener.next().await.unwrap().unwrap() {
+                        ListenerEvent::NewAddress(listen_addr) => {
+                            ready_tx.send(listen_addr).await.unwrap();
+                        }
+                        ListenerEvent::Upgrade { upgrade, .. } => break upgrade.await.unwrap().1,
+                        ListenerEvent::Error(e) => panic!("Unexpected listener error: {}", e),
+                        ListenerEvent::AddressExpired(_) => {}
+                    }
+                };
+                assert_eq!(listener.num_connections(), 1);
+
+                let second = match listener.next().await.unwrap().unwrap() {
+                    ListenerEvent::Upgrade { upgrade, .. } => upgrade.await.unwrap().1,
+                    _ => panic!("expected a second upgrade"),
+                };
+                assert_eq!(listener.num_connections(), 2);
+
+                // Dropping a `QuicMuxer` without an intervening `poll_event` or
+                // `close_connections` call never touches the endpoint's
+                // registry directly; `num_connections` is what prunes the now-dead
+                // weak reference it left behind.
+                drop(first);
+                assert_eq!(listener.num_connections(), 1);
+
+                drive_in_background(Arc::new(second));
+            }
+
+            async fn dialer(mut ready_rx: futures::channel::mpsc::Receiver<Multiaddr>) {
+                let addr = ready_rx.next().await.unwrap();
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+
+                let (_, first) = transport.clone().dial(addr.clone()).unwrap().await.unwrap();
+                drive_in_background(Arc::new(first));
+
+                let (_, second) = transport.dial(addr).unwrap().await.unwrap();
+                drive_in_background(Arc::new(second));
+            }
+
+            let addr: Multiaddr = "/ip4/127.0.0.1/udp/0/quic".parse().unwrap();
+            let (ready_tx, ready_rx) = futures::channel::mpsc::channel(1);
+            let listener = async_std::task::spawn(listener(addr, ready_tx));
+            async_std::task::block_on(dialer(ready_rx));
+            async_std::task::block_on(listener);
+        }
+
+        /// [`QuicListenStream::begin_drain`] should resolve as soon as every
+        /// accepted connection finishes, rather than waiting out its full
+        /// deadline with nothing left to drain; and, symmetrically, it should
+        /// resolve once the deadline elapses if a connection is still open by
+        /// then, rather than waiting on it forever.
+        #[test]
+        fn begin_drain_completes_when_connections_finish_or_the_deadline_elapses() {
+            env_logger::try_init().ok();
+
+            const DEADLINE: Duration = Duration::from_millis(200);
+
+            async fn listener(
+                addr: Multiaddr,
+                mut ready_tx: futures::channel::mpsc::Sender<Multiaddr>,
+                keep_connection_open: bool,
+            ) -> (Duration, usize) {
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+                let mut listener = transport.listen_on(addr).unwrap();
+
+                let muxer = loop {
+                    match listener.next().await.unwrap().unwrap() {
+                        ListenerEvent::NewAddress(listen_addr) => {
+                            ready_tx.send(listen_addr).await.unwrap();
+                        }
+                        ListenerEvent::Upgrade { upgrade, .. } => break upgrade.await.unwrap().1,
+                        ListenerEvent::Error(e) => panic!("Unexpected listener error: {}", e),
+                        ListenerEvent::AddressExpired(_) => {}
+                    }
+                };
+
+                if keep_connection_open {
+                    drive_in_background(Arc::new(muxer));
+                } else {
+                    // Mirrors `num_connections_tracks_connections_opening_and_closing`:
+                    // dropping the muxer leaves a dead weak reference behind that
+                    // `num_connections` (and so `begin_drain`'s polling loop)
+                    // prunes lazily the next time it's called.
+                    drop(muxer);
+                }
+
+                let started = std::time::Instant::now();
+                listener.begin_drain(DEADLINE).await;
+                (started.elapsed(), listener.num_connections())
+            }
+
+            async fn dialer(mut ready_rx: futures::channel::mpsc::Receiver<Multiaddr>) {
+                let addr = ready_rx.next().await.unwrap();
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+                let (_, muxer) = transport.dial(addr).unwrap().await.unwrap();
+                drive_in_background(Arc::new(muxer));
+            }
+
+            async fn run(keep_connection_open: bool) -> (Duration, usize) {
+                let addr: Multiaddr = "/ip4/127.0.0.1/udp/0/quic".parse().unwrap();
+                let (ready_tx, ready_rx) = futures::channel::mpsc::channel(1);
+                let listener_task =
+                    async_std::task::spawn(listener(addr, ready_tx, keep_connection_open));
+                dialer(ready_rx).await;
+                listener_task.await
+            }
+
+            let (finished_elapsed, finished_remaining) = async_std::task::block_on(run(false));
+            assert_eq!(finished_remaining, 0);
+            assert!(
+                finished_elapsed < DEADLINE,
+                "draining with nothing left open should resolve well before the deadline, took {:?}",
+                finished_elapsed
+            );
+
+            let (timed_out_elapsed, timed_out_remaining) = async_std::task::block_on(run(true));
+            assert_eq!(timed_out_remaining, 1);
+            assert!(
+                (DEADLINE..DEADLINE * 10).contains(&timed_out_elapsed),
+                "draining with a connection still open should wait out roughly the full \
+                 deadline and no longer, took {:?}",
+                timed_out_elapsed
+            );
+        }
+
+        /// Keeps a second handshake attempt sitting in the accept backlog by
+        /// never polling the listener again after its first connection, and
+        /// checks [`QuicListenStream::pending_connections`] reports it - with its
+        /// real remote address and a [`PendingInfo::received_at`] that reflects
+        /// when it actually arrived - right up until the listener accepts it, at
+        /// which point it drops back out.
+        #[test]
+        fn pending_connections_reports_handshakes_not_yet_accepted() {
+            env_logger::try_init().ok();
+
+            async fn listener(
+                addr: Multiaddr,
+                mut ready_tx: futures::channel::mpsc::Sender<Multiaddr>,
+                mut pending_tx: futures::channel::mpsc::Sender<Vec<PendingInfo>>,
+            ) {
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+                let mut listener = transport.listen_on(addr).unwrap();
+                let mut accepted = 0;
+                loop {
+                    match listener.next().await.unwrap().unwrap() {
+                        ListenerEvent::NewAddress(listen_addr) => {
+                            ready_tx.send(listen_addr).await.unwrap();
+                        }
+                        ListenerEvent::Upgrade { upgrade, .. } => {
+                            let (_, muxer) = upgrade.await.unwrap();
+                            accepted += 1;
+                            if accepted == 1 {
+                                // Keep driving the first connection's shared
+                                // socket in the background: this is what reads
+                                // the second dialer's handshake packet off the
+                                // wire and queues it up, without this loop
+                                // polling the listener (and so draining the
+                                // accept backlog) again until after the snapshot
+                                // below.
+                                drive_in_background(Arc::new(muxer));
+                                async_std::task::sleep(Duration::from_millis(300)).await;
+                                pending_tx
+                                    .send(listener.pending_connections())
+                                    .await
+                                    .unwrap();
+                            } else {
+                                drive_in_background(Arc::new(muxer));
+                                pending_tx
+                                    .send(listener.pending_connections())
+                                    .await
+                                    .unwrap();
+                                return;
+                            }
+                        }
+                        ListenerEvent::Error(e) => panic!("Unexpected listener error: {}", e),
+                        ListenerEvent::AddressExpired(_) => {}
+                    }
+                }
+            }
+
+            let addr: Multiaddr = "/ip4/127.0.0.1/udp/0/quic".parse().unwrap();
+            let (ready_tx, mut ready_rx) = futures::channel::mpsc::channel(1);
+            let (pending_tx, mut pending_rx) = futures::channel::mpsc::channel(2);
+            let listener_task = async_std::task::spawn(listener(addr, ready_tx, pending_tx));
+
+            async_std::task::block_on(async {
+                let listen_addr = ready_rx.next().await.unwrap();
+
+                let first = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+                let (_, muxer) = first.dial(listen_addr.clone()).unwrap().await.unwrap();
+                drive_in_background(Arc::new(muxer));
+
+                // Never completes its handshake, since the listener above stops
+                // polling after accepting `first`; just gets its initial packet
+                // onto the wire and is left running in the background.
+                let second = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+                async_std::task::spawn(second.dial(listen_addr).unwrap());
+
+                let while_pending = pending_rx.next().await.unwrap();
+                assert_eq!(
+                    while_pending.len(),
+                    1,
+                    "the second handshake attempt should still be queued up waiting to be accepted"
+                );
+                assert_eq!(
+                    while_pending[0].remote_address.ip(),
+                    std::net::Ipv4Addr::LOCALHOST
+                );
+                assert!(
+                    while_pending[0].received_at.elapsed() >= Duration::from_millis(250),
+                    "received_at should reflect when the handshake attempt actually arrived, \
+                     not when pending_connections() was called"
+                );
+
+                let once_accepted = pending_rx.next().await.unwrap();
+                assert!(
+                    once_accepted.is_empty(),
+                    "the connection should no longer be pending once the listener accepted it"
+                );
+            });
+            async_std::task::block_on(listener_task);
+        }
+
+        /// `ConnectionHandle`s are freed once their connection drains and reused
+        /// for whatever dials or arrives next; forces that churn with
+        /// `max_connections(1)` so every connection after the first is only
+        /// ever accepted once its predecessor's handle has been freed up, and
+        /// has each round tag its data with its own round number so a handle
+        /// reused with stale state left behind under the same key would show up
+        /// as a round reporting someone else's tag instead of its own.
+        ///
+        /// The listener reports each tag it reads over a plain in-process
+        /// channel rather than echoing it back over the connection: echoing
+        /// would need the listener to explicitly close its side once done, and
+        /// closing a connection before its own outgoing echo has actually been
+        /// flushed can discard that echo outright, which isn't what this test
+        /// is after. Reading is all the listener's side of this needs to do to
+        /// exercise the same reused-handle bookkeeping the dialer's side does.
+        #[test]
+        fn rapid_connection_churn_does_not_misroute_events_between_reused_handles() {
+            env_logger::try_init().ok();
+
+            const ROUNDS: u32 = 3;
+
+            async fn listener(
+                addr: Multiaddr,
+                mut ready_tx: futures::channel::mpsc::Sender<Multiaddr>,
+                mut tag_tx: futures::channel::mpsc::Sender<Vec<u8>>,
+            ) {
+                // A generous idle timeout keeps this test from reading an
+                // unrelated timeout as a misrouted event under the scheduling
+                // delays a loaded test suite can introduce.
+                let transport = QuicTransport::new(
+                    Config::new(&Keypair::generate_ed25519())
+                        .max_connections(1)
+                        .max_idle_timeout(Duration::from_secs(30)),
+                );
+                let mut listener = transport.listen_on(addr).unwrap();
+                let mut accepted = 0;
+                loop {
+                    match listener.next().await.unwrap().unwrap() {
+                        ListenerEvent::NewAddress(listen_addr) => {
+                            ready_tx.send(listen_addr).await.unwrap();
+                        }
+                        ListenerEvent::Upgrade { upgrade, .. } => {
+                            let (_, muxer) = upgrade.await.unwrap();
+
+                            let id = match future::poll_fn(|cx| muxer.poll_event(cx)).await.unwrap()
+                            {
+                                StreamMuxerEvent::InboundSubstream(substream) => substream.id,
+                                StreamMuxerEvent::AddressChange(_) => {
+                                    panic!("did not expect an address change")
+                                }
+                            };
+
+                            let mut recv = RecvStream::new(&muxer, id);
+                            let mut tag = Vec::new();
+                            recv.read_to_end(&mut tag).await.unwrap();
+                            tag_tx.send(tag).await.unwrap();
+                            drop(recv);
+
+                            // Nothing of ours was ever written on this
+                            // connection, so there's nothing to flush: closing
+                            // it immediately is what actually frees this handle
+                            // for the round after next, rather than waiting on
+                            // the dialer's own close to passively drain it.
+                            future::poll_fn(|cx| StreamMuxer::close(&muxer, cx))
+                                .await
+                                .unwrap();
+                            drive_in_background_briefly(Arc::new(muxer), Duration::from_secs(2));
+
+                            accepted += 1;
+                            if accepted == ROUNDS {
+                                return;
+                            }
+                        }
+                        ListenerEvent::Error(e) => panic!("Unexpected listener error: {}", e),
+                        ListenerEvent::AddressExpired(_) => {}
+                    }
+                }
+            }
+
+            async fn dialer(
+                mut ready_rx: futures::channel::mpsc::Receiver<Multiaddr>,
+                mut tag_rx: futures::channel::mpsc::Receiver<Vec<u8>>,
+            ) {
+                let addr = ready_rx.next().await.unwrap();
+                let transport = QuicTransport::new(
+                    Config::new(&Keypair::generate_ed25519())
+                        .max_idle_timeout(Duration::from_secs(30)),
+                );
+
+                for round in 0..ROUNDS {
+                    let tag = round.to_le_bytes().to_vec();
+
+                    // The listener's single slot frees up asynchronously as the
+                    // previous round's connection drains, same as
+                    // `max_connections_refuses_beyond_cap_until_one_closes`.
+                    let mut established = None;
+                    for _ in 0..1500 {
+                        match transport.clone().dial(addr.clone()).unwrap().await {
+                            Ok(result) => {
+                                established = Some(result);
+                                break;
+                            }
+                            Err(_) => async_std::task::sleep(Duration::from_millis(20)).await,
+                        }
+                    }
+                    let (_, muxer) = established.unwrap_or_else(|| {
+                        panic!(
+                            "round {} kept being refused after the previous connection closed",
+                            round
+                        )
+                    });
+
+                    let mut stream = muxer.open_bi().unwrap();
+                    stream.send.write_all(&tag).await.unwrap();
+                    stream.send.close().await.unwrap();
+                    drop(stream);
+                    drive_in_background(Arc::new(muxer));
+
+                    let received = tag_rx.next().await.unwrap();
+                    assert_eq!(
+                        received, tag,
+                        "round {round} was reported with a different round's tag; a \
+                         reused ConnectionHandle must have had its events misrouted"
+                    );
+                }
+            }
+
+            let addr: Multiaddr = "/ip4/127.0.0.1/udp/0/quic".parse().unwrap();
+            let (ready_tx, ready_rx) = futures::channel::mpsc::channel(1);
+            let (tag_tx, tag_rx) = futures::channel::mpsc::channel(1);
+            // Boxed for the same reason as `max_connections_refuses_beyond_cap_until_one_closes`:
+            // many connections' worth of state across many `.await` points can
+            // overflow a worker thread's stack if moved onto it by value.
+            let listener_task = async_std::task::spawn(Box::pin(listener(addr, ready_tx, tag_tx)));
+            async_std::task::block_on(Box::pin(dialer(ready_rx, tag_rx)));
+            async_std::task::block_on(listener_task);
+        }
+
+        /// Floods a listener that never accepts beyond its first connection with
+        /// many more handshake attempts than [`Config::max_pending_connections`]
+        /// allows, and asserts the accept backlog stays bounded instead of
+        /// growing by one `quinn_proto::Connection` per attempt.
+        ///
+        /// Uses the default [`BacklogOverflowPolicy::Reject`]; the sibling test
+        /// `backlog_overflow_drop_oldest_policy_evicts_the_longest_waiting_connection`
+        /// runs the same flood under [`BacklogOverflowPolicy::DropOldest`].
+        #[test]
+        fn accept_backlog_stays_bounded_under_a_flood_of_connections() {
+            env_logger::try_init().ok();
+
+            const CAP: u32 = 4;
+            const FLOOD: usize = 50;
+
+            async fn listener(
+                addr: Multiaddr,
+                mut ready_tx: futures::channel::mpsc::Sender<Multiaddr>,
+            ) -> QuicListenStream {
+                let transport = QuicTransport::new(
+                    Config::new(&Keypair::generate_ed25519()).max_pending_connections(CAP),
+                );
+                let mut listener = transport.listen_on(addr).unwrap();
+                // Accept only the very first connection, then stop polling the
+                // listener (and so stop draining its accept backlog) entirely;
+                // every later handshake attempt is left sitting in `Endpoint`'s
+                // accept queue for the flood below to pile up against.
+                loop {
+                    match listener.next().await.unwrap().unwrap() {
+                        ListenerEvent::NewAddress(listen_addr) => {
+                            ready_tx.send(listen_addr).await.unwrap();
+                        }
+                        ListenerEvent::Upgrade { upgrade, .. } => {
+                            let (_, muxer) = upgrade.await.unwrap();
+                            // Keep driving the first connection's shared socket
+                            // in the background: this is what actually reads the
+                            // flood's datagrams off the wire and routes them
+                            // into the accept backlog, without the listener
+                            // itself ever polling again.
+                            drive_in_background(Arc::new(muxer));
+                            return listener;
+                        }
+                        ListenerEvent::Error(e) => panic!("Unexpected listener error: {}", e),
+                        ListenerEvent::AddressExpired(_) => {}
+                    }
+                }
+            }
+
+            async fn dialer(mut ready_rx: futures::channel::mpsc::Receiver<Multiaddr>) {
+                let addr = ready_rx.next().await.unwrap();
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+
+                let (_, first) = transport.clone().dial(addr.clone()).unwrap().await.unwrap();
+                drive_in_background(Arc::new(first));
+
+                // None of these will ever complete their handshake: a connection
+                // queued behind a listener that stopped accepting is never
+                // driven either, so it never sends its own response flight back.
+                // Just get their initial packets onto the wire and leave them
+                // running in the background; the assertions below only care
+                // about what piled up on the listener's side.
+                for _ in 0..FLOOD {
+                    async_std::task::spawn(transport.clone().dial(addr.clone()).unwrap());
+                }
+
+                // Give the flood's datagrams time to reach the listener and be
+                // dispatched by its first connection's background driver.
+                async_std::task::sleep(std::time::Duration::from_millis(500)).await;
+            }
+
+            let addr: Multiaddr = "/ip4/127.0.0.1/udp/0/quic".parse().unwrap();
+            let (ready_tx, ready_rx) = futures::channel::mpsc::channel(1);
+            let listener_task = async_std::task::spawn(Box::pin(listener(addr, ready_tx)));
+            async_std::task::block_on(Box::pin(dialer(ready_rx)));
+            let listener = async_std::task::block_on(listener_task);
+
+            assert!(
+                listener.endpoint.pending_connections_len() <= CAP as usize,
+                "accept backlog should never grow past max_pending_connections"
+            );
+            assert!(
+                listener.refused_pending_connections() > 0,
+                "flooding {} connections past a backlog of {} should have dropped some of them",
+                FLOOD,
+                CAP
+            );
+        }
+
+        /// Same flood as [`accept_backlog_stays_bounded_under_a_flood_of_connections`],
+        /// but with [`BacklogOverflowPolicy::DropOldest`] configured: the backlog
+        /// still stays bounded, and additionally the handles left in it once the
+        /// flood settles are the most recently arrived ones, not the first ones
+        /// queued, proving the policy evicted from the front rather than just
+        /// refusing new arrivals like the default.
+        #[test]
+        fn backlog_overflow_drop_oldest_policy_evicts_the_longest_waiting_connection() {
+            env_logger::try_init().ok();
+
+            const CAP: u32 = 4;
+            const FLOOD: usize = 50;
+
+            async fn listener(
+                addr: Multiaddr,
+                mut ready_tx: futures::channel::mpsc::Sender<Multiaddr>,
+            ) -> QuicListenStream {
+                let transport = QuicTransport::new(
+                    Config::new(&Keypair::generate_ed25519())
+                        .max_pending_connections(CAP)
+                        .backlog_overflow_policy(BacklogOverflowPolicy::DropOldest),
+                );
+                let mut listener = transport.listen_on(addr).unwrap();
+                loop {
+                    match listener.next().await.unwrap().unwrap() {
+                        ListenerEvent::NewAddress(listen_addr) => {
+                            ready_tx.send(listen_addr).await.unwrap();
+                        }
+                        ListenerEvent::Upgrade { upgrade, .. } => {
+                            let (_, muxer) = upgrade.await.unwrap();
+                            drive_in_background(Arc::new(muxer));
+                            return listener;
+                        }
+                        ListenerEvent::Error(e) => panic!("Unexpected listener error: {}", e),
+                        ListenerEvent::AddressExpired(_) => {}
+                    }
+                }
+            }
+
+            async fn dialer(mut ready_rx: futures::channel::mpsc::Receiver<Multiaddr>) {
+                let addr = ready_rx.next().await.unwrap();
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+
+                let (_, first) = transport.clone().dial(addr.clone()).unwrap().await.unwrap();
+                drive_in_background(Arc::new(first));
+
+                for _ in 0..FLOOD {
+                    async_std::task::spawn(transport.clone().dial(addr.clone()).unwrap());
+                }
+
+                async_std::task::sleep(std::time::Duration::from_millis(500)).await;
+            }
+
+            let addr: Multiaddr = "/ip4/127.0.0.1/udp/0/quic".parse().unwrap();
+            let (ready_tx, ready_rx) = futures::channel::mpsc::channel(1);
+            let listener_task = async_std::task::spawn(Box::pin(listener(addr, ready_tx)));
+            async_std::task::block_on(Box::pin(dialer(ready_rx)));
+            let listener = async_std::task::block_on(listener_task);
+
+            let remaining = listener.endpoint.pending_connection_handles();
+            assert!(
+                remaining.len() <= CAP as usize,
+                "accept backlog should never grow past max_pending_connections"
+            );
+            assert!(
+                listener.refused_pending_connections() > 0,
+                "flooding {} connections past a backlog of {} should have evicted some of them",
+                FLOOD,
+                CAP
+            );
+            assert!(
+                remaining.iter().all(|h| h.0 > CAP as usize),
+                "DropOldest should leave only later-arriving handles behind, got {:?}",
+                remaining
+            );
+        }
+
+        /// `quinn_proto` 0.7.3 keeps its connection IDs entirely internal, so
+        /// [`QuicMuxer::local_connection_id`] and [`QuicMuxer::remote_connection_id`]
+        /// can't actually report them yet; this documents that current
+        /// limitation rather than asserting behavior we can't provide.
+        #[test]
+        fn connection_ids_are_not_yet_exposed_by_the_underlying_quinn_proto_version() {
+            env_logger::try_init().ok();
+
+            async fn listener(
+                addr: Multiaddr,
+                mut ready_tx: futures::channel::mpsc::Sender<Multiaddr>,
+            ) {
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+                let mut listener = transport.listen_on(addr).unwrap();
+                loop {
+                    match listener.next().await.unwrap().unwrap() {
+                        ListenerEvent::NewAddress(listen_addr) => {
+                            ready_tx.send(listen_addr).await.unwrap();
+                        }
+                        ListenerEvent::Upgrade { upgrade, .. } => {
+                            let (_, muxer) = upgrade.await.unwrap();
+                            assert_eq!(muxer.local_connection_id(), None);
+                            assert_eq!(muxer.remote_connection_id(), None);
+                            return;
+                        }
+                        ListenerEvent::Error(e) => panic!("Unexpected listener error: {}", e),
+                        ListenerEvent::AddressExpired(_) => {}
+                    }
+                }
+            }
+
+            async fn dialer(mut ready_rx: futures::channel::mpsc::Receiver<Multiaddr>) {
+                let addr = ready_rx.next().await.unwrap();
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+                let (_, muxer) = transport.dial(addr).unwrap().await.unwrap();
+                assert_eq!(muxer.local_connection_id(), None);
+                assert_eq!(muxer.remote_connection_id(), None);
+                drive_in_background(Arc::new(muxer));
+            }
+
+            let addr: Multiaddr = "/ip4/127.0.0.1/udp/0/quic".parse().unwrap();
+            let (ready_tx, ready_rx) = futures::channel::mpsc::channel(1);
+            let listener = async_std::task::spawn(listener(addr, ready_tx));
+            async_std::task::block_on(dialer(ready_rx));
+            async_std::task::block_on(listener);
+        }
+
+        /// Shrinks the connection ID space down to one byte via
+        /// [`Config::connection_id_length`] so that repeatedly dialling out on
+        /// the same [`Endpoint`] genuinely runs it out of connection IDs,
+        /// without needing anywhere near `2^64` connections the way the default
+        /// 8-byte IDs would; `Endpoint::dial` should then report
+        /// `Error::EndpointAtCapacity` instead of the opaque `Handshake` error
+        /// `quinn_proto`'s own `ConnectError::TooManyConnections` would
+        /// otherwise be reduced to.
+        #[test]
+        fn endpoint_at_capacity_is_surfaced_once_connection_ids_run_out() {
+            env_logger::try_init().ok();
+
+            let config = Config::new(&Keypair::generate_ed25519()).connection_id_length(1);
+            let socket = StdUdpSocket::bind((std::net::Ipv4Addr::LOCALHOST, 0)).unwrap();
+            let endpoint = Endpoint::from_socket(&config, socket).unwrap();
+            // Never actually reached; `connect` hands out a connection ID
+            // without needing the remote to answer.
+            let remote: SocketAddr = "127.0.0.1:1".parse().unwrap();
+
+            let mut connections = Vec::new();
+            let error = loop {
+                match endpoint.dial(&config, remote) {
+                    Ok(connection) => connections.push(connection),
+                    Err(e) => break e,
+                }
+            };
+
+            assert!(
+                matches!(error, Error::EndpointAtCapacity),
+                "expected the endpoint to report it was out of connection IDs, \
+                 got {:?}",
+                error
+            );
+            assert!(
+                connections.len() > 1,
+                "expected several connections to succeed before the 1-byte \
+                 connection ID space ran out, got {}",
+                connections.len()
+            );
+        }
+
+        /// `Config::local_cid_len` reuses the same `quinn_proto` connection ID
+        /// generator knob that [`Config::connection_id_length`] uses internally
+        /// for the test above, so shrinking it down to one byte should exhaust
+        /// the endpoint's connection ID space after the same small number of
+        /// dials - confirming the configured length is actually what reaches
+        /// `quinn_proto`, since nothing in this crate's public API can read an
+        /// issued connection ID back out to compare directly (see
+        /// [`QuicMuxer::local_connection_id`]).
+        #[test]
+        fn local_cid_len_drives_the_endpoint_to_capacity_at_the_configured_length() {
+            env_logger::try_init().ok();
+
+            let config = Config::new(&Keypair::generate_ed25519()).local_cid_len(1);
+            let socket = StdUdpSocket::bind((std::net::Ipv4Addr::LOCALHOST, 0)).unwrap();
+            let endpoint = Endpoint::from_socket(&config, socket).unwrap();
+            let remote: SocketAddr = "127.0.0.1:1".parse().unwrap();
+
+            let mut connections = Vec::new();
+            let error = loop {
+                match endpoint.dial(&config, remote) {
+                    Ok(connection) => connections.push(connection),
+                    Err(e) => break e,
+                }
+            };
+
+            assert!(
+                matches!(error, Error::EndpointAtCapacity),
+                "expected the endpoint to report it was out of connection IDs, \
+                 got {:?}",
+                error
+            );
+            assert!(
+                connections.len() > 1,
+                "expected several connections to succeed before the 1-byte \
+                 connection ID space ran out, got {}",
+                connections.len()
+            );
+        }
+
+        /// QUIC connection IDs can be at most 20 bytes; a longer
+        /// [`Config::local_cid_len`] should be rejected when the endpoint is
+        /// constructed rather than silently clamped or left to `quinn_proto`'s
+        /// own `debug_assert!`, which would only catch it in debug builds.
+        #[test]
+        fn local_cid_len_beyond_the_quic_maximum_is_rejected() {
+            env_logger::try_init().ok();
+
+            let config = Config::new(&Keypair::generate_ed25519()).local_cid_len(21);
+            let socket = StdUdpSocket::bind((std::net::Ipv4Addr::LOCALHOST, 0)).unwrap();
+            let error = match Endpoint::from_socket(&config, socket) {
+                Ok(_) => panic!("expected local_cid_len(21) to be rejected"),
+                Err(e) => e,
+            };
+
+            assert!(
+                matches!(error, Error::InvalidConfig(_)),
+                "expected an out-of-range local_cid_len to be rejected as an \
+                 invalid configuration, got {:?}",
+                error
+            );
+        }
+    }
+
+    mod tls {
+        //! The TLS handshake itself: cipher suite/key-exchange selection, session tickets, 0-RTT, and the negotiated peer identity.
+        use super::*;
+
+        /// `keys_ready` should resolve for a freshly handed out [`QuicMuxer`] on
+        /// both sides of the handshake: a [`QuicMuxer`] is only ever constructed
+        /// once [`Upgrade`] has already observed `quinn_proto`'s `Connected`
+        /// event, which only fires once the handshake is confirmed, not merely
+        /// complete, so there's no window after `upgrade.await` resolves during
+        /// which `keys_ready` could still be pending.
+        #[test]
+        fn keys_ready_resolves_once_the_handshake_is_confirmed() {
+            env_logger::try_init().ok();
+
+            async fn listener(
+                addr: Multiaddr,
+                mut ready_tx: futures::channel::mpsc::Sender<Multiaddr>,
+            ) {
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+                let mut listener = transport.listen_on(addr).unwrap();
+                loop {
+                    match listener.next().await.unwrap().unwrap() {
+                        ListenerEvent::NewAddress(listen_addr) => {
+                            ready_tx.send(listen_addr).await.unwrap();
+                        }
+                        ListenerEvent::Upgrade { upgrade, .. } => {
+                            let (_, muxer) = upgrade.await.unwrap();
+                            muxer.keys_ready().await;
+                            return;
+                        }
+                        ListenerEvent::Error(e) => panic!("Unexpected listener error: {}", e),
+                        ListenerEvent::AddressExpired(_) => {}
+                    }
+                }
+            }
+
+            async fn dialer(mut ready_rx: futures::channel::mpsc::Receiver<Multiaddr>) {
+                let addr = ready_rx.next().await.unwrap();
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+                let (_, muxer) = transport.dial(addr).unwrap().await.unwrap();
+                muxer.keys_ready().await;
+                drive_in_background(Arc::new(muxer));
+            }
+
+            let addr: Multiaddr = "/ip4/127.0.0.1/udp/0/quic".parse().unwrap();
+            let (ready_tx, ready_rx) = futures::channel::mpsc::channel(1);
+            let listener = async_std::task::spawn(listener(addr, ready_tx));
+            async_std::task::block_on(dialer(ready_rx));
+            async_std::task::block_on(listener);
+        }
+
+        /// This transport doesn't cache session tickets yet, so it never
+        /// actually attempts 0-RTT; `early_data_rejected` should accordingly
+        /// read `false` for a normal handshake rather than, say, panicking or
+        /// reporting a rejection that never happened.
+        #[test]
+        fn early_data_rejected_is_false_without_0rtt_support() {
+            env_logger::try_init().ok();
+
+            async fn listener(
+                addr: Multiaddr,
+                mut ready_tx: futures::channel::mpsc::Sender<Multiaddr>,
+            ) {
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+                let mut listener = transport.listen_on(addr).unwrap();
+                loop {
+                    match listener.next().await.unwrap().unwrap() {
+                        ListenerEvent::NewAddress(listen_addr) => {
+                            ready_tx.send(listen_addr).await.unwrap();
+                        }
+                        ListenerEvent::Upgrade { upgrade, .. } => {
+                            let (_, muxer) = upgrade.await.unwrap();
+                            assert!(!muxer.early_data_rejected());
+                            return;
+                        }
+                        ListenerEvent::Error(e) => panic!("Unexpected listener error: {}", e),
+                        ListenerEvent::AddressExpired(_) => {}
+                    }
+                }
+            }
+
+            async fn dialer(mut ready_rx: futures::channel::mpsc::Receiver<Multiaddr>) {
+                let addr = ready_rx.next().await.unwrap();
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+                let (_, muxer) = transport.dial(addr).unwrap().await.unwrap();
+                assert!(!muxer.early_data_rejected());
+                drive_in_background(Arc::new(muxer));
+            }
+
+            let addr: Multiaddr = "/ip4/127.0.0.1/udp/0/quic".parse().unwrap();
+            let (ready_tx, ready_rx) = futures::channel::mpsc::channel(1);
+            let listener = async_std::task::spawn(listener(addr, ready_tx));
+            async_std::task::block_on(dialer(ready_rx));
+            async_std::task::block_on(listener);
+        }
+
+        /// Round-trips [`QuicTransport::export_session_tickets`] and
+        /// [`QuicTransport::import_session_tickets`] through a brand new
+        /// [`QuicTransport`] standing in for the dialer after a process
+        /// restart, and confirms the second dial actually resumes via 0-RTT
+        /// rather than merely not erroring out.
+        #[test]
+        fn imported_session_tickets_let_a_second_dial_resume_with_0rtt() {
+            env_logger::try_init().ok();
+
+            async fn listener(
+                addr: Multiaddr,
+                mut ready_tx: futures::channel::mpsc::Sender<Multiaddr>,
+            ) {
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+                let mut listener = transport.listen_on(addr).unwrap();
+                let mut accepted = 0;
+                loop {
+                    match listener.next().await.unwrap().unwrap() {
+                        ListenerEvent::NewAddress(listen_addr) => {
+                            ready_tx.send(listen_addr).await.unwrap();
+                        }
+                        ListenerEvent::Upgrade { upgrade, .. } => {
+                            let (_, muxer) = upgrade.await.unwrap();
+                            drive_in_background(Arc::new(muxer));
+                            accepted += 1;
+                            if accepted == 2 {
+                                return;
+                            }
+                        }
+                        ListenerEvent::Error(e) => panic!("Unexpected listener error: {}", e),
+                        ListenerEvent::AddressExpired(_) => {}
+                    }
+                }
+            }
+
+            async fn dialer(mut ready_rx: futures::channel::mpsc::Receiver<Multiaddr>) {
+                let addr = ready_rx.next().await.unwrap();
+                let keypair = Keypair::generate_ed25519();
+
+                // First dial earns a session ticket; there's nothing to resume
+                // yet, so it falls back to a full handshake as usual.
+                let first_transport = QuicTransport::new(Config::new(&keypair));
+                let (_, first) = first_transport
+                    .clone()
+                    .dial(addr.clone())
+                    .unwrap()
+                    .await
+                    .unwrap();
+                assert!(!first.early_data_rejected());
+                drive_in_background(Arc::new(first));
+
+                // The server's post-handshake `NewSessionTicket` arrives
+                // asynchronously - `drive_in_background`'s background task
+                // keeps polling for it - so give it a generous window to land
+                // in the store before checking, the same as every other
+                // timing-dependent wait in this file. `rustls` also caches a
+                // much smaller key-exchange-group hint under its own key as
+                // soon as the handshake starts, so a non-empty store alone
+                // isn't a reliable signal that the actual ticket has arrived
+                // yet - a fixed wait avoids racing that.
+                async_std::task::sleep(Duration::from_millis(300)).await;
+
+                // Stand in for a process restart: a fresh `QuicTransport` that
+                // never dialled this peer before, seeded only with the
+                // exported ticket.
+                let exported = first_transport.export_session_tickets();
+                assert!(
+                    !exported.is_empty(),
+                    "no session ticket was cached within the deadline"
+                );
+                let second_transport = QuicTransport::new(Config::new(&keypair));
+                second_transport.import_session_tickets(&exported);
+                let (_, second) = second_transport.dial(addr).unwrap().await.unwrap();
+                assert!(
+                    second.early_data_accepted(),
+                    "imported ticket should have let the second dial resume via 0-RTT"
+                );
+                drive_in_background(Arc::new(second));
+            }
+
+            let addr: Multiaddr = "/ip4/127.0.0.1/udp/0/quic".parse().unwrap();
+            let (ready_tx, ready_rx) = futures::channel::mpsc::channel(1);
+            let listener = async_std::task::spawn(listener(addr, ready_tx));
+            async_std::task::block_on(dialer(ready_rx));
+            async_std::task::block_on(listener);
+        }
+
+        /// `Upgrade` only ever hands out a [`QuicMuxer`] once it has already
+        /// observed `quinn_proto`'s `Event::Connected`, so by the time a server
+        /// can reach this accessor the full handshake - including the client's
+        /// `Finished` - is already done; `half_rtt_write_available` should
+        /// accordingly read `false` rather than claiming a 0.5-RTT window that
+        /// was never actually reachable.
+        #[test]
+        fn half_rtt_write_available_is_false_without_a_pre_handshake_handle() {
+            env_logger::try_init().ok();
+
+            async fn listener(
+                addr: Multiaddr,
+                mut ready_tx: futures::channel::mpsc::Sender<Multiaddr>,
+            ) {
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+                let mut listener = transport.listen_on(addr).unwrap();
+                loop {
+                    match listener.next().await.unwrap().unwrap() {
+                        ListenerEvent::NewAddress(listen_addr) => {
+                            ready_tx.send(listen_addr).await.unwrap();
+                        }
+                        ListenerEvent::Upgrade { upgrade, .. } => {
+                            let (_, muxer) = upgrade.await.unwrap();
+                            assert!(!muxer.half_rtt_write_available());
+                            return;
+                        }
+                        ListenerEvent::Error(e) => panic!("Unexpected listener error: {}", e),
+                        ListenerEvent::AddressExpired(_) => {}
+                    }
+                }
+            }
+
+            async fn dialer(mut ready_rx: futures::channel::mpsc::Receiver<Multiaddr>) {
+                let addr = ready_rx.next().await.unwrap();
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+                let (_, muxer) = transport.dial(addr).unwrap().await.unwrap();
+                assert!(!muxer.half_rtt_write_available());
+                drive_in_background(Arc::new(muxer));
+            }
+
+            let addr: Multiaddr = "/ip4/127.0.0.1/udp/0/quic".parse().unwrap();
+            let (ready_tx, ready_rx) = futures::channel::mpsc::channel(1);
+            let listener = async_std::task::spawn(listener(addr, ready_tx));
+            async_std::task::block_on(dialer(ready_rx));
+            async_std::task::block_on(listener);
+        }
+
+        /// Restricting [`Config::set_cipher_suites`] to a single suite doesn't
+        /// stop two endpoints configured identically from completing a
+        /// handshake - they still have that one suite in common.
+        #[test]
+        fn set_cipher_suites_to_a_single_suite_still_completes_a_handshake() {
+            env_logger::try_init().ok();
+
+            fn config() -> Config {
+                Config::new(&Keypair::generate_ed25519())
+                    .set_cipher_suites(vec![CipherSuite::Aes128GcmSha256])
+                    .unwrap()
+            }
+
+            async fn listener(
+                addr: Multiaddr,
+                mut ready_tx: futures::channel::mpsc::Sender<Multiaddr>,
+            ) {
+                let transport = QuicTransport::new(config());
+                let mut listener = transport.listen_on(addr).unwrap();
+                loop {
+                    match listener.next().await.unwrap().unwrap() {
+                        ListenerEvent::NewAddress(listen_addr) => {
+                            ready_tx.send(listen_addr).await.unwrap();
+                        }
+                        ListenerEvent::Upgrade { upgrade, .. } => {
+                            let (_, muxer) = upgrade.await.unwrap();
+                            drive_in_background(Arc::new(muxer));
+                            return;
+                        }
+                        ListenerEvent::Error(e) => panic!("Unexpected listener error: {}", e),
+                        ListenerEvent::AddressExpired(_) => {}
+                    }
+                }
+            }
+
+            async fn dialer(mut ready_rx: futures::channel::mpsc::Receiver<Multiaddr>) {
+                let addr = ready_rx.next().await.unwrap();
+                let transport = QuicTransport::new(config());
+                let (_, muxer) = transport.dial(addr).unwrap().await.unwrap();
+                drive_in_background(Arc::new(muxer));
+            }
+
+            let addr: Multiaddr = "/ip4/127.0.0.1/udp/0/quic".parse().unwrap();
+            let (ready_tx, ready_rx) = futures::channel::mpsc::channel(1);
+            let listener = async_std::task::spawn(listener(addr, ready_tx));
+            async_std::task::block_on(dialer(ready_rx));
+            async_std::task::block_on(listener);
+        }
+
+        /// [`Config::set_cipher_suites`] and [`Config::set_kx_groups`] reject an
+        /// empty selection outright, without waiting for a handshake attempt to
+        /// fail.
+        #[test]
+        fn set_cipher_suites_and_set_kx_groups_reject_an_empty_selection() {
+            assert!(matches!(
+                Config::new(&Keypair::generate_ed25519()).set_cipher_suites(vec![]),
+                Err(Error::InvalidTlsConfig(_))
+            ));
+            assert!(matches!(
+                Config::new(&Keypair::generate_ed25519()).set_kx_groups(vec![]),
+                Err(Error::InvalidTlsConfig(_))
+            ));
+        }
+
+        /// [`QuicMuxer::negotiated_crypto`] reports back a cipher suite both
+        /// ends actually allow - here restricted with [`Config::set_cipher_suites`]
+        /// to a single suite, so both the dialer's and the listener's view of it
+        /// must also be that same suite, not merely one of the three this crate
+        /// could otherwise negotiate.
+        #[test]
+        fn negotiated_crypto_reports_a_cipher_suite_both_ends_allowed() {
+            env_logger::try_init().ok();
+
+            fn config() -> Config {
+                Config::new(&Keypair::generate_ed25519())
+                    .set_cipher_suites(vec![CipherSuite::Chacha20Poly1305Sha256])
+                    .unwrap()
+            }
+
+            async fn listener(
+                addr: Multiaddr,
+                mut ready_tx: futures::channel::mpsc::Sender<Multiaddr>,
+            ) {
+                let transport = QuicTransport::new(config());
+                let mut listener = transport.listen_on(addr).unwrap();
+                loop {
+                    match listener.next().await.unwrap().unwrap() {
+                        ListenerEvent::NewAddress(listen_addr) => {
+                            ready_tx.send(listen_addr).await.unwrap();
+                        }
+                        ListenerEvent::Upgrade { upgrade, .. } => {
+                            let (_, muxer) = upgrade.await.unwrap();
+                            assert_eq!(
+                                muxer.negotiated_crypto().unwrap().cipher_suite,
+                                CipherSuite::Chacha20Poly1305Sha256
+                            );
+                            drive_in_background(Arc::new(muxer));
+                            return;
+                        }
+                        ListenerEvent::Error(e) => panic!("Unexpected listener error: {}", e),
+                        ListenerEvent::AddressExpired(_) => {}
+                    }
+                }
+            }
+
+            async fn dialer(mut ready_rx: futures::channel::mpsc::Receiver<Multiaddr>) {
+                let addr = ready_rx.next().await.unwrap();
+                let transport = QuicTransport::new(config());
+                let (_, muxer) = transport.dial(addr).unwrap().await.unwrap();
+                assert_eq!(
+                    muxer.negotiated_crypto().unwrap().cipher_suite,
+                    CipherSuite::Chacha20Poly1305Sha256
+                );
+                drive_in_background(Arc::new(muxer));
+            }
+
+            let addr: Multiaddr = "/ip4/127.0.0.1/udp/0/quic".parse().unwrap();
+            let (ready_tx, ready_rx) = futures::channel::mpsc::channel(1);
+            let listener = async_std::task::spawn(listener(addr, ready_tx));
+            async_std::task::block_on(dialer(ready_rx));
+            async_std::task::block_on(listener);
+        }
+
+        /// Exercises [`QuicMuxer::peer_certificates`]: the listener reads back
+        /// the dialler's self-signed certificate chain and verifies the leaf
+        /// certificate embeds the dialler's own public key, the same way
+        /// [`crate::certificate::extract_peer_id`] does internally to recover
+        /// the [`PeerId`] during the handshake.
+        #[test]
+        fn peer_certificates_embed_the_remote_libp2p_public_key() {
+            env_logger::try_init().ok();
+
+            async fn listener(
+                addr: Multiaddr,
+                mut ready_tx: futures::channel::mpsc::Sender<Multiaddr>,
+            ) -> PeerId {
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+                let mut listener = transport.listen_on(addr).unwrap();
+                loop {
+                    match listener.next().await.unwrap().unwrap() {
+                        ListenerEvent::NewAddress(listen_addr) => {
+                            ready_tx.send(listen_addr).await.unwrap();
+                        }
+                        ListenerEvent::Upgrade { upgrade, .. } => {
+                            let (_, muxer) = upgrade.await.unwrap();
+                            let chain = muxer.peer_certificates().expect(
+                                "a completed handshake should have a certificate chain available",
+                            );
+                            let leaf = chain.first().expect("the chain should not be empty");
+                            let peer_id = crate::certificate::extract_peer_id(leaf).unwrap();
+                            drive_in_background(Arc::new(muxer));
+                            return peer_id;
+                        }
+                        ListenerEvent::Error(e) => panic!("Unexpected listener error: {}", e),
+                        ListenerEvent::AddressExpired(_) => {}
+                    }
+                }
+            }
+
+            async fn dialer(
+                mut ready_rx: futures::channel::mpsc::Receiver<Multiaddr>,
+                keypair: Keypair,
+            ) {
+                let addr = ready_rx.next().await.unwrap();
+                let transport = QuicTransport::new(Config::new(&keypair));
+                let (_, muxer) = transport.dial(addr).unwrap().await.unwrap();
+                assert!(
+                    muxer.peer_certificates().is_some(),
+                    "the dialler should also be able to read the listener's chain back"
+                );
+                drive_in_background(Arc::new(muxer));
+            }
+
+            let keypair = Keypair::generate_ed25519();
+            let addr: Multiaddr = "/ip4/127.0.0.1/udp/0/quic".parse().unwrap();
+            let (ready_tx, ready_rx) = futures::channel::mpsc::channel(1);
+            let listener = async_std::task::spawn(listener(addr, ready_tx));
+            async_std::task::block_on(dialer(ready_rx, keypair.clone()));
+            let observed_peer_id = async_std::task::block_on(listener);
+
+            assert_eq!(observed_peer_id, PeerId::from(keypair.public()));
+        }
+    }
+
+    mod streams {
+        //! Substreams opened over an established connection: bidirectional/unidirectional transfer, flow control, and scheduling.
+        use super::*;
+
+        /// Exercises [`QuicMuxer::open_bi`] end to end: the dialler opens a
+        /// bidirectional stream and writes on it, the listener receives it
+        /// through the usual [`StreamMuxerEvent::InboundSubstream`] and pairs it
+        /// back up into typed [`SendStream`]/[`RecvStream`] halves to reply on,
+        /// and the dialler reads the reply off the same stream it opened.
+        #[test]
+        fn open_bi_transfers_data_in_both_directions() {
+            env_logger::try_init().ok();
+
+            async fn listener(
+                addr: Multiaddr,
+                mut ready_tx: futures::channel::mpsc::Sender<Multiaddr>,
+            ) {
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+                let mut listener = transport.listen_on(addr).unwrap();
+                let muxer = loop {
+                    match listener.next().await.unwrap().unwrap() {
+                        ListenerEvent::NewAddress(listen_addr) => {
+                            ready_tx.send(listen_addr).await.unwrap();
+                        }
+                        ListenerEvent::Upgrade { upgrade, .. } => {
+                            let (_, muxer) = upgrade.await.unwrap();
+                            break Arc::new(muxer);
+                        }
+                        ListenerEvent::Error(e) => panic!("Unexpected listener error: {}", e),
+                        ListenerEvent::AddressExpired(_) => {}
+                    }
+                };
+
+                let id = match future::poll_fn(|cx| muxer.poll_event(cx)).await.unwrap() {
+                    StreamMuxerEvent::InboundSubstream(substream) => substream.id,
+                    StreamMuxerEvent::AddressChange(_) => {
+                        panic!("did not expect an address change")
+                    }
+                };
+                drive_in_background(muxer.clone());
+
+                let mut recv = RecvStream::new(&muxer, id);
+                let mut received = Vec::new();
+                recv.read_to_end(&mut received).await.unwrap();
+                assert_eq!(received, b"ping");
+
+                let mut send = SendStream::new(&muxer, id);
+                send.write_all(b"pong").await.unwrap();
+                send.close().await.unwrap();
+            }
+
+            async fn dialer(mut ready_rx: futures::channel::mpsc::Receiver<Multiaddr>) {
+                let addr = ready_rx.next().await.unwrap();
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+                let (_, muxer) = transport.dial(addr).unwrap().await.unwrap();
+                let muxer = Arc::new(muxer);
+
+                let mut stream = muxer.open_bi().unwrap();
+                stream.send.write_all(b"ping").await.unwrap();
+                stream.send.close().await.unwrap();
+
+                let mut received = Vec::new();
+                stream.recv.read_to_end(&mut received).await.unwrap();
+                assert_eq!(received, b"pong");
+
+                drop(stream);
+                drive_in_background(muxer);
+            }
+
+            let addr: Multiaddr = "/ip4/127.0.0.1/udp/0/quic".parse().unwrap();
+            let (ready_tx, ready_rx) = futures::channel::mpsc::channel(1);
+            let listener = async_std::task::spawn(listener(addr, ready_tx));
+            async_std::task::block_on(dialer(ready_rx));
+            async_std::task::block_on(listener);
+        }
+
+        /// Exercises [`SendStream::write_all_and_finish`]: the dialler writes its
+        /// whole request in one call instead of a separate `write_all` followed
+        /// by `close`, and the listener should still see the full buffer
+        /// followed by EOF, exactly as if the two calls had been made
+        /// separately.
+        #[test]
+        fn write_all_and_finish_delivers_the_buffer_then_eof() {
+            env_logger::try_init().ok();
+
+            const PAYLOAD: &[u8] = b"the quick brown fox jumps over the lazy dog";
+
+            async fn listener(
+                addr: Multiaddr,
+                mut ready_tx: futures::channel::mpsc::Sender<Multiaddr>,
+            ) {
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+                let mut listener = transport.listen_on(addr).unwrap();
+                let muxer = loop {
+                    match listener.next().await.unwrap().unwrap() {
+                        ListenerEvent::NewAddress(listen_addr) => {
+                            ready_tx.send(listen_addr).await.unwrap();
+                        }
+                        ListenerEvent::Upgrade { upgrade, .. } => {
+                            let (_, muxer) = upgrade.await.unwrap();
+                            break Arc::new(muxer);
+                        }
+                        ListenerEvent::Error(e) => panic!("Unexpected listener error: {}", e),
+                        ListenerEvent::AddressExpired(_) => {}
+                    }
+                };
+
+                let mut recv = future::poll_fn(|cx| muxer.poll_accept_uni(cx))
+                    .await
+                    .unwrap();
+                let mut received = Vec::new();
+                recv.read_to_end(&mut received).await.unwrap();
+                assert_eq!(received, PAYLOAD);
+
+                drop(recv);
+                drive_in_background(muxer);
+            }
+
+            async fn dialer(mut ready_rx: futures::channel::mpsc::Receiver<Multiaddr>) {
+                let addr = ready_rx.next().await.unwrap();
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+                let (_, muxer) = transport.dial(addr).unwrap().await.unwrap();
+                let muxer = Arc::new(muxer);
+
+                let mut send = muxer.open_uni().unwrap();
+                send.write_all_and_finish(PAYLOAD).await.unwrap();
+
+                drive_in_background(muxer);
+            }
+
+            let addr: Multiaddr = "/ip4/127.0.0.1/udp/0/quic".parse().unwrap();
+            let (ready_tx, ready_rx) = futures::channel::mpsc::channel(1);
+            let listener = async_std::task::spawn(listener(addr, ready_tx));
+            async_std::task::block_on(dialer(ready_rx));
+            async_std::task::block_on(listener);
+        }
+
+        /// Exercises [`RecvStream::poll_read_chunk`]: sends a payload larger
+        /// than any single `quinn_proto` chunk is likely to hold, reads it back
+        /// chunk-by-chunk instead of through [`AsyncReadExt::read_to_end`], and
+        /// confirms concatenating the chunks in the order they were polled
+        /// reassembles the original payload exactly, with no chunk empty (other
+        /// than the final `None` signalling EOF).
+        #[test]
+        fn poll_read_chunk_reassembles_a_large_payload() {
+            env_logger::try_init().ok();
+
+            const PAYLOAD_LEN: usize = 256 * 1024;
+
+            async fn listener(
+                addr: Multiaddr,
+                mut ready_tx: futures::channel::mpsc::Sender<Multiaddr>,
+            ) {
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+                let mut listener = transport.listen_on(addr).unwrap();
+                let muxer = loop {
+                    match listener.next().await.unwrap().unwrap() {
+                        ListenerEvent::NewAddress(listen_addr) => {
+                            ready_tx.send(listen_addr).await.unwrap();
+                        }
+                        ListenerEvent::Upgrade { upgrade, .. } => {
+                            let (_, muxer) = upgrade.await.unwrap();
+                            break Arc::new(muxer);
+                        }
+                        ListenerEvent::Error(e) => panic!("Unexpected listener error: {}", e),
+                        ListenerEvent::AddressExpired(_) => {}
+                    }
+                };
+
+                let id = match future::poll_fn(|cx| muxer.poll_event(cx)).await.unwrap() {
+                    StreamMuxerEvent::InboundSubstream(substream) => substream.id,
+                    StreamMuxerEvent::AddressChange(_) => {
+                        panic!("did not expect an address change")
+                    }
+                };
+                drive_in_background(muxer.clone());
+
+                let mut recv = RecvStream::new(&muxer, id);
+                let mut reassembled = Vec::with_capacity(PAYLOAD_LEN);
+                while let Some(chunk) = future::poll_fn(|cx| recv.poll_read_chunk(cx))
+                    .await
+                    .unwrap()
+                {
+                    assert!(
+                        !chunk.is_empty(),
+                        "poll_read_chunk should never yield an empty chunk before EOF"
+                    );
+                    reassembled.extend_from_slice(&chunk);
+                }
+                assert_eq!(reassembled.len(), PAYLOAD_LEN);
+                assert_eq!(reassembled, vec![0x5au8; PAYLOAD_LEN]);
+            }
+
+            async fn dialer(mut ready_rx: futures::channel::mpsc::Receiver<Multiaddr>) {
+                let addr = ready_rx.next().await.unwrap();
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+                let (_, muxer) = transport.dial(addr).unwrap().await.unwrap();
+                let muxer = Arc::new(muxer);
+
+                let mut stream = muxer.open_bi().unwrap();
+                stream
+                    .send
+                    .write_all(&vec![0x5au8; PAYLOAD_LEN])
+                    .await
+                    .unwrap();
+                stream.send.close().await.unwrap();
+
+                drop(stream);
+                drive_in_background(muxer);
+            }
+
+            let addr: Multiaddr = "/ip4/127.0.0.1/udp/0/quic".parse().unwrap();
+            let (ready_tx, ready_rx) = futures::channel::mpsc::channel(1);
+            let listener = async_std::task::spawn(listener(addr, ready_tx));
+            async_std::task::block_on(dialer(ready_rx));
+            async_std::task::block_on(listener);
+        }
+
+        /// Opens a large-payload stream and a tiny-payload stream at the same
+        /// [`QuicMuxer::open_bi_with_priority`] priority, both started together,
+        /// and confirms the tiny one's transfer completes while the large one is
+        /// still only partway through. Under
+        /// [`StreamScheduler::RoundRobin`] - `quinn_proto`'s only actual
+        /// behavior, see [`StreamScheduler`] - streams at the same priority take
+        /// turns being packed into outgoing packets, so the tiny stream isn't
+        /// stuck behind the large one draining first; a scheduler that instead
+        /// served one stream to exhaustion before touching the next would make
+        /// this fail.
+        #[test]
+        fn round_robin_interleaves_equal_priority_streams_so_neither_starves() {
+            env_logger::try_init().ok();
+
+            const BIG_PAYLOAD: &[u8] = &[0xAAu8; 4 * 1024 * 1024];
+            const SMALL_PAYLOAD: &[u8] = b"small stream payload";
+
+            async fn listener(
+                addr: Multiaddr,
+                mut ready_tx: futures::channel::mpsc::Sender<Multiaddr>,
+            ) {
+                let transport = QuicTransport::new(
+                    Config::new(&Keypair::generate_ed25519())
+                        .stream_scheduler(StreamScheduler::RoundRobin),
+                );
+                let mut listener = transport.listen_on(addr).unwrap();
+                let muxer = loop {
+                    match listener.next().await.unwrap().unwrap() {
+                        ListenerEvent::NewAddress(listen_addr) => {
+                            ready_tx.send(listen_addr).await.unwrap();
+                        }
+                        ListenerEvent::Upgrade { upgrade, .. } => {
+                            let (_, muxer) = upgrade.await.unwrap();
+                            break Arc::new(muxer);
+                        }
+                        ListenerEvent::Error(e) => panic!("Unexpected listener error: {}", e),
+                        ListenerEvent::AddressExpired(_) => {}
+                    }
+                };
+
+                // The dialer opens the big stream first, so quinn_proto assigns
+                // it the lower stream ID and `poll_event` reports inbound
+                // substreams in ID order regardless of which one's data arrives
+                // first.
+                let big_id = match future::poll_fn(|cx| muxer.poll_event(cx)).await.unwrap() {
+                    StreamMuxerEvent::InboundSubstream(substream) => substream.id,
+                    StreamMuxerEvent::AddressChange(_) => {
+                        panic!("did not expect an address change")
+                    }
+                };
+                let small_id = match future::poll_fn(|cx| muxer.poll_event(cx)).await.unwrap() {
+                    StreamMuxerEvent::InboundSubstream(substream) => substream.id,
+                    StreamMuxerEvent::AddressChange(_) => {
+                        panic!("did not expect an address change")
+                    }
+                };
+
+                // Reads `recv` to completion in small chunks, tracking bytes
+                // read so far in `progress` as it goes - unlike `read_to_end`,
+                // which would hold the output buffer borrowed for as long as the
+                // future exists, making its length unobservable from outside
+                // while racing it against another future below.
+                async fn read_tracking_progress(
+                    mut recv: RecvStream<'_>,
+                    progress: &std::sync::atomic::AtomicUsize,
+                ) -> Result<Vec<u8>, std::io::Error> {
+                    let mut received = Vec::new();
+                    let mut chunk = vec![0u8; 64 * 1024];
+                    loop {
+                        let n = recv.read(&mut chunk).await?;
+                        if n == 0 {
+                            return Ok(received);
+                        }
+                        received.extend_from_slice(&chunk[..n]);
+                        progress.store(received.len(), std::sync::atomic::Ordering::Relaxed);
+                    }
+                }
+
+                let big_received = {
+                    let big_progress = std::sync::atomic::AtomicUsize::new(0);
+                    let mut small_recv = RecvStream::new(&muxer, small_id);
+                    let mut small_received = Vec::new();
+                    let big_done =
+                        read_tracking_progress(RecvStream::new(&muxer, big_id), &big_progress);
+                    let small_done = small_recv.read_to_end(&mut small_received);
+                    futures::pin_mut!(big_done);
+                    futures::pin_mut!(small_done);
+
+                    match future::select(big_done, small_done).await {
+                        future::Either::Left(_) => panic!(
+                            "the large stream's transfer finished before the small one's; the \
+                             scheduler let it run to completion uninterrupted instead of \
+                             interleaving the two"
+                        ),
+                        future::Either::Right((small_result, big_done)) => {
+                            small_result.unwrap();
+                            assert_eq!(small_received, SMALL_PAYLOAD);
+                            assert!(
+                                big_progress.load(std::sync::atomic::Ordering::Relaxed)
+                                    < BIG_PAYLOAD.len(),
+                                "the large stream should still be incomplete when the small one \
+                                 finishes"
+                            );
+                            big_done.await.unwrap()
+                        }
+                    }
+                };
+                assert_eq!(big_received, BIG_PAYLOAD);
+
+                drive_in_background(muxer);
+            }
+
+            async fn dialer(mut ready_rx: futures::channel::mpsc::Receiver<Multiaddr>) {
+                let addr = ready_rx.next().await.unwrap();
+                let transport = QuicTransport::new(
+                    Config::new(&Keypair::generate_ed25519())
+                        .stream_scheduler(StreamScheduler::RoundRobin),
+                );
+                let (_, muxer) = transport.dial(addr).unwrap().await.unwrap();
+                let muxer = Arc::new(muxer);
+
+                let mut big = muxer.open_bi_with_priority(5).unwrap();
+                let mut small = muxer.open_bi_with_priority(5).unwrap();
+
+                let big_write = async {
+                    big.send.write_all(BIG_PAYLOAD).await.unwrap();
+                    big.send.close().await.unwrap();
+                };
+                let small_write = async {
+                    small.send.write_all(SMALL_PAYLOAD).await.unwrap();
+                    small.send.close().await.unwrap();
+                };
+                future::join(big_write, small_write).await;
+
+                drop(big);
+                drop(small);
+                drive_in_background(muxer);
+            }
+
+            let addr: Multiaddr = "/ip4/127.0.0.1/udp/0/quic".parse().unwrap();
+            let (ready_tx, ready_rx) = futures::channel::mpsc::channel(1);
+            let listener_task = async_std::task::spawn(listener(addr, ready_tx));
+            async_std::task::block_on(dialer(ready_rx));
+            async_std::task::block_on(listener_task);
+        }
+
+        /// Closing a [`SendStream`] only half-closes the stream: the peer reads
+        /// EOF on its own receive side, but can still write back on the same
+        /// (bidirectional) stream and have the original opener read that reply.
+        /// Exercises [`QuicMuxer::shutdown_substream`]/[`SendStream::close`] to
+        /// settle that they send a `FIN`, not a reset, since a reset would also
+        /// tear down the peer's ability to reply.
+        #[test]
+        fn closing_the_send_side_half_closes_rather_than_resets_the_stream() {
+            env_logger::try_init().ok();
+
+            async fn listener(
+                addr: Multiaddr,
+                mut ready_tx: futures::channel::mpsc::Sender<Multiaddr>,
+            ) {
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+                let mut listener = transport.listen_on(addr).unwrap();
+                let muxer = loop {
+                    match listener.next().await.unwrap().unwrap() {
+                        ListenerEvent::NewAddress(listen_addr) => {
+                            ready_tx.send(listen_addr).await.unwrap();
+                        }
+                        ListenerEvent::Upgrade { upgrade, .. } => {
+                            let (_, muxer) = upgrade.await.unwrap();
+                            break Arc::new(muxer);
+                        }
+                        ListenerEvent::Error(e) => panic!("Unexpected listener error: {}", e),
+                        ListenerEvent::AddressExpired(_) => {}
+                    }
+                };
+
+                let id = match future::poll_fn(|cx| muxer.poll_event(cx)).await.unwrap() {
+                    StreamMuxerEvent::InboundSubstream(substream) => substream.id,
+                    StreamMuxerEvent::AddressChange(_) => {
+                        panic!("did not expect an address change")
+                    }
+                };
+                drive_in_background(muxer.clone());
+
+                // The dialler already closed its send side by the time "ping" is
+                // fully read; a zero-byte `read` (rather than `read_to_end`,
+                // which can't tell EOF apart from "not done yet") confirms this
+                // is a clean half-close and not, say, a stream left dangling.
+                let mut recv = RecvStream::new(&muxer, id);
+                let mut received = vec![0u8; 4];
+                recv.read_exact(&mut received).await.unwrap();
+                assert_eq!(&received, b"ping");
+                let n = recv.read(&mut [0u8; 1]).await.unwrap();
+                assert_eq!(n, 0, "peer's FIN should surface as an immediate EOF");
+
+                // A reset would have torn down this side's ability to reply too;
+                // a half-close leaves it intact.
+                let mut send = SendStream::new(&muxer, id);
+                send.write_all(b"pong").await.unwrap();
+                send.close().await.unwrap();
+            }
+
+            async fn dialer(mut ready_rx: futures::channel::mpsc::Receiver<Multiaddr>) {
+                let addr = ready_rx.next().await.unwrap();
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+                let (_, muxer) = transport.dial(addr).unwrap().await.unwrap();
+                let muxer = Arc::new(muxer);
+
+                let mut stream = muxer.open_bi().unwrap();
+                stream.send.write_all(b"ping").await.unwrap();
+                stream.send.close().await.unwrap();
+
+                let mut received = Vec::new();
+                stream.recv.read_to_end(&mut received).await.unwrap();
+                assert_eq!(received, b"pong");
+
+                drop(stream);
+                drive_in_background(muxer);
+            }
+
+            let addr: Multiaddr = "/ip4/127.0.0.1/udp/0/quic".parse().unwrap();
+            let (ready_tx, ready_rx) = futures::channel::mpsc::channel(1);
+            let listener = async_std::task::spawn(listener(addr, ready_tx));
+            async_std::task::block_on(dialer(ready_rx));
+            async_std::task::block_on(listener);
+        }
+
+        /// Exercises [`QuicMuxer::open_uni`]/[`QuicMuxer::poll_accept_uni`] end
+        /// to end: the dialler opens a unidirectional stream and writes on it,
+        /// and the listener accepts the peer's read-only half and reads the
+        /// data back off it.
+        ///
+        /// Unlike [`open_bi_transfers_data_in_both_directions`], there is no
+        /// reply: `open_uni`'s [`SendStream`] has no [`AsyncRead`] impl at all,
+        /// since `quinn_proto` itself treats reading a self-opened
+        /// unidirectional stream as a programmer error (it panics rather than
+        /// returning a `Result`). That is enforced at compile time by the type
+        /// returned from `open_uni`, so there is nothing to assert on at
+        /// runtime: a `SendStream` simply has no `poll_read` to call.
+        #[test]
+        fn open_uni_transfers_data_to_accepted_recv_stream() {
+            env_logger::try_init().ok();
+
+            async fn listener(
+                addr: Multiaddr,
+                mut ready_tx: futures::channel::mpsc::Sender<Multiaddr>,
+            ) {
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+                let mut listener = transport.listen_on(addr).unwrap();
+                let muxer = loop {
+                    match listener.next().await.unwrap().unwrap() {
+                        ListenerEvent::NewAddress(listen_addr) => {
+                            ready_tx.send(listen_addr).await.unwrap();
+                        }
+                        ListenerEvent::Upgrade { upgrade, .. } => {
+                            let (_, muxer) = upgrade.await.unwrap();
+                            break Arc::new(muxer);
+                        }
+                        ListenerEvent::Error(e) => panic!("Unexpected listener error: {}", e),
+                        ListenerEvent::AddressExpired(_) => {}
+                    }
+                };
+
+                let mut recv = future::poll_fn(|cx| muxer.poll_accept_uni(cx))
+                    .await
+                    .unwrap();
+                let mut received = Vec::new();
+                recv.read_to_end(&mut received).await.unwrap();
+                assert_eq!(received, b"hello, uni");
+
+                drop(recv);
+                drive_in_background(muxer);
+            }
+
+            async fn dialer(mut ready_rx: futures::channel::mpsc::Receiver<Multiaddr>) {
+                let addr = ready_rx.next().await.unwrap();
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+                let (_, muxer) = transport.dial(addr).unwrap().await.unwrap();
+                let muxer = Arc::new(muxer);
+
+                let mut send = muxer.open_uni().unwrap();
+                send.write_all(b"hello, uni").await.unwrap();
+                send.close().await.unwrap();
+
+                drive_in_background(muxer);
+            }
+
+            let addr: Multiaddr = "/ip4/127.0.0.1/udp/0/quic".parse().unwrap();
+            let (ready_tx, ready_rx) = futures::channel::mpsc::channel(1);
+            let listener = async_std::task::spawn(listener(addr, ready_tx));
+            async_std::task::block_on(dialer(ready_rx));
+            async_std::task::block_on(listener);
+        }
+
+        /// [`RecvStream::buffered`] is currently a documented no-op - see its
+        /// doc comment for why `quinn_proto` 0.7.3 leaves this crate no way to
+        /// observe a stream's unread byte count without consuming it - so this
+        /// confirms the actual, limited behavior: it reports `0` regardless of
+        /// how much unread data has piled up on the stream, and once the stream
+        /// has been read to completion and `quinn_proto` frees it, it reports
+        /// [`Error::Stream`] instead of a stale `0`.
+        #[test]
+        fn buffered_reports_zero_without_upstream_support() {
+            env_logger::try_init().ok();
+
+            async fn listener(
+                addr: Multiaddr,
+                mut ready_tx: futures::channel::mpsc::Sender<Multiaddr>,
+            ) {
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+                let mut listener = transport.listen_on(addr).unwrap();
+                let muxer = loop {
+                    match listener.next().await.unwrap().unwrap() {
+                        ListenerEvent::NewAddress(listen_addr) => {
+                            ready_tx.send(listen_addr).await.unwrap();
+                        }
+                        ListenerEvent::Upgrade { upgrade, .. } => {
+                            let (_, muxer) = upgrade.await.unwrap();
+                            break Arc::new(muxer);
+                        }
+                        ListenerEvent::Error(e) => panic!("Unexpected listener error: {}", e),
+                        ListenerEvent::AddressExpired(_) => {}
+                    }
+                };
+
+                let mut recv = future::poll_fn(|cx| muxer.poll_accept_uni(cx))
+                    .await
+                    .unwrap();
+
+                // Give the dialer's write time to actually land before checking
+                // that it doesn't move the reported count.
+                async_std::task::sleep(Duration::from_millis(200)).await;
+                assert_eq!(
+                    recv.buffered().unwrap(),
+                    0,
+                    "buffered() has no upstream support to draw on yet, so it \
+                     should report 0 even with unread data sitting on the stream"
+                );
+
+                let mut received = Vec::new();
+                recv.read_to_end(&mut received).await.unwrap();
+                assert_eq!(received, b"hello, uni");
+
+                assert!(
+                    recv.buffered().is_err(),
+                    "quinn_proto frees a stream's state once it's been read to \
+                     EOF, so buffered() should report an error rather than a \
+                     stale 0 for a stream that no longer exists"
+                );
+
+                drop(recv);
+                drive_in_background(muxer);
+            }
+
+            async fn dialer(mut ready_rx: futures::channel::mpsc::Receiver<Multiaddr>) {
+                let addr = ready_rx.next().await.unwrap();
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+                let (_, muxer) = transport.dial(addr).unwrap().await.unwrap();
+                let muxer = Arc::new(muxer);
+
+                let mut send = muxer.open_uni().unwrap();
+                send.write_all(b"hello, uni").await.unwrap();
+                send.close().await.unwrap();
+
+                drive_in_background(muxer);
+            }
+
+            let addr: Multiaddr = "/ip4/127.0.0.1/udp/0/quic".parse().unwrap();
+            let (ready_tx, ready_rx) = futures::channel::mpsc::channel(1);
+            let listener = async_std::task::spawn(listener(addr, ready_tx));
+            async_std::task::block_on(dialer(ready_rx));
+            async_std::task::block_on(listener);
+        }
+
+        /// When the peer resets a stream with [`SendStream::reset`], the read
+        /// side should surface the application error code it reset with as
+        /// [`Error::StreamReset`], not a generic stream error, so protocols that
+        /// assign meaning to particular reset codes can match on it directly.
+        #[test]
+        fn reading_a_peer_reset_stream_surfaces_its_error_code() {
+            env_logger::try_init().ok();
+
+            async fn listener(
+                addr: Multiaddr,
+                mut ready_tx: futures::channel::mpsc::Sender<Multiaddr>,
+            ) {
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+                let mut listener = transport.listen_on(addr).unwrap();
+                let muxer = loop {
+                    match listener.next().await.unwrap().unwrap() {
+                        ListenerEvent::NewAddress(listen_addr) => {
+                            ready_tx.send(listen_addr).await.unwrap();
+                        }
+                        ListenerEvent::Upgrade { upgrade, .. } => {
+                            let (_, muxer) = upgrade.await.unwrap();
+                            break Arc::new(muxer);
+                        }
+                        ListenerEvent::Error(e) => panic!("Unexpected listener error: {}", e),
+                        ListenerEvent::AddressExpired(_) => {}
+                    }
+                };
+
+                let mut recv = future::poll_fn(|cx| muxer.poll_accept_uni(cx))
+                    .await
+                    .unwrap();
+                let error = future::poll_fn(|cx| recv.poll_read_chunk(cx))
+                    .await
+                    .unwrap_err();
+
+                assert!(
+                    matches!(error, Error::StreamReset(99)),
+                    "expected the reset's error code to survive as Error::StreamReset(99), \
+                     got {:?}",
+                    error
+                );
+
+                drop(recv);
+                drive_in_background(muxer);
+            }
+
+            async fn dialer(mut ready_rx: futures::channel::mpsc::Receiver<Multiaddr>) {
+                let addr = ready_rx.next().await.unwrap();
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+                let (_, muxer) = transport.dial(addr).unwrap().await.unwrap();
+                let muxer = Arc::new(muxer);
+
+                let send = muxer.open_uni().unwrap();
+                send.reset(99).unwrap();
+
+                drive_in_background(muxer);
+            }
+
+            let addr: Multiaddr = "/ip4/127.0.0.1/udp/0/quic".parse().unwrap();
+            let (ready_tx, ready_rx) = futures::channel::mpsc::channel(1);
+            let listener = async_std::task::spawn(listener(addr, ready_tx));
+            async_std::task::block_on(dialer(ready_rx));
+            async_std::task::block_on(listener);
+        }
+
+        /// When the reader calls [`RecvStream::stop`], the peer's next write
+        /// sees [`Error::SendStopped`] with that code, the same way a sender's
+        /// own reset surfaces as [`Error::StreamReset`] on the other side.
+        #[test]
+        fn stopping_a_recv_stream_fails_the_peers_next_write_with_its_error_code() {
+            env_logger::try_init().ok();
+
+            async fn listener(
+                addr: Multiaddr,
+                mut ready_tx: futures::channel::mpsc::Sender<Multiaddr>,
+            ) {
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+                let mut listener = transport.listen_on(addr).unwrap();
+                let muxer = loop {
+                    match listener.next().await.unwrap().unwrap() {
+                        ListenerEvent::NewAddress(listen_addr) => {
+                            ready_tx.send(listen_addr).await.unwrap();
+                        }
+                        ListenerEvent::Upgrade { upgrade, .. } => {
+                            let (_, muxer) = upgrade.await.unwrap();
+                            break Arc::new(muxer);
+                        }
+                        ListenerEvent::Error(e) => panic!("Unexpected listener error: {}", e),
+                        ListenerEvent::AddressExpired(_) => {}
+                    }
+                };
+
+                let mut recv = future::poll_fn(|cx| muxer.poll_accept_uni(cx))
+                    .await
+                    .unwrap();
+                future::poll_fn(|cx| recv.poll_read_chunk(cx))
+                    .await
+                    .unwrap();
+                recv.stop(42).unwrap();
+
+                drive_in_background(muxer);
+            }
+
+            async fn dialer(mut ready_rx: futures::channel::mpsc::Receiver<Multiaddr>) {
+                let addr = ready_rx.next().await.unwrap();
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+                let (_, muxer) = transport.dial(addr).unwrap().await.unwrap();
+                let muxer = Arc::new(muxer);
+
+                // Driven from here on, rather than only once this function
+                // returns: a write that never blocks on flow control (as every
+                // one below does, since nothing here fills the window) never
+                // itself polls the connection, so without a concurrent driver
+                // the incoming `STOP_SENDING` would never actually get
+                // processed and every write would just keep succeeding.
+                drive_in_background(Arc::clone(&muxer));
+
+                let mut send = muxer.open_uni().unwrap();
+                // Gets through before the listener has stopped the stream; just
+                // establishes the stream so the listener has something to
+                // `poll_accept_uni`/read from before it calls `stop`.
+                send.write_with_deadline(
+                    b"first",
+                    std::time::Instant::now() + Duration::from_secs(5),
+                )
+                .await
+                .unwrap();
+
+                let deadline = std::time::Instant::now() + Duration::from_secs(5);
+                let error = loop {
+                    match send.write_with_deadline(b"second", deadline).await {
+                        Ok(_) => continue,
+                        Err(e) => break e,
+                    }
+                };
+
+                assert!(
+                    matches!(error, Error::SendStopped(42)),
+                    "expected the stop's error code to surface as Error::SendStopped(42) \
+                     on the writer's next write, got {:?}",
+                    error
+                );
+            }
+
+            let addr: Multiaddr = "/ip4/127.0.0.1/udp/0/quic".parse().unwrap();
+            let (ready_tx, ready_rx) = futures::channel::mpsc::channel(1);
+            let listener = async_std::task::spawn(listener(addr, ready_tx));
+            async_std::task::block_on(dialer(ready_rx));
+            async_std::task::block_on(listener);
+        }
+
+        /// Exercises [`SendStream::write_with_deadline`]: the dialler opens a
+        /// unidirectional stream and writes far more than the peer's initial
+        /// per-stream flow-control window, while the listener accepts the
+        /// stream but never reads from it, so the window never grows past its
+        /// initial value. The first write fits within that window and
+        /// succeeds; the second has no room left to make progress, and the
+        /// peer never reads to free any up, so it times out with
+        /// `Error::WriteTimeout` instead of hanging forever.
+        #[test]
+        fn write_with_deadline_times_out_once_the_peers_receive_window_fills_up() {
+            env_logger::try_init().ok();
+
+            // Comfortably larger than `quinn_proto`'s default initial
+            // per-stream receive window (~1.25 MB), so the window fills up
+            // before this buffer does.
+            const BUF: &[u8] = &[0u8; 4_000_000];
+
+            async fn listener(
+                addr: Multiaddr,
+                mut ready_tx: futures::channel::mpsc::Sender<Multiaddr>,
+            ) {
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+                let mut listener = transport.listen_on(addr).unwrap();
+                let muxer = loop {
+                    match listener.next().await.unwrap().unwrap() {
+                        ListenerEvent::NewAddress(listen_addr) => {
+                            ready_tx.send(listen_addr).await.unwrap();
+                        }
+                        ListenerEvent::Upgrade { upgrade, .. } => {
+                            let (_, muxer) = upgrade.await.unwrap();
+                            break Arc::new(muxer);
+                        }
+                        ListenerEvent::Error(e) => panic!("Unexpected listener error: {}", e),
+                        ListenerEvent::AddressExpired(_) => {}
+                    }
+                };
+
+                // Accept the peer's unidirectional stream, but never read from
+                // it: `quinn_proto` only grants more send window once the
+                // receiver actually consumes what it already has. Forgotten
+                // rather than dropped: dropping it would itself send
+                // `STOP_SENDING` and free up the window this test is trying to
+                // keep full.
+                let recv = future::poll_fn(|cx| muxer.poll_accept_uni(cx))
+                    .await
+                    .unwrap();
+                std::mem::forget(recv);
+                drive_in_background(muxer);
+            }
+
+            async fn dialer(mut ready_rx: futures::channel::mpsc::Receiver<Multiaddr>) {
+                let addr = ready_rx.next().await.unwrap();
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+                let (_, muxer) = transport.dial(addr).unwrap().await.unwrap();
+                let mut send = muxer.open_uni().unwrap();
+
+                let written = send
+                    .write_with_deadline(
+                        BUF,
+                        std::time::Instant::now() + std::time::Duration::from_secs(5),
+                    )
+                    .await
+                    .unwrap();
+                assert!(
+                    written < BUF.len(),
+                    "the initial window shouldn't cover the whole buffer"
+                );
+
+                let result = send
+                    .write_with_deadline(
+                        &BUF[written..],
+                        std::time::Instant::now() + std::time::Duration::from_millis(200),
+                    )
+                    .await;
+                assert!(
+                    matches!(result, Err(Error::WriteTimeout)),
+                    "expected a write timeout once the peer's window filled up, got {:?}",
+                    result
+                );
+            }
+
+            let addr: Multiaddr = "/ip4/127.0.0.1/udp/0/quic".parse().unwrap();
+            let (ready_tx, ready_rx) = futures::channel::mpsc::channel(1);
+            let listener = async_std::task::spawn(listener(addr, ready_tx));
+            async_std::task::block_on(dialer(ready_rx));
+            async_std::task::block_on(listener);
+        }
+
+        /// [`QuicMuxer::set_receive_window`] is currently a no-op (see its doc
+        /// comment): calling it mid-transfer with a much larger value should
+        /// make no difference to when the peer's write blocks, since this
+        /// transport has no way to actually grant the extra credit yet. Mirrors
+        /// `write_with_deadline_times_out_once_the_peers_receive_window_fills_up`,
+        /// with a `set_receive_window` call spliced in between the two writes.
+        #[test]
+        fn set_receive_window_is_accepted_but_does_not_yet_grant_more_credit() {
+            env_logger::try_init().ok();
+
+            const BUF: &[u8] = &[0u8; 4_000_000];
+
+            async fn listener(
+                addr: Multiaddr,
+                mut ready_tx: futures::channel::mpsc::Sender<Multiaddr>,
+            ) {
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+                let mut listener = transport.listen_on(addr).unwrap();
+                let muxer = loop {
+                    match listener.next().await.unwrap().unwrap() {
+                        ListenerEvent::NewAddress(listen_addr) => {
+                            ready_tx.send(listen_addr).await.unwrap();
+                        }
+                        ListenerEvent::Upgrade { upgrade, .. } => {
+                            let (_, muxer) = upgrade.await.unwrap();
+                            break Arc::new(muxer);
+                        }
+                        ListenerEvent::Error(e) => panic!("Unexpected listener error: {}", e),
+                        ListenerEvent::AddressExpired(_) => {}
+                    }
+                };
+
+                // Forgotten rather than dropped, same as in
+                // `write_with_deadline_times_out_once_the_peers_receive_window_fills_up`:
+                // dropping it would send `STOP_SENDING` and free up the window
+                // this test is trying to keep full.
+                let recv = future::poll_fn(|cx| muxer.poll_accept_uni(cx))
+                    .await
+                    .unwrap();
+                std::mem::forget(recv);
+                muxer.set_receive_window(64 * 1024 * 1024);
+                drive_in_background(muxer);
+            }
+
+            async fn dialer(mut ready_rx: futures::channel::mpsc::Receiver<Multiaddr>) {
+                let addr = ready_rx.next().await.unwrap();
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+                let (_, muxer) = transport.dial(addr).unwrap().await.unwrap();
+                let mut send = muxer.open_uni().unwrap();
+
+                let written = send
+                    .write_with_deadline(
+                        BUF,
+                        std::time::Instant::now() + std::time::Duration::from_secs(5),
+                    )
+                    .await
+                    .unwrap();
+                assert!(
+                    written < BUF.len(),
+                    "the initial window shouldn't cover the whole buffer"
+                );
+
+                let result = send
+                    .write_with_deadline(
+                        &BUF[written..],
+                        std::time::Instant::now() + std::time::Duration::from_millis(200),
+                    )
+                    .await;
+                assert!(
+                    matches!(result, Err(Error::WriteTimeout)),
+                    "the listener's set_receive_window call shouldn't have freed up any \
+                     more room to write into, got {:?}",
+                    result
+                );
+            }
+
+            let addr: Multiaddr = "/ip4/127.0.0.1/udp/0/quic".parse().unwrap();
+            let (ready_tx, ready_rx) = futures::channel::mpsc::channel(1);
+            let listener = async_std::task::spawn(listener(addr, ready_tx));
+            async_std::task::block_on(dialer(ready_rx));
+            async_std::task::block_on(listener);
+        }
+
+        /// Sets a tiny [`StreamWindows::bidi_remote`] alongside a huge
+        /// [`StreamWindows::uni`] on the listener and confirms the remote-opened
+        /// bidi stream the dialler writes to actually gets the large window
+        /// rather than its own small one: as documented on [`StreamWindows`],
+        /// `quinn_proto` 0.7.3 only has one lumped `stream_receive_window`, so
+        /// the largest of the three configured values wins for every stream
+        /// type rather than each getting its own.
+        #[test]
+        fn stream_windows_are_not_yet_enforced_independently() {
+            env_logger::try_init().ok();
+
+            // Comfortably larger than the tiny `bidi_remote` window configured
+            // below, but well within the huge `uni` window that ends up backing
+            // every stream type once `quinn_proto` lumps them together.
+            const BUF: &[u8] = &[0u8; 2_000_000];
+
+            async fn listener(
+                addr: Multiaddr,
+                mut ready_tx: futures::channel::mpsc::Sender<Multiaddr>,
+            ) {
+                let transport = QuicTransport::new(
+                    Config::new(&Keypair::generate_ed25519()).stream_windows(StreamWindows {
+                        bidi_local: 16_384,
+                        bidi_remote: 16_384,
+                        uni: 4_000_000,
+                    }),
+                );
+                let mut listener = transport.listen_on(addr).unwrap();
+                let muxer = loop {
+                    match listener.next().await.unwrap().unwrap() {
+                        ListenerEvent::NewAddress(listen_addr) => {
+                            ready_tx.send(listen_addr).await.unwrap();
+                        }
+                        ListenerEvent::Upgrade { upgrade, .. } => {
+                            let (_, muxer) = upgrade.await.unwrap();
+                            break Arc::new(muxer);
+                        }
+                        ListenerEvent::Error(e) => panic!("Unexpected listener error: {}", e),
+                        ListenerEvent::AddressExpired(_) => {}
+                    }
+                };
+
+                // Accept the peer's bidi stream, but never read from it, same as
+                // `write_with_deadline_times_out_once_the_peers_receive_window_fills_up`.
+                match future::poll_fn(|cx| muxer.poll_event(cx)).await.unwrap() {
+                    StreamMuxerEvent::InboundSubstream(_) => {}
+                    StreamMuxerEvent::AddressChange(_) => {
+                        panic!("did not expect an address change")
+                    }
+                }
+                drive_in_background(muxer);
+            }
+
+            async fn dialer(mut ready_rx: futures::channel::mpsc::Receiver<Multiaddr>) {
+                let addr = ready_rx.next().await.unwrap();
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+                let (_, muxer) = transport.dial(addr).unwrap().await.unwrap();
+                let muxer = Arc::new(muxer);
+                let BiStream { mut send, recv } = muxer.open_bi().unwrap();
+
+                let result = send
+                    .write_with_deadline(BUF, std::time::Instant::now() + Duration::from_secs(5))
+                    .await;
+                assert_eq!(
+                    result.unwrap(),
+                    BUF.len(),
+                    "a fully independent bidi_remote window of 16 KiB would have \
+                     left most of this write unsent; getting it all out confirms \
+                     the listener granted the much larger uni window instead"
+                );
+                // A single write that fits entirely within quinn_proto's send
+                // buffer, as this one does, never itself drives the connection
+                // (only a `WriteError::Blocked` does); without this, the bytes
+                // above would sit queued forever and the listener would never
+                // see them.
+                drop(recv);
+                drive_in_background(muxer);
+            }
+
+            let addr: Multiaddr = "/ip4/127.0.0.1/udp/0/quic".parse().unwrap();
+            let (ready_tx, ready_rx) = futures::channel::mpsc::channel(1);
+            let listener = async_std::task::spawn(listener(addr, ready_tx));
+            async_std::task::block_on(dialer(ready_rx));
+            async_std::task::block_on(listener);
+        }
+
+        /// The first [`QuicTransport::open_stream_to`] call for an address
+        /// dials a fresh connection; a second call for the same address, made
+        /// while the first is still alive, reuses it instead of dialling again -
+        /// confirmed both by the two calls handing back the very same
+        /// [`QuicMuxer`] (`Arc::ptr_eq`) and by the listener seeing two streams
+        /// arrive on what is, from its side, a single accepted connection.
+        #[test]
+        fn open_stream_to_dials_once_and_reuses_the_connection_for_a_second_stream() {
+            env_logger::try_init().ok();
+
+            async fn listener(
+                addr: Multiaddr,
+                mut ready_tx: futures::channel::mpsc::Sender<Multiaddr>,
+            ) {
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+                let mut listener = transport.listen_on(addr).unwrap();
+                let muxer = loop {
+                    match listener.next().await.unwrap().unwrap() {
+                        ListenerEvent::NewAddress(listen_addr) => {
+                            ready_tx.send(listen_addr).await.unwrap();
+                        }
+                        ListenerEvent::Upgrade { upgrade, .. } => {
+                            let (_, muxer) = upgrade.await.unwrap();
+                            break Arc::new(muxer);
+                        }
+                        ListenerEvent::Error(e) => panic!("Unexpected listener error: {}", e),
+                        ListenerEvent::AddressExpired(_) => {}
+                    }
+                };
+
+                for expected in [b"first".as_slice(), b"second".as_slice()] {
+                    let id = match future::poll_fn(|cx| muxer.poll_event(cx)).await.unwrap() {
+                        StreamMuxerEvent::InboundSubstream(substream) => substream.id,
+                        StreamMuxerEvent::AddressChange(_) => {
+                            panic!("did not expect an address change")
+                        }
+                    };
+
+                    let mut recv = RecvStream::new(&muxer, id);
+                    let mut received = Vec::new();
+                    recv.read_to_end(&mut received).await.unwrap();
+                    assert_eq!(received, expected);
+
+                    let mut send = SendStream::new(&muxer, id);
+                    send.write_all(b"ack").await.unwrap();
+                    send.close().await.unwrap();
+                }
+
+                drive_in_background(muxer);
+            }
+
+            async fn dialer(mut ready_rx: futures::channel::mpsc::Receiver<Multiaddr>) {
+                let addr = ready_rx.next().await.unwrap();
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+
+                let first = transport
+                    .open_stream_to(addr.clone(), Duration::from_secs(5))
+                    .await
+                    .unwrap();
+                let mut stream = first.open_bi().unwrap();
+                stream.send.write_all(b"first").await.unwrap();
+                stream.send.close().await.unwrap();
+                let mut reply = Vec::new();
+                stream.recv.read_to_end(&mut reply).await.unwrap();
+                assert_eq!(reply, b"ack");
+
+                let second = transport
+                    .open_stream_to(addr, Duration::from_secs(5))
+                    .await
+                    .unwrap();
+                assert!(
+                    Arc::ptr_eq(&first, &second),
+                    "a second open_stream_to call for the same address should reuse the \
+                     first call's connection instead of dialling a new one"
+                );
+                let mut stream = second.open_bi().unwrap();
+                stream.send.write_all(b"second").await.unwrap();
+                stream.send.close().await.unwrap();
+                let mut reply = Vec::new();
+                stream.recv.read_to_end(&mut reply).await.unwrap();
+                assert_eq!(reply, b"ack");
+
+                drop(stream);
+                drive_in_background(second);
+            }
+
+            let addr: Multiaddr = "/ip4/127.0.0.1/udp/0/quic".parse().unwrap();
+            let (ready_tx, ready_rx) = futures::channel::mpsc::channel(1);
+            let listener_task = async_std::task::spawn(listener(addr, ready_tx));
+            async_std::task::block_on(dialer(ready_rx));
+            async_std::task::block_on(listener_task);
+        }
+
+        /// Several small [`StreamMuxer::write_substream`] calls on a substream
+        /// configured with [`Config::stream_write_buffer`] are coalesced into
+        /// this crate's own buffer and only handed to the connection once it
+        /// fills, instead of reaching it one write at a time. Counting packets
+        /// via [`Config::transmit_interceptor`] - while forcing an actual send
+        /// after every unbuffered write, the way an eagerly-flushing caller
+        /// would - confirms the buffered run produces fewer of them for the
+        /// same payload.
+        #[test]
+        fn stream_write_buffer_coalesces_small_writes_into_fewer_packets() {
+            env_logger::try_init().ok();
+
+            const WRITES: usize = 30;
+            const CHUNK: &[u8] = b"0123456789";
+
+            async fn listener(
+                addr: Multiaddr,
+                mut ready_tx: futures::channel::mpsc::Sender<Multiaddr>,
+            ) {
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+                let mut listener = transport.listen_on(addr).unwrap();
+                loop {
+                    match listener.next().await.unwrap().unwrap() {
+                        ListenerEvent::NewAddress(listen_addr) => {
+                            ready_tx.send(listen_addr).await.unwrap();
+                        }
+                        ListenerEvent::Upgrade { upgrade, .. } => {
+                            let (_, muxer) = upgrade.await.unwrap();
+                            let muxer = Arc::new(muxer);
+                            let id = match future::poll_fn(|cx| muxer.poll_event(cx)).await.unwrap()
+                            {
+                                StreamMuxerEvent::InboundSubstream(substream) => substream.id,
+                                StreamMuxerEvent::AddressChange(_) => {
+                                    panic!("did not expect an address change")
+                                }
+                            };
+                            let mut recv = RecvStream::new(&muxer, id);
+                            let mut received = Vec::new();
+                            recv.read_to_end(&mut received).await.unwrap();
+                            assert_eq!(received.len(), WRITES * CHUNK.len());
+                            drop(recv);
+                            drive_in_background(muxer);
+                            return;
+                        }
+                        ListenerEvent::Error(e) => panic!("Unexpected listener error: {}", e),
+                        ListenerEvent::AddressExpired(_) => {}
+                    }
+                }
+            }
+
+            /// Dials `addr`, writes `WRITES` chunks of `CHUNK` through
+            /// [`StreamMuxer::write_substream`] directly (bypassing [`SendStream`],
+            /// which never buffers), and returns how many packets
+            /// [`Config::transmit_interceptor`] observed while doing so.
+            ///
+            /// `write_substream`/`flush_substream` never drive the connection
+            /// themselves - same as every other `StreamMuxer` trait method - so
+            /// a real caller's own ambient polling is what turns a write into a
+            /// packet. This stands in for that polling with an explicit,
+            /// synchronous drive right after every write: the same "send it now"
+            /// behaviour an eagerly-flushing caller would get from its executor,
+            /// made deterministic instead of dependent on some later, unrelated
+            /// wakeup.
+            async fn dialer(
+                mut ready_rx: futures::channel::mpsc::Receiver<Multiaddr>,
+                stream_write_buffer: usize,
+            ) -> usize {
+                let addr = ready_rx.next().await.unwrap();
+                let transmits = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+                let config = Config::new(&Keypair::generate_ed25519())
+                    .stream_write_buffer(stream_write_buffer)
+                    .transmit_interceptor({
+                        let transmits = transmits.clone();
+                        move |_| {
+                            transmits.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                            TransmitAction::Pass
+                        }
+                    });
+                let transport = QuicTransport::new(config);
+                let (_, muxer) = transport.dial(addr).unwrap().await.unwrap();
+                let muxer = Arc::new(muxer);
+
+                let mut outbound = muxer.open_outbound();
+                let mut substream = future::poll_fn(|cx| muxer.poll_outbound(cx, &mut outbound))
+                    .await
+                    .unwrap();
+
+                let waker = futures::task::noop_waker();
+                let mut drive_cx = Context::from_waker(&waker);
+                for _ in 0..WRITES {
+                    future::poll_fn(|cx| muxer.write_substream(cx, &mut substream, CHUNK))
+                        .await
+                        .unwrap();
+                    let _ = muxer.poll_event(&mut drive_cx);
+                }
+                future::poll_fn(|cx| muxer.flush_substream(cx, &mut substream))
+                    .await
+                    .unwrap();
+                let _ = muxer.poll_event(&mut drive_cx);
+                future::poll_fn(|cx| StreamMuxer::shutdown_substream(&*muxer, cx, &mut substream))
+                    .await
+                    .unwrap();
+                let _ = muxer.poll_event(&mut drive_cx);
+
+                let transmits = transmits.load(std::sync::atomic::Ordering::Relaxed);
+                drive_in_background(muxer);
+                transmits
+            }
+
+            async fn run(stream_write_buffer: usize) -> usize {
+                let addr: Multiaddr = "/ip4/127.0.0.1/udp/0/quic".parse().unwrap();
+                let (ready_tx, ready_rx) = futures::channel::mpsc::channel(1);
+                let listener_task = async_std::task::spawn(listener(addr, ready_tx));
+                let transmits = dialer(ready_rx, stream_write_buffer).await;
+                listener_task.await;
+                transmits
+            }
+
+            let unbuffered = async_std::task::block_on(run(0));
+            let buffered = async_std::task::block_on(run(WRITES * CHUNK.len()));
+
+            assert!(
+                buffered < unbuffered,
+                "expected buffering {} small writes to produce fewer packets than sending each \
+                 one as soon as it arrived, got {} buffered vs {} unbuffered",
+                WRITES,
+                buffered,
+                unbuffered
+            );
+        }
+
+        /// Has the dialler write a uni stream's worth of data in two chunks
+        /// with a pause in between, and confirms
+        /// [`RecvStream::read_with_timeout`] returns the first chunk tagged
+        /// [`ReadOutcome::TimedOut`] rather than waiting for the rest, then
+        /// goes on to read the second chunk tagged [`ReadOutcome::Filled`]
+        /// once it arrives.
+        #[test]
+        fn read_with_timeout_returns_a_partial_buffer_while_data_still_trickles_in() {
+            env_logger::try_init().ok();
+
+            const FIRST: &[u8] = b"ab";
+            const SECOND: &[u8] = b"cd";
+
+            async fn listener(
+                addr: Multiaddr,
+                mut ready_tx: futures::channel::mpsc::Sender<Multiaddr>,
+            ) -> Vec<u8> {
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+                let mut listener = transport.listen_on(addr).unwrap();
+                let muxer = loop {
+                    match listener.next().await.unwrap().unwrap() {
+                        ListenerEvent::NewAddress(listen_addr) => {
+                            ready_tx.send(listen_addr).await.unwrap();
+                        }
+                        ListenerEvent::Upgrade { upgrade, .. } => {
+                            let (_, muxer) = upgrade.await.unwrap();
+                            break Arc::new(muxer);
+                        }
+                        ListenerEvent::Error(e) => panic!("Unexpected listener error: {}", e),
+                        ListenerEvent::AddressExpired(_) => {}
+                    }
+                };
+
+                let mut recv = future::poll_fn(|cx| muxer.poll_accept_uni(cx))
+                    .await
+                    .unwrap();
+
+                let mut buf = [0; FIRST.len() + SECOND.len()];
+                let (read, outcome) = recv
+                    .read_with_timeout(&mut buf, Duration::from_millis(100))
+                    .await
+                    .unwrap();
+                assert_eq!(outcome, ReadOutcome::TimedOut);
+                assert_eq!(&buf[..read], FIRST);
+
+                let (read2, outcome2) = recv
+                    .read_with_timeout(&mut buf[read..], Duration::from_secs(5))
+                    .await
+                    .unwrap();
+                assert_eq!(outcome2, ReadOutcome::Filled);
+                assert_eq!(&buf[..read + read2], b"abcd");
+
+                drop(recv);
+                drive_in_background(muxer);
+                buf.to_vec()
+            }
+
+            async fn dialer(mut ready_rx: futures::channel::mpsc::Receiver<Multiaddr>) {
+                let addr = ready_rx.next().await.unwrap();
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+                let (_, muxer) = transport.dial(addr).unwrap().await.unwrap();
+                let muxer = Arc::new(muxer);
+                // Drive from the start, not just after each write: otherwise
+                // `FIRST` would sit unflushed in the local send buffer for the
+                // whole sleep below instead of actually trickling in.
+                drive_in_background(muxer.clone());
+
+                // `write_all` only buffers into `quinn_proto`'s send state; it
+                // never transmits on its own unless the buffer is actually
+                // full. Nudge a single synchronous drive pass after each write
+                // so the listener's `read_with_timeout` calls above see data
+                // arrive (and not arrive) on the schedule this test expects,
+                // rather than depending on the background task happening to
+                // get scheduled in between.
+                let waker = futures::task::noop_waker();
+                let mut cx = Context::from_waker(&waker);
+
+                let mut send = muxer.open_uni().unwrap();
+                send.write_all(FIRST).await.unwrap();
+                let _ = muxer.poll_event(&mut cx);
+                async_std::task::sleep(Duration::from_millis(500)).await;
+                send.write_all(SECOND).await.unwrap();
+                let _ = muxer.poll_event(&mut cx);
+                send.close().await.unwrap();
+            }
+
+            let addr: Multiaddr = "/ip4/127.0.0.1/udp/0/quic".parse().unwrap();
+            let (ready_tx, ready_rx) = futures::channel::mpsc::channel(1);
+            let listener_task = async_std::task::spawn(listener(addr, ready_tx));
+            async_std::task::block_on(dialer(ready_rx));
+            let received = async_std::task::block_on(listener_task);
+            assert_eq!(received, b"abcd");
+        }
+    }
+
+    mod datagrams {
+        //! The endpoint's unreliable datagram path: sending, receiving, buffering, and malformed input.
+        use super::*;
+
+        #[test]
+        fn sends_datagrams_through_sink_in_order() {
+            env_logger::try_init().ok();
+
+            const COUNT: u8 = 5;
+
+            async fn listener(
+                addr: Multiaddr,
+                mut ready_tx: futures::channel::mpsc::Sender<Multiaddr>,
+            ) -> Vec<Vec<u8>> {
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+                let mut listener = transport.listen_on(addr).unwrap();
+                loop {
+                    match listener.next().await.unwrap().unwrap() {
+                        ListenerEvent::NewAddress(listen_addr) => {
+                            ready_tx.send(listen_addr).await.unwrap();
+                        }
+                        ListenerEvent::Upgrade { upgrade, .. } => {
+                            let (_, muxer) = upgrade.await.unwrap();
+                            let mut received = Vec::new();
+                            while received.len() < COUNT as usize {
+                                received.push(
+                                    future::poll_fn(|cx| muxer.poll_datagram(cx)).await.unwrap(),
+                                );
+                            }
+                            return received;
+                        }
+                        ListenerEvent::Error(e) => panic!("Unexpected listener error: {}", e),
+                        ListenerEvent::AddressExpired(_) => {}
+                    }
+                }
+            }
+
+            async fn dialer(mut ready_rx: futures::channel::mpsc::Receiver<Multiaddr>) {
+                let addr = ready_rx.next().await.unwrap();
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+                let (_, muxer) = transport.dial(addr).unwrap().await.unwrap();
+                let muxer = Arc::new(muxer);
+                let datagrams = (0..COUNT).map(|i| Ok(vec![i]));
+                muxer
+                    .datagram_sink()
+                    .send_all(&mut stream::iter(datagrams))
+                    .await
+                    .unwrap();
+                drive_in_background(muxer);
+            }
+
+            let addr: Multiaddr = "/ip4/127.0.0.1/udp/0/quic".parse().unwrap();
+            let (ready_tx, ready_rx) = futures::channel::mpsc::channel(1);
+            let listener = async_std::task::spawn(listener(addr, ready_tx));
+            async_std::task::block_on(dialer(ready_rx));
+            let received = async_std::task::block_on(listener);
+
+            assert_eq!(received, (0..COUNT).map(|i| vec![i]).collect::<Vec<_>>());
+        }
+
+        /// `incoming_datagrams` is a thin [`Stream`](futures::Stream) wrapper
+        /// around [`QuicMuxer::poll_datagram`]; confirms three datagrams sent by
+        /// the dialler can be collected off the listener's stream in order.
+        #[test]
+        fn incoming_datagrams_yields_datagrams_as_they_arrive() {
+            env_logger::try_init().ok();
+
+            const COUNT: u8 = 3;
+
+            async fn listener(
+                addr: Multiaddr,
+                mut ready_tx: futures::channel::mpsc::Sender<Multiaddr>,
+            ) -> Vec<Vec<u8>> {
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+                let mut listener = transport.listen_on(addr).unwrap();
+                loop {
+                    match listener.next().await.unwrap().unwrap() {
+                        ListenerEvent::NewAddress(listen_addr) => {
+                            ready_tx.send(listen_addr).await.unwrap();
+                        }
+                        ListenerEvent::Upgrade { upgrade, .. } => {
+                            let (_, muxer) = upgrade.await.unwrap();
+                            return muxer
+                                .incoming_datagrams()
+                                .take(COUNT as usize)
+                                .collect()
+                                .await;
+                        }
+                        ListenerEvent::Error(e) => panic!("Unexpected listener error: {}", e),
+                        ListenerEvent::AddressExpired(_) => {}
+                    }
+                }
+            }
+
+            async fn dialer(mut ready_rx: futures::channel::mpsc::Receiver<Multiaddr>) {
+                let addr = ready_rx.next().await.unwrap();
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+                let (_, muxer) = transport.dial(addr).unwrap().await.unwrap();
+                let muxer = Arc::new(muxer);
+                let datagrams = (0..COUNT).map(|i| Ok(vec![i]));
+                muxer
+                    .datagram_sink()
+                    .send_all(&mut stream::iter(datagrams))
+                    .await
+                    .unwrap();
+                drive_in_background(muxer);
+            }
+
+            let addr: Multiaddr = "/ip4/127.0.0.1/udp/0/quic".parse().unwrap();
+            let (ready_tx, ready_rx) = futures::channel::mpsc::channel(1);
+            let listener = async_std::task::spawn(listener(addr, ready_tx));
+            async_std::task::block_on(dialer(ready_rx));
+            let received = async_std::task::block_on(listener);
+
+            assert_eq!(received, (0..COUNT).map(|i| vec![i]).collect::<Vec<_>>());
+        }
+
+        /// Confirms that datagrams the dialler sends right after its own
+        /// handshake completes survive a listener that doesn't get around to
+        /// calling [`QuicMuxer::poll_datagram`] for a while: the shared endpoint
+        /// keeps queuing up whatever `quinn_proto` hands it regardless of
+        /// whether the application is actually reading, so
+        /// [`QuicMuxer::drain_buffered_datagrams`] can recover all of them in
+        /// one pass once the listener does get around to it, rather than some
+        /// having been silently dropped in the meantime.
+        #[test]
+        fn buffered_datagrams_survive_a_slow_accept() {
+            env_logger::try_init().ok();
+
+            const COUNT: u8 = 5;
+
+            async fn listener(
+                addr: Multiaddr,
+                mut ready_tx: futures::channel::mpsc::Sender<Multiaddr>,
+            ) -> Vec<Vec<u8>> {
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+                let mut listener = transport.listen_on(addr).unwrap();
+                loop {
+                    match listener.next().await.unwrap().unwrap() {
+                        ListenerEvent::NewAddress(listen_addr) => {
+                            ready_tx.send(listen_addr).await.unwrap();
+                        }
+                        ListenerEvent::Upgrade { upgrade, .. } => {
+                            let (_, muxer) = upgrade.await.unwrap();
+                            let muxer = Arc::new(muxer);
+                            // Keeps acks and pings flowing in the background
+                            // without ever calling `poll_datagram`, standing in
+                            // for an application that's slow to start reading
+                            // datagrams after accepting.
+                            drive_in_background(muxer.clone());
+                            async_std::task::sleep(Duration::from_millis(300)).await;
+                            return muxer.drain_buffered_datagrams();
+                        }
+                        ListenerEvent::Error(e) => panic!("Unexpected listener error: {}", e),
+                        ListenerEvent::AddressExpired(_) => {}
+                    }
+                }
+            }
+
+            async fn dialer(mut ready_rx: futures::channel::mpsc::Receiver<Multiaddr>) {
+                let addr = ready_rx.next().await.unwrap();
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+                let (_, muxer) = transport.dial(addr).unwrap().await.unwrap();
+                let muxer = Arc::new(muxer);
+                let datagrams = (0..COUNT).map(|i| Ok(vec![i]));
+                muxer
+                    .datagram_sink()
+                    .send_all(&mut stream::iter(datagrams))
+                    .await
+                    .unwrap();
+                drive_in_background(muxer);
+            }
+
+            let addr: Multiaddr = "/ip4/127.0.0.1/udp/0/quic".parse().unwrap();
+            let (ready_tx, ready_rx) = futures::channel::mpsc::channel(1);
+            let listener = async_std::task::spawn(listener(addr, ready_tx));
+            async_std::task::block_on(dialer(ready_rx));
+            let received = async_std::task::block_on(listener);
+
+            assert_eq!(received, (0..COUNT).map(|i| vec![i]).collect::<Vec<_>>());
+        }
+
+        /// [`DatagramSink::poll_ready`] already applies back-pressure once
+        /// [`Config::datagram_send_buffer_size`] datagrams are queued, but a
+        /// caller that calls `start_send` directly without waiting for it (in
+        /// violation of the `Sink` contract) should still get a typed error
+        /// back instead of the sink silently growing past its configured bound.
+        #[test]
+        fn datagram_sink_rejects_sends_past_the_configured_buffer_size() {
+            env_logger::try_init().ok();
+
+            const BUFFER_SIZE: usize = 2;
+
+            async fn listener(
+                addr: Multiaddr,
+                mut ready_tx: futures::channel::mpsc::Sender<Multiaddr>,
+            ) {
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+                let mut listener = transport.listen_on(addr).unwrap();
+                loop {
+                    match listener.next().await.unwrap().unwrap() {
+                        ListenerEvent::NewAddress(listen_addr) => {
+                            ready_tx.send(listen_addr).await.unwrap();
+                        }
+                        ListenerEvent::Upgrade { upgrade, .. } => {
+                            let (_, muxer) = upgrade.await.unwrap();
+                            drive_in_background(Arc::new(muxer));
+                            return;
+                        }
+                        ListenerEvent::Error(e) => panic!("Unexpected listener error: {}", e),
+                        ListenerEvent::AddressExpired(_) => {}
+                    }
+                }
+            }
+
+            async fn dialer(mut ready_rx: futures::channel::mpsc::Receiver<Multiaddr>) {
+                let addr = ready_rx.next().await.unwrap();
+                let transport = QuicTransport::new(
+                    Config::new(&Keypair::generate_ed25519())
+                        .datagram_send_buffer_size(BUFFER_SIZE),
+                );
+                let (_, muxer) = transport.dial(addr).unwrap().await.unwrap();
+                let mut sink = muxer.datagram_sink();
+
+                for i in 0..BUFFER_SIZE {
+                    Pin::new(&mut sink).start_send(vec![i as u8]).unwrap();
+                }
+
+                let result = Pin::new(&mut sink).start_send(vec![0xff]);
+                assert!(
+                    matches!(result, Err(Error::DatagramQueueFull)),
+                    "expected the send past the configured buffer size to be rejected, got {:?}",
+                    result
+                );
+            }
+
+            let addr: Multiaddr = "/ip4/127.0.0.1/udp/0/quic".parse().unwrap();
+            let (ready_tx, ready_rx) = futures::channel::mpsc::channel(1);
+            let listener = async_std::task::spawn(listener(addr, ready_tx));
+            async_std::task::block_on(dialer(ready_rx));
+            async_std::task::block_on(listener);
+        }
+
+        /// Regression test for the `cargo fuzz` target at `fuzz/fuzz_targets/ingest_datagram.rs`,
+        /// which feeds arbitrary bytes to [`Endpoint::ingest_datagram`]: a
+        /// datagram too short to be a valid QUIC packet must be dropped rather
+        /// than panicking.
+        #[test]
+        fn ingest_datagram_does_not_panic_on_a_truncated_packet() {
+            let socket = StdUdpSocket::bind((std::net::Ipv4Addr::LOCALHOST, 0)).unwrap();
+            let endpoint =
+                Endpoint::from_socket(&Config::new(&Keypair::generate_ed25519()), socket).unwrap();
+            let from: SocketAddr = "127.0.0.1:12345".parse().unwrap();
+            endpoint.ingest_datagram(from, &[0u8; 3]);
+        }
+
+        /// A datagram larger than the socket's send buffer can never be queued,
+        /// however many times it's retried, so the OS reports it as
+        /// undeliverable immediately rather than asking us to wait for
+        /// writability; confirms [`Endpoint::dropped_datagrams`] picks that up.
+        #[test]
+        fn dropped_datagrams_are_counted_when_the_socket_reports_them_undeliverable() {
+            let socket = socket2::Socket::new(
+                socket2::Domain::IPV4,
+                socket2::Type::DGRAM,
+                Some(socket2::Protocol::UDP),
+            )
+            .unwrap();
+            socket
+                .bind(&"127.0.0.1:0".parse::<SocketAddr>().unwrap().into())
+                .unwrap();
+            socket.set_send_buffer_size(1024).unwrap();
+
+            let config = Config::new(&Keypair::generate_ed25519());
+            let endpoint = Endpoint::from_socket(&config, StdUdpSocket::from(socket)).unwrap();
+
+            let transmit = quinn_proto::Transmit {
+                destination: "127.0.0.1:12345".parse().unwrap(),
+                ecn: None,
+                contents: vec![0u8; 10 * 1024 * 1024],
+                segment_size: None,
+                src_ip: None,
+            };
+
+            assert_eq!(endpoint.dropped_datagrams(), 0);
+            let waker = futures::task::noop_waker();
+            let mut cx = Context::from_waker(&waker);
+            let result = endpoint.poll_outgoing(&mut cx, None, &transmit);
+            assert!(
+                matches!(result, Poll::Ready(Err(_))),
+                "an oversized datagram should be rejected by the socket rather than queued"
+            );
+            assert_eq!(endpoint.dropped_datagrams(), 1);
+        }
+
+        /// Configures a small [`Config::max_udp_payload_size`] on the dialling
+        /// side and transfers a payload many times larger than it, recording
+        /// every transmit's byte length through [`Config::transmit_interceptor`];
+        /// confirms none of them ever exceeds the clamp, even though
+        /// `quinn_proto` would otherwise pack its default-sized (1480-byte)
+        /// packets.
+        #[test]
+        fn max_udp_payload_size_clamps_every_outgoing_packet() {
+            env_logger::try_init().ok();
+
+            // The protocol's own required minimum, well below `quinn_proto`'s
+            // 1480-byte default, so a clamped packet is trivially distinguished
+            // from an unclamped one.
+            const CLAMP: u16 = 1200;
+            const PAYLOAD: &[u8] = &[0x42u8; 64 * 1024];
+
+            async fn listener(
+                addr: Multiaddr,
+                mut ready_tx: futures::channel::mpsc::Sender<Multiaddr>,
+            ) {
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+                let mut listener = transport.listen_on(addr).unwrap();
+                loop {
+                    match listener.next().await.unwrap().unwrap() {
+                        ListenerEvent::NewAddress(listen_addr) => {
+                            ready_tx.send(listen_addr).await.unwrap();
+                        }
+                        ListenerEvent::Upgrade { upgrade, .. } => {
+                            let (_, muxer) = upgrade.await.unwrap();
+                            let muxer = Arc::new(muxer);
+                            let id = match future::poll_fn(|cx| muxer.poll_event(cx)).await.unwrap()
+                            {
+                                StreamMuxerEvent::InboundSubstream(substream) => substream.id,
+                                StreamMuxerEvent::AddressChange(_) => {
+                                    panic!("did not expect an address change")
+                                }
+                            };
+                            let mut recv = RecvStream::new(&muxer, id);
+                            let mut received = Vec::new();
+                            recv.read_to_end(&mut received).await.unwrap();
+                            assert_eq!(received, PAYLOAD);
+                            drop(recv);
+                            drive_in_background(muxer);
+                            return;
+                        }
+                        ListenerEvent::Error(e) => panic!("Unexpected listener error: {}", e),
+                        ListenerEvent::AddressExpired(_) => {}
+                    }
+                }
+            }
+
+            async fn dialer(mut ready_rx: futures::channel::mpsc::Receiver<Multiaddr>) {
+                let addr = ready_rx.next().await.unwrap();
+                let max_seen = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+                let config = Config::new(&Keypair::generate_ed25519())
+                    .max_udp_payload_size(CLAMP)
+                    .transmit_interceptor({
+                        let max_seen = max_seen.clone();
+                        move |transmit| {
+                            max_seen.fetch_max(
+                                transmit.contents.len(),
+                                std::sync::atomic::Ordering::Relaxed,
+                            );
+                            TransmitAction::Pass
+                        }
+                    });
+                let transport = QuicTransport::new(config);
+                let (_, muxer) = transport.dial(addr).unwrap().await.unwrap();
+                let muxer = Arc::new(muxer);
+
+                let mut stream = muxer.open_bi().unwrap();
+                stream.send.write_all(PAYLOAD).await.unwrap();
+                stream.send.close().await.unwrap();
+
+                assert!(
+                    max_seen.load(std::sync::atomic::Ordering::Relaxed) <= CLAMP as usize,
+                    "a packet exceeded the configured max_udp_payload_size clamp of {} bytes",
+                    CLAMP
+                );
+
+                drop(stream);
+                drive_in_background(muxer);
+            }
+
+            let addr: Multiaddr = "/ip4/127.0.0.1/udp/0/quic".parse().unwrap();
+            let (ready_tx, ready_rx) = futures::channel::mpsc::channel(1);
+            let listener_task = async_std::task::spawn(listener(addr, ready_tx));
+            async_std::task::block_on(dialer(ready_rx));
+            async_std::task::block_on(listener_task);
+        }
+    }
+
+    mod connection {
+        //! Established-connection lifecycle: migration, idle timeout, closing, and the no-op `Config` knobs pinned by `quinn_proto` 0.7.3.
+        use super::*;
+
+        /// Simulates the peer migrating to a new network path with a
+        /// transparent UDP relay sitting between dialer and listener: the relay
+        /// has no notion of QUIC, so switching which of its two sockets it
+        /// forwards the dialer's traffic through is indistinguishable, from the
+        /// listener's point of view, from the dialer rebinding to a new local
+        /// address. [`QuicMuxer::remote_address`] should track that switch.
+        #[test]
+        fn remote_address_reflects_the_peers_new_address_after_it_migrates() {
+            env_logger::try_init().ok();
+
+            /// Forwards datagrams between `listener_addr` and whoever last sent
+            /// it a datagram, through `outgoing[1]` once `migrated` is set and
+            /// through `outgoing[0]` until then. Returns the relay's own
+            /// address (what the dialer should target) and the address traffic
+            /// appears to come from after migrating (what the listener should
+            /// end up reporting).
+            fn spawn_relay(
+                listener_addr: SocketAddr,
+                migrated: Arc<std::sync::atomic::AtomicBool>,
+            ) -> (SocketAddr, SocketAddr) {
+                let incoming = StdUdpSocket::bind((std::net::Ipv4Addr::LOCALHOST, 0)).unwrap();
+                incoming.set_nonblocking(true).unwrap();
+                let relay_addr = incoming.local_addr().unwrap();
+
+                let outgoing = [
+                    StdUdpSocket::bind((std::net::Ipv4Addr::LOCALHOST, 0)).unwrap(),
+                    StdUdpSocket::bind((std::net::Ipv4Addr::LOCALHOST, 0)).unwrap(),
+                ];
+                for socket in &outgoing {
+                    socket.set_nonblocking(true).unwrap();
+                }
+                let new_path = outgoing[1].local_addr().unwrap();
+
+                std::thread::spawn(move || {
+                    let mut dialer_addr = None;
+                    let mut buf = [0u8; 2048];
+                    loop {
+                        if let Ok((n, from)) = incoming.recv_from(&mut buf) {
+                            dialer_addr = Some(from);
+                            let active =
+                                migrated.load(std::sync::atomic::Ordering::SeqCst) as usize;
+                            let _ = outgoing[active].send_to(&buf[..n], listener_addr);
+                        }
+                        for socket in &outgoing {
+                            if let (Ok((n, _)), Some(addr)) =
+                                (socket.recv_from(&mut buf), dialer_addr)
+                            {
+                                let _ = incoming.send_to(&buf[..n], addr);
+                            }
+                        }
+                        std::thread::sleep(std::time::Duration::from_millis(1));
+                    }
+                });
+
+                (relay_addr, new_path)
+            }
+
+            async fn listener(
+                addr: Multiaddr,
+                mut ready_tx: futures::channel::mpsc::Sender<Multiaddr>,
+            ) -> Arc<QuicMuxer> {
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+                let mut listener = transport.listen_on(addr).unwrap();
+                loop {
+                    match listener.next().await.unwrap().unwrap() {
+                        ListenerEvent::NewAddress(listen_addr) => {
+                            ready_tx.send(listen_addr).await.unwrap();
+                        }
+                        ListenerEvent::Upgrade { upgrade, .. } => {
+                            let (_, muxer) = upgrade.await.unwrap();
+                            let muxer = Arc::new(muxer);
+                            drive_in_background(muxer.clone());
+                            return muxer;
+                        }
+                        ListenerEvent::Error(e) => panic!("Unexpected listener error: {}", e),
+                        ListenerEvent::AddressExpired(_) => {}
+                    }
+                }
+            }
+
+            async fn dialer(relay_addr: Multiaddr, migrated: Arc<std::sync::atomic::AtomicBool>) {
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+                let (_, muxer) = transport.dial(relay_addr).unwrap().await.unwrap();
+
+                // Establish the path the listener sees before migrating.
+                muxer.ping().await.unwrap();
+
+                migrated.store(true, std::sync::atomic::Ordering::SeqCst);
+
+                // Cross the now-switched relay socket, giving the listener a
+                // datagram to observe the new address from.
+                muxer.ping().await.unwrap();
+
+                drive_in_background(Arc::new(muxer));
+            }
+
+            let addr: Multiaddr = "/ip4/127.0.0.1/udp/0/quic".parse().unwrap();
+            let (ready_tx, mut ready_rx) = futures::channel::mpsc::channel(1);
+            let listener_task = async_std::task::spawn(listener(addr, ready_tx));
+
+            let listen_addr = async_std::task::block_on(ready_rx.next()).unwrap();
+            let listen_socket_addr = multiaddr_to_socketaddr(listen_addr, true).unwrap();
+
+            let migrated = Arc::new(std::sync::atomic::AtomicBool::new(false));
+            let (relay_addr, new_path) = spawn_relay(listen_socket_addr, migrated.clone());
+
+            async_std::task::block_on(dialer(socketaddr_to_quic_multiaddr(relay_addr), migrated));
+
+            let listener_muxer = async_std::task::block_on(listener_task);
+            assert_eq!(listener_muxer.remote_address(), new_path);
+        }
+
+        /// The same relay-based migration setup as
+        /// `remote_address_reflects_the_peers_new_address_after_it_migrates`,
+        /// except the listener's [`Config::allow_migration`] is disabled: the
+        /// packet that would otherwise be recognized as a migration is instead
+        /// dropped as coming from an unrecognized address, so the dialer's ping
+        /// across the new path never gets a response and the connection just
+        /// stays pinned - and eventually idle-timeout-dead - on the original
+        /// one, rather than silently migrating.
+        #[test]
+        fn allow_migration_false_stops_a_client_from_migrating() {
+            env_logger::try_init().ok();
+
+            /// Like `remote_address_reflects_the_peers_new_address_after_it_migrates`'s
+            /// identical helper, but also returns the original path's address so
+            /// the caller can confirm the listener never switched off it.
+            fn spawn_relay(
+                listener_addr: SocketAddr,
+                migrated: Arc<std::sync::atomic::AtomicBool>,
+            ) -> (SocketAddr, SocketAddr, SocketAddr) {
+                let incoming = StdUdpSocket::bind((std::net::Ipv4Addr::LOCALHOST, 0)).unwrap();
+                incoming.set_nonblocking(true).unwrap();
+                let relay_addr = incoming.local_addr().unwrap();
+
+                let outgoing = [
+                    StdUdpSocket::bind((std::net::Ipv4Addr::LOCALHOST, 0)).unwrap(),
+                    StdUdpSocket::bind((std::net::Ipv4Addr::LOCALHOST, 0)).unwrap(),
+                ];
+                for socket in &outgoing {
+                    socket.set_nonblocking(true).unwrap();
+                }
+                let original_path = outgoing[0].local_addr().unwrap();
+                let new_path = outgoing[1].local_addr().unwrap();
+
+                std::thread::spawn(move || {
+                    let mut dialer_addr = None;
+                    let mut buf = [0u8; 2048];
+                    loop {
+                        if let Ok((n, from)) = incoming.recv_from(&mut buf) {
+                            dialer_addr = Some(from);
+                            let active =
+                                migrated.load(std::sync::atomic::Ordering::SeqCst) as usize;
+                            let _ = outgoing[active].send_to(&buf[..n], listener_addr);
+                        }
+                        for socket in &outgoing {
+                            if let (Ok((n, _)), Some(addr)) =
+                                (socket.recv_from(&mut buf), dialer_addr)
+                            {
+                                let _ = incoming.send_to(&buf[..n], addr);
+                            }
+                        }
+                        std::thread::sleep(std::time::Duration::from_millis(1));
+                    }
+                });
+
+                (relay_addr, original_path, new_path)
+            }
+
+            async fn listener(
+                addr: Multiaddr,
+                mut ready_tx: futures::channel::mpsc::Sender<Multiaddr>,
+            ) -> Arc<QuicMuxer> {
+                let config = Config::new(&Keypair::generate_ed25519()).allow_migration(false);
+                let transport = QuicTransport::new(config);
+                let mut listener = transport.listen_on(addr).unwrap();
+                loop {
+                    match listener.next().await.unwrap().unwrap() {
+                        ListenerEvent::NewAddress(listen_addr) => {
+                            ready_tx.send(listen_addr).await.unwrap();
+                        }
+                        ListenerEvent::Upgrade { upgrade, .. } => {
+                            let (_, muxer) = upgrade.await.unwrap();
+                            let muxer = Arc::new(muxer);
+                            drive_in_background(muxer.clone());
+                            return muxer;
+                        }
+                        ListenerEvent::Error(e) => panic!("Unexpected listener error: {}", e),
+                        ListenerEvent::AddressExpired(_) => {}
+                    }
+                }
+            }
+
+            async fn dialer(
+                relay_addr: Multiaddr,
+                migrated: Arc<std::sync::atomic::AtomicBool>,
+            ) -> bool {
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+                let (_, muxer) = transport.dial(relay_addr).unwrap().await.unwrap();
+                let muxer = Arc::new(muxer);
+
+                // Keep this side driven continuously from here on, the same as
+                // the listener: otherwise a stray retransmission left over from
+                // the first `ping()` can sit unread in the socket buffer across
+                // the whole migration below and get folded into the second
+                // `ping()`'s "did the rtt change" check the moment it's finally
+                // drained, making it look answered when it never was.
+                drive_in_background(muxer.clone());
+
+                // Establish the path the listener sees before migrating.
+                muxer.ping().await.unwrap();
+
+                // Give the background driver above a moment to actually drain
+                // any trailing retransmission of that first ping's own ack;
+                // otherwise it can sit in the socket buffer until the second
+                // `ping()` below drains it instead, making that one look
+                // answered the instant it's polled regardless of which path it
+                // actually probed.
+                async_std::task::sleep(Duration::from_millis(300)).await;
+
+                migrated.store(true, std::sync::atomic::Ordering::SeqCst);
+
+                // Cross the now-switched relay socket; with migration disabled
+                // on the listener, it never answers a ping arriving from this
+                // address, so this only resolves via the timeout below.
+                let ping_answered = match future::select(
+                    Box::pin(muxer.ping()),
+                    Delay::new(Duration::from_secs(2)),
+                )
+                .await
+                {
+                    future::Either::Left((result, _)) => result.is_ok(),
+                    future::Either::Right(((), _)) => false,
+                };
+
+                ping_answered
+            }
+
+            let addr: Multiaddr = "/ip4/127.0.0.1/udp/0/quic".parse().unwrap();
+            let (ready_tx, mut ready_rx) = futures::channel::mpsc::channel(1);
+            let listener_task = async_std::task::spawn(listener(addr, ready_tx));
+
+            let listen_addr = async_std::task::block_on(ready_rx.next()).unwrap();
+            let listen_socket_addr = multiaddr_to_socketaddr(listen_addr, true).unwrap();
+
+            let migrated = Arc::new(std::sync::atomic::AtomicBool::new(false));
+            let (relay_addr, original_path, _new_path) =
+                spawn_relay(listen_socket_addr, migrated.clone());
+
+            let ping_answered = async_std::task::block_on(dialer(
+                socketaddr_to_quic_multiaddr(relay_addr),
+                migrated,
+            ));
+            assert!(
+                !ping_answered,
+                "a ping across the migrated path was answered despite allow_migration(false)"
+            );
+
+            let listener_muxer = async_std::task::block_on(listener_task);
+            assert_eq!(listener_muxer.remote_address(), original_path);
+        }
+
+        /// This transport doesn't implement connection migration yet, so a
+        /// freshly established connection only ever has the one path it
+        /// completed its handshake on; `path_validated` should accordingly read
+        /// `true` immediately, rather than, say, defaulting to `false` and
+        /// waiting for a PATH_CHALLENGE/RESPONSE exchange that will never
+        /// happen.
+        #[test]
+        fn path_validated_is_true_immediately_without_migration_support() {
+            env_logger::try_init().ok();
+
+            async fn listener(
+                addr: Multiaddr,
+                mut ready_tx: futures::channel::mpsc::Sender<Multiaddr>,
+            ) {
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+                let mut listener = transport.listen_on(addr).unwrap();
+                loop {
+                    match listener.next().await.unwrap().unwrap() {
+                        ListenerEvent::NewAddress(listen_addr) => {
+                            ready_tx.send(listen_addr).await.unwrap();
+                        }
+                        ListenerEvent::Upgrade { upgrade, .. } => {
+                            let (_, muxer) = upgrade.await.unwrap();
+                            assert!(muxer.path_validated());
+                            return;
+                        }
+                        ListenerEvent::Error(e) => panic!("Unexpected listener error: {}", e),
+                        ListenerEvent::AddressExpired(_) => {}
+                    }
+                }
+            }
+
+            async fn dialer(mut ready_rx: futures::channel::mpsc::Receiver<Multiaddr>) {
+                let addr = ready_rx.next().await.unwrap();
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+                let (_, muxer) = transport.dial(addr).unwrap().await.unwrap();
+                assert!(muxer.path_validated());
+                drive_in_background(Arc::new(muxer));
+            }
+
+            let addr: Multiaddr = "/ip4/127.0.0.1/udp/0/quic".parse().unwrap();
+            let (ready_tx, ready_rx) = futures::channel::mpsc::channel(1);
+            let listener = async_std::task::spawn(listener(addr, ready_tx));
+            async_std::task::block_on(dialer(ready_rx));
+            async_std::task::block_on(listener);
+        }
+
+        /// Without [`Config::close_timeout`], closing a connection whose peer
+        /// has vanished (dropped its socket without ever sending its own
+        /// `CONNECTION_CLOSE` or acknowledging ours) would only finish draining
+        /// once `quinn_proto`'s own closing timer lapses; with it set, [`close`](
+        /// StreamMuxer::close) gives up sooner, and [`num_connections`](
+        /// QuicListenStream::num_connections) reflects the handle as reclaimed
+        /// once it does.
+        #[test]
+        fn close_timeout_reclaims_the_handle_once_the_peer_has_vanished() {
+            env_logger::try_init().ok();
+
+            let close_timeout = Duration::from_millis(200);
+
+            async fn listener(
+                addr: Multiaddr,
+                close_timeout: Duration,
+                mut ready_tx: futures::channel::mpsc::Sender<Multiaddr>,
+            ) {
+                let transport = QuicTransport::new(
+                    Config::new(&Keypair::generate_ed25519()).close_timeout(close_timeout),
+                );
+                let mut listener = transport.listen_on(addr).unwrap();
+                let muxer = loop {
+                    match listener.next().await.unwrap().unwrap() {
+                        ListenerEvent::NewAddress(listen_addr) => {
+                            ready_tx.send(listen_addr).await.unwrap();
+                        }
+                        ListenerEvent::Upgrade { upgrade, .. } => break upgrade.await.unwrap().1,
+                        ListenerEvent::Error(e) => panic!("Unexpected listener error: {}", e),
+                        ListenerEvent::AddressExpired(_) => {}
+                    }
+                };
+                assert_eq!(listener.num_connections(), 1);
+
+                // Give the dialer's task a moment to actually drop its socket
+                // before closing, so the `CONNECTION_CLOSE` this sends truly
+                // goes unanswered rather than racing the dialer's own exit.
+                async_std::task::sleep(Duration::from_millis(50)).await;
+
+                let start = std::time::Instant::now();
+                future::poll_fn(|cx| StreamMuxer::close(&muxer, cx))
+                    .await
+                    .unwrap();
+                let elapsed = start.elapsed();
+                assert!(
+                    elapsed < close_timeout * 4,
+                    "close() should give up within roughly its configured close_timeout \
+                     once the peer is gone, took {:?}",
+                    elapsed
+                );
+
+                drop(muxer);
+                assert_eq!(listener.num_connections(), 0);
+            }
+
+            async fn dialer(mut ready_rx: futures::channel::mpsc::Receiver<Multiaddr>) {
+                let addr = ready_rx.next().await.unwrap();
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+                transport.dial(addr).unwrap().await.unwrap();
+                // Dropping the dialled connection (and so its socket) here,
+                // without closing it, mirrors a peer process that has simply
+                // vanished: nothing further it's sent ever gets answered.
+            }
+
+            let addr: Multiaddr = "/ip4/127.0.0.1/udp/0/quic".parse().unwrap();
+            let (ready_tx, ready_rx) = futures::channel::mpsc::channel(1);
+            let listener = async_std::task::spawn(listener(addr, close_timeout, ready_tx));
+            async_std::task::block_on(dialer(ready_rx));
+            async_std::task::block_on(listener);
+        }
+
+        /// [`StreamMuxer::close`] only reports `Ready` once `quinn_proto` reaches
+        /// `Drained`, which can't happen before the `CONNECTION_CLOSE` it queues
+        /// up front has actually been handed to the socket; this confirms that
+        /// guarantee directly via [`QuicMuxer::stats`]'s transmit counter, rather
+        /// than relying on `Drained` implying it. (`quinn_proto` 0.7.3 doesn't
+        /// bump its own `frame_tx.connection_close` counter for a locally
+        /// initiated close, so the packet-level counter is what's actually
+        /// observable here.)
+        #[test]
+        fn close_transmits_the_close_frame_before_resolving() {
+            env_logger::try_init().ok();
+
+            async fn listener(
+                addr: Multiaddr,
+                mut ready_tx: futures::channel::mpsc::Sender<Multiaddr>,
+            ) {
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+                let mut listener = transport.listen_on(addr).unwrap();
+                let muxer = loop {
+                    match listener.next().await.unwrap().unwrap() {
+                        ListenerEvent::NewAddress(listen_addr) => {
+                            ready_tx.send(listen_addr).await.unwrap();
+                        }
+                        ListenerEvent::Upgrade { upgrade, .. } => break upgrade.await.unwrap().1,
+                        ListenerEvent::Error(e) => panic!("Unexpected listener error: {}", e),
+                        ListenerEvent::AddressExpired(_) => {}
+                    }
+                };
+
+                let transmits_before_close = muxer.stats().udp_tx.transmits;
+
+                future::poll_fn(|cx| StreamMuxer::close(&muxer, cx))
+                    .await
+                    .unwrap();
+
+                let transmits_after_close = muxer.stats().udp_tx.transmits;
+                assert!(
+                    transmits_after_close > transmits_before_close,
+                    "close() should not have resolved before at least one more \
+                     packet - the CONNECTION_CLOSE it queues - was handed to the \
+                     socket, went from {} to {} transmits",
+                    transmits_before_close,
+                    transmits_after_close
+                );
+            }
+
+            async fn dialer(mut ready_rx: futures::channel::mpsc::Receiver<Multiaddr>) {
+                let addr = ready_rx.next().await.unwrap();
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+                let (_, muxer) = transport.dial(addr).unwrap().await.unwrap();
+                drive_in_background(Arc::new(muxer));
+            }
+
+            let addr: Multiaddr = "/ip4/127.0.0.1/udp/0/quic".parse().unwrap();
+            let (ready_tx, ready_rx) = futures::channel::mpsc::channel(1);
+            let listener = async_std::task::spawn(listener(addr, ready_tx));
+            async_std::task::block_on(dialer(ready_rx));
+            async_std::task::block_on(listener);
+        }
+
+        /// `quinn_proto` 0.7.3 has no public way to send a genuine transport-level
+        /// `CONNECTION_CLOSE`, so `close_with_transport_error` folds `code` into
+        /// an application close instead (see its doc comment); this just
+        /// confirms the peer still recovers both pieces of information - the
+        /// numeric code and the reason text - via `close_reason` once it notices.
+        #[test]
+        fn close_with_transport_error_surfaces_code_and_reason_to_the_peer() {
+            env_logger::try_init().ok();
+
+            async fn listener(
+                addr: Multiaddr,
+                mut ready_tx: futures::channel::mpsc::Sender<Multiaddr>,
+            ) {
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+                let mut listener = transport.listen_on(addr).unwrap();
+                let muxer = loop {
+                    match listener.next().await.unwrap().unwrap() {
+                        ListenerEvent::NewAddress(listen_addr) => {
+                            ready_tx.send(listen_addr).await.unwrap();
+                        }
+                        ListenerEvent::Upgrade { upgrade, .. } => break upgrade.await.unwrap().1,
+                        ListenerEvent::Error(e) => panic!("Unexpected listener error: {}", e),
+                        ListenerEvent::AddressExpired(_) => {}
+                    }
+                };
+
+                assert!(
+                    future::poll_fn(|cx| muxer.poll_event(cx)).await.is_err(),
+                    "poll_event should report the dialer's transport-error close as a lost connection"
+                );
+                let reason = muxer
+                    .close_reason()
+                    .expect("poll_event observing the loss should have recorded why");
+                let reason = reason.to_string();
+                assert!(
+                    reason.contains("rude"),
+                    "expected the reason text to survive the round trip, got {:?}",
+                    reason
+                );
+                assert!(
+                    reason.contains(
+                        &u64::from(quinn_proto::TransportErrorCode::PROTOCOL_VIOLATION).to_string()
+                    ),
+                    "expected the transport error code to survive the round trip, got {:?}",
+                    reason
+                );
+            }
+
+            async fn dialer(mut ready_rx: futures::channel::mpsc::Receiver<Multiaddr>) {
+                let addr = ready_rx.next().await.unwrap();
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+                let (_, muxer) = transport.dial(addr).unwrap().await.unwrap();
+                // This side's own handshake can complete - and so `dial` can
+                // resolve - before the listener has processed this side's
+                // `Finished` and reached `Connected` on its own; closing too
+                // eagerly would race that and fail the listener's `Upgrade`
+                // outright instead of exercising `poll_event`/`close_reason` as
+                // intended. Give it a moment first, the same as
+                // `close_timeout_reclaims_the_handle_once_the_peer_has_vanished`.
+                async_std::task::sleep(Duration::from_millis(50)).await;
+                muxer
+                    .close_with_transport_error(
+                        quinn_proto::TransportErrorCode::PROTOCOL_VIOLATION,
+                        Bytes::from_static(b"rude"),
+                    )
+                    .await
+                    .unwrap();
+            }
+
+            let addr: Multiaddr = "/ip4/127.0.0.1/udp/0/quic".parse().unwrap();
+            let (ready_tx, ready_rx) = futures::channel::mpsc::channel(1);
+            let listener = async_std::task::spawn(listener(addr, ready_tx));
+            async_std::task::block_on(dialer(ready_rx));
+            async_std::task::block_on(listener);
+        }
+
+        /// [`QuicMuxer::closed`] should resolve with the peer's close reason on
+        /// its own, without anything else ever calling `poll_event` on the
+        /// muxer.
+        #[test]
+        fn closed_resolves_with_the_reason_once_the_peer_closes() {
+            env_logger::try_init().ok();
+
+            async fn listener(
+                addr: Multiaddr,
+                mut ready_tx: futures::channel::mpsc::Sender<Multiaddr>,
+            ) {
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+                let mut listener = transport.listen_on(addr).unwrap();
+                let muxer = loop {
+                    match listener.next().await.unwrap().unwrap() {
+                        ListenerEvent::NewAddress(listen_addr) => {
+                            ready_tx.send(listen_addr).await.unwrap();
+                        }
+                        ListenerEvent::Upgrade { upgrade, .. } => break upgrade.await.unwrap().1,
+                        ListenerEvent::Error(e) => panic!("Unexpected listener error: {}", e),
+                        ListenerEvent::AddressExpired(_) => {}
+                    }
+                };
+
+                let reason = muxer.closed().await.to_string();
+                assert!(
+                    reason.contains("bye"),
+                    "expected the peer's close reason to survive the round trip, got {:?}",
+                    reason
+                );
+            }
+
+            async fn dialer(mut ready_rx: futures::channel::mpsc::Receiver<Multiaddr>) {
+                let addr = ready_rx.next().await.unwrap();
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+                let (_, muxer) = transport.dial(addr).unwrap().await.unwrap();
+                // See `close_with_transport_error_surfaces_code_and_reason_to_the_peer`
+                // for why this waits before closing.
+                async_std::task::sleep(Duration::from_millis(50)).await;
+                muxer
+                    .close_with_transport_error(
+                        quinn_proto::TransportErrorCode::NO_ERROR,
+                        Bytes::from_static(b"bye"),
+                    )
+                    .await
+                    .unwrap();
+            }
+
+            let addr: Multiaddr = "/ip4/127.0.0.1/udp/0/quic".parse().unwrap();
+            let (ready_tx, ready_rx) = futures::channel::mpsc::channel(1);
+            let listener = async_std::task::spawn(listener(addr, ready_tx));
+            async_std::task::block_on(dialer(ready_rx));
+            async_std::task::block_on(listener);
+        }
+
+        #[test]
+        fn idle_timeout_negotiates_the_minimum_of_both_sides() {
+            env_logger::try_init().ok();
+
+            async fn listener(
+                addr: Multiaddr,
+                mut ready_tx: futures::channel::mpsc::Sender<Multiaddr>,
+                lost_tx: futures::channel::oneshot::Sender<Duration>,
+            ) {
+                let transport = QuicTransport::new(
+                    Config::new(&Keypair::generate_ed25519())
+                        .max_idle_timeout(Duration::from_secs(5)),
+                );
+                let mut listener = transport.listen_on(addr).unwrap();
+                loop {
+                    match listener.next().await.unwrap().unwrap() {
+                        ListenerEvent::NewAddress(listen_addr) => {
+                            ready_tx.send(listen_addr).await.unwrap();
+                        }
+                        ListenerEvent::Upgrade { upgrade, .. } => {
+                            let (_, muxer) = upgrade.await.unwrap();
+                            assert_eq!(muxer.effective_idle_timeout(), Duration::from_secs(5));
+
+                            let started = std::time::Instant::now();
+                            future::poll_fn(|cx| muxer.poll_event(cx))
+                                .await
+                                .expect_err("the dialer went away without a connection close, so this side should only notice via its own idle timeout");
+                            lost_tx.send(started.elapsed()).unwrap();
+                            return;
+                        }
+                        ListenerEvent::Error(e) => panic!("Unexpected listener error: {}", e),
+                        ListenerEvent::AddressExpired(_) => {}
+                    }
+                }
+            }
+
+            let addr: Multiaddr = "/ip4/127.0.0.1/udp/0/quic".parse().unwrap();
+            let (ready_tx, mut ready_rx) = futures::channel::mpsc::channel(1);
+            let (lost_tx, lost_rx) = futures::channel::oneshot::channel();
+            let listener_task = async_std::task::spawn(listener(addr, ready_tx, lost_tx));
+
+            async_std::task::block_on(async {
+                let listen_addr = ready_rx.next().await.unwrap();
+
+                let dialer = QuicTransport::new(
+                    Config::new(&Keypair::generate_ed25519())
+                        .max_idle_timeout(Duration::from_millis(300)),
+                );
+                let (_, muxer) = dialer.dial(listen_addr).unwrap().await.unwrap();
+                assert_eq!(muxer.effective_idle_timeout(), Duration::from_millis(300));
+
+                // Drop the muxer, and with it the dialer's endpoint and socket,
+                // right after the handshake completes instead of driving it any
+                // further: with nothing left to send a `CONNECTION_CLOSE` or
+                // respond to keep-alives, the listener can only notice the
+                // connection is gone once its own idle timer fires.
+                drop(muxer);
+
+                let elapsed = lost_rx.await.unwrap();
+                assert!(
+                    elapsed < Duration::from_secs(2),
+                    "the negotiated idle timeout should be the minimum of both \
+                     sides' configured values (300ms here), not the listener's \
+                     own 5s config on its own; detecting the lost connection \
+                     took {:?}",
+                    elapsed
+                );
+            });
+            async_std::task::block_on(listener_task);
+        }
+
+        /// Relays a bulk transfer through a deliberately reordering UDP relay
+        /// twice, once per [`Config::packet_threshold`], with
+        /// [`Config::time_threshold`] pinned to the same very large value on both
+        /// runs so that only the packet-count-based loss detection can fire, and
+        /// confirms the run with the higher threshold needed fewer total
+        /// transmits: with the low (default) threshold, the relay's reordering
+        /// is enough to make `quinn_proto` declare some in-flight packets lost
+        /// and spuriously retransmit them, while the higher threshold tolerates
+        /// the same reordering without doing so.
+        #[test]
+        fn raising_the_reordering_threshold_reduces_spurious_retransmits() {
+            env_logger::try_init().ok();
+
+            /// Forwards dialer-to-listener datagrams with a fixed reordering
+            /// pattern: the first datagram of every batch of `BATCH` is held
+            /// back and only forwarded after the other `BATCH - 1` have
+            /// already gone out, so it always arrives `BATCH - 1` packets late.
+            /// Listener-to-dialer traffic (acks) passes straight through.
+            fn spawn_reordering_relay(listener_addr: SocketAddr) -> SocketAddr {
+                const BATCH: u32 = 6;
+
+                fn bind_with_large_buffers() -> StdUdpSocket {
+                    let socket = socket2::Socket::new(
+                        socket2::Domain::IPV4,
+                        socket2::Type::DGRAM,
+                        Some(socket2::Protocol::UDP),
+                    )
+                    .unwrap();
+                    socket
+                        .bind(&SocketAddr::from((std::net::Ipv4Addr::LOCALHOST, 0)).into())
+                        .unwrap();
+                    // Large enough that the relay thread's own scheduling
+                    // latency never causes a genuine drop, so every packet
+                    // this test loses is lost to the deliberate reordering
+                    // below, not to an overwhelmed socket buffer.
+                    socket.set_recv_buffer_size(4 * 1024 * 1024).unwrap();
+                    socket.set_send_buffer_size(4 * 1024 * 1024).unwrap();
+                    socket.set_nonblocking(true).unwrap();
+                    socket.into()
+                }
+
+                let incoming = bind_with_large_buffers();
+                let relay_addr = incoming.local_addr().unwrap();
+                let outgoing = bind_with_large_buffers();
+
+                std::thread::spawn(move || {
+                    let mut dialer_addr = None;
+                    let mut held: Option<Vec<u8>> = None;
+                    let mut forwarded_since_held = 0u32;
+                    let mut buf = [0u8; 2048];
+                    loop {
+                        if let Ok((n, from)) = incoming.recv_from(&mut buf) {
+                            dialer_addr = Some(from);
+                            match &held {
+                                None => {
+                                    held = Some(buf[..n].to_vec());
+                                    forwarded_since_held = 0;
+                                }
+                                Some(_) => {
+                                    let _ = outgoing.send_to(&buf[..n], listener_addr);
+                                    forwarded_since_held += 1;
+                                    if forwarded_since_held >= BATCH - 1 {
+                                        let packet = held.take().unwrap();
+                                        let _ = outgoing.send_to(&packet, listener_addr);
+                                    }
+                                }
+                            }
+                        }
+                        if let Ok((n, _)) = outgoing.recv_from(&mut buf) {
+                            if let Some(addr) = dialer_addr {
+                                let _ = incoming.send_to(&buf[..n], addr);
+                            }
+                        }
+                        std::thread::sleep(std::time::Duration::from_micros(200));
+                    }
+                });
+
+                relay_addr
+            }
+
+            async fn listener(
+                addr: Multiaddr,
+                mut ready_tx: futures::channel::mpsc::Sender<Multiaddr>,
+            ) {
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+                let mut listener = transport.listen_on(addr).unwrap();
+                let muxer = loop {
+                    match listener.next().await.unwrap().unwrap() {
+                        ListenerEvent::NewAddress(listen_addr) => {
+                            ready_tx.send(listen_addr).await.unwrap();
+                        }
+                        ListenerEvent::Upgrade { upgrade, .. } => {
+                            let (_, muxer) = upgrade.await.unwrap();
+                            break Arc::new(muxer);
+                        }
+                        ListenerEvent::Error(e) => panic!("Unexpected listener error: {}", e),
+                        ListenerEvent::AddressExpired(_) => {}
+                    }
+                };
+
+                let (mut send, mut recv) =
+                    match future::poll_fn(|cx| muxer.poll_event(cx)).await.unwrap() {
+                        StreamMuxerEvent::InboundSubstream(substream) => (
+                            SendStream::new(&muxer, substream.id),
+                            RecvStream::new(&muxer, substream.id),
+                        ),
+                        StreamMuxerEvent::AddressChange(_) => panic!("unexpected address change"),
+                    };
+
+                let mut received = Vec::new();
+                recv.read_to_end(&mut received).await.unwrap();
+                send.write_all(b"done").await.unwrap();
+                send.close().await.unwrap();
+
+                drop(recv);
+                drive_in_background(muxer);
+            }
+
+            async fn dialer(
+                relay_addr: Multiaddr,
+                packet_threshold: u32,
+            ) -> quinn_proto::ConnectionStats {
+                // Pinned to the same very large value on every run: isolates the
+                // effect of `packet_threshold` by ensuring quinn_proto's
+                // time-threshold-based loss detection never fires on its own,
+                // regardless of how long the relay's reordering delays a packet.
+                let config = Config::new(&Keypair::generate_ed25519())
+                    .packet_threshold(packet_threshold)
+                    .time_threshold(1_000.0);
+                let transport = QuicTransport::new(config);
+                let (_, muxer) = transport.dial(relay_addr).unwrap().await.unwrap();
+                let muxer = Arc::new(muxer);
+
+                let payload = vec![0x42u8; 256 * 1024];
+                let mut bi = muxer.open_bi().unwrap();
+                bi.send.write_all(&payload).await.unwrap();
+                bi.send.close().await.unwrap();
+
+                let mut ack = Vec::new();
+                bi.recv.read_to_end(&mut ack).await.unwrap();
+                assert_eq!(ack, b"done");
+                drop(bi);
+
+                let stats = muxer.stats();
+                drive_in_background(muxer);
+                stats
+            }
+
+            async fn run(packet_threshold: u32) -> quinn_proto::ConnectionStats {
+                let addr: Multiaddr = "/ip4/127.0.0.1/udp/0/quic".parse().unwrap();
+                let (ready_tx, mut ready_rx) = futures::channel::mpsc::channel(1);
+                let listener_task = async_std::task::spawn(listener(addr, ready_tx));
+
+                let listen_addr = ready_rx.next().await.unwrap();
+                let listen_socket_addr = multiaddr_to_socketaddr(listen_addr, true).unwrap();
+                let relay_addr = spawn_reordering_relay(listen_socket_addr);
+
+                let stats =
+                    dialer(socketaddr_to_quic_multiaddr(relay_addr), packet_threshold).await;
+                listener_task.await;
+                stats
+            }
+
+            // 3 is `quinn_proto`'s own default.
+            let low_threshold = async_std::task::block_on(run(3));
+            let high_threshold = async_std::task::block_on(run(50));
+
+            assert!(
+                high_threshold.udp_tx.transmits < low_threshold.udp_tx.transmits,
+                "a higher reordering threshold should need fewer transmits for the \
+                 same payload over the same reordering relay: low threshold sent \
+                 {}, high threshold sent {}",
+                low_threshold.udp_tx.transmits,
+                high_threshold.udp_tx.transmits
+            );
+        }
+
+        /// `Config::qlog_dir` is currently a no-op (see its doc comment): there's
+        /// no qlog hook in this transport's `quinn_proto` version for it to wire
+        /// up, so it's only honest to assert what actually happens today, namely
+        /// nothing being written, rather than a real qlog file.
+        #[test]
+        #[cfg(feature = "qlog")]
+        fn qlog_dir_is_accepted_but_not_yet_written_without_upstream_support() {
+            env_logger::try_init().ok();
+
+            let qlog_dir =
+                std::env::temp_dir().join(format!("libp2p-quic-qlog-test-{}", std::process::id()));
+            std::fs::create_dir_all(&qlog_dir).unwrap();
+
+            async fn listener(
+                addr: Multiaddr,
+                mut ready_tx: futures::channel::mpsc::Sender<Multiaddr>,
+                qlog_dir: std::path::PathBuf,
+            ) {
+                let transport = QuicTransport::new(
+                    Config::new(&Keypair::generate_ed25519()).qlog_dir(qlog_dir),
+                );
+                let mut listener = transport.listen_on(addr).unwrap();
+                loop {
+                    match listener.next().await.unwrap().unwrap() {
+                        ListenerEvent::NewAddress(listen_addr) => {
+                            ready_tx.send(listen_addr).await.unwrap();
+                        }
+                        ListenerEvent::Upgrade { upgrade, .. } => {
+                            let (_, muxer) = upgrade.await.unwrap();
+                            drive_in_background(Arc::new(muxer));
+                            return;
+                        }
+                        ListenerEvent::Error(e) => panic!("Unexpected listener error: {}", e),
+                        ListenerEvent::AddressExpired(_) => {}
+                    }
+                }
+            }
+
+            async fn dialer(mut ready_rx: futures::channel::mpsc::Receiver<Multiaddr>) {
+                let addr = ready_rx.next().await.unwrap();
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+                let (_, muxer) = transport.dial(addr).unwrap().await.unwrap();
+                drive_in_background(Arc::new(muxer));
+            }
+
+            let addr: Multiaddr = "/ip4/127.0.0.1/udp/0/quic".parse().unwrap();
+            let (ready_tx, ready_rx) = futures::channel::mpsc::channel(1);
+            let listener = async_std::task::spawn(listener(addr, ready_tx, qlog_dir.clone()));
+            async_std::task::block_on(dialer(ready_rx));
+            async_std::task::block_on(listener);
+
+            assert!(
+                std::fs::read_dir(&qlog_dir).unwrap().next().is_none(),
+                "no qlog file should have been written yet; Config::qlog_dir is \
+                 currently a no-op pending upstream quinn_proto support"
+            );
+            std::fs::remove_dir_all(&qlog_dir).unwrap();
+        }
+
+        /// `Config::max_ack_delay` is currently a no-op (see its doc comment):
+        /// `quinn_proto` 0.7.3 exposes no public hook for it, so this only
+        /// confirms a bulk transfer still completes byte-for-byte with a
+        /// (currently inert) delay configured on both sides, rather than
+        /// asserting the ACK-overhead reduction the option is meant to
+        /// eventually provide once upstream support exists.
+        #[test]
+        fn max_ack_delay_is_accepted_but_does_not_yet_change_ack_overhead() {
+            env_logger::try_init().ok();
+
+            const BUF: &[u8] = &[0u8; 4_000_000];
+
+            async fn listener(
+                addr: Multiaddr,
+                mut ready_tx: futures::channel::mpsc::Sender<Multiaddr>,
+            ) {
+                let transport = QuicTransport::new(
+                    Config::new(&Keypair::generate_ed25519())
+                        .max_ack_delay(Duration::from_millis(200)),
+                );
+                let mut listener = transport.listen_on(addr).unwrap();
+                let muxer = loop {
+                    match listener.next().await.unwrap().unwrap() {
+                        ListenerEvent::NewAddress(listen_addr) => {
+                            ready_tx.send(listen_addr).await.unwrap();
+                        }
+                        ListenerEvent::Upgrade { upgrade, .. } => {
+                            let (_, muxer) = upgrade.await.unwrap();
+                            break Arc::new(muxer);
+                        }
+                        ListenerEvent::Error(e) => panic!("Unexpected listener error: {}", e),
+                        ListenerEvent::AddressExpired(_) => {}
+                    }
+                };
+
+                let mut recv = future::poll_fn(|cx| muxer.poll_accept_uni(cx))
+                    .await
+                    .unwrap();
+                let mut received = Vec::new();
+                recv.read_to_end(&mut received).await.unwrap();
+                assert_eq!(received.len(), BUF.len());
+
+                drop(recv);
+                drive_in_background(muxer);
+            }
+
+            async fn dialer(mut ready_rx: futures::channel::mpsc::Receiver<Multiaddr>) {
+                let addr = ready_rx.next().await.unwrap();
+                let transport = QuicTransport::new(
+                    Config::new(&Keypair::generate_ed25519())
+                        .max_ack_delay(Duration::from_millis(200)),
+                );
+                let (_, muxer) = transport.dial(addr).unwrap().await.unwrap();
+                let muxer = Arc::new(muxer);
+
+                let mut send = muxer.open_uni().unwrap();
+                send.write_all(BUF).await.unwrap();
+                send.close().await.unwrap();
+                drive_in_background(muxer);
+            }
+
+            let addr: Multiaddr = "/ip4/127.0.0.1/udp/0/quic".parse().unwrap();
+            let (ready_tx, ready_rx) = futures::channel::mpsc::channel(1);
+            let listener = async_std::task::spawn(listener(addr, ready_tx));
+            async_std::task::block_on(dialer(ready_rx));
+            async_std::task::block_on(listener);
+        }
+
+        /// [`QuicMuxer::set_connection_priority`] only changes anything once
+        /// more than one connection is actually stalled on the socket's write
+        /// readiness at the same time - exercised here directly against the
+        /// transmit scheduler's own bookkeeping via test-only `Endpoint`
+        /// helpers, since a real loopback UDP send essentially never blocks
+        /// long enough to saturate the socket with traffic from this test.
+        #[test]
+        fn connection_priority_decides_who_leads_once_both_are_stalled() {
+            let config = Config::new(&Keypair::generate_ed25519());
+            let socket = StdUdpSocket::bind((std::net::Ipv4Addr::LOCALHOST, 0)).unwrap();
+            let endpoint = Endpoint::from_socket(&config, socket).unwrap();
+
+            let bulk = quinn_proto::ConnectionHandle(0);
+            let interactive = quinn_proto::ConnectionHandle(1);
+            endpoint.set_connection_priority(interactive, 10);
+
+            let waker = futures::task::noop_waker();
+            assert!(
+                endpoint.is_transmit_leader(bulk) && endpoint.is_transmit_leader(interactive),
+                "neither has stalled yet, so both are free to proceed on their own"
+            );
+
+            endpoint.stall_for_test(bulk, waker.clone());
+            assert!(
+                endpoint.is_transmit_leader(bulk),
+                "the only stalled connection leads by default"
+            );
+
+            endpoint.stall_for_test(interactive, waker);
+            assert!(
+                endpoint.is_transmit_leader(interactive),
+                "the higher-priority connection should lead once both are stalled"
+            );
+            assert!(
+                !endpoint.is_transmit_leader(bulk),
+                "the lower-priority connection should yield while a higher-priority one is stalled"
+            );
+        }
+
+        /// [`Config::auto_migrate`] is currently a no-op: this transport has no
+        /// `rebind`-style API for a background watcher to drive even if it
+        /// detected a local address change, the same gap documented on
+        /// [`QuicMuxer::path_validated`]. Enabling it should still be harmless —
+        /// a connection opened with it set establishes and carries traffic
+        /// exactly as one without it, and never migrates off the single path it
+        /// completed its handshake on.
+        #[test]
+        fn auto_migrate_is_accepted_but_does_not_yet_migrate_anything() {
+            env_logger::try_init().ok();
+
+            async fn listener(
+                addr: Multiaddr,
+                mut ready_tx: futures::channel::mpsc::Sender<Multiaddr>,
+            ) {
+                let transport = QuicTransport::new(
+                    Config::new(&Keypair::generate_ed25519()).auto_migrate(true),
+                );
+                let mut listener = transport.listen_on(addr).unwrap();
+                loop {
+                    match listener.next().await.unwrap().unwrap() {
+                        ListenerEvent::NewAddress(listen_addr) => {
+                            ready_tx.send(listen_addr).await.unwrap();
+                        }
+                        ListenerEvent::Upgrade { upgrade, .. } => {
+                            let (_, muxer) = upgrade.await.unwrap();
+                            assert!(muxer.path_validated());
+                            drive_in_background(Arc::new(muxer));
+                            return;
+                        }
+                        ListenerEvent::Error(e) => panic!("Unexpected listener error: {}", e),
+                        ListenerEvent::AddressExpired(_) => {}
+                    }
+                }
+            }
+
+            async fn dialer(mut ready_rx: futures::channel::mpsc::Receiver<Multiaddr>) {
+                let addr = ready_rx.next().await.unwrap();
+                let transport = QuicTransport::new(
+                    Config::new(&Keypair::generate_ed25519()).auto_migrate(true),
+                );
+                let (_, muxer) = transport.dial(addr).unwrap().await.unwrap();
+                assert!(muxer.path_validated());
+                drive_in_background(Arc::new(muxer));
+            }
+
+            let addr: Multiaddr = "/ip4/127.0.0.1/udp/0/quic".parse().unwrap();
+            let (ready_tx, ready_rx) = futures::channel::mpsc::channel(1);
+            let listener = async_std::task::spawn(listener(addr, ready_tx));
+            async_std::task::block_on(dialer(ready_rx));
+            async_std::task::block_on(listener);
+        }
+
+        /// [`Config::migration_probing`] is currently a no-op for the same
+        /// reason documented on its doc comment: `quinn_proto` 0.7.3 never
+        /// spontaneously probes a path on a stable connection in the first
+        /// place, with or without this set, so there's nothing to disable yet -
+        /// confirms `ConnectionStats::frame_tx::path_challenge` stays at zero on
+        /// both sides of a connection that never migrates.
+        #[test]
+        fn migration_probing_disabled_sends_no_spontaneous_path_challenge() {
+            env_logger::try_init().ok();
+
+            async fn listener(
+                addr: Multiaddr,
+                mut ready_tx: futures::channel::mpsc::Sender<Multiaddr>,
+            ) {
+                let transport = QuicTransport::new(
+                    Config::new(&Keypair::generate_ed25519()).migration_probing(false),
+                );
+                let mut listener = transport.listen_on(addr).unwrap();
+                loop {
+                    match listener.next().await.unwrap().unwrap() {
+                        ListenerEvent::NewAddress(listen_addr) => {
+                            ready_tx.send(listen_addr).await.unwrap();
+                        }
+                        ListenerEvent::Upgrade { upgrade, .. } => {
+                            let (_, muxer) = upgrade.await.unwrap();
+                            async_std::task::sleep(Duration::from_millis(300)).await;
+                            assert_eq!(muxer.stats().frame_tx.path_challenge, 0);
+                            drive_in_background(Arc::new(muxer));
+                            return;
+                        }
+                        ListenerEvent::Error(e) => panic!("Unexpected listener error: {}", e),
+                        ListenerEvent::AddressExpired(_) => {}
+                    }
+                }
+            }
+
+            async fn dialer(mut ready_rx: futures::channel::mpsc::Receiver<Multiaddr>) {
+                let addr = ready_rx.next().await.unwrap();
+                let transport = QuicTransport::new(
+                    Config::new(&Keypair::generate_ed25519()).migration_probing(false),
+                );
+                let (_, muxer) = transport.dial(addr).unwrap().await.unwrap();
+                async_std::task::sleep(Duration::from_millis(300)).await;
+                assert_eq!(muxer.stats().frame_tx.path_challenge, 0);
+                drive_in_background(Arc::new(muxer));
+            }
+
+            let addr: Multiaddr = "/ip4/127.0.0.1/udp/0/quic".parse().unwrap();
+            let (ready_tx, ready_rx) = futures::channel::mpsc::channel(1);
+            let listener = async_std::task::spawn(listener(addr, ready_tx));
+            async_std::task::block_on(dialer(ready_rx));
+            async_std::task::block_on(listener);
+        }
+
+        /// Lets a connection sit idle after a round trip and confirms
+        /// [`QuicMuxer::idle_duration`] grows to reflect it, then sends another
+        /// round trip and confirms it drops back down - unlike
+        /// [`Config::max_idle_timeout`], which is driven by `quinn_proto`'s own
+        /// protocol-level traffic (ACKs, keep-alives) and so would never notice
+        /// an application that's gone quiet on an otherwise-healthy connection.
+        #[test]
+        fn idle_duration_grows_while_quiet_and_resets_on_traffic() {
+            env_logger::try_init().ok();
+
+            async fn listener(
+                addr: Multiaddr,
+                mut ready_tx: futures::channel::mpsc::Sender<Multiaddr>,
+            ) {
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+                let mut listener = transport.listen_on(addr).unwrap();
+                let muxer = loop {
+                    match listener.next().await.unwrap().unwrap() {
+                        ListenerEvent::NewAddress(listen_addr) => {
+                            ready_tx.send(listen_addr).await.unwrap();
+                        }
+                        ListenerEvent::Upgrade { upgrade, .. } => {
+                            let (_, muxer) = upgrade.await.unwrap();
+                            break Arc::new(muxer);
+                        }
+                        ListenerEvent::Error(e) => panic!("Unexpected listener error: {}", e),
+                        ListenerEvent::AddressExpired(_) => {}
+                    }
+                };
+
+                for _ in 0..2 {
+                    let id = match future::poll_fn(|cx| muxer.poll_event(cx)).await.unwrap() {
+                        StreamMuxerEvent::InboundSubstream(substream) => substream.id,
+                        StreamMuxerEvent::AddressChange(_) => {
+                            panic!("did not expect an address change")
+                        }
+                    };
+                    let mut recv = RecvStream::new(&muxer, id);
+                    let mut received = Vec::new();
+                    recv.read_to_end(&mut received).await.unwrap();
+                    let mut send = SendStream::new(&muxer, id);
+                    send.write_all(&received).await.unwrap();
+                    send.close().await.unwrap();
+                }
+
+                drive_in_background(muxer);
+            }
+
+            async fn dialer(mut ready_rx: futures::channel::mpsc::Receiver<Multiaddr>) {
+                let addr = ready_rx.next().await.unwrap();
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+                let (_, muxer) = transport.dial(addr).unwrap().await.unwrap();
+                let muxer = Arc::new(muxer);
+
+                async fn round_trip(muxer: &Arc<QuicMuxer>) {
+                    let mut stream = muxer.open_bi().unwrap();
+                    stream.send.write_all(b"ping").await.unwrap();
+                    stream.send.close().await.unwrap();
+                    let mut received = Vec::new();
+                    stream.recv.read_to_end(&mut received).await.unwrap();
+                    assert_eq!(received, b"ping");
+                }
+
+                round_trip(&muxer).await;
+                assert!(
+                    muxer.idle_duration() < Duration::from_millis(300),
+                    "idle_duration should be small right after a round trip"
+                );
+
+                async_std::task::sleep(Duration::from_millis(300)).await;
+                assert!(
+                    muxer.idle_duration() >= Duration::from_millis(300),
+                    "idle_duration should have grown by at least the time spent sleeping"
+                );
+
+                round_trip(&muxer).await;
+                assert!(
+                    muxer.idle_duration() < Duration::from_millis(300),
+                    "idle_duration should have reset after another round trip"
+                );
+
+                drive_in_background(muxer);
+            }
+
+            let addr: Multiaddr = "/ip4/127.0.0.1/udp/0/quic".parse().unwrap();
+            let (ready_tx, ready_rx) = futures::channel::mpsc::channel(1);
+            let listener_task = async_std::task::spawn(listener(addr, ready_tx));
+            async_std::task::block_on(dialer(ready_rx));
+            async_std::task::block_on(listener_task);
+        }
+
+        /// Configures the listener's [`Config::max_unvalidated_handshake_bytes`]
+        /// far below what even a single handshake response packet needs, and
+        /// confirms the dial never completes: every transmit the server tries
+        /// to send while the dialer's address is still unvalidated gets dropped
+        /// once the cap is hit, so no amount of waiting turns up a handshake
+        /// response on the wire.
+        #[test]
+        fn max_unvalidated_handshake_bytes_stops_an_unvalidated_server_from_responding() {
+            // As with `stateless_reset_key_lets_a_restarted_listener_reset_an_old_connection`,
+            // juggling a listener, a dialer, and a timeout future concurrently
+            // overflows a default 2 MiB test thread stack.
+            std::thread::Builder::new()
+                .stack_size(8 * 1024 * 1024)
+                .spawn(run)
+                .unwrap()
+                .join()
+                .unwrap();
+
+            fn run() {
+                env_logger::try_init().ok();
+
+                async fn listener(
+                    addr: Multiaddr,
+                    mut ready_tx: futures::channel::mpsc::Sender<Multiaddr>,
+                ) {
+                    let config = Config::new(&Keypair::generate_ed25519())
+                        .max_unvalidated_handshake_bytes(1);
+                    let transport = QuicTransport::new(config);
+                    let mut listener = transport.listen_on(addr).unwrap();
+                    loop {
+                        match listener.next().await.unwrap().unwrap() {
+                            ListenerEvent::NewAddress(listen_addr) => {
+                                ready_tx.send(listen_addr).await.unwrap();
+                            }
+                            ListenerEvent::Upgrade { upgrade, .. } => {
+                                // Never actually reached: the dialer's timeout
+                                // below fires first, since the server's
+                                // handshake response is capped down to nothing.
+                                let (_, muxer) = upgrade.await.unwrap();
+                                drive_in_background(Arc::new(muxer));
+                                return;
+                            }
+                            ListenerEvent::Error(e) => panic!("Unexpected listener error: {}", e),
+                            ListenerEvent::AddressExpired(_) => {}
+                        }
+                    }
+                }
+
+                async fn dialer(mut ready_rx: futures::channel::mpsc::Receiver<Multiaddr>) {
+                    let addr = ready_rx.next().await.unwrap();
+                    let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+                    let dial = transport.dial(addr).unwrap();
+
+                    let result = async_std::future::timeout(Duration::from_secs(2), dial).await;
+                    assert!(
+                        result.is_err(),
+                        "expected the dial to still be waiting on a handshake response \
+                         the capped server never sent"
+                    );
+                }
+
+                let addr: Multiaddr = "/ip4/127.0.0.1/udp/0/quic".parse().unwrap();
+                let (ready_tx, ready_rx) = futures::channel::mpsc::channel(1);
+                let listener_task = async_std::task::spawn(listener(addr, ready_tx));
+                async_std::task::block_on(dialer(ready_rx));
+                drop(listener_task);
+            }
+        }
+    }
+
+    mod stats {
+        //! Observability surfaces: RTT/throughput stats, `dump_state`, and congestion/bandwidth events.
+        use super::*;
+
+        #[test]
+        fn reports_max_datagram_size_once_handshake_completes() {
+            env_logger::try_init().ok();
+
+            async fn listener(
+                addr: Multiaddr,
+                mut ready_tx: futures::channel::mpsc::Sender<Multiaddr>,
+            ) {
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+                let mut listener = transport.listen_on(addr).unwrap();
+                loop {
+                    match listener.next().await.unwrap().unwrap() {
+                        ListenerEvent::NewAddress(listen_addr) => {
+                            ready_tx.send(listen_addr).await.unwrap();
+                        }
+                        ListenerEvent::Upgrade { upgrade, .. } => {
+                            let (_, muxer) = upgrade.await.unwrap();
+                            assert!(muxer.max_datagram_size().is_some());
+                            return;
+                        }
+                        ListenerEvent::Error(e) => panic!("Unexpected listener error: {}", e),
+                        ListenerEvent::AddressExpired(_) => {}
+                    }
+                }
+            }
+
+            async fn dialer(mut ready_rx: futures::channel::mpsc::Receiver<Multiaddr>) {
+                let addr = ready_rx.next().await.unwrap();
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+                let (_, muxer) = transport.dial(addr).unwrap().await.unwrap();
+                let muxer = Arc::new(muxer);
+                assert!(muxer.max_datagram_size().is_some());
+                drive_in_background(muxer);
+            }
+
+            let addr: Multiaddr = "/ip4/127.0.0.1/udp/0/quic".parse().unwrap();
+            let (ready_tx, ready_rx) = futures::channel::mpsc::channel(1);
+            let listener = async_std::task::spawn(listener(addr, ready_tx));
+            async_std::task::block_on(dialer(ready_rx));
+            async_std::task::block_on(listener);
+        }
+
+        /// Exercises [`QuicMuxer::ping`] on a freshly established loopback
+        /// connection: it should resolve quickly and report an RTT well under a
+        /// second, since nothing but the ping itself and its ack ever crosses
+        /// the wire.
+        #[test]
+        fn ping_reports_a_sensible_rtt_on_loopback() {
+            env_logger::try_init().ok();
+
+            async fn listener(
+                addr: Multiaddr,
+                mut ready_tx: futures::channel::mpsc::Sender<Multiaddr>,
+            ) {
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+                let mut listener = transport.listen_on(addr).unwrap();
+                loop {
+                    match listener.next().await.unwrap().unwrap() {
+                        ListenerEvent::NewAddress(listen_addr) => {
+                            ready_tx.send(listen_addr).await.unwrap();
+                        }
+                        ListenerEvent::Upgrade { upgrade, .. } => {
+                            let (_, muxer) = upgrade.await.unwrap();
+                            drive_in_background(Arc::new(muxer));
+                            return;
+                        }
+                        ListenerEvent::Error(e) => panic!("Unexpected listener error: {}", e),
+                        ListenerEvent::AddressExpired(_) => {}
+                    }
+                }
+            }
+
+            async fn dialer(mut ready_rx: futures::channel::mpsc::Receiver<Multiaddr>) {
+                let addr = ready_rx.next().await.unwrap();
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+                let (_, muxer) = transport.dial(addr).unwrap().await.unwrap();
+
+                let rtt = muxer.ping().await.unwrap();
+                assert!(
+                    rtt < std::time::Duration::from_secs(1),
+                    "a loopback ping took an unreasonably long {:?}",
+                    rtt
+                );
+
+                drive_in_background(Arc::new(muxer));
+            }
+
+            let addr: Multiaddr = "/ip4/127.0.0.1/udp/0/quic".parse().unwrap();
+            let (ready_tx, ready_rx) = futures::channel::mpsc::channel(1);
+            let listener = async_std::task::spawn(listener(addr, ready_tx));
+            async_std::task::block_on(dialer(ready_rx));
+            async_std::task::block_on(listener);
+        }
+
+        /// [`QuicMuxer::with_raw_connection`] reaches the same
+        /// `quinn_proto::Connection` the wrapped API does: a stat read through
+        /// it should match [`QuicMuxer::stats`]'s own read of the identical
+        /// counter.
+        #[test]
+        #[cfg(feature = "unstable-internals")]
+        fn with_raw_connection_reads_the_same_stats_as_the_wrapped_accessor() {
+            env_logger::try_init().ok();
+
+            async fn listener(
+                addr: Multiaddr,
+                mut ready_tx: futures::channel::mpsc::Sender<Multiaddr>,
+            ) {
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+                let mut listener = transport.listen_on(addr).unwrap();
+                loop {
+                    match listener.next().await.unwrap().unwrap() {
+                        ListenerEvent::NewAddress(listen_addr) => {
+                            ready_tx.send(listen_addr).await.unwrap();
+                        }
+                        ListenerEvent::Upgrade { upgrade, .. } => {
+                            let (_, muxer) = upgrade.await.unwrap();
+                            drive_in_background(Arc::new(muxer));
+                            return;
+                        }
+                        ListenerEvent::Error(e) => panic!("Unexpected listener error: {}", e),
+                        ListenerEvent::AddressExpired(_) => {}
+                    }
+                }
+            }
+
+            async fn dialer(mut ready_rx: futures::channel::mpsc::Receiver<Multiaddr>) {
+                let addr = ready_rx.next().await.unwrap();
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+                let (_, muxer) = transport.dial(addr).unwrap().await.unwrap();
+
+                let wrapped = muxer.stats();
+                let raw = muxer.with_raw_connection(|connection| connection.stats());
+
+                assert_eq!(raw.udp_tx.datagrams, wrapped.udp_tx.datagrams);
+
+                drive_in_background(Arc::new(muxer));
+            }
+
+            let addr: Multiaddr = "/ip4/127.0.0.1/udp/0/quic".parse().unwrap();
+            let (ready_tx, ready_rx) = futures::channel::mpsc::channel(1);
+            let listener = async_std::task::spawn(listener(addr, ready_tx));
+            async_std::task::block_on(dialer(ready_rx));
+            async_std::task::block_on(listener);
+        }
+
+        /// Has the dialler write a known number of bytes to the listener over
+        /// [`QuicMuxer::open_bi`] and confirms [`QuicListenStream::aggregate_stats`],
+        /// summed across `quinn_proto`'s own per-connection counters, grows by at
+        /// least that many bytes received; the reply going back the other way is
+        /// checked the same way against bytes sent.
+        #[test]
+        fn aggregate_stats_reflects_a_known_transfer() {
+            env_logger::try_init().ok();
+
+            async fn listener(
+                addr: Multiaddr,
+                mut ready_tx: futures::channel::mpsc::Sender<Multiaddr>,
+            ) -> QuicListenStream {
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+                let mut listener = transport.listen_on(addr).unwrap();
+                let muxer = loop {
+                    match listener.next().await.unwrap().unwrap() {
+                        ListenerEvent::NewAddress(listen_addr) => {
+                            ready_tx.send(listen_addr).await.unwrap();
+                        }
+                        ListenerEvent::Upgrade { upgrade, .. } => {
+                            let (_, muxer) = upgrade.await.unwrap();
+                            break Arc::new(muxer);
+                        }
+                        ListenerEvent::Error(e) => panic!("Unexpected listener error: {}", e),
+                        ListenerEvent::AddressExpired(_) => {}
+                    }
+                };
+
+                let id = match future::poll_fn(|cx| muxer.poll_event(cx)).await.unwrap() {
+                    StreamMuxerEvent::InboundSubstream(substream) => substream.id,
+                    StreamMuxerEvent::AddressChange(_) => {
+                        panic!("did not expect an address change")
+                    }
+                };
+                drive_in_background(muxer.clone());
+
+                let mut recv = RecvStream::new(&muxer, id);
+                let mut received = Vec::new();
+                recv.read_to_end(&mut received).await.unwrap();
+                assert_eq!(received, b"ping");
+
+                let mut send = SendStream::new(&muxer, id);
+                send.write_all(b"pong").await.unwrap();
+                send.close().await.unwrap();
+
+                listener
+            }
+
+            async fn dialer(mut ready_rx: futures::channel::mpsc::Receiver<Multiaddr>) {
+                let addr = ready_rx.next().await.unwrap();
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+                let (_, muxer) = transport.dial(addr).unwrap().await.unwrap();
+                let muxer = Arc::new(muxer);
+
+                let mut stream = muxer.open_bi().unwrap();
+                stream.send.write_all(b"ping").await.unwrap();
+                stream.send.close().await.unwrap();
+
+                let mut received = Vec::new();
+                stream.recv.read_to_end(&mut received).await.unwrap();
+                assert_eq!(received, b"pong");
+
+                drop(stream);
+                drive_in_background(muxer);
+            }
+
+            let addr: Multiaddr = "/ip4/127.0.0.1/udp/0/quic".parse().unwrap();
+            let (ready_tx, ready_rx) = futures::channel::mpsc::channel(1);
+            let listener_task = async_std::task::spawn(listener(addr, ready_tx));
+            async_std::task::block_on(dialer(ready_rx));
+            let listener = async_std::task::block_on(listener_task);
+
+            let stats = listener.aggregate_stats();
+            assert!(
+                stats.bytes_received >= 4,
+                "expected at least \"ping\"'s worth of bytes received, got {:?}",
+                stats
+            );
+            assert!(
+                stats.bytes_sent >= 4,
+                "expected at least \"pong\"'s worth of bytes sent, got {:?}",
+                stats
+            );
+        }
+
+        /// Has two dialers connect to the same listener and confirms
+        /// [`QuicListenStream::dump_state`] reports exactly that many live,
+        /// handshake-complete connections, each with a driver that has scheduled
+        /// itself a next deadline.
+        #[test]
+        fn dump_state_reflects_a_known_connection_count_and_a_running_driver() {
+            env_logger::try_init().ok();
+
+            async fn listener(
+                addr: Multiaddr,
+                mut ready_tx: futures::channel::mpsc::Sender<Multiaddr>,
+            ) -> QuicListenStream {
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+                let mut listener = transport.listen_on(addr).unwrap();
+                let mut accepted = 0;
+                loop {
+                    match listener.next().await.unwrap().unwrap() {
+                        ListenerEvent::NewAddress(listen_addr) => {
+                            ready_tx.send(listen_addr).await.unwrap();
+                        }
+                        ListenerEvent::Upgrade { upgrade, .. } => {
+                            let (_, muxer) = upgrade.await.unwrap();
+                            drive_in_background(Arc::new(muxer));
+                            accepted += 1;
+                            if accepted == 2 {
+                                return listener;
+                            }
+                        }
+                        ListenerEvent::Error(e) => panic!("Unexpected listener error: {}", e),
+                        ListenerEvent::AddressExpired(_) => {}
+                    }
+                }
+            }
+
+            async fn dialer(mut ready_rx: futures::channel::mpsc::Receiver<Multiaddr>) {
+                let addr = ready_rx.next().await.unwrap();
+                for _ in 0..2 {
+                    let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+                    let (_, muxer) = transport.dial(addr.clone()).unwrap().await.unwrap();
+                    drive_in_background(Arc::new(muxer));
+                }
+            }
+
+            let addr: Multiaddr = "/ip4/127.0.0.1/udp/0/quic".parse().unwrap();
+            let (ready_tx, ready_rx) = futures::channel::mpsc::channel(1);
+            let listener_task = async_std::task::spawn(listener(addr, ready_tx));
+            async_std::task::block_on(dialer(ready_rx));
+            let listener = async_std::task::block_on(listener_task);
+
+            // The driver only schedules its next deadline once something has
+            // actually driven the connection at least once; the background
+            // tasks above are still polling, but give this a moment to land
+            // rather than racing it, the same as every other timing-dependent
+            // wait in this file.
+            async_std::task::block_on(async_std::task::sleep(Duration::from_millis(300)));
+
+            let dump = listener.dump_state();
+            assert_eq!(
+                dump.connections.len(),
+                2,
+                "expected exactly the two connections dialled in, got {:?}",
+                dump.connections
+            );
+            for connection in &dump.connections {
+                assert!(
+                    !connection.is_handshaking,
+                    "connection should have finished handshaking by now: {:?}",
+                    connection
+                );
+                assert!(
+                    !connection.is_closed,
+                    "connection should still be open: {:?}",
+                    connection
+                );
+                assert!(
+                    connection.driver_running,
+                    "a driven connection should have a timer scheduled: {:?}",
+                    connection
+                );
+            }
+        }
+
+        /// [`Config::offloads`] is currently a no-op (see its doc comment):
+        /// this transport's socket I/O only ever sends and receives one
+        /// datagram per syscall regardless of what's passed here, so a large,
+        /// multi-packet transfer with both offloads explicitly disabled should
+        /// complete exactly as it would with the default `Config`, forcing the
+        /// same per-packet path either way.
+        #[test]
+        fn large_transfer_completes_with_offloads_disabled() {
+            env_logger::try_init().ok();
+
+            const PAYLOAD: &[u8] = &[0x17u8; 256 * 1024];
+
+            async fn listener(
+                addr: Multiaddr,
+                mut ready_tx: futures::channel::mpsc::Sender<Multiaddr>,
+            ) {
+                let config = Config::new(&Keypair::generate_ed25519()).offloads(Offloads {
+                    gso: false,
+                    gro: false,
+                });
+                let transport = QuicTransport::new(config);
+                let mut listener = transport.listen_on(addr).unwrap();
+                let muxer = loop {
+                    match listener.next().await.unwrap().unwrap() {
+                        ListenerEvent::NewAddress(listen_addr) => {
+                            ready_tx.send(listen_addr).await.unwrap();
+                        }
+                        ListenerEvent::Upgrade { upgrade, .. } => {
+                            let (_, muxer) = upgrade.await.unwrap();
+                            break Arc::new(muxer);
+                        }
+                        ListenerEvent::Error(e) => panic!("Unexpected listener error: {}", e),
+                        ListenerEvent::AddressExpired(_) => {}
+                    }
+                };
+
+                let id = match future::poll_fn(|cx| muxer.poll_event(cx)).await.unwrap() {
+                    StreamMuxerEvent::InboundSubstream(substream) => substream.id,
+                    StreamMuxerEvent::AddressChange(_) => {
+                        panic!("did not expect an address change")
+                    }
+                };
+
+                let mut recv = RecvStream::new(&muxer, id);
+                let mut received = Vec::new();
+                recv.read_to_end(&mut received).await.unwrap();
+                assert_eq!(received, PAYLOAD);
+                drop(recv);
+
+                let mut send = SendStream::new(&muxer, id);
+                send.write_all(b"done").await.unwrap();
+                send.close().await.unwrap();
+
+                drive_in_background(muxer);
+            }
+
+            async fn dialer(mut ready_rx: futures::channel::mpsc::Receiver<Multiaddr>) {
+                let addr = ready_rx.next().await.unwrap();
+                let config = Config::new(&Keypair::generate_ed25519()).offloads(Offloads {
+                    gso: false,
+                    gro: false,
+                });
+                let transport = QuicTransport::new(config);
+                let (_, muxer) = transport.dial(addr).unwrap().await.unwrap();
+                let muxer = Arc::new(muxer);
+
+                let mut stream = muxer.open_bi().unwrap();
+                stream.send.write_all(PAYLOAD).await.unwrap();
+                stream.send.close().await.unwrap();
+
+                let mut received = Vec::new();
+                stream.recv.read_to_end(&mut received).await.unwrap();
+                assert_eq!(received, b"done");
+
+                drop(stream);
+                drive_in_background(muxer);
+            }
+
+            let addr: Multiaddr = "/ip4/127.0.0.1/udp/0/quic".parse().unwrap();
+            let (ready_tx, ready_rx) = futures::channel::mpsc::channel(1);
+            let listener_task = async_std::task::spawn(listener(addr, ready_tx));
+            async_std::task::block_on(dialer(ready_rx));
+            async_std::task::block_on(listener_task);
+        }
+
+        /// Configures [`Config::transmit_interceptor`] to drop one in ten
+        /// outgoing packets on the dialling side - deterministically by a
+        /// counter, rather than by a random roll, so this test can't flake - and
+        /// confirms a sizeable transfer still completes intact: `quinn_proto`'s
+        /// own loss detection and retransmission is relied on to recover every
+        /// packet the interceptor drops.
+        #[test]
+        fn transfer_completes_despite_the_interceptor_dropping_one_in_ten_packets() {
+            env_logger::try_init().ok();
+
+            const PAYLOAD: &[u8] = &[0x42u8; 256 * 1024];
+
+            async fn listener(
+                addr: Multiaddr,
+                mut ready_tx: futures::channel::mpsc::Sender<Multiaddr>,
+            ) {
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+                let mut listener = transport.listen_on(addr).unwrap();
+                let muxer = loop {
+                    match listener.next().await.unwrap().unwrap() {
+                        ListenerEvent::NewAddress(listen_addr) => {
+                            ready_tx.send(listen_addr).await.unwrap();
+                        }
+                        ListenerEvent::Upgrade { upgrade, .. } => {
+                            let (_, muxer) = upgrade.await.unwrap();
+                            break Arc::new(muxer);
+                        }
+                        ListenerEvent::Error(e) => panic!("Unexpected listener error: {}", e),
+                        ListenerEvent::AddressExpired(_) => {}
+                    }
+                };
+
+                let id = match future::poll_fn(|cx| muxer.poll_event(cx)).await.unwrap() {
+                    StreamMuxerEvent::InboundSubstream(substream) => substream.id,
+                    StreamMuxerEvent::AddressChange(_) => {
+                        panic!("did not expect an address change")
+                    }
+                };
+
+                let mut recv = RecvStream::new(&muxer, id);
+                let mut received = Vec::new();
+                recv.read_to_end(&mut received).await.unwrap();
+                assert_eq!(received, PAYLOAD);
+                drop(recv);
+
+                let mut send = SendStream::new(&muxer, id);
+                send.write_all(b"done").await.unwrap();
+                send.close().await.unwrap();
+
+                drive_in_background(muxer);
+            }
+
+            async fn dialer(mut ready_rx: futures::channel::mpsc::Receiver<Multiaddr>) {
+                let addr = ready_rx.next().await.unwrap();
+                let sent = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+                let config =
+                    Config::new(&Keypair::generate_ed25519()).transmit_interceptor(move |_| {
+                        let n = sent.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                        if n % 10 == 9 {
+                            TransmitAction::Drop
+                        } else {
+                            TransmitAction::Pass
+                        }
+                    });
+                let transport = QuicTransport::new(config);
+                let (_, muxer) = transport.dial(addr).unwrap().await.unwrap();
+                let muxer = Arc::new(muxer);
+
+                let mut stream = muxer.open_bi().unwrap();
+                stream.send.write_all(PAYLOAD).await.unwrap();
+                stream.send.close().await.unwrap();
+
+                let mut received = Vec::new();
+                stream.recv.read_to_end(&mut received).await.unwrap();
+                assert_eq!(received, b"done");
+
+                drop(stream);
+                drive_in_background(muxer);
+            }
+
+            let addr: Multiaddr = "/ip4/127.0.0.1/udp/0/quic".parse().unwrap();
+            let (ready_tx, ready_rx) = futures::channel::mpsc::channel(1);
+            let listener_task = async_std::task::spawn(listener(addr, ready_tx));
+            async_std::task::block_on(dialer(ready_rx));
+            async_std::task::block_on(listener_task);
+        }
+
+        /// Registers [`QuicMuxer::on_bandwidth_change`] on the dialling side of a
+        /// transfer whose [`Config::transmit_interceptor`] drops one in three
+        /// packets - heavy enough loss that `quinn_proto`'s congestion
+        /// controller is guaranteed to shrink the congestion window at least
+        /// once - and confirms the callback actually fires with a smaller value
+        /// than it started with.
+        #[test]
+        fn on_bandwidth_change_fires_when_heavy_loss_shrinks_the_congestion_window() {
+            env_logger::try_init().ok();
+
+            const PAYLOAD: &[u8] = &[0x42u8; 512 * 1024];
+
+            async fn listener(
+                addr: Multiaddr,
+                mut ready_tx: futures::channel::mpsc::Sender<Multiaddr>,
+            ) {
+                let transport = QuicTransport::new(Config::new(&Keypair::generate_ed25519()));
+                let mut listener = transport.listen_on(addr).unwrap();
+                let muxer = loop {
+                    match listener.next().await.unwrap().unwrap() {
+                        ListenerEvent::NewAddress(listen_addr) => {
+                            ready_tx.send(listen_addr).await.unwrap();
+                        }
+                        ListenerEvent::Upgrade { upgrade, .. } => {
+                            let (_, muxer) = upgrade.await.unwrap();
+                            break Arc::new(muxer);
+                        }
+                        ListenerEvent::Error(e) => panic!("Unexpected listener error: {}", e),
+                        ListenerEvent::AddressExpired(_) => {}
+                    }
+                };
+
+                let id = match future::poll_fn(|cx| muxer.poll_event(cx)).await.unwrap() {
+                    StreamMuxerEvent::InboundSubstream(substream) => substream.id,
+                    StreamMuxerEvent::AddressChange(_) => {
+                        panic!("did not expect an address change")
+                    }
+                };
+
+                let mut recv = RecvStream::new(&muxer, id);
+                let mut received = Vec::new();
+                recv.read_to_end(&mut received).await.unwrap();
+                assert_eq!(received, PAYLOAD);
+                drop(recv);
+
+                let mut send = SendStream::new(&muxer, id);
+                send.write_all(b"done").await.unwrap();
+                send.close().await.unwrap();
+
+                drive_in_background(muxer);
+            }
+
+            async fn dialer(mut ready_rx: futures::channel::mpsc::Receiver<Multiaddr>) {
+                let addr = ready_rx.next().await.unwrap();
+                let sent = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+                let config =
+                    Config::new(&Keypair::generate_ed25519()).transmit_interceptor(move |_| {
+                        let n = sent.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                        if n % 3 == 2 {
+                            TransmitAction::Drop
+                        } else {
+                            TransmitAction::Pass
+                        }
+                    });
+                let transport = QuicTransport::new(config);
+                let (_, muxer) = transport.dial(addr).unwrap().await.unwrap();
+                let muxer = Arc::new(muxer);
+
+                let initial_cwnd = muxer.stats().path.cwnd;
+                let smallest_seen = Arc::new(std::sync::atomic::AtomicU64::new(initial_cwnd));
+                let fired = Arc::new(std::sync::atomic::AtomicBool::new(false));
+                muxer.on_bandwidth_change(0.1, {
+                    let smallest_seen = smallest_seen.clone();
+                    let fired = fired.clone();
+                    move |cwnd| {
+                        fired.store(true, std::sync::atomic::Ordering::Relaxed);
+                        smallest_seen.fetch_min(cwnd, std::sync::atomic::Ordering::Relaxed);
+                    }
+                });
+
+                let mut stream = muxer.open_bi().unwrap();
+                stream.send.write_all(PAYLOAD).await.unwrap();
+                stream.send.close().await.unwrap();
+
+                // Waiting for the listener's "done" ack, rather than returning
+                // right after the write above, is what actually drives this
+                // connection forward: `quinn_proto` only hands outstanding
+                // writes to the wire, and observes the resulting loss, the next
+                // time something polls it, and nothing does that between a
+                // `write_all`/`close` pair that never blocked and this read.
+                let mut received = Vec::new();
+                stream.recv.read_to_end(&mut received).await.unwrap();
+                assert_eq!(received, b"done");
+
+                assert!(
+                    fired.load(std::sync::atomic::Ordering::Relaxed),
+                    "on_bandwidth_change never fired despite a third of packets being dropped"
+                );
+                assert!(
+                    smallest_seen.load(std::sync::atomic::Ordering::Relaxed) < initial_cwnd,
+                    "on_bandwidth_change never reported a congestion window smaller than the \
+                     connection started with"
+                );
+
+                drop(stream);
+                drive_in_background(muxer);
+            }
+
+            let addr: Multiaddr = "/ip4/127.0.0.1/udp/0/quic".parse().unwrap();
+            let (ready_tx, ready_rx) = futures::channel::mpsc::channel(1);
+            let listener_task = async_std::task::spawn(listener(addr, ready_tx));
+            async_std::task::block_on(dialer(ready_rx));
+            async_std::task::block_on(listener_task);
+        }
+    }
+}